@@ -0,0 +1,143 @@
+// テープ・フラグメントを集めて、テープ・ボックスに詰め直す。検査に通ったテープ・フラグメントは消す。
+extern crate getopts;
+extern crate kifuwarabe_wcsc29_lib;
+
+use getopts::Options;
+use kifuwarabe_wcsc29_lib::conv::fragment_compactor::*;
+use kifuwarabe_wcsc29_lib::studio::application::*;
+use std::env;
+use std::process;
+
+#[derive(Debug)]
+pub struct Arguments {
+    pub input_directory: Option<String>,
+    pub output_directory: Option<String>,
+    pub size: usize,
+    pub dry_run: bool,
+    pub debug: bool,
+}
+impl Arguments {
+    /// １箱に詰めるテープの本数。
+    const DEFAULT_SIZE: usize = 100;
+
+    pub fn parse(app: &Application) -> Arguments {
+        let args: Vec<String> = env::args().collect();
+
+        let mut opts = Options::new();
+        opts.optopt(
+            "",
+            "directory",
+            "set tape fragments directory. Default is the tapes_fragments directory.",
+            "NAME",
+        );
+        opts.optopt(
+            "o",
+            "output",
+            "set output tape box directory. Default is the training directory.",
+            "NAME",
+        );
+        opts.optopt(
+            "s",
+            "size",
+            "set tapes per tape box. Default is 100.",
+            "NUMBER",
+        );
+        opts.optflag(
+            "n",
+            "dry-run",
+            "only check the tapes. Do not write tape boxes or delete fragments.",
+        );
+        opts.optflag("d", "debug", "Debug.");
+
+        let matches = opts
+            .parse(&args[1..])
+            .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
+
+        let size = match matches.opt_str("size") {
+            Some(size) => size
+                .parse()
+                .unwrap_or_else(|err| panic!("{}", app.comm.panic(&format!("--size: {}", err)))),
+            None => Arguments::DEFAULT_SIZE,
+        };
+
+        Arguments {
+            input_directory: matches.opt_str("directory"),
+            output_directory: matches.opt_str("output"),
+            size,
+            dry_run: matches.opt_present("dry-run"),
+            debug: matches.opt_present("debug"),
+        }
+    }
+}
+
+fn main() {
+    // The application contains all immutable content.
+    let mut app = Application::new();
+
+    // Command line arguments.
+    let args = Arguments::parse(&app);
+
+    if args.debug {
+        app.kifuwarabe_flag = true;
+        app.comm.println("Debug on!");
+    }
+
+    let input_directory = args
+        .input_directory
+        .unwrap_or_else(|| app.kw29_conf.tapes_fragments.to_string());
+    let output_directory = args
+        .output_directory
+        .unwrap_or_else(|| app.kw29_conf.training.to_string());
+
+    let report = FragmentCompactor::compact(
+        &input_directory,
+        &output_directory,
+        args.size,
+        args.dry_run,
+        &app,
+    );
+
+    for compacted_box in &report.boxes {
+        if compacted_box.is_ok() {
+            println!(
+                "OK '{}'. Tapes: {}.",
+                compacted_box.file, compacted_box.tapes
+            );
+        } else {
+            println!(
+                "NG '{}'. Tapes: {}.",
+                compacted_box.file, compacted_box.tapes
+            );
+            if let Some(ref error) = compacted_box.error {
+                println!("    {}", error);
+            }
+        }
+    }
+    for (fragment_file, reason) in &report.kept {
+        println!("Keep '{}'. {}", fragment_file, reason);
+        for (_fragment_file, verdict) in report
+            .ng_verdicts
+            .iter()
+            .filter(|(ng_file, _verdict)| ng_file == fragment_file)
+        {
+            println!("    {}", verdict.to_human_presentable());
+        }
+    }
+
+    println!(
+        "Boxes: {}. Tapes: {}. Deleted fragments: {}. Kept fragments: {}.{}",
+        report.boxes.len(),
+        report
+            .boxes
+            .iter()
+            .map(|compacted_box| compacted_box.tapes)
+            .sum::<usize>(),
+        report.deleted.len(),
+        report.kept.len(),
+        if args.dry_run { " Dry run." } else { "" }
+    );
+
+    if !report.is_ok() {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,89 @@
+// テープ・ボックスを、JSON 形式とバイナリ形式の間で変換する。向きは 出力ファイルの拡張子で決める。
+extern crate getopts;
+extern crate kifuwarabe_wcsc29_lib;
+
+use getopts::Options;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_binary::*;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_tape_box::*;
+use kifuwarabe_wcsc29_lib::studio::application::*;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug)]
+pub struct Arguments {
+    pub input_file: Option<String>,
+    pub output_file: Option<String>,
+    pub debug: bool,
+}
+impl Arguments {
+    pub fn parse(app: &Application) -> Arguments {
+        let args: Vec<String> = env::args().collect();
+
+        let mut opts = Options::new();
+        opts.optopt("i", "input", "set input tape box file name.", "NAME");
+        opts.optopt(
+            "o",
+            "output",
+            "set output tape box file name. Default is the input file with the other extension.",
+            "NAME",
+        );
+        opts.optflag("d", "debug", "Debug.");
+
+        let matches = opts
+            .parse(&args[1..])
+            .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
+
+        Arguments {
+            input_file: matches.opt_str("input"),
+            output_file: matches.opt_str("output"),
+            debug: matches.opt_present("debug"),
+        }
+    }
+}
+
+fn main() {
+    // The application contains all immutable content.
+    let mut app = Application::new();
+
+    // Command line arguments.
+    let args = Arguments::parse(&app);
+
+    if args.debug {
+        app.kifuwarabe_flag = true;
+        app.comm.println("Debug on!");
+    }
+
+    let in_file = args
+        .input_file
+        .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. Please set --input.")));
+
+    // 出力ファイル名が無ければ、入力ファイルの拡張子を入れ替える。
+    let out_file = args.output_file.unwrap_or_else(|| {
+        let extension = if RpmBinary::is_binary_file(&in_file) {
+            "json"
+        } else {
+            RpmBinary::EXTENSION
+        };
+        Path::new(&in_file)
+            .with_extension(extension)
+            .display()
+            .to_string()
+    });
+
+    let rpm_tape_box = RpmTapeBox::from_box_file(&in_file, &app)
+        .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())));
+
+    // 拡張子が .rpmb ならバイナリ形式、それ以外は JSON 形式で書く。
+    rpm_tape_box.write(&out_file, &app);
+
+    let size = |file: &str| fs::metadata(file).map(|meta| meta.len()).unwrap_or(0);
+    println!(
+        "Converted '{}' ({} bytes) -> '{}' ({} bytes). Tapes: {}.",
+        in_file,
+        size(&in_file),
+        out_file,
+        size(&out_file),
+        rpm_tape_box.tape_box.len()
+    );
+}
@@ -4,6 +4,8 @@ extern crate kifuwarabe_wcsc29_lib;
 use getopts::Options;
 use kifuwarabe_wcsc29_lib::audio_compo::audio_rack::*;
 use kifuwarabe_wcsc29_lib::audio_compo::cassette_deck::*;
+use kifuwarabe_wcsc29_lib::conv::batch_converter::*;
+use kifuwarabe_wcsc29_lib::conv::conversion_report::*;
 use kifuwarabe_wcsc29_lib::conv::converter::*;
 use kifuwarabe_wcsc29_lib::instrument::position::*;
 use kifuwarabe_wcsc29_lib::media::cassette_tape::*;
@@ -15,6 +17,9 @@ use std::env;
 pub struct Arguments {
     pub input_file: Option<String>,
     pub output_file: Option<String>,
+    pub report_file: Option<String>,
+    pub batch: bool,
+    pub watch: Option<u64>,
     pub debug: bool,
 }
 impl Arguments {
@@ -24,6 +29,23 @@ impl Arguments {
         let mut opts = Options::new();
         opts.optopt("i", "input", "set input record file name.", "NAME");
         opts.optopt("o", "output", "set output record file name.", "NAME");
+        opts.optopt(
+            "r",
+            "report",
+            "write the conversion report in JSON.",
+            "NAME",
+        );
+        opts.optflag(
+            "b",
+            "batch",
+            "convert all records in the input, expanded and encoded directories.",
+        );
+        opts.optopt(
+            "w",
+            "watch",
+            "keep converting the directories at intervals.",
+            "SECONDS",
+        );
         opts.optflag("d", "debug", "Debug.");
 
         let matches = opts
@@ -33,6 +55,12 @@ impl Arguments {
         Arguments {
             input_file: matches.opt_str("input"),
             output_file: matches.opt_str("output"),
+            report_file: matches.opt_str("report"),
+            batch: matches.opt_present("batch"),
+            watch: matches.opt_str("watch").map(|text| {
+                text.parse::<u64>()
+                    .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())))
+            }),
             debug: matches.opt_present("debug"),
         }
     }
@@ -45,6 +73,27 @@ fn main() {
     // Command line arguments.
     let args = Arguments::parse(&app);
 
+    if args.debug {
+        app.kifuwarabe_flag = true;
+        app.comm.println("Debug on!");
+    }
+
+    if let Some(interval) = args.watch {
+        // 棋譜ディレクトリーを見張り続ける。
+        BatchConverter::watch_directories(interval, &app);
+        return;
+    } else if args.batch {
+        // 棋譜ディレクトリーを１回だけ見る。
+        let report = BatchConverter::convert_directories(&app);
+        if let Some(report_file) = args.report_file {
+            report.write(&report_file, &app);
+        }
+        app.comm
+            .println(&format!("Batch finished. Files: {}.", report.files.len()));
+        println!("{}", report.to_human_presentable());
+        return;
+    }
+
     let in_file = args
         .input_file
         .unwrap_or_else(|| panic!(app.comm.panic("Fail. args.input_file.")));
@@ -54,11 +103,6 @@ fn main() {
         .output_file
         .unwrap_or_else(|| panic!(app.comm.panic("Fail. args.output_file.")));
 
-    if args.debug {
-        app.kifuwarabe_flag = true;
-        app.comm.println("Debug on!");
-    }
-
     // Position.
     let mut position = Position::new_honshogi_origin(&app);
 
@@ -70,7 +114,29 @@ fn main() {
 
     if !in_file.is_empty() {
         // 棋譜解析。
-        Converter::convert(in_file, &mut rack, &mut position, &app);
+        let mut file_report = FileReport::new(&in_file);
+        let result = Converter::convert(in_file, &mut rack, &mut position, &mut file_report, &app);
+        file_report.outcome = match result {
+            Ok(0) => "error".to_string(),
+            Ok(_games) if !file_report.errors.is_empty() => "jammed".to_string(),
+            Ok(_games) => "converted".to_string(),
+            Err(ref err) => {
+                file_report.push_error(&err.to_string());
+                "error".to_string()
+            }
+        };
+        if result.is_ok() {
+            file_report.output = format!("{}.tapesfrag", tape_file_name_without_extension);
+        }
+
+        let mut report = ConversionReport::new();
+        report.push(file_report);
+        if let Some(report_file) = args.report_file {
+            report.write(&report_file, &app);
+        }
+        println!("{}", report.to_human_presentable());
+
+        result.unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())));
     } else {
         main_loop();
     }
@@ -0,0 +1,172 @@
+// テープ・ボックスの中の、同じ対局のテープを１本にまとめて、別のディレクトリーに書き出す。
+extern crate getopts;
+extern crate kifuwarabe_wcsc29_lib;
+
+use getopts::Options;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_binary::*;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_deduplicator::*;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_tape_box::*;
+use kifuwarabe_wcsc29_lib::studio::application::*;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+#[derive(Debug)]
+pub struct Arguments {
+    pub input_directories: Vec<String>,
+    pub output_directory: Option<String>,
+    pub size: usize,
+    pub dry_run: bool,
+    pub debug: bool,
+}
+impl Arguments {
+    /// １箱に詰めるテープの本数。
+    const DEFAULT_SIZE: usize = 100;
+
+    pub fn parse(app: &Application) -> Arguments {
+        let args: Vec<String> = env::args().collect();
+
+        let mut opts = Options::new();
+        opts.optmulti(
+            "",
+            "directory",
+            "read all tape boxes in the directory. Default is the training directory.",
+            "NAME",
+        );
+        opts.optopt("o", "output", "set output tape box directory.", "NAME");
+        opts.optopt(
+            "s",
+            "size",
+            "set tapes per tape box. Default is 100.",
+            "NUMBER",
+        );
+        opts.optflag("n", "dry-run", "only show the duplicates.");
+        opts.optflag("d", "debug", "Debug.");
+
+        let matches = opts
+            .parse(&args[1..])
+            .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
+
+        let size = match matches.opt_str("size") {
+            Some(size) => size
+                .parse()
+                .unwrap_or_else(|err| panic!("{}", app.comm.panic(&format!("--size: {}", err)))),
+            None => Arguments::DEFAULT_SIZE,
+        };
+
+        Arguments {
+            input_directories: matches.opt_strs("directory"),
+            output_directory: matches.opt_str("output"),
+            size,
+            dry_run: matches.opt_present("dry-run"),
+            debug: matches.opt_present("debug"),
+        }
+    }
+}
+
+fn main() {
+    // The application contains all immutable content.
+    let mut app = Application::new();
+
+    // Command line arguments.
+    let args = Arguments::parse(&app);
+
+    if args.debug {
+        app.kifuwarabe_flag = true;
+        app.comm.println("Debug on!");
+    }
+
+    let directories = if args.input_directories.is_empty() {
+        vec![app.kw29_conf.training.to_string()]
+    } else {
+        args.input_directories
+    };
+
+    // 書き出し先が読み込み元だと、重なりが増えてしまう。
+    let output_directory = if args.dry_run {
+        "".to_string()
+    } else {
+        let output_directory = args
+            .output_directory
+            .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. Please set --output.")));
+        if directories
+            .iter()
+            .any(|directory| Path::new(directory) == Path::new(&output_directory))
+        {
+            panic!(
+                "{}",
+                app.comm
+                    .panic("Fail. --output must differ from --directory.")
+            );
+        }
+        output_directory
+    };
+
+    let mut box_files: Vec<String> = Vec::new();
+    for directory in &directories {
+        box_files.extend(
+            fs::read_dir(directory)
+                .unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)))
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension().is_some_and(|extension| {
+                        extension == "json" || extension == RpmBinary::EXTENSION
+                    })
+                })
+                .map(|path| path.display().to_string()),
+        );
+    }
+    box_files.sort();
+
+    let mut tapes = Vec::new();
+    let mut errors = 0;
+    for box_file in &box_files {
+        match RpmTapeBox::from_box_file(box_file, &app) {
+            Ok(rpm_tape_box) => tapes.extend(rpm_tape_box.tape_box),
+            Err(err) => {
+                // 読めない箱は飛ばす。
+                println!("Error: {}", err);
+                errors += 1;
+            }
+        }
+    }
+
+    let input_tapes = tapes.len();
+    let (unique, groups) = RpmDeduplicator::deduplicate(tapes);
+    for group in &groups {
+        println!(
+            "Duplicate: '{}' <- '{}'.",
+            group.kept,
+            group.merged.join("', '")
+        );
+    }
+
+    let unique_tapes = unique.len();
+    let mut output_boxes = 0;
+    if !args.dry_run {
+        let mut rpm_tape_box = RpmTapeBox::new();
+        rpm_tape_box.tape_box = unique;
+        for rpm_tape_box in rpm_tape_box.split_by_count(args.size) {
+            let box_file = RpmTapeBox::create_file_full_name_in(Path::new(&output_directory), &app);
+            rpm_tape_box.write(&box_file, &app);
+            output_boxes += 1;
+        }
+    }
+
+    println!(
+        "Boxes: {}. Tapes: {}. Unique: {}. Duplicates: {}. Output boxes: {}. Error: {}.{}",
+        box_files.len(),
+        input_tapes,
+        unique_tapes,
+        input_tapes - unique_tapes,
+        output_boxes,
+        errors,
+        if args.dry_run { " Dry run." } else { "" }
+    );
+
+    if 0 < errors {
+        process::exit(1);
+    }
+}
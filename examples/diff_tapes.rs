@@ -0,0 +1,130 @@
+// ２本のテープを比べて、初めて違った手目と、その局面と、左右に並べた指し手を表示する。
+extern crate getopts;
+extern crate kifuwarabe_wcsc29_lib;
+
+use getopts::Options;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_tape::*;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_tape_box::*;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_tape_diff::*;
+use kifuwarabe_wcsc29_lib::studio::application::*;
+use std::env;
+use std::path::Path;
+use std::process;
+
+#[derive(Debug)]
+pub struct Arguments {
+    pub left_file: Option<String>,
+    pub left_index: usize,
+    pub right_file: Option<String>,
+    pub right_index: usize,
+    pub notes: bool,
+    pub debug: bool,
+}
+impl Arguments {
+    pub fn parse(app: &Application) -> Arguments {
+        let args: Vec<String> = env::args().collect();
+
+        let mut opts = Options::new();
+        opts.optopt("l", "left", "set left tape box file name.", "NAME");
+        opts.optopt(
+            "",
+            "left-index",
+            "set index of the tape in the left tape box. Default is 0.",
+            "NUMBER",
+        );
+        opts.optopt("r", "right", "set right tape box file name.", "NAME");
+        opts.optopt(
+            "",
+            "right-index",
+            "set index of the tape in the right tape box. Default is 0.",
+            "NUMBER",
+        );
+        opts.optflag("n", "notes", "compare notes instead of moves.");
+        opts.optflag("d", "debug", "Debug.");
+
+        let matches = opts
+            .parse(&args[1..])
+            .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
+
+        let index = |name: &str| match matches.opt_str(name) {
+            Some(index) => index.parse().unwrap_or_else(|err| {
+                panic!("{}", app.comm.panic(&format!("--{}: {}", name, err)))
+            }),
+            None => 0,
+        };
+
+        Arguments {
+            left_file: matches.opt_str("left"),
+            left_index: index("left-index"),
+            right_file: matches.opt_str("right"),
+            right_index: index("right-index"),
+            notes: matches.opt_present("notes"),
+            debug: matches.opt_present("debug"),
+        }
+    }
+}
+
+/// テープ・ボックスから テープを１本取り出す。
+fn read_tape(box_file: &Option<String>, index: usize, app: &Application) -> RpmTape {
+    let box_file = box_file
+        .as_ref()
+        .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. Please set --left and --right.")));
+    // 無いファイルを読むと、空っぽの箱ができてしまう。
+    if !Path::new(box_file).exists() {
+        panic!(
+            "{}",
+            app.comm
+                .panic(&format!("Fail. Tape box not found. '{}'.", box_file))
+        );
+    }
+
+    let mut rpm_tape_box = RpmTapeBox::from_box_file(box_file, app)
+        .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())));
+    if rpm_tape_box.tape_box.len() <= index {
+        panic!(
+            "{}",
+            app.comm.panic(&format!(
+                "Fail. Tape #{} not found. '{}' has {} tapes.",
+                index,
+                box_file,
+                rpm_tape_box.tape_box.len()
+            ))
+        );
+    }
+    rpm_tape_box.tape_box.swap_remove(index)
+}
+
+fn main() {
+    // The application contains all immutable content.
+    let mut app = Application::new();
+
+    // Command line arguments.
+    let args = Arguments::parse(&app);
+
+    if args.debug {
+        app.kifuwarabe_flag = true;
+        app.comm.println("Debug on!");
+    }
+
+    let left = read_tape(&args.left_file, args.left_index, &app);
+    let right = read_tape(&args.right_file, args.right_index, &app);
+
+    let mode = if args.notes {
+        RpmTapeDiffMode::Note
+    } else {
+        RpmTapeDiffMode::Move
+    };
+    let diff = RpmTapeDiff::diff(&left, &right, mode, &app);
+
+    println!(
+        "Left: '{}'. Right: '{}'.",
+        left.label.get_name(),
+        right.label.get_name()
+    );
+    print!("{}", diff.to_human_presentable());
+
+    // diff コマンドのように、違っていれば終了コードは 1。
+    if diff.divergence.is_some() {
+        process::exit(1);
+    }
+}
@@ -49,10 +49,16 @@ pub fn main() {
     // Deck.
     let mut rack = AudioRack::new(&app);
 
-    let crecord = CsaTape::from_file(&path, &app);
+    // 対局がいくつか入っていても、最初の対局だけ食べる。全部の対局は converter で変換する。
+    let crecords = CsaTape::from_file(&path, &app)
+        .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())));
+    let crecord = crecords
+        .first()
+        .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. crecords.first.")));
 
     // Play out.
-    CsaConverter::play_out_csa_tape(&crecord, &mut rack, &mut position, &app);
+    CsaConverter::play_out_csa_tape(crecord, &mut rack, &mut position, &app)
+        .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())));
     HumanInterface::bo(&mut rack, &position, &app);
 
     // Save.
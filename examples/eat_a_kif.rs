@@ -48,10 +48,12 @@ pub fn main() {
     let mut rack = AudioRack::new(&app);
 
     // Training data.
-    let ktape = KifTape::from_file(&path, &app);
+    let ktape = KifTape::from_file(&path, &app)
+        .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())));
 
     // Play out.
-    KifConverter::play_out_kifu_tape(&ktape, &mut rack, &mut position, &app);
+    KifConverter::play_out_kifu_tape(&ktape, &mut rack, &mut position, &app)
+        .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())));
 
     // Write.
     rack.write_leaning_tapes_fragment(position.get_board_size(), &app);
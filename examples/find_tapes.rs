@@ -0,0 +1,116 @@
+// テープ・ボックスの中から、テープ・ラベルが条件に合うテープを探す。
+extern crate getopts;
+extern crate kifuwarabe_wcsc29_lib;
+
+use getopts::Options;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_binary::*;
+use kifuwarabe_wcsc29_lib::sheet_music_format::tape_label_query::*;
+use kifuwarabe_wcsc29_lib::studio::application::*;
+use std::env;
+use std::fs;
+use std::process;
+
+#[derive(Debug)]
+pub struct Arguments {
+    pub input_directories: Vec<String>,
+    pub query: String,
+    pub debug: bool,
+}
+impl Arguments {
+    pub fn parse(app: &Application) -> Arguments {
+        let args: Vec<String> = env::args().collect();
+
+        let mut opts = Options::new();
+        opts.optmulti(
+            "",
+            "directory",
+            "read all tape boxes in the directory. Default is the training directory.",
+            "NAME",
+        );
+        opts.optopt(
+            "q",
+            "query",
+            "set query. Example: 'player1~\"羽生\" date>=2018-01-01 handicap=平手'.",
+            "QUERY",
+        );
+        opts.optflag("d", "debug", "Debug.");
+
+        let matches = opts
+            .parse(&args[1..])
+            .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
+
+        Arguments {
+            input_directories: matches.opt_strs("directory"),
+            query: matches.opt_str("query").unwrap_or_default(),
+            debug: matches.opt_present("debug"),
+        }
+    }
+}
+
+fn main() {
+    // The application contains all immutable content.
+    let mut app = Application::new();
+
+    // Command line arguments.
+    let args = Arguments::parse(&app);
+
+    if args.debug {
+        app.kifuwarabe_flag = true;
+        app.comm.println("Debug on!");
+    }
+
+    let query = TapeLabelQuery::parse(&args.query)
+        .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())));
+
+    let directories = if args.input_directories.is_empty() {
+        vec![app.kw29_conf.training.to_string()]
+    } else {
+        args.input_directories
+    };
+
+    let mut box_files: Vec<String> = Vec::new();
+    for directory in &directories {
+        box_files.extend(
+            fs::read_dir(directory)
+                .unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)))
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension().is_some_and(|extension| {
+                        extension == "json" || extension == RpmBinary::EXTENSION
+                    })
+                })
+                .map(|path| path.display().to_string()),
+        );
+    }
+    box_files.sort();
+
+    let mut found = 0;
+    let mut errors = 0;
+    for box_file in &box_files {
+        match query.search_box_file(box_file) {
+            Ok(references) => {
+                for reference in &references {
+                    println!("{}", reference.to_human_presentable());
+                }
+                found += references.len();
+            }
+            Err(err) => {
+                // 読めない箱は飛ばす。
+                println!("Error: {}", err);
+                errors += 1;
+            }
+        }
+    }
+
+    println!(
+        "Boxes: {}. Found: {}. Error: {}.",
+        box_files.len(),
+        found,
+        errors
+    );
+
+    if 0 < errors {
+        process::exit(1);
+    }
+}
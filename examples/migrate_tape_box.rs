@@ -0,0 +1,121 @@
+// ディレクトリーのテープ・ボックスを、最新の版で書き直す。
+extern crate getopts;
+extern crate kifuwarabe_wcsc29_lib;
+
+use getopts::Options;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_binary::*;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_migration::*;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_tape_box::*;
+use kifuwarabe_wcsc29_lib::studio::application::*;
+use std::env;
+use std::fs;
+use std::process;
+
+#[derive(Debug)]
+pub struct Arguments {
+    pub input_directories: Vec<String>,
+    pub dry_run: bool,
+    pub debug: bool,
+}
+impl Arguments {
+    pub fn parse(app: &Application) -> Arguments {
+        let args: Vec<String> = env::args().collect();
+
+        let mut opts = Options::new();
+        opts.optmulti(
+            "",
+            "directory",
+            "rewrite all tape boxes in the directory. Default is the training and learning directories.",
+            "NAME",
+        );
+        opts.optflag("n", "dry-run", "only show the tape boxes to rewrite.");
+        opts.optflag("d", "debug", "Debug.");
+
+        let matches = opts
+            .parse(&args[1..])
+            .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
+
+        Arguments {
+            input_directories: matches.opt_strs("directory"),
+            dry_run: matches.opt_present("dry-run"),
+            debug: matches.opt_present("debug"),
+        }
+    }
+}
+
+fn main() {
+    // The application contains all immutable content.
+    let mut app = Application::new();
+
+    // Command line arguments.
+    let args = Arguments::parse(&app);
+
+    if args.debug {
+        app.kifuwarabe_flag = true;
+        app.comm.println("Debug on!");
+    }
+
+    let directories = if args.input_directories.is_empty() {
+        vec![
+            app.kw29_conf.training.to_string(),
+            app.kw29_conf.learning.to_string(),
+        ]
+    } else {
+        args.input_directories
+    };
+
+    let mut box_files: Vec<String> = Vec::new();
+    for directory in &directories {
+        box_files.extend(
+            fs::read_dir(directory)
+                .unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)))
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension().is_some_and(|extension| {
+                        extension == "json" || extension == RpmBinary::EXTENSION
+                    })
+                })
+                .map(|path| path.display().to_string()),
+        );
+    }
+    box_files.sort();
+
+    let latest = RpmMigration::get_latest_version();
+    let mut rewritten = 0;
+    let mut errors = 0;
+    for box_file in &box_files {
+        match RpmTapeBox::from_box_file_with_version(box_file, &app) {
+            Ok((_rpm_tape_box, version)) if version == latest => {
+                println!("Latest: '{}'. Version: {}.", box_file, version);
+            }
+            Ok((rpm_tape_box, version)) => {
+                if !args.dry_run {
+                    rpm_tape_box.write(box_file, &app);
+                }
+                println!(
+                    "Rewrite: '{}'. Version: {} -> {}.",
+                    box_file, version, latest
+                );
+                rewritten += 1;
+            }
+            Err(err) => {
+                // 読めない箱は、書き直さずに残す。
+                println!("Error: {}", err);
+                errors += 1;
+            }
+        }
+    }
+
+    println!(
+        "Boxes: {}. Rewrite: {}. Error: {}.{}",
+        box_files.len(),
+        rewritten,
+        errors,
+        if args.dry_run { " Dry run." } else { "" }
+    );
+
+    if 0 < errors {
+        process::exit(1);
+    }
+}
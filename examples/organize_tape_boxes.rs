@@ -0,0 +1,267 @@
+// テープ・ボックスを まとめる (merge)、分ける (split)、絞り込む (filter)。
+// テープのラベルも トラックも そのまま移す。書き出す箱は、一時ファイルに書き切ってから名前を付け替える。
+extern crate getopts;
+extern crate kifuwarabe_wcsc29_lib;
+
+use getopts::Options;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_binary::*;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_tape_box::*;
+use kifuwarabe_wcsc29_lib::sheet_music_format::tape_label_query::*;
+use kifuwarabe_wcsc29_lib::studio::application::*;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug)]
+pub struct Arguments {
+    pub command: String,
+    pub input_files: Vec<String>,
+    pub input_directories: Vec<String>,
+    pub output: String,
+    pub size: Option<usize>,
+    pub by: Option<String>,
+    pub query: String,
+    pub binary: bool,
+    pub debug: bool,
+}
+impl Arguments {
+    pub fn parse(app: &Application) -> Arguments {
+        let args: Vec<String> = env::args().collect();
+
+        let mut opts = Options::new();
+        opts.optmulti("i", "input", "add input tape box file name.", "NAME");
+        opts.optmulti(
+            "",
+            "directory",
+            "add all tape boxes in the directory.",
+            "NAME",
+        );
+        opts.optopt(
+            "o",
+            "output",
+            "set output tape box file name. split: set output directory.",
+            "NAME",
+        );
+        opts.optopt("s", "size", "split: set tapes per tape box.", "NUMBER");
+        opts.optopt(
+            "",
+            "by",
+            "split: set tape label field. Example: player1.",
+            "KEY",
+        );
+        opts.optopt(
+            "q",
+            "query",
+            "filter: set query. Example: 'handicap=平手 date>=2018'.",
+            "QUERY",
+        );
+        opts.optflag("b", "binary", "split: write .rpmb tape boxes.");
+        opts.optflag("d", "debug", "Debug.");
+
+        let matches = opts
+            .parse(&args[1..])
+            .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
+
+        let command = matches.free.first().cloned().unwrap_or_else(|| {
+            panic!(
+                "{}",
+                app.comm
+                    .panic("Fail. Please set a command. merge, split or filter.")
+            )
+        });
+
+        let size = matches.opt_str("size").map(|size| {
+            size.parse()
+                .unwrap_or_else(|err| panic!("{}", app.comm.panic(&format!("--size: {}", err))))
+        });
+
+        Arguments {
+            command,
+            input_files: matches.opt_strs("input"),
+            input_directories: matches.opt_strs("directory"),
+            output: matches
+                .opt_str("output")
+                .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. Please set --output."))),
+            size,
+            by: matches.opt_str("by"),
+            query: matches.opt_str("query").unwrap_or_default(),
+            binary: matches.opt_present("binary"),
+            debug: matches.opt_present("debug"),
+        }
+    }
+}
+
+/// 値を ファイル名に使えるようにする。
+fn to_file_stem(value: &str) -> String {
+    if value.is_empty() {
+        return "none".to_string();
+    }
+    value
+        .chars()
+        .map(|ch| {
+            if ch.is_whitespace() || "\\/:*?\"<>|".contains(ch) {
+                '_'
+            } else {
+                ch
+            }
+        })
+        .collect()
+}
+
+fn main() {
+    // The application contains all immutable content.
+    let mut app = Application::new();
+
+    // Command line arguments.
+    let args = Arguments::parse(&app);
+
+    if args.debug {
+        app.kifuwarabe_flag = true;
+        app.comm.println("Debug on!");
+    }
+
+    let mut box_files = args.input_files.clone();
+    for directory in &args.input_directories {
+        let mut files: Vec<String> = fs::read_dir(directory)
+            .unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)))
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension().is_some_and(|extension| {
+                    extension == "json" || extension == RpmBinary::EXTENSION
+                })
+            })
+            .map(|path| path.display().to_string())
+            .collect();
+        files.sort();
+        box_files.extend(files);
+    }
+    if box_files.is_empty() {
+        panic!(
+            "{}",
+            app.comm.panic("Fail. Please set --input or --directory.")
+        );
+    }
+
+    // 並べ替えで テープが消えると困るので、読めない箱があれば 何も書かずに止める。
+    let mut boxes = Vec::new();
+    for box_file in &box_files {
+        // 無いファイルを読むと、空っぽの箱ができてしまう。
+        if !Path::new(box_file).exists() {
+            panic!(
+                "{}",
+                app.comm
+                    .panic(&format!("Fail. Tape box not found. '{}'.", box_file))
+            );
+        }
+        boxes.push(
+            RpmTapeBox::from_box_file(box_file, &app)
+                .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string()))),
+        );
+    }
+    let rpm_tape_box = RpmTapeBox::merge(boxes);
+    let input_tapes = rpm_tape_box.tape_box.len();
+
+    // (書き出すファイル名, 箱)
+    let outputs: Vec<(String, RpmTapeBox)> = match args.command.as_str() {
+        "merge" => vec![(args.output.to_string(), rpm_tape_box)],
+        "filter" => {
+            let query = TapeLabelQuery::parse(&args.query)
+                .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())));
+            vec![(
+                args.output.to_string(),
+                rpm_tape_box.filter(|tape| query.matches(&tape.label)),
+            )]
+        }
+        "split" => {
+            let extension = if args.binary {
+                RpmBinary::EXTENSION
+            } else {
+                "json"
+            };
+            let directory = Path::new(&args.output);
+            match (args.size, &args.by) {
+                (Some(size), None) => rpm_tape_box
+                    .split_by_count(size)
+                    .into_iter()
+                    .map(|rpm_tape_box| {
+                        let box_file = RpmTapeBox::create_file_full_name_in(directory, &app);
+                        (
+                            Path::new(&box_file)
+                                .with_extension(extension)
+                                .display()
+                                .to_string(),
+                            rpm_tape_box,
+                        )
+                    })
+                    .collect(),
+                (None, Some(key)) => rpm_tape_box
+                    .split_by_label(key)
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "{}",
+                            app.comm
+                                .panic(&format!("Fail. Unknown tape label field '{}'.", key))
+                        )
+                    })
+                    .into_iter()
+                    .map(|(value, rpm_tape_box)| {
+                        let box_file = directory
+                            .join(format!("{}-tape-box.{}", to_file_stem(&value), extension))
+                            .display()
+                            .to_string();
+                        // 同じ名前の箱を上書きすると、元の箱のテープが消える。
+                        if Path::new(&box_file).exists() {
+                            panic!(
+                                "{}",
+                                app.comm
+                                    .panic(&format!("Fail. '{}' already exists.", box_file))
+                            );
+                        }
+                        (box_file, rpm_tape_box)
+                    })
+                    .collect(),
+                _ => panic!(
+                    "{}",
+                    app.comm.panic("Fail. split needs either --size or --by.")
+                ),
+            }
+        }
+        command => panic!(
+            "{}",
+            app.comm.panic(&format!(
+                "Fail. Unknown command '{}'. merge, split or filter.",
+                command
+            ))
+        ),
+    };
+
+    // 違う値でも、ファイル名にすると同じになることがある。同じファイル名に２箱書くと、先の箱のテープが消える。
+    for (i, (box_file, _rpm_tape_box)) in outputs.iter().enumerate() {
+        if outputs[..i]
+            .iter()
+            .any(|(other, _rpm_tape_box)| other == box_file)
+        {
+            panic!(
+                "{}",
+                app.comm
+                    .panic(&format!("Fail. '{}' is written twice.", box_file))
+            );
+        }
+    }
+
+    let mut output_tapes = 0;
+    for (box_file, rpm_tape_box) in &outputs {
+        rpm_tape_box.write(box_file, &app);
+        println!("{} Tapes: {}.", box_file, rpm_tape_box.tape_box.len());
+        output_tapes += rpm_tape_box.tape_box.len();
+    }
+
+    println!(
+        "Input boxes: {}. Tapes: {}. Output boxes: {}. Tapes: {}.",
+        box_files.len(),
+        input_tapes,
+        outputs.len(),
+        output_tapes
+    );
+}
@@ -0,0 +1,83 @@
+// テープ・ボックスの棋譜を、KIF に書き出す。テープ１本につき、ファイル１つ。
+extern crate getopts;
+extern crate kifuwarabe_wcsc29_lib;
+
+use getopts::Options;
+use kifuwarabe_wcsc29_lib::instrument::position::*;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_kif::kif_tape::*;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_tape_box::*;
+use kifuwarabe_wcsc29_lib::studio::application::*;
+use std::env;
+use std::path::Path;
+
+#[derive(Debug)]
+pub struct Arguments {
+    pub input_file: Option<String>,
+    pub output_directory: Option<String>,
+    pub debug: bool,
+}
+impl Arguments {
+    pub fn parse(app: &Application) -> Arguments {
+        let args: Vec<String> = env::args().collect();
+
+        let mut opts = Options::new();
+        opts.optopt("i", "input", "set input tape box file name.", "NAME");
+        opts.optopt("o", "output", "set output directory.", "NAME");
+        opts.optflag("d", "debug", "Debug.");
+
+        let matches = opts
+            .parse(&args[1..])
+            .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
+
+        Arguments {
+            input_file: matches.opt_str("input"),
+            output_directory: matches.opt_str("output"),
+            debug: matches.opt_present("debug"),
+        }
+    }
+}
+
+fn main() {
+    // The application contains all immutable content.
+    let mut app = Application::new();
+
+    // Command line arguments.
+    let args = Arguments::parse(&app);
+
+    let in_file = args
+        .input_file
+        .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. args.input_file.")));
+
+    let out_directory = args
+        .output_directory
+        .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. args.output_directory.")));
+
+    if args.debug {
+        app.kifuwarabe_flag = true;
+        app.comm.println("Debug on!");
+    }
+
+    // Position. 盤のサイズを知るのに使う。
+    let position = Position::new_honshogi_origin(&app);
+
+    let rpm_tape_box = RpmTapeBox::from_box_file(&in_file, &app)
+        .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())));
+    for (i, rtape) in rpm_tape_box.tape_box.iter().enumerate() {
+        let ktape = KifTape::from_rpm_tape(rtape, position.get_board_size(), &app);
+
+        // テープ名が無ければ、番号にする。
+        let name = if ktape.get_tape_label().get_name().is_empty() {
+            i.to_string()
+        } else {
+            format!("{}-{}", i, ktape.get_tape_label().get_name())
+        };
+
+        let out_file = Path::new(&out_directory).join(format!("{}.kif", name));
+        ktape.write(
+            out_file
+                .to_str()
+                .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. out_file."))),
+            &app,
+        );
+    }
+}
@@ -54,11 +54,14 @@ pub fn main() {
     let mut rack = AudioRack::new(&app);
 
     let mut start = 0;
-    if Fen::parse_initial_position(&line, &mut start, &mut position, &mut rack, &app) {
+    if Fen::parse_initial_position(&line, &mut start, &mut position, &mut rack, &app)
+        .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())))
+    {
         app.comm.println("Position parsed.");
 
         if let Some(parsed_utape) =
             UsiPosition::parse_usi_line_moves(&line, &mut start, position.get_board_size(), &app)
+                .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())))
         {
             app.comm.println("Moves parsed.");
             utape = parsed_utape;
@@ -68,11 +71,14 @@ pub fn main() {
 
     // ポジションをもう１回初期局面に戻す。
     let mut start = 0;
-    if Fen::parse_initial_position(&line, &mut start, &mut position, &mut rack, &app) {
+    if Fen::parse_initial_position(&line, &mut start, &mut position, &mut rack, &app)
+        .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())))
+    {
         app.comm.println("Position parsed.");
     }
 
     //rack.change_training_tape(None, position.get_board_size(), &app);
-    UsiConverter::play_out_usi_tape(&mut position, &utape, &mut rack, &app);
+    UsiConverter::play_out_usi_tape(&mut position, &utape, &mut rack, &app)
+        .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())));
     HumanInterface::bo(&mut rack, &position, &app);
 }
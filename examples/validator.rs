@@ -0,0 +1,117 @@
+// テープ・ボックスのテープを、まっさらな局面で再生して検査する。テープ１本につき、判定１行。
+extern crate getopts;
+extern crate kifuwarabe_wcsc29_lib;
+
+use getopts::Options;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_binary::*;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_tape_box::*;
+use kifuwarabe_wcsc29_lib::sheet_music_format::kifu_rpm::rpm_tape_validator::*;
+use kifuwarabe_wcsc29_lib::studio::application::*;
+use std::env;
+use std::fs;
+use std::process;
+
+#[derive(Debug)]
+pub struct Arguments {
+    pub input_file: Option<String>,
+    pub input_directory: Option<String>,
+    pub debug: bool,
+}
+impl Arguments {
+    pub fn parse(app: &Application) -> Arguments {
+        let args: Vec<String> = env::args().collect();
+
+        let mut opts = Options::new();
+        opts.optopt("i", "input", "set input tape box file name.", "NAME");
+        opts.optopt(
+            "",
+            "directory",
+            "check all tape boxes in the directory. Default is the training directory.",
+            "NAME",
+        );
+        opts.optflag("d", "debug", "Debug.");
+
+        let matches = opts
+            .parse(&args[1..])
+            .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
+
+        Arguments {
+            input_file: matches.opt_str("input"),
+            input_directory: matches.opt_str("directory"),
+            debug: matches.opt_present("debug"),
+        }
+    }
+}
+
+fn main() {
+    // The application contains all immutable content.
+    let mut app = Application::new();
+
+    // Command line arguments.
+    let args = Arguments::parse(&app);
+
+    if args.debug {
+        app.kifuwarabe_flag = true;
+        app.comm.println("Debug on!");
+    }
+
+    // 検査するテープ・ボックス。
+    let box_files = if let Some(in_file) = args.input_file {
+        vec![in_file]
+    } else {
+        let directory = args
+            .input_directory
+            .unwrap_or_else(|| app.kw29_conf.training.to_string());
+        let mut box_files: Vec<String> = fs::read_dir(&directory)
+            .unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)))
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension().is_some_and(|extension| {
+                    extension == "json" || extension == RpmBinary::EXTENSION
+                })
+            })
+            .map(|path| path.display().to_string())
+            .collect();
+        box_files.sort();
+        box_files
+    };
+
+    let mut tapes = 0;
+    let mut ng_tapes = 0;
+    let mut ng_boxes = 0;
+    for box_file in &box_files {
+        println!("{}", box_file);
+
+        let rpm_tape_box = match RpmTapeBox::from_box_file(box_file, &app) {
+            Ok(rpm_tape_box) => rpm_tape_box,
+            Err(err) => {
+                // 読めない箱は、中のテープを検査できない。
+                println!("NG {}", err);
+                ng_boxes += 1;
+                continue;
+            }
+        };
+
+        for verdict in RpmTapeValidator::validate_tape_box(&rpm_tape_box, &app) {
+            println!("{}", verdict.to_human_presentable());
+            tapes += 1;
+            if !verdict.is_ok() {
+                ng_tapes += 1;
+            }
+        }
+    }
+
+    println!(
+        "Boxes: {}. Tapes: {}. OK: {}, NG: {}. Unreadable boxes: {}.",
+        box_files.len(),
+        tapes,
+        tapes - ng_tapes,
+        ng_tapes,
+        ng_boxes
+    );
+
+    if 0 < ng_tapes || 0 < ng_boxes {
+        process::exit(1);
+    }
+}
@@ -1,5 +1,7 @@
 use audio_compo::cassette_deck::*;
 use media::cassette_tape::*;
+use media::tape_branch::*;
+use sheet_music_format::move_annotation::MoveAnnotation;
 use sheet_music_format::tape_label::TapeLabel;
 use sound::shogi_move::ShogiMove;
 use sound::shogi_note::ShogiNote;
@@ -52,6 +54,10 @@ impl AudioRack {
     pub fn clear_of_tapes(&mut self, slot: Slot, app: &Application) {
         self.deck.clear_of_tapes(slot, &app)
     }
+    /// プリンキパル・テープを、中身の無いテープにする。
+    pub fn clear_principal_tape(&mut self, app: &Application) {
+        self.deck.clear_principal_tape(app)
+    }
     pub fn clear_tape_body(&mut self, slot: Slot, app: &Application) {
         self.deck.clear_tape_body(slot, &app)
     }
@@ -60,8 +66,13 @@ impl AudioRack {
     // # D #
     // #####
 
-    pub fn delete_1note(&mut self, slot: Slot, app: &Application) -> Option<ShogiNote> {
-        self.deck.delete_1note(slot, &app)
+    pub fn delete_1note(
+        &mut self,
+        slot: Slot,
+        board_size: BoardSize,
+        app: &Application,
+    ) -> Option<ShogiNote> {
+        self.deck.delete_1note(slot, board_size, app)
     }
 
     // #####
@@ -72,6 +83,31 @@ impl AudioRack {
         &mut self.deck
     }
 
+    /// 今のテープの、手目の注釈。
+    pub fn get_annotation_of_tape(&self, slot: Slot, ply: i16) -> Option<MoveAnnotation> {
+        self.deck.get_annotation_of_tape(slot, ply)
+    }
+
+    /// 今のテープの、注釈全部。
+    pub fn get_annotations_of_tape(&self, slot: Slot) -> Vec<MoveAnnotation> {
+        self.deck.get_annotations_of_tape(slot)
+    }
+
+    /// 取り外しておいた元の筋を、今のテープの筋に継ぐ。
+    pub fn graft_line_of_tape(&mut self, slot: Slot, line: TapeBranch) {
+        self.deck.graft_line_of_tape(slot, line);
+    }
+
+    /// 今のテープの写し。テープが無ければ None。
+    pub fn get_current_tape(&self, slot: Slot) -> Option<CassetteTape> {
+        self.deck.get_current_tape(slot)
+    }
+
+    /// 今のテープのラベル。テープが無ければ None。
+    pub fn get_label_of_current_tape(&self, slot: Slot) -> Option<&TapeLabel> {
+        self.deck.get_label_of_current_tape(slot)
+    }
+
     pub fn get_ply(&self, slot: Slot) -> i16 {
         self.deck.get_ply(slot)
     }
@@ -124,6 +160,14 @@ impl AudioRack {
         self.deck.look_back_caret(slot, &app);
     }
 
+    // #####
+    // # O #
+    // #####
+
+    pub fn open_learning_journal(&mut self, app: &Application) {
+        self.deck.open_learning_journal(app);
+    }
+
     // #####
     // # P #
     // #####
@@ -132,8 +176,45 @@ impl AudioRack {
     pub fn push_note(&mut self, slot: Slot, note: ShogiNote) {
         self.deck.push_note(slot, note);
     }
-    pub fn pop_note(&mut self, slot: Slot) -> Option<ShogiNote> {
-        self.deck.pop_note(slot)
+    /// 正の方のテープの末端のノートを削除。
+    pub fn pop_note(
+        &mut self,
+        slot: Slot,
+        board_size: BoardSize,
+        app: &Application,
+    ) -> Option<ShogiNote> {
+        self.deck.pop_note(slot, board_size, app)
+    }
+    /// 取り外しておいた筋を、今のテープに 取り外す前のとおりに戻す。
+    pub fn put_back_line_of_tape(&mut self, slot: Slot, line: TapeBranch) {
+        self.deck.put_back_line_of_tape(slot, line);
+    }
+
+    // #####
+    // # R #
+    // #####
+
+    /// キャレットの所にノートを書いて、キャレットを進める。
+    /// 戻したところから違う手を指したら、元の続きは枝として残る。
+    pub fn record_note(
+        &mut self,
+        slot: Slot,
+        note: ShogiNote,
+        board_size: BoardSize,
+        app: &Application,
+    ) {
+        self.deck.record_note(slot, note, board_size, app);
+    }
+
+    /// 今のテープを、キャレットごと丸ごと差し替える。
+    pub fn replace_current_tape(
+        &mut self,
+        slot: Slot,
+        tape: CassetteTape,
+        board_size: BoardSize,
+        app: &Application,
+    ) {
+        self.deck.replace_current_tape(slot, tape, board_size, app);
     }
 
     // #####
@@ -151,11 +232,40 @@ impl AudioRack {
         );
     }
 
+    /// テープの、指し手ごとの注釈を書く。
+    pub fn set_annotations_of_tape(&mut self, slot: Slot, annotations: &[MoveAnnotation]) {
+        self.deck.set_annotations_of_tape(slot, annotations);
+    }
+
     /// テープのラベルを書く。
     pub fn set_label_of_tape(&mut self, slot: Slot, label: &TapeLabel) {
         self.deck.set_label_of_tape(slot, label);
     }
 
+    pub fn set_ply(&mut self, slot: Slot, ply: i16) {
+        self.deck.set_ply(slot, ply);
+    }
+
+    /// テープの、手目の注釈に 読み筋を書く。
+    pub fn set_principal_variation_of_tape(&mut self, slot: Slot, ply: i16, pv: &[String]) {
+        self.deck.set_principal_variation_of_tape(slot, ply, pv);
+    }
+
+    /// キャレットの所で分かれる枝の nth 番目に乗り換える。
+    ///
+    /// # Returns
+    ///
+    /// 乗り換えたら真。そんな枝が無ければ偽。
+    pub fn switch_branch_at_caret(
+        &mut self,
+        slot: Slot,
+        nth: usize,
+        board_size: BoardSize,
+        app: &Application,
+    ) -> bool {
+        self.deck.switch_branch_at_caret(slot, nth, board_size, app)
+    }
+
     /// 次のテープを利用するぜ☆（＾～＾）
     /// 次のテープが無ければ、おわり☆（＾ｑ＾）
     ///
@@ -166,6 +276,34 @@ impl AudioRack {
         self.deck.seek_of_next_tape(slot, &app)
     }
 
+    /// テープのキャレットを、caret 番地へ飛ばす。
+    ///
+    /// # Returns
+    ///
+    /// 通り過ぎたノート。局面で触る順。飛べなければ None。
+    pub fn seek_to_caret_of_tape(
+        &mut self,
+        slot: Slot,
+        caret: i16,
+        app: &Application,
+    ) -> Option<Vec<ShogiNote>> {
+        self.deck.seek_to_caret_of_tape(slot, caret, app)
+    }
+
+    /// テープのキャレットを、ply 手目の指し手の頭へ飛ばす。
+    ///
+    /// # Returns
+    ///
+    /// 通り過ぎたノート。局面で触る順。その手目が無ければ None。
+    pub fn seek_to_ply_of_tape(
+        &mut self,
+        slot: Slot,
+        ply: i16,
+        app: &Application,
+    ) -> Option<Vec<ShogiNote>> {
+        self.deck.seek_to_ply_of_tape(slot, ply, app)
+    }
+
     pub fn step_in_of_tape(&self, slot: Slot) -> i16 {
         self.deck.step_in_of_tape(slot)
     }
@@ -206,6 +344,11 @@ impl AudioRack {
     // # T #
     // #####
 
+    /// 今のテープの筋を、枝も注釈も丸ごと取り外す。テープが無ければ None。
+    pub fn take_line_of_tape(&mut self, slot: Slot) -> Option<TapeBranch> {
+        self.deck.take_line_of_tape(slot)
+    }
+
     pub fn to_human_presentable_of_current_tape_of_training_box(
         &self,
         board_size: BoardSize,
@@ -220,6 +363,14 @@ impl AudioRack {
     pub fn to_human_presentable_of_tape_box(&self, slot: Slot) -> String {
         self.deck.to_human_presentable_of_tape_box(slot)
     }
+    pub fn to_human_presentable_of_branches_at_caret(
+        &self,
+        slot: Slot,
+        board_size: BoardSize,
+    ) -> Vec<String> {
+        self.deck
+            .to_human_presentable_of_branches_at_caret(slot, board_size)
+    }
     pub fn turn_caret_towards_positive_infinity(&mut self, slot: Slot, app: &Application) {
         self.deck.turn_caret_towards_positive_infinity(slot, &app);
     }
@@ -1,9 +1,15 @@
 use media::cassette_tape::*;
 use media::cassette_tape_box::*;
+use media::learning_journal::*;
+use media::tape_branch::*;
 use sheet_music_format::kifu_rpm::rpm_tape_box::*;
+use sheet_music_format::kifu_rpm::rpm_tape_box_index::*;
+use sheet_music_format::kifu_rpm::rpm_tape_reader::*;
+use sheet_music_format::move_annotation::MoveAnnotation;
 use sheet_music_format::tape_label::TapeLabel;
 use sound::shogi_move::ShogiMove;
 use sound::shogi_note::ShogiNote;
+use std::fs;
 use studio::application::Application;
 use studio::board_size::BoardSize;
 use studio::common::caret::Awareness;
@@ -24,6 +30,9 @@ pub enum Slot {
 pub struct CassetteDeck {
     // カセットのスロット。
     pub slots: [CassetteTapeBox; 3],
+
+    // 対局中のラーニング・テープのジャーナル。対局中でなければ None。
+    journal: Option<LearningJournal>,
 }
 impl CassetteDeck {
     // ###############
@@ -53,6 +62,7 @@ impl CassetteDeck {
 
         CassetteDeck {
             slots: [training_tape_box, learning_tape_box, principal_tape_box],
+            journal: None,
         }
     }
 
@@ -60,7 +70,8 @@ impl CassetteDeck {
     // # A #
     // #####
 
-    /// ◆JSONファイルを読み込んで、テープを詰め込みます。
+    /// ◆テープ・ボックス・ファイルを開いて、テープを詰め込みます。
+    /// JSON でもバイナリでも、テープはシークで届いた時に１本ずつ読みます。
     pub fn add_tapes_from_file(
         &mut self,
         box_file_name: &str,
@@ -69,24 +80,39 @@ impl CassetteDeck {
         app: &Application,
     ) {
         let tape_box = &mut self.slots[slot as usize];
-        let rpm_tape_box = RpmTapeBox::from_box_file(&box_file_name, &app);
 
         // 仮のテープ・ボックス・ファイル名。
         tape_box.set_file_name_without_extension(
             &RpmTapeBox::create_file_full_name_without_extension(&app.kw29_conf, &app),
         );
 
-        for tape_j in &rpm_tape_box.tape_box {
-            // テープを追加中。テープを追加しても、キャレットは進まない☆（*＾～＾*）
-            let tape = tape_j.to_object(board_size, &app);
-            tape_box.add_tape(tape, &app);
-        }
+        let reader = RpmTapeReader::open(box_file_name)
+            .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())));
+        tape_box.add_tapes_from_reader(reader, board_size);
     }
 
     pub fn add_tape_to_tape_box(&mut self, slot: Slot, tape: CassetteTape, app: &Application) {
         self.slots[slot as usize].add_tape(tape, &app);
     }
 
+    /// 対局中なら、ラーニング・テープに書いたノートをジャーナルにも書く。
+    pub fn append_learning_journal(
+        &mut self,
+        note: &ShogiNote,
+        board_size: BoardSize,
+        app: &Application,
+    ) {
+        let failed = match self.journal {
+            Some(ref mut journal) => journal.append(note, board_size).err(),
+            None => None,
+        };
+        if let Some(err) = failed {
+            // ジャーナルが書けなくても、対局は続ける☆（＾～＾）
+            app.comm.println(&format!("#Journal append fail. {}", err));
+            self.journal = None;
+        }
+    }
+
     /*
     /// トレーニング・テープを交換するぜ☆（＾～＾）
     ///
@@ -100,28 +126,14 @@ impl CassetteDeck {
         // self.set_tape_box(Slot::Training, training_tape_box_opt, &app);
         self.slots[Slot::Training as usize].ply = 1;
 
-        let mut full = false;
         let learning_box = &self.slots[Slot::Learning as usize];
         // ラーニング・テープの、テープ・ボックスを外部ファイルに保存する（今までのラーニング・テープは、このテープ・ボックスに入っている）。
         let file_name = learning_box.get_file_name();
         learning_box.to_rpm(board_size).write(&file_name, &app);
 
-        if 499 < learning_box.len_tapes() {
-            // TODO 満杯になったら次のボックスを新しく作りたい☆（＾～＾）
-            full = true;
-        }
-
-        if full {
-            // TODO 満杯になったら次のボックスを新しく作りたい☆（＾～＾）
-            if app.is_debug() {
-                app.comm.println("Change learning tape box.");
-            }
-
-            self.clear_of_tapes(Slot::Learning, &app);
-            self.set_file_name_without_extension_of_tape_box(
-                Slot::Learning,
-                &RpmTapeBox::create_file_full_name_without_extension(&app.kw29_conf, &app),
-            );
+        // 満杯になったら次のボックスを新しく作る☆（＾～＾）
+        if self.is_full_of_learning_tape_box(&app) {
+            self.roll_over_learning_tape_box(&app);
         }
 
         // 新しいラーニング・テープに差し替える。
@@ -142,8 +154,29 @@ impl CassetteDeck {
     pub fn clear_of_tapes(&mut self, slot: Slot, app: &Application) {
         self.slots[slot as usize].clear_tape_box(&app)
     }
+    /// プリンキパル・テープを、中身の無いテープにする。テープが無ければ１本差し込む。
+    pub fn clear_principal_tape(&mut self, app: &Application) {
+        if self.is_none_current_tape(Slot::Principal) {
+            let tape = CassetteTape::new_facing_right(app);
+            self.add_tape_to_tape_box(Slot::Principal, tape, app);
+            self.seek_of_next_tape(Slot::Principal, app);
+        } else {
+            self.clear_tape_body(Slot::Principal, app);
+        }
+    }
     pub fn clear_tape_body(&mut self, slot: Slot, app: &Application) {
-        self.slots[slot as usize].clear_tape_body(&app)
+        self.slots[slot as usize].clear_tape_body(app);
+
+        if let Slot::Learning = slot {
+            let failed = match self.journal {
+                Some(ref mut journal) => journal.clear().err(),
+                None => None,
+            };
+            if let Some(err) = failed {
+                app.comm.println(&format!("#Journal clear fail. {}", err));
+                self.journal = None;
+            }
+        }
     }
 
     // #####
@@ -154,14 +187,50 @@ impl CassetteDeck {
     /// TODO ply が変わることがある。
     ///
     /// 削除したノート。
-    pub fn delete_1note(&mut self, slot: Slot, app: &Application) -> Option<ShogiNote> {
-        self.slots[slot as usize].delete_1note(&app)
+    pub fn delete_1note(
+        &mut self,
+        slot: Slot,
+        board_size: BoardSize,
+        app: &Application,
+    ) -> Option<ShogiNote> {
+        let note = self.slots[slot as usize].delete_1note(app);
+
+        if let Slot::Learning = slot {
+            self.rewrite_learning_journal(board_size, app);
+        }
+
+        note
     }
 
     // #####
     // # G #
     // #####
 
+    /// 今のテープの、手目の注釈。
+    pub fn get_annotation_of_tape(&self, slot: Slot, ply: i16) -> Option<MoveAnnotation> {
+        self.slots[slot as usize].get_annotation_of_current_tape(ply)
+    }
+
+    /// 今のテープの、注釈全部。
+    pub fn get_annotations_of_tape(&self, slot: Slot) -> Vec<MoveAnnotation> {
+        self.slots[slot as usize].get_annotations_of_current_tape()
+    }
+
+    /// 取り外しておいた元の筋を、今のテープの筋に継ぐ。
+    pub fn graft_line_of_tape(&mut self, slot: Slot, line: TapeBranch) {
+        self.slots[slot as usize].graft_line_of_current_tape(line);
+    }
+
+    /// 今のテープの写し。テープが無ければ None。
+    pub fn get_current_tape(&self, slot: Slot) -> Option<CassetteTape> {
+        self.slots[slot as usize].get_current_tape().cloned()
+    }
+
+    /// 今のテープのラベル。テープが無ければ None。
+    pub fn get_label_of_current_tape(&self, slot: Slot) -> Option<&TapeLabel> {
+        self.slots[slot as usize].get_label_of_current_tape()
+    }
+
     pub fn get_ply(&self, slot: Slot) -> i16 {
         self.slots[slot as usize].ply
     }
@@ -196,12 +265,30 @@ impl CassetteDeck {
         app: &Application,
     ) {
         self.slots[slot as usize].insert_note(note, board_size, &app);
+
+        if let Slot::Learning = slot {
+            self.rewrite_learning_journal(board_size, app);
+        }
     }
 
     pub fn is_facing_left_of_current_tape(&self, slot: Slot, _app: &Application) -> bool {
         self.slots[slot as usize].is_facing_left_of_current_tape()
     }
 
+    /// ラーニング・テープ・ボックスが、設定のテープの本数か バイト数に届いていれば真。
+    pub fn is_full_of_learning_tape_box(&self, app: &Application) -> bool {
+        let learning_box = &self.slots[Slot::Learning as usize];
+
+        let max_tapes = app.kw29_conf.learning_box_max_tapes;
+        if 0 < max_tapes && max_tapes <= learning_box.len_tapes() {
+            return true;
+        }
+
+        let max_bytes = app.kw29_conf.learning_box_max_bytes;
+        0 < max_bytes
+            && fs::metadata(learning_box.get_file_name()).is_ok_and(|meta| max_bytes <= meta.len())
+    }
+
     pub fn is_none_current_tape(&self, slot: Slot) -> bool {
         self.slots[slot as usize].is_none_current_tape()
     }
@@ -221,6 +308,22 @@ impl CassetteDeck {
         self.slots[slot as usize].turn_caret_towards_negative_infinity(&app);
     }
 
+    // #####
+    // # O #
+    // #####
+
+    /// 対局を始める時に、ラーニング・テープのジャーナルを作り直す。
+    pub fn open_learning_journal(&mut self, app: &Application) {
+        let box_file = self.slots[Slot::Learning as usize].get_file_name();
+        self.journal = match LearningJournal::open(&box_file, app) {
+            Ok(journal) => Some(journal),
+            Err(err) => {
+                app.comm.println(&format!("#Journal open fail. {}", err));
+                None
+            }
+        };
+    }
+
     // #####
     // # P #
     // #####
@@ -229,8 +332,117 @@ impl CassetteDeck {
     pub fn push_note(&mut self, slot: Slot, note: ShogiNote) {
         self.slots[slot as usize].push_note(note);
     }
-    pub fn pop_note(&mut self, slot: Slot) -> Option<ShogiNote> {
-        self.slots[slot as usize].pop_note()
+    /// 正の方のテープの末端のノートを削除。
+    pub fn pop_note(
+        &mut self,
+        slot: Slot,
+        board_size: BoardSize,
+        app: &Application,
+    ) -> Option<ShogiNote> {
+        let note = self.slots[slot as usize].pop_note(app);
+
+        if let Slot::Learning = slot {
+            self.rewrite_learning_journal(board_size, app);
+        }
+
+        note
+    }
+    /// 取り外しておいた筋を、今のテープに 取り外す前のとおりに戻す。
+    pub fn put_back_line_of_tape(&mut self, slot: Slot, line: TapeBranch) {
+        self.slots[slot as usize].put_back_line_of_current_tape(line);
+    }
+
+    // #####
+    // # R #
+    // #####
+
+    /// キャレットの所にノートを書いて、キャレットを進める。
+    /// 戻したところから違う手を指したら、元の続きは枝として残る。
+    pub fn record_note(
+        &mut self,
+        slot: Slot,
+        note: ShogiNote,
+        board_size: BoardSize,
+        app: &Application,
+    ) {
+        let changed = self.slots[slot as usize].record_note(note, app);
+
+        if let Slot::Learning = slot {
+            if changed {
+                self.rewrite_learning_journal(board_size, app);
+            } else {
+                self.append_learning_journal(&note, board_size, app);
+            }
+        }
+    }
+
+    /// 今のテープを、キャレットごと丸ごと差し替える。
+    pub fn replace_current_tape(
+        &mut self,
+        slot: Slot,
+        tape: CassetteTape,
+        board_size: BoardSize,
+        app: &Application,
+    ) {
+        self.slots[slot as usize].replace_current_tape(tape);
+
+        if let Slot::Learning = slot {
+            self.rewrite_learning_journal(board_size, app);
+        }
+    }
+
+    /// ジャーナルを、今のラーニング・テープの筋で書き直す。
+    fn rewrite_learning_journal(&mut self, board_size: BoardSize, app: &Application) {
+        let notes = self.slots[Slot::Learning as usize].get_notes_of_current_tape();
+        let failed = match self.journal {
+            Some(ref mut journal) => journal
+                .clear()
+                .and_then(|_| {
+                    notes
+                        .iter()
+                        .try_for_each(|note| journal.append(note, board_size))
+                })
+                .err(),
+            None => None,
+        };
+        if let Some(err) = failed {
+            app.comm.println(&format!("#Journal rewrite fail. {}", err));
+            self.journal = None;
+        }
+    }
+
+    /// 書き終えたラーニング・テープ・ボックスを閉じて、索引に載せる。
+    /// 次からは、新しいファイル名の空っぽの箱にテープを足していく。
+    pub fn roll_over_learning_tape_box(&mut self, app: &Application) {
+        let learning_box = &mut self.slots[Slot::Learning as usize];
+        let file = learning_box.get_file_name();
+        if app.is_debug() {
+            app.comm
+                .println(&format!("[#Roll over learning tape box: '{}']", file));
+        }
+
+        // 捨てたテープも まだ読んでいないテープも、箱の並びで数える。
+        let entry = RpmTapeBoxIndexEntry {
+            file: file.to_string(),
+            tapes: learning_box.len_tapes(),
+            bytes: fs::metadata(&file).map_or(0, |meta| meta.len()),
+            first_tape: learning_box.get_name_of_first_tape().unwrap_or_default(),
+            last_tape: learning_box.get_name_of_last_tape().unwrap_or_default(),
+            ..Default::default()
+        };
+
+        // 索引に載せられなくても、箱は書き終わっているので 対局は続ける☆（＾～＾）
+        let index_file = app.kw29_conf.get_learning_index_file(app);
+        if let Err(err) = RpmTapeBoxIndex::push_closed_box(&index_file, entry, app) {
+            app.comm
+                .println(&format!("#Learning index write fail. {}", err));
+        }
+
+        self.clear_of_tapes(Slot::Learning, app);
+        self.set_file_name_without_extension_of_tape_box(
+            Slot::Learning,
+            &RpmTapeBox::create_file_full_name_without_extension(&app.kw29_conf, app),
+        );
     }
 
     // #####
@@ -246,11 +458,48 @@ impl CassetteDeck {
             .set_file_name_without_extension(tape_box_file_name_without_extension);
     }
 
+    /// テープの、指し手ごとの注釈を書く。
+    pub fn set_annotations_of_tape(&mut self, slot: Slot, annotations: &[MoveAnnotation]) {
+        self.slots[slot as usize].set_annotations_of_tape(annotations);
+    }
+
     /// テープのラベルを書く。
     pub fn set_label_of_tape(&mut self, slot: Slot, label: &TapeLabel) {
         self.slots[slot as usize].set_label_of_tape(label);
     }
 
+    pub fn set_ply(&mut self, slot: Slot, ply: i16) {
+        self.slots[slot as usize].ply = ply;
+    }
+
+    /// テープの、手目の注釈に 読み筋を書く。
+    pub fn set_principal_variation_of_tape(&mut self, slot: Slot, ply: i16, pv: &[String]) {
+        self.slots[slot as usize].set_principal_variation_of_tape(ply, pv);
+    }
+
+    /// キャレットの所で分かれる枝の nth 番目に乗り換える。
+    ///
+    /// # Returns
+    ///
+    /// 乗り換えたら真。そんな枝が無ければ偽。
+    pub fn switch_branch_at_caret(
+        &mut self,
+        slot: Slot,
+        nth: usize,
+        board_size: BoardSize,
+        app: &Application,
+    ) -> bool {
+        let switched = self.slots[slot as usize].switch_branch_at_caret(nth);
+
+        if switched {
+            if let Slot::Learning = slot {
+                self.rewrite_learning_journal(board_size, app);
+            }
+        }
+
+        switched
+    }
+
     /// 次のテープを利用するぜ☆（＾～＾）
     /// 次のテープが無ければ、おわり☆（＾ｑ＾）
     ///
@@ -261,6 +510,34 @@ impl CassetteDeck {
         self.slots[slot as usize].seek_of_tapes(&app)
     }
 
+    /// テープのキャレットを、caret 番地へ飛ばす。
+    ///
+    /// # Returns
+    ///
+    /// 通り過ぎたノート。局面で触る順。飛べなければ None。
+    pub fn seek_to_caret_of_tape(
+        &mut self,
+        slot: Slot,
+        caret: i16,
+        app: &Application,
+    ) -> Option<Vec<ShogiNote>> {
+        self.slots[slot as usize].seek_to_caret_of_tape(caret, app)
+    }
+
+    /// テープのキャレットを、ply 手目の指し手の頭へ飛ばす。
+    ///
+    /// # Returns
+    ///
+    /// 通り過ぎたノート。局面で触る順。その手目が無ければ None。
+    pub fn seek_to_ply_of_tape(
+        &mut self,
+        slot: Slot,
+        ply: i16,
+        app: &Application,
+    ) -> Option<Vec<ShogiNote>> {
+        self.slots[slot as usize].seek_to_ply_of_tape(ply, app)
+    }
+
     pub fn step_in_of_tape(&self, slot: Slot) -> i16 {
         self.slots[slot as usize].step_in_of_tape()
     }
@@ -301,6 +578,11 @@ impl CassetteDeck {
     // # T #
     // #####
 
+    /// 今のテープの筋を、枝も注釈も丸ごと取り外す。テープが無ければ None。
+    pub fn take_line_of_tape(&mut self, slot: Slot) -> Option<TapeBranch> {
+        self.slots[slot as usize].take_line_of_current_tape()
+    }
+
     pub fn to_human_presentable_of_current_tape_of_training_box(
         &self,
         board_size: BoardSize,
@@ -314,6 +596,13 @@ impl CassetteDeck {
     pub fn to_human_presentable_of_tape_box(&self, slot: Slot) -> String {
         self.slots[slot as usize].to_human_presentable()
     }
+    pub fn to_human_presentable_of_branches_at_caret(
+        &self,
+        slot: Slot,
+        board_size: BoardSize,
+    ) -> Vec<String> {
+        self.slots[slot as usize].to_human_presentable_of_branches_at_caret(board_size)
+    }
 
     // #####
     // # W #
@@ -324,8 +613,21 @@ impl CassetteDeck {
         self.slots[Slot::Learning as usize].write_current_tapes_fragment(board_size, &app);
     }
 
+    /// ラーニング・テープ・ボックスを書きだすぜ☆（＾～＾）ジャーナルは消す。
+    /// 満杯になったら、その箱は閉じて 次の対局からは新しい箱に書く。
     pub fn write_tape_box(&mut self, board_size: BoardSize, app: &Application) {
         self.slots[Slot::Learning as usize].write_tape_box(board_size, &app);
+
+        // テープ・ボックスに書けたので、ジャーナルはもう要らない。
+        if let Some(journal) = self.journal.take() {
+            if let Err(err) = journal.close() {
+                app.comm.println(&format!("#Journal close fail. {}", err));
+            }
+        }
+
+        if self.is_full_of_learning_tape_box(app) {
+            self.roll_over_learning_tape_box(app);
+        }
     }
 
     pub fn to_human_presentable(&self) -> String {
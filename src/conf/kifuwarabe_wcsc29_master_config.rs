@@ -1,5 +1,7 @@
 use conf::kifuwarabe_wcsc29_exe_config::*;
 use serde::Deserialize;
+use sheet_music_format::parse_error::ParseError;
+use sheet_music_format::tape_label_query::TapeLabelQuery;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
@@ -16,8 +18,25 @@ pub struct KifuwarabeWcsc29MasterConfig {
     pub learning: String,
     pub book: String,
     pub tapes_fragments: String,
+
+    // ラーニング・テープ・ボックス１箱に入れるテープの本数。超えたら次の箱にする。0 なら上限なし。
+    #[serde(default = "KifuwarabeWcsc29MasterConfig::default_learning_box_max_tapes")]
+    pub learning_box_max_tapes: usize,
+    // ラーニング・テープ・ボックス１箱のバイト数。超えたら次の箱にする。0 なら上限なし。
+    #[serde(default)]
+    pub learning_box_max_bytes: u64,
+    // トレーニング・テープを テープ・ラベルで絞り込む。空っぽなら全部使う。 例: `player~"羽生" handicap=平手`
+    #[serde(default)]
+    pub training_query: String,
+    // training_query を読んだもの。起動時に１回だけ読む。
+    #[serde(skip)]
+    pub training_filter: TapeLabelQuery,
 }
 impl KifuwarabeWcsc29MasterConfig {
+    fn default_learning_box_max_tapes() -> usize {
+        500
+    }
+
     /// 設定ファイル読込。
     pub fn load(my_app_conf: &KifuwarabeWcsc29ExeConfig) -> KifuwarabeWcsc29MasterConfig {
         let kw29_path = &my_app_conf.kifuwarabe_wcsc29_master_config_path;
@@ -38,6 +57,21 @@ impl KifuwarabeWcsc29MasterConfig {
         }
     }
 
+    /// training_query を読んで training_filter にする。
+    /// 読めなければ 絞り込まずに全部使うので、エラーは知らせるだけでいい。
+    pub fn parse_training_query(&mut self) -> Result<(), ParseError> {
+        match TapeLabelQuery::parse(&self.training_query) {
+            Ok(query) => {
+                self.training_filter = query;
+                Ok(())
+            }
+            Err(err) => {
+                self.training_filter = TapeLabelQuery::default();
+                Err(err)
+            }
+        }
+    }
+
     /// 実行ファイルへのパス。
     pub fn get_kifuwarabe_wcsc29_exe(&self, app: &Application) -> String {
         Path::new(&self.kifuwarabe_wcsc29_opt)
@@ -104,4 +138,60 @@ impl KifuwarabeWcsc29MasterConfig {
             .unwrap_or_else(|| panic!(app.comm.panic("Fail. get_jammed_directory_of_converter.")))
             .to_string()
     }
+
+    /// 変換の報告を置くディレクトリーへのパス。
+    pub fn get_report_directory_of_converter(&self, app: &Application) -> String {
+        Path::new(&self.converter_var_lib)
+            .join("report")
+            .to_str()
+            .unwrap_or_else(|| {
+                panic!(
+                    "{}",
+                    app.comm.panic("Fail. get_report_directory_of_converter.")
+                )
+            })
+            .to_string()
+    }
+
+    /// 満杯になったラーニング・テープ・ボックスの索引ファイルへのパス。
+    /// 中身は JSON だが、テープ・ボックスと取り違えないように 拡張子は .json にしない。
+    pub fn get_learning_index_file(&self, app: &Application) -> String {
+        Path::new(&self.learning)
+            .join("learning-index.txt")
+            .to_str()
+            .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. get_learning_index_file.")))
+            .to_string()
+    }
+
+    /// 対局中のラーニング・テープのジャーナル・ファイルへのパス。
+    pub fn get_learning_journal_file(&self, app: &Application) -> String {
+        Path::new(&self.learning)
+            .join("learning-journal.txt")
+            .to_str()
+            .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. get_learning_journal_file.")))
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sheet_music_format::tape_label::TapeLabel;
+
+    /// 読めない条件なら、前の条件も捨てて 絞り込まない。
+    #[test]
+    fn falls_back_to_no_filter_on_bad_query() {
+        let mut conf = Application::new_for_test().kw29_conf;
+        let mut label = TapeLabel::new();
+        label.set_handicap("平手");
+
+        conf.training_query = "handicap=香落ち".to_string();
+        assert!(conf.parse_training_query().is_ok());
+        assert!(!conf.training_filter.matches(&label));
+
+        conf.training_query = "handicap=香落ち color=red".to_string();
+        assert!(conf.parse_training_query().is_err());
+        assert!(conf.training_filter.is_empty());
+        assert!(conf.training_filter.matches(&label));
+    }
 }
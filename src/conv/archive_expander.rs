@@ -0,0 +1,257 @@
+use encoding_rs::*;
+use flate2::read::GzDecoder;
+use sheet_music_format::parse_error::*;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tar::Archive;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+/// 棋譜をまとめたアーカイブ (.zip, .tar, .tar.gz) を展開する。
+/// floodgate の年ごとの .zip など。
+pub struct ArchiveExpander {}
+impl ArchiveExpander {
+    /// アーカイブの拡張子。小文字。
+    pub const EXTENSIONS: [&'static str; 4] = [".zip", ".tar", ".tar.gz", ".tgz"];
+
+    /// 展開先のディレクトリー名は、アーカイブのファイル名にこれを付けたもの。
+    /// 展開したファイルを converted ディレクトリーへ移しても、アーカイブとぶつからない。
+    pub const DIRECTORY_SUFFIX: &'static str = ".d";
+
+    /// 報告とエラーに書く形式の名前。
+    pub const FORMAT: &'static str = "ARCHIVE";
+
+    // #####
+    // # D #
+    // #####
+
+    /// アーカイブの中のファイル名。日本語の Windows で作ったアーカイブは Shift_JIS のことが多い。
+    fn decode_name(bytes: &[u8]) -> String {
+        match std::str::from_utf8(bytes) {
+            Ok(name) => name.to_string(),
+            Err(_err) => SHIFT_JIS.decode_without_bom_handling(bytes).0.into_owned(),
+        }
+    }
+
+    // #####
+    // # E #
+    // #####
+
+    /// アーカイブの中のファイルを、ディレクトリーの下に展開する。
+    /// アーカイブの中のディレクトリー構成は保つ。
+    /// 途中で読めなくなったら、途中まで展開したディレクトリーは消す。残しておくと、次に変換する時に拾ってしまう。
+    ///
+    /// # Returns
+    ///
+    /// 展開したファイルの数。
+    pub fn expand(archive: &Path, directory: &Path) -> Result<usize, ParseError> {
+        let name = archive.to_string_lossy().to_string();
+        let lower_name = name.to_lowercase();
+        let result = File::open(archive)
+            .map_err(|err| ParseError::from_io(ArchiveExpander::FORMAT, &name, &err))
+            .and_then(|file| {
+                if lower_name.ends_with(".zip") {
+                    ArchiveExpander::expand_zip(file, directory, &name)
+                } else if lower_name.ends_with(".tar") {
+                    ArchiveExpander::expand_tar(file, directory, &name)
+                } else {
+                    ArchiveExpander::expand_tar(GzDecoder::new(file), directory, &name)
+                }
+            });
+
+        if result.is_err() && directory.exists() {
+            // 消せなくても、展開できなかったことの方を返す。
+            let _ = fs::remove_dir_all(directory);
+        }
+        result
+    }
+
+    fn expand_tar<R: Read>(reader: R, directory: &Path, name: &str) -> Result<usize, ParseError> {
+        let to_error = |err: io::Error| ParseError::from_io(ArchiveExpander::FORMAT, name, &err);
+        let mut count = 0;
+        let mut tar = Archive::new(reader);
+        for entry in tar.entries().map_err(to_error)? {
+            let mut entry = entry.map_err(to_error)?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let relative = ArchiveExpander::to_safe_relative_path(&ArchiveExpander::decode_name(
+                &entry.path_bytes(),
+            ));
+            if let Some(relative) = relative {
+                ArchiveExpander::write_entry(&mut entry, &directory.join(relative))
+                    .map_err(to_error)?;
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    fn expand_zip(file: File, directory: &Path, name: &str) -> Result<usize, ParseError> {
+        let to_error = |err: ZipError| {
+            ParseError::new(ArchiveExpander::FORMAT, &err.to_string()).with_file(name)
+        };
+        let mut count = 0;
+        let mut zip = ZipArchive::new(file).map_err(to_error)?;
+        for i in 0..zip.len() {
+            let mut entry = zip.by_index(i).map_err(to_error)?;
+            if entry.is_dir() {
+                continue;
+            }
+
+            let relative = ArchiveExpander::to_safe_relative_path(&ArchiveExpander::decode_name(
+                entry.name_raw(),
+            ));
+            if let Some(relative) = relative {
+                ArchiveExpander::write_entry(&mut entry, &directory.join(relative))
+                    .map_err(|err| ParseError::from_io(ArchiveExpander::FORMAT, name, &err))?;
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    // #####
+    // # G #
+    // #####
+
+    /// 展開先のディレクトリー。 `{ディレクトリー}/{アーカイブの相対パス}.d`。
+    pub fn get_directory_of_expanded(expanded: &Path, relative: &Path) -> PathBuf {
+        let mut directory = expanded.join(relative).into_os_string();
+        directory.push(ArchiveExpander::DIRECTORY_SUFFIX);
+        PathBuf::from(directory)
+    }
+
+    /// 展開したファイルの相対パスから、元のアーカイブの相対パスを探す。
+    /// 展開先は `expanded/{アーカイブの相対パス}.d/{アーカイブの中のパス}` なので、
+    /// 先頭から見て 最初に `{アーカイブの名前}.d` になっているところまで。
+    ///
+    /// # Returns
+    ///
+    /// 区切りは `/`。アーカイブからでなければ空っぽ。
+    pub fn get_archive_of_expanded(relative: &Path) -> String {
+        let mut archive: Vec<String> = Vec::new();
+        for component in relative.components() {
+            let name = component.as_os_str().to_string_lossy().to_string();
+            if name.ends_with(ArchiveExpander::DIRECTORY_SUFFIX) {
+                let archive_name = &name[..name.len() - ArchiveExpander::DIRECTORY_SUFFIX.len()];
+                if ArchiveExpander::is_archive(Path::new(archive_name)) {
+                    archive.push(archive_name.to_string());
+                    return archive.join("/");
+                }
+            }
+            archive.push(name);
+        }
+
+        "".to_string()
+    }
+
+    // #####
+    // # I #
+    // #####
+
+    /// 拡張子で、アーカイブか判定する。
+    pub fn is_archive(file: &Path) -> bool {
+        let name = file.to_string_lossy().to_lowercase();
+        ArchiveExpander::EXTENSIONS
+            .iter()
+            .any(|extension| name.ends_with(extension))
+    }
+
+    // #####
+    // # T #
+    // #####
+
+    /// アーカイブの中のパスを、展開先からはみ出さない相対パスにする。
+    /// `..` や 絶対パスが入っていれば、そのファイルは展開しない。
+    fn to_safe_relative_path(name: &str) -> Option<PathBuf> {
+        let mut path = PathBuf::new();
+        for part in name.split(['/', '\\']) {
+            match part {
+                "" | "." => {}
+                ".." => return None,
+                _ if part.contains(':') => return None,
+                _ => path.push(part),
+            }
+        }
+
+        if path.as_os_str().is_empty() {
+            None
+        } else {
+            Some(path)
+        }
+    }
+
+    // #####
+    // # W #
+    // #####
+
+    fn write_entry<R: Read>(reader: &mut R, destination: &Path) -> io::Result<()> {
+        // ディレクトリー作成。
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // 全文上書き☆（＾～＾）
+        let mut file_obj = File::create(destination)?;
+        io::copy(reader, &mut file_obj)?;
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use studio::application::Application;
+    use tar::{Builder, Header};
+
+    /// 縮まないように、でたらめに並べたバイト列。
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut x = seed;
+        (0..len)
+            .map(|_| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (x >> 16) as u8
+            })
+            .collect()
+    }
+
+    /// 途中で切れたアーカイブは エラーを返し、途中まで展開したディレクトリーは残さない。
+    #[test]
+    fn removes_partly_expanded_directory_of_truncated_archive() {
+        let mut builder = Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (name, seed) in &[("a.csa", 1), ("b.csa", 2)] {
+            let data = noise(8192, *seed);
+            let mut header = Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, data.as_slice())
+                .unwrap_or_else(|err| panic!("{}", err));
+        }
+        let bytes = builder
+            .into_inner()
+            .and_then(|encoder| encoder.finish())
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        // a.csa は読み切れて、b.csa の途中で切れる。
+        let archive = Application::write_test_file(
+            "archive-expander/truncated.tar.gz",
+            &bytes[..bytes.len() * 3 / 4],
+        );
+        let directory = PathBuf::from(format!("{}{}", archive, ArchiveExpander::DIRECTORY_SUFFIX));
+        match ArchiveExpander::expand(Path::new(&archive), &directory) {
+            Ok(_) => panic!("Unexpected success."),
+            Err(err) => assert_eq!(err.format, ArchiveExpander::FORMAT),
+        }
+        assert!(!directory.exists());
+    }
+}
@@ -0,0 +1,460 @@
+use audio_compo::audio_rack::*;
+use audio_compo::cassette_deck::*;
+use chrono::Utc;
+use conv::archive_expander::*;
+use conv::conversion_report::*;
+use conv::converter::*;
+use instrument::position::*;
+use media::cassette_tape::*;
+use std::ffi::OsStr;
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use studio::application::*;
+
+/// バッチ変換した棋譜ファイル１つの結果。
+pub enum BatchOutcome {
+    /// 全部変換できた。対局がいくつ入っていてもよい。converted ディレクトリーへ。
+    Converted,
+    /// 変換できた対局もあるが、途中で詰まって飛ばしたものがある。jammed ディレクトリーへ。飛ばした数を横に置く。
+    Jammed(usize),
+    /// 変換できなかった。error ディレクトリーへ。理由は横に置く。
+    Error(String),
+}
+impl BatchOutcome {
+    /// 報告に書く結果の名前。
+    pub fn get_name(&self) -> &'static str {
+        match *self {
+            BatchOutcome::Converted => "converted",
+            BatchOutcome::Jammed(_) => "jammed",
+            BatchOutcome::Error(_) => "error",
+        }
+    }
+}
+
+/// input, expanded, encoded ディレクトリーに置かれた棋譜を、全部テープ・フラグメントにする。
+/// input ディレクトリーのアーカイブは、先に expanded ディレクトリーへ展開する。
+/// 変換し終わった棋譜ファイルは converted, jammed, error ディレクトリーのどれかへ移す。
+pub struct BatchConverter {}
+impl BatchConverter {
+    /// 失敗した理由を書くファイルの拡張子。
+    const REASON_EXTENSION: &'static str = "reason.txt";
+
+    // #####
+    // # C #
+    // #####
+
+    /// ディレクトリーの下のファイルを、サブ・ディレクトリーも含めて全部集める。
+    fn collect_files(directory: &Path, files: &mut Vec<PathBuf>) {
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            // ディレクトリーが無ければ、棋譜も無い。
+            Err(_err) => return,
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                BatchConverter::collect_files(&path, files);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+
+    /// パニックを捕まえて、メッセージを返す。
+    fn catch_panic<F: FnOnce() -> T, T>(f: F) -> Result<T, String> {
+        panic::catch_unwind(panic::AssertUnwindSafe(f)).map_err(|payload| {
+            if let Some(message) = payload.downcast_ref::<String>() {
+                message.to_string()
+            } else if let Some(message) = payload.downcast_ref::<&str>() {
+                message.to_string()
+            } else {
+                "Unknown panic.".to_string()
+            }
+        })
+    }
+
+    /// 棋譜ディレクトリーを１回だけ見て、置かれていた棋譜を全部変換する。
+    /// ファイルがあれば、報告を report ディレクトリーに `report-{日時}.json` で書き出す。
+    ///
+    /// # Returns
+    ///
+    /// 処理したファイルごとの報告。アーカイブも入る。
+    pub fn convert_directories(app: &Application) -> ConversionReport {
+        let input = app.kw29_conf.get_input_directory_of_converter(app);
+        let expanded = app.kw29_conf.get_expanded_directory_of_converter(app);
+        let encoded = app.kw29_conf.get_encoded_directory_of_converter(app);
+
+        let mut report = ConversionReport::new();
+
+        // アーカイブを展開する。
+        let mut files = Vec::new();
+        BatchConverter::collect_files(Path::new(&input), &mut files);
+        files.sort();
+        for file in files {
+            if ArchiveExpander::is_archive(&file) {
+                let mut file_report = FileReport::new(&file.to_string_lossy());
+                BatchConverter::expand_file(
+                    Path::new(&input),
+                    &file,
+                    Path::new(&expanded),
+                    &mut file_report,
+                    app,
+                );
+                report.push(file_report);
+            }
+        }
+
+        // 棋譜を変換する。
+        for root in [&input, &expanded, &encoded].iter() {
+            let mut files = Vec::new();
+            BatchConverter::collect_files(Path::new(root), &mut files);
+            files.sort();
+
+            for file in files {
+                // 展開した棋譜なら、テープ・ラベルにアーカイブのパスを残す。
+                let archive = if *root == &expanded {
+                    ArchiveExpander::get_archive_of_expanded(
+                        file.strip_prefix(root).unwrap_or(&file),
+                    )
+                } else {
+                    "".to_string()
+                };
+
+                let mut file_report = FileReport::new(&file.to_string_lossy());
+                BatchConverter::convert_file(
+                    Path::new(root),
+                    &file,
+                    &archive,
+                    &mut file_report,
+                    app,
+                );
+                report.push(file_report);
+            }
+        }
+
+        if !report.files.is_empty() {
+            let report_file = Path::new(&app.kw29_conf.get_report_directory_of_converter(app))
+                .join(format!(
+                    "report-{}.json",
+                    Utc::now().format("%Y%m%d-%H%M%S")
+                ));
+            report.write(&report_file.to_string_lossy(), app);
+        }
+
+        report
+    }
+
+    /// 棋譜ファイル１つを変換して、結果に合わせたディレクトリーへ移す。
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - 棋譜を置くディレクトリー。移動先でも、ここからの相対パスを保つ。
+    /// * `archive` - 展開したアーカイブのパス。アーカイブからでなければ空っぽ。
+    /// * `report` - 結果と、書き出したテープ・フラグメントのファイル名を書き込む。
+    pub fn convert_file(
+        root: &Path,
+        file: &Path,
+        archive: &str,
+        report: &mut FileReport,
+        app: &Application,
+    ) -> BatchOutcome {
+        let relative = file.strip_prefix(root).unwrap_or(file);
+        let file_name = file
+            .to_str()
+            .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. file.to_str.")))
+            .to_string();
+
+        let extension = file
+            .extension()
+            .and_then(OsStr::to_str)
+            .unwrap_or("")
+            .to_uppercase();
+
+        let outcome = if Converter::EXTENSIONS.contains(&extension.as_str()) {
+            // テープ・フラグメントのファイル名は、相対パスの区切りを `$%` にしたもの。
+            let fragment = BatchConverter::get_fragment_file_name_without_extension(relative, app);
+            let fragment_file = format!("{}.tapesfrag", fragment);
+
+            // 前に変換した残りがあれば、追記せず作り直す。
+            let _ = fs::remove_file(&fragment_file);
+
+            let result = BatchConverter::catch_panic(|| {
+                // Position.
+                let mut position = Position::new_honshogi_origin(app);
+
+                // Deck.
+                let mut rack = AudioRack::new(app);
+                let mut tape = CassetteTape::new_facing_right(app);
+                tape.set_file_full_name_without_extension(&fragment);
+                rack.add_tape_to_tape_box(Slot::Learning, tape, app);
+                rack.seek_of_next_tape(Slot::Learning, app);
+
+                Converter::convert_from_archive(
+                    file_name.to_string(),
+                    archive,
+                    &mut rack,
+                    &mut position,
+                    report,
+                    app,
+                )
+            });
+
+            // 読めない棋譜はエラーが返ってくる。パニックも念のため捕まえる。
+            let result = match result {
+                Ok(Ok(games)) => Ok(games),
+                Ok(Err(err)) => Err(err.to_string()),
+                Err(message) => Err(message),
+            };
+
+            match result {
+                Ok(0) => BatchOutcome::Error("No game found.".to_string()),
+                Ok(_games) if !report.errors.is_empty() => {
+                    // 読み飛ばした対局がある。
+                    report.output = fragment_file;
+                    BatchOutcome::Jammed(report.errors.len())
+                }
+                Ok(_games) => {
+                    report.output = fragment_file;
+                    BatchOutcome::Converted
+                }
+                Err(message) => {
+                    // 途中まで書いたテープ・フラグメントは捨てる。
+                    let _ = fs::remove_file(&fragment_file);
+                    report.tapes.clear();
+                    BatchOutcome::Error(message)
+                }
+            }
+        } else {
+            report.format = extension.to_string();
+            BatchOutcome::Error(format!("Unsupported extension: '{}'.", extension))
+        };
+
+        BatchConverter::fill_report(report, &outcome);
+        BatchConverter::move_file(file, relative, &outcome, app);
+        outcome
+    }
+
+    // #####
+    // # E #
+    // #####
+
+    /// アーカイブ１つを `expanded/{アーカイブの相対パス}.d/` の下に展開して、アーカイブを移す。
+    /// 展開できたアーカイブは converted ディレクトリーへ、途中で読めなくなったアーカイブは jammed ディレクトリーへ。
+    ///
+    /// # Arguments
+    ///
+    /// * `report` - 結果と、展開先のディレクトリーを書き込む。
+    pub fn expand_file(
+        root: &Path,
+        file: &Path,
+        expanded: &Path,
+        report: &mut FileReport,
+        app: &Application,
+    ) -> BatchOutcome {
+        let relative = file.strip_prefix(root).unwrap_or(file);
+        let directory = ArchiveExpander::get_directory_of_expanded(expanded, relative);
+        report.format = ArchiveExpander::FORMAT.to_string();
+
+        let outcome = match ArchiveExpander::expand(file, &directory) {
+            Ok(0) => BatchOutcome::Error("No file in the archive.".to_string()),
+            Ok(_files) => {
+                report.output = directory.to_string_lossy().to_string();
+                BatchOutcome::Converted
+            }
+            // 途中で読めなくなったアーカイブ。途中まで展開したものは消してあるので、直して input に戻せば やり直せる。
+            Err(err) => {
+                report.push_error(&err.to_string());
+                BatchOutcome::Jammed(report.errors.len())
+            }
+        };
+
+        BatchConverter::fill_report(report, &outcome);
+        BatchConverter::move_file(file, relative, &outcome, app);
+        outcome
+    }
+
+    // #####
+    // # F #
+    // #####
+
+    /// 結果を報告に書く。変換できなかった理由も。
+    fn fill_report(report: &mut FileReport, outcome: &BatchOutcome) {
+        report.outcome = outcome.get_name().to_string();
+        if let BatchOutcome::Error(ref reason) = *outcome {
+            report.push_error(reason);
+        }
+    }
+
+    // #####
+    // # G #
+    // #####
+
+    /// テープ・フラグメントのファイル名。 .tapesfrag は付けない。
+    /// 名前の同じ .kif と .csa がぶつからないように、棋譜の拡張子は残す。
+    fn get_fragment_file_name_without_extension(relative: &Path, app: &Application) -> String {
+        let name = relative
+            .to_str()
+            .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. relative.to_str.")))
+            .replace(['\\', '/'], "$%");
+
+        Path::new(&app.kw29_conf.tapes_fragments)
+            .join(name)
+            .to_str()
+            .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. tape.fragment.")))
+            .to_string()
+    }
+
+    // #####
+    // # M #
+    // #####
+
+    /// 結果に合わせて、棋譜ファイルを移す。失敗した理由は `{ファイル名}.reason.txt` に書く。
+    fn move_file(file: &Path, relative: &Path, outcome: &BatchOutcome, app: &Application) {
+        let directory = match *outcome {
+            BatchOutcome::Converted => app.kw29_conf.get_converted_directory_of_converter(app),
+            BatchOutcome::Jammed(_) => app.kw29_conf.get_jammed_directory_of_converter(app),
+            BatchOutcome::Error(_) => app.kw29_conf.get_error_directory_of_converter(app),
+        };
+        let destination = Path::new(&directory).join(relative);
+
+        // ディレクトリー作成。
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)));
+        }
+
+        // 別のドライブへは rename できないので、コピーして消す。
+        if fs::rename(file, &destination).is_err() {
+            fs::copy(file, &destination)
+                .unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)));
+            fs::remove_file(file).unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)));
+        }
+
+        match *outcome {
+            BatchOutcome::Converted => {
+                app.comm
+                    .println(&format!("Converted: '{}'.", destination.display()));
+            }
+            BatchOutcome::Jammed(skipped) => {
+                app.comm.println(&format!(
+                    "Jammed: '{}'. Skipped: {}.",
+                    destination.display(),
+                    skipped
+                ));
+            }
+            BatchOutcome::Error(ref reason) => {
+                let reason_file = format!(
+                    "{}.{}",
+                    destination.display(),
+                    BatchConverter::REASON_EXTENSION
+                );
+                fs::write(&reason_file, format!("{}\n", reason))
+                    .unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)));
+                app.comm.println(&format!(
+                    "Error: '{}'. Reason: {}",
+                    destination.display(),
+                    reason
+                ));
+            }
+        }
+    }
+
+    // #####
+    // # W #
+    // #####
+
+    /// 棋譜ディレクトリーを見張って、置かれた棋譜を変換し続ける。
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - 見に行く間隔。秒。
+    pub fn watch_directories(interval: u64, app: &Application) {
+        loop {
+            let report = BatchConverter::convert_directories(app);
+            if !report.files.is_empty() {
+                println!("{}", report.to_human_presentable());
+            }
+            thread::sleep(Duration::from_secs(interval));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 棋譜ファイル１つを input ディレクトリーに置いて、バッチ変換する。
+    fn convert(name: &str, contents: &str, app: &Application) -> (BatchOutcome, FileReport) {
+        let input = app.kw29_conf.get_input_directory_of_converter(app);
+        let file = Application::write_test_file(
+            &Path::new("var-lib")
+                .join("input")
+                .join(name)
+                .to_string_lossy(),
+            contents.as_bytes(),
+        );
+        let mut report = FileReport::new(&file);
+        let outcome =
+            BatchConverter::convert_file(Path::new(&input), Path::new(&file), "", &mut report, app);
+        (outcome, report)
+    }
+
+    /// 対局がいくつ入っていても、全部変換できれば converted へ移す。
+    #[test]
+    fn moves_multi_game_file_to_converted() {
+        let app = Application::new_for_test();
+        let (outcome, report) = convert(
+            "batch-multi-game.csa",
+            "V2.2\nPI\n+\n+2726FU\n%TORYO\n/\nV2.2\nPI\n+\n+7776FU\n-3334FU\n%TORYO\n",
+            &app,
+        );
+        match outcome {
+            BatchOutcome::Converted => {}
+            _ => panic!("Unexpected outcome: {}.", outcome.get_name()),
+        }
+        assert_eq!(report.tapes.len(), 2);
+        assert!(
+            Path::new(&app.kw29_conf.get_converted_directory_of_converter(&app))
+                .join("batch-multi-game.csa")
+                .exists()
+        );
+    }
+
+    /// 読めないアーカイブは jammed へ移し、理由を報告に書く。展開先のディレクトリーは残さない。
+    #[test]
+    fn moves_broken_archive_to_jammed() {
+        let app = Application::new_for_test();
+        let input = app.kw29_conf.get_input_directory_of_converter(&app);
+        let expanded = app.kw29_conf.get_expanded_directory_of_converter(&app);
+        let file = Application::write_test_file(
+            &Path::new("var-lib")
+                .join("input")
+                .join("batch-broken.zip")
+                .to_string_lossy(),
+            b"This is not a zip.",
+        );
+
+        let mut report = FileReport::new(&file);
+        let outcome = BatchConverter::expand_file(
+            Path::new(&input),
+            Path::new(&file),
+            Path::new(&expanded),
+            &mut report,
+            &app,
+        );
+        match outcome {
+            BatchOutcome::Jammed(1) => {}
+            _ => panic!("Unexpected outcome: {}.", outcome.get_name()),
+        }
+        assert_eq!(report.errors.len(), 1);
+        assert!(!Path::new(&expanded).join("batch-broken.zip.d").exists());
+        assert!(
+            Path::new(&app.kw29_conf.get_jammed_directory_of_converter(&app))
+                .join("batch-broken.zip")
+                .exists()
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use serde::*;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::prelude::*;
+use std::path::Path;
+use studio::application::Application;
+
+/// 変換で作ったテープ１本。
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")] // プロパティ名が JSON 側でスネークケースであることを指定
+pub struct TapeReport {
+    // テープ名。
+    pub name: String,
+    // 指し手の数。投了は数えない。
+    pub moves: usize,
+}
+
+/// 棋譜ファイル１つの変換の結果。
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")] // プロパティ名が JSON 側でスネークケースであることを指定
+pub struct FileReport {
+    // 棋譜ファイル名。
+    pub file: String,
+    // 拡張子から決めた形式。 "KIF", "CSA" など。アーカイブなら "ARCHIVE"。
+    pub format: String,
+    // 判定した文字コード。 "UTF-8", "Shift_JIS" など。
+    pub encoding: String,
+    // "converted", "jammed", "error" のどれか。
+    pub outcome: String,
+    // 書き出したテープ・フラグメントのファイル名。
+    pub output: String,
+    // 作ったテープ。変化も１本に数える。
+    pub tapes: Vec<TapeReport>,
+    // 変換はできたが、読み飛ばしたもの。知らないヘッダーなど。
+    pub warnings: Vec<String>,
+    // 変換できなかった理由。読み飛ばした行も入る。
+    pub errors: Vec<String>,
+}
+impl FileReport {
+    pub fn new(file: &str) -> Self {
+        FileReport {
+            file: file.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // #####
+    // # G #
+    // #####
+
+    /// 全部のテープの指し手の数。
+    pub fn get_moves(&self) -> usize {
+        self.tapes.iter().map(|tape| tape.moves).sum()
+    }
+
+    // #####
+    // # P #
+    // #####
+
+    pub fn push_error(&mut self, error: &str) {
+        self.errors.push(error.to_string());
+    }
+
+    pub fn push_tape(&mut self, name: &str, moves: usize) {
+        self.tapes.push(TapeReport {
+            name: name.to_string(),
+            moves,
+        });
+    }
+
+    pub fn push_warnings(&mut self, warnings: &[String]) {
+        self.warnings.extend_from_slice(warnings);
+    }
+}
+
+/// 変換の報告。棋譜ファイルごとの結果を並べる。
+/// JSON で書き出し、人間向けのまとめも作る。
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")] // プロパティ名が JSON 側でスネークケースであることを指定
+pub struct ConversionReport {
+    pub files: Vec<FileReport>,
+}
+impl ConversionReport {
+    pub fn new() -> Self {
+        ConversionReport { files: Vec::new() }
+    }
+
+    // #####
+    // # C #
+    // #####
+
+    /// 結果の数。
+    pub fn count_outcome(&self, outcome: &str) -> usize {
+        self.files
+            .iter()
+            .filter(|file| file.outcome == outcome)
+            .count()
+    }
+
+    // #####
+    // # P #
+    // #####
+
+    pub fn push(&mut self, file: FileReport) {
+        self.files.push(file);
+    }
+
+    // #####
+    // # T #
+    // #####
+
+    /// 人間向けのまとめ。エラーのあったファイルは、理由も並べる。
+    pub fn to_human_presentable(&self) -> String {
+        let mut text = "---------- Conversion report ----------\n".to_string();
+        text = format!(
+            "{}Files: {}. Converted: {}, Jammed: {}, Error: {}.\n",
+            text,
+            self.files.len(),
+            self.count_outcome("converted"),
+            self.count_outcome("jammed"),
+            self.count_outcome("error")
+        );
+        text = format!(
+            "{}Tapes: {}. Moves: {}. Warnings: {}.\n",
+            text,
+            self.files
+                .iter()
+                .map(|file| file.tapes.len())
+                .sum::<usize>(),
+            self.files.iter().map(FileReport::get_moves).sum::<usize>(),
+            self.files
+                .iter()
+                .map(|file| file.warnings.len())
+                .sum::<usize>()
+        );
+
+        for file in &self.files {
+            for error in &file.errors {
+                text = format!("{}  Error: '{}'. {}\n", text, file.file, error);
+            }
+        }
+
+        text
+    }
+
+    // #####
+    // # W #
+    // #####
+
+    /// JSON で書き出す。
+    pub fn write(&self, file_name: &str, app: &Application) {
+        if app.is_debug() {
+            app.comm
+                .println(&format!("#Write report to '{}'...", file_name));
+        }
+
+        let path = Path::new(&file_name);
+
+        // ディレクトリー作成。
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)));
+        }
+
+        // 全文上書き☆（＾～＾）
+        let mut file_obj = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)
+            .unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)));
+
+        let json = serde_json::to_string_pretty(self)
+            .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
+        if let Err(e) = writeln!(file_obj, "{}", json) {
+            eprintln!("Couldn't write to file: {}", e);
+        }
+    }
+}
@@ -1,10 +1,22 @@
 use audio_compo::audio_rack::*;
 use audio_compo::cassette_deck::*;
+use conv::conversion_report::*;
 use instrument::position::*;
 use sheet_music_format::kifu_csa::csa_converter::CsaConverter;
 use sheet_music_format::kifu_csa::csa_tape::*;
+use sheet_music_format::kifu_jkf::jkf_converter::JkfConverter;
+use sheet_music_format::kifu_jkf::jkf_tape::*;
+use sheet_music_format::kifu_ki2::ki2_converter::Ki2Converter;
+use sheet_music_format::kifu_ki2::ki2_tape::*;
 use sheet_music_format::kifu_kif::kif_converter::KifConverter;
 use sheet_music_format::kifu_kif::kif_tape::*;
+use sheet_music_format::kifu_usi::fen::*;
+use sheet_music_format::kifu_usi::usi_converter::*;
+use sheet_music_format::kifu_usi::usi_position::*;
+use sheet_music_format::kifu_usi::usi_tape::*;
+use sheet_music_format::parse_error::*;
+use sheet_music_format::record_encoding::*;
+use sheet_music_format::tape_label::*;
 use std::ffi::OsStr;
 use std::path::Path;
 use studio::application::*;
@@ -12,51 +24,405 @@ use studio::application::*;
 pub struct Converter {}
 
 impl Converter {
+    /// 変換できる拡張子。大文字。
+    pub const EXTENSIONS: [&'static str; 5] = ["KIF", "KI2", "CSA", "JKF", "USI"];
+
+    /// 棋譜ファイル１つを、テープ・フラグメントに変換する。
+    ///
+    /// # Returns
+    ///
+    /// ファイルに入っていた対局の数。変化は数えない。
+    /// 読めなければ、ファイル名と行番号を付けたエラー。
+    ///
+    /// # Arguments
+    ///
+    /// * `report` - 形式、文字コード、作ったテープ、読み飛ばしたものを書き込む。
     pub fn convert(
         in_file: String,
         rack: &mut AudioRack,
         position: &mut Position,
+        report: &mut FileReport,
+        app: &Application,
+    ) -> Result<usize, ParseError> {
+        Converter::convert_from_archive(in_file, "", rack, position, report, app)
+    }
+
+    /// アーカイブから展開した棋譜ファイル１つを、テープ・フラグメントに変換する。
+    ///
+    /// # Arguments
+    ///
+    /// * `archive` - テープ・ラベルに残すアーカイブのパス。アーカイブからでなければ空っぽ。
+    /// * `report` - 形式、文字コード、作ったテープ、読み飛ばしたものを書き込む。
+    ///
+    /// # Returns
+    ///
+    /// ファイルに入っていた対局の数。変化は数えない。
+    /// 読めなければ、ファイル名と行番号を付けたエラー。
+    pub fn convert_from_archive(
+        in_file: String,
+        archive: &str,
+        rack: &mut AudioRack,
+        position: &mut Position,
+        report: &mut FileReport,
+        app: &Application,
+    ) -> Result<usize, ParseError> {
+        report.file = in_file.to_string();
+        if let Ok(encoding) = RecordEncoding::detect_file(&in_file, app) {
+            report.encoding = encoding.name().to_string();
+        }
+
+        // 指し手のエラーは ファイル名を知らないので、ここで付ける。
+        Converter::convert_by_extension(&in_file, archive, rack, position, report, app)
+            .map_err(|err| err.with_file(&in_file))
+    }
+
+    fn convert_by_extension(
+        in_file: &str,
+        archive: &str,
+        rack: &mut AudioRack,
+        position: &mut Position,
+        report: &mut FileReport,
         app: &Application,
-    ) {
+    ) -> Result<usize, ParseError> {
         let extension = Path::new(&in_file)
             .extension()
             .and_then(OsStr::to_str)
-            .unwrap_or_else(|| panic!(app.comm.panic("Fail. get_extension_from_file_path.")))
+            .unwrap_or("")
             .to_uppercase();
+        report.format = extension.to_string();
 
-        match extension.as_str() {
+        let games = match extension.as_str() {
             "KIF" => {
                 // Training data.
-                let mut tape = KifTape::from_file(&in_file, &app);
+                let mut tape = KifTape::from_file(in_file, app)?;
+                report.push_warnings(&tape.warnings);
+                let mut label = tape.get_mut_tape_label().clone();
+                label.set_archive(archive);
+
+                // 本譜と変化を、それぞれ１本のテープにする。
+                let lines = tape.to_lines();
+                let mut names: Vec<String> = Vec::new();
+                for (i, line) in lines.iter().enumerate() {
+                    if 0 < i {
+                        // 変化は 初期局面から指し直す。
+                        position.repeat_origin_position(app);
+                        rack.clear_tape_body(Slot::Learning, app);
+                    }
+
+                    // Play out.
+                    let moves = KifConverter::play_out_kif_moves(&line.moves, rack, position, app)?;
+
+                    // コメントと消費時間。
+                    rack.set_annotations_of_tape(Slot::Learning, &tape.to_annotations(line));
+
+                    // Tape label
+                    let line_label = if 0 < i {
+                        label.create_variation_label(i, line.branch_ply, &names[line.parent])
+                    } else {
+                        label.clone()
+                    };
+                    names.push(line_label.get_name());
+                    rack.set_label_of_tape(Slot::Learning, &line_label);
+
+                    // Write.
+                    rack.write_leaning_tapes_fragment(position.get_board_size(), app);
+                    report.push_tape(&line_label.get_name(), moves);
+                }
+
+                1
+            }
+            "KI2" => {
+                // Training data.
+                let mut tape = Ki2Tape::from_file(in_file, app)?;
+                report.push_warnings(&tape.warnings);
 
                 // Play out.
-                KifConverter::play_out_kifu_tape(&tape, rack, position, &app);
+                let moves = Ki2Converter::play_out_ki2_tape(&tape, rack, position, app)?;
+
+                // コメント。
+                rack.set_annotations_of_tape(Slot::Learning, &tape.to_annotations());
 
                 // Tape label
+                tape.get_mut_tape_label().set_archive(archive);
                 rack.set_label_of_tape(Slot::Learning, tape.get_mut_tape_label());
 
                 // Write.
-                rack.write_leaning_tapes_fragment(position.get_board_size(), &app);
+                rack.write_leaning_tapes_fragment(position.get_board_size(), app);
+                report.push_tape(&tape.get_mut_tape_label().get_name(), moves);
+
+                1
             }
             "CSA" => {
                 // Training data.
-                let mut tape = CsaTape::from_file(&in_file, &app);
+                let mut tapes = CsaTape::from_file(in_file, app)?;
+                for tape in &tapes {
+                    report.push_warnings(&tape.warnings);
+                }
+
+                // 対局を、それぞれ１本のテープにする。
+                for (i, tape) in tapes.iter_mut().enumerate() {
+                    if 0 < i {
+                        // 次の対局は 初期局面から指し直す。
+                        position.repeat_origin_position(app);
+                        rack.clear_tape_body(Slot::Learning, app);
+                    }
+
+                    if app.is_debug() {
+                        app.comm
+                            .println(&format!("Ctape: '{}'", tape.to_human_presentable()));
+                    }
+
+                    // Play out.
+                    let moves = CsaConverter::play_out_csa_tape(tape, rack, position, app)?;
+
+                    // コメントと消費時間。
+                    rack.set_annotations_of_tape(Slot::Learning, &tape.annotations);
 
-                if app.is_debug() {
-                    app.comm
-                        .println(&format!("Ctape: '{}'", tape.to_human_presentable()));
+                    // Tape label
+                    tape.get_mut_tape_label().set_archive(archive);
+                    rack.set_label_of_tape(Slot::Learning, tape.get_mut_tape_label());
+
+                    // Write.
+                    rack.write_leaning_tapes_fragment(position.get_board_size(), app);
+                    report.push_tape(&tape.get_mut_tape_label().get_name(), moves);
                 }
 
-                // Play out.
-                CsaConverter::play_out_csa_tape(&tape, rack, position, &app);
+                tapes.len()
+            }
+            "JKF" => {
+                // Training data.
+                let mut tape = JkfTape::from_file(in_file, app)?;
+                report.push_warnings(&tape.warnings);
+                let mut label = tape.get_mut_tape_label().clone();
+                label.set_archive(archive);
 
-                // Tape label
-                rack.set_label_of_tape(Slot::Learning, tape.get_mut_tape_label());
+                // 本譜と変化を、それぞれ１本のテープにする。
+                let lines = tape.to_lines();
+                let mut names: Vec<String> = Vec::new();
+                for (i, line) in lines.iter().enumerate() {
+                    if 0 < i {
+                        // 変化は 初期局面から指し直す。
+                        position.repeat_origin_position(app);
+                        rack.clear_tape_body(Slot::Learning, app);
+                    }
 
-                // Write.
-                rack.write_leaning_tapes_fragment(position.get_board_size(), &app);
+                    // Play out.
+                    let moves = JkfConverter::play_out_jkf_line(&tape, line, rack, position, app)?;
+
+                    // コメントと消費時間。
+                    rack.set_annotations_of_tape(Slot::Learning, &tape.to_annotations(line));
+
+                    // Tape label
+                    let line_label = if 0 < i {
+                        label.create_variation_label(i, line.branch_ply, &names[line.parent])
+                    } else {
+                        label.clone()
+                    };
+                    names.push(line_label.get_name());
+                    rack.set_label_of_tape(Slot::Learning, &line_label);
+
+                    // Write.
+                    rack.write_leaning_tapes_fragment(position.get_board_size(), app);
+                    report.push_tape(&line_label.get_name(), moves);
+                }
+
+                1
+            }
+            "USI" => {
+                // `position startpos moves ...` １行が対局１つ。自己対局の棋譜は、何行も続く。
+                let lines = UsiTape::read_position_lines(in_file, app)?;
+
+                let mut label = TapeLabel::new();
+                label.set_name(
+                    Path::new(in_file)
+                        .file_stem()
+                        .and_then(OsStr::to_str)
+                        .ok_or_else(|| ParseError::new("USI", "No file stem."))?,
+                );
+                label.set_format("USI");
+                label.set_archive(archive);
+
+                // 読めない行は知らせて飛ばす。１行も読めなければ、最初のエラーを返す。
+                let mut games = 0;
+                let mut first_error = None;
+                for (line_number, line) in &lines {
+                    // 対局が２つ以上あれば、テープ名の後ろに行番号を付ける。
+                    let mut line_label = label.clone();
+                    if 1 < lines.len() {
+                        line_label.set_name(&format!("{} #{}", label.get_name(), line_number));
+                    }
+
+                    // 平手でない sfen は 大橋流から書くテープにできないので、知らせて飛ばす。
+                    let mut start = 0;
+                    if let Some(false) = UsiPosition::parse_startpos_test(
+                        &UsiPosition::normalize_line(line),
+                        &mut start,
+                        &app.comm,
+                    ) {
+                        let warning = format!(
+                            "Line {}: Skip. Only the hirate sfen can be converted.",
+                            line_number
+                        );
+                        app.comm.println(&warning);
+                        report.push_warnings(&[warning]);
+                        continue;
+                    }
+
+                    match Converter::convert_usi_line(line, &line_label, rack, position, app) {
+                        Ok(moves) => {
+                            games += 1;
+                            report.push_tape(&line_label.get_name(), moves);
+                        }
+                        Err(err) => {
+                            let err = err.with_file(in_file).with_line(*line_number, line);
+                            app.comm.println(&format!("Skip: {}", err));
+                            report.push_error(&err.to_string());
+                            if first_error.is_none() {
+                                first_error = Some(err);
+                            }
+                        }
+                    }
+                }
+
+                match first_error {
+                    Some(err) if games == 0 => return Err(err),
+                    _ => games,
+                }
+            }
+            _ => {
+                print!("Pass extension: {}", extension);
+                0
+            }
+        };
+
+        Ok(games)
+    }
+
+    /// USI の `position startpos moves ...` １行を、テープ１本にする。
+    /// テープは大橋流で駒を並べるところから書くので、sfen は 平手の初期局面のものだけ変換できる。
+    /// 先頭の `position ` は省いてもよい。
+    ///
+    /// # Returns
+    ///
+    /// 指し手の数。局面に合わない指し手があれば、エラー。
+    pub fn convert_usi_line(
+        line: &str,
+        label: &TapeLabel,
+        rack: &mut AudioRack,
+        position: &mut Position,
+        app: &Application,
+    ) -> Result<usize, ParseError> {
+        let line = &UsiPosition::normalize_line(line);
+        let mut start = 0;
+        let error =
+            |message: &str| -> ParseError { ParseError::new("USI", message).with_text(line) };
+        match UsiPosition::parse_startpos_test(line, &mut start, &app.comm) {
+            Some(true) => {}
+            Some(false) => {
+                return Err(error(
+                    "Only the hirate sfen can be converted. Tapes start from the Ohashi opening.",
+                ))
             }
-            _ => print!("Pass extension: {}", extension),
+            None => return Err(error("Not a usi position.")),
         }
+
+        // 指し手を読む。局面は動かさない。
+        let mut start = 0;
+        Fen::parse_initial_position(line, &mut start, position, rack, app)?;
+        let utape =
+            UsiPosition::parse_usi_line_moves(line, &mut start, position.get_board_size(), app)?
+                .unwrap_or_default();
+
+        // 初期局面に戻して、大橋流から指し直す。
+        let mut start = 0;
+        Fen::parse_initial_position(line, &mut start, position, rack, app)?;
+
+        // Play out.
+        UsiConverter::play_out_usi_tape(position, &utape, rack, app)?;
+
+        // Tape label
+        rack.set_label_of_tape(Slot::Learning, label);
+
+        // Write.
+        rack.write_leaning_tapes_fragment(position.get_board_size(), app);
+
+        Ok(utape.moves.len())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use media::cassette_tape::CassetteTape;
+
+    #[test]
+    fn converts_usi_lines_and_skips_illegal_ones() {
+        let app = Application::new_for_test();
+        let file = Application::write_test_file(
+            "converter/self-play.usi",
+            b"position startpos moves 7g7f 3c3d 8h2b+ 3a2b
+startpos moves 7g7f 7g7f
+position sfen 8k/9/9/9/9/9/9/9/4K4 b - 1 moves 5i5h
+position startpos moves 2g2f P*5e
+",
+        );
+        let fragment = Application::write_test_file("converter/self-play", b"");
+
+        let mut position = Position::new_honshogi_origin(&app);
+        let mut rack = AudioRack::new(&app);
+        let mut tape = CassetteTape::new_facing_right(&app);
+        tape.set_file_full_name_without_extension(&fragment);
+        rack.add_tape_to_tape_box(Slot::Learning, tape, &app);
+        rack.seek_of_next_tape(Slot::Learning, &app);
+
+        let mut report = FileReport::new(&file);
+        let games = Converter::convert(file, &mut rack, &mut position, &mut report, &app)
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        assert_eq!(games, 1);
+        assert_eq!(report.tapes.len(), 1);
+        assert_eq!(report.tapes[0].name, "self-play #1");
+        assert_eq!(report.tapes[0].moves, 4);
+
+        // 局面に合わない指し手と、持っていない駒を打つ行は 飛ばす。
+        assert_eq!(report.errors.len(), 2);
+        assert!(report.errors[0].contains(":2:"));
+        assert!(report.errors[0].contains("No piece to move. Ply: 2."));
+        assert!(report.errors[1].contains(":4:"));
+        assert!(report.errors[1].contains("No piece to move. Ply: 2."));
+
+        // 平手でない sfen の行は、エラーにせず 知らせて飛ばす。
+        assert_eq!(
+            report.warnings,
+            vec!["Line 3: Skip. Only the hirate sfen can be converted."]
+        );
+    }
+
+    #[test]
+    fn warns_and_skips_non_hirate_sfen() {
+        let app = Application::new_for_test();
+        let file = Application::write_test_file(
+            "converter/tsume.usi",
+            b"position sfen 8k/9/9/9/9/9/9/9/4K4 b G 1 moves G*1b\n",
+        );
+        let fragment = Application::write_test_file("converter/tsume", b"");
+
+        let mut position = Position::new_honshogi_origin(&app);
+        let mut rack = AudioRack::new(&app);
+        let mut tape = CassetteTape::new_facing_right(&app);
+        tape.set_file_full_name_without_extension(&fragment);
+        rack.add_tape_to_tape_box(Slot::Learning, tape, &app);
+        rack.seek_of_next_tape(Slot::Learning, &app);
+
+        let mut report = FileReport::new(&file);
+        let games = Converter::convert(file, &mut rack, &mut position, &mut report, &app)
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        assert_eq!(games, 0);
+        assert!(report.tapes.is_empty());
+        assert!(report.errors.is_empty());
+        assert_eq!(
+            report.warnings,
+            vec!["Line 1: Skip. Only the hirate sfen can be converted."]
+        );
     }
 }
@@ -0,0 +1,391 @@
+use serde_json::Value;
+use sheet_music_format::kifu_rpm::rpm_migration::*;
+use sheet_music_format::kifu_rpm::rpm_tape::*;
+use sheet_music_format::kifu_rpm::rpm_tape_box::*;
+use sheet_music_format::kifu_rpm::rpm_tape_validator::*;
+use sheet_music_format::parse_error::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use studio::application::*;
+
+/// 書き出したテープ・ボックス１箱の結果。
+pub struct CompactedBox {
+    // テープ・ボックスのファイル名。
+    pub file: String,
+    // 詰めたテープの本数。
+    pub tapes: usize,
+    // 書けなかった、読み直せなかった理由。
+    pub error: Option<String>,
+}
+impl CompactedBox {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// 圧縮の結果。
+#[derive(Default)]
+pub struct CompactionReport {
+    pub boxes: Vec<CompactedBox>,
+    // 消したテープ・フラグメント。
+    pub deleted: Vec<String>,
+    // 残したテープ・フラグメントと、その理由。
+    pub kept: Vec<(String, String)>,
+    // 再生して通らなかったテープの、テープ・フラグメントと判定。
+    pub ng_verdicts: Vec<(String, RpmTapeVerdict)>,
+}
+impl CompactionReport {
+    pub fn new() -> Self {
+        CompactionReport::default()
+    }
+
+    /// 全部の箱が合格で、残したテープ・フラグメントも無ければ真。
+    pub fn is_ok(&self) -> bool {
+        self.boxes.iter().all(CompactedBox::is_ok) && self.kept.is_empty()
+    }
+}
+
+/// テープ・フラグメント・フォルダーの .tapesfrag を集めて、
+/// 決めた本数ずつ `-tape-box.json` に詰め直す。
+///
+/// テープは先に全部再生して検査し、通らなかったテープのあるテープ・フラグメントは 箱に詰めずに残す。
+/// テープ・フラグメントは箱をまたがせないので、どの箱が書けなくても、
+/// 書けた箱に入ったテープ・フラグメントだけを消せる。次に圧縮し直しても、テープは重ならない。
+pub struct FragmentCompactor {}
+impl FragmentCompactor {
+    pub const EXTENSION: &'static str = "tapesfrag";
+
+    // #####
+    // # C #
+    // #####
+
+    /// テープ・フラグメントを集めて、テープ・ボックスに詰め直す。
+    ///
+    /// # Arguments
+    ///
+    /// * `fragments_directory` - テープ・フラグメントのあるディレクトリー。
+    /// * `output_directory` - テープ・ボックスを書き出すディレクトリー。
+    /// * `box_size` - １箱に詰めるテープの本数。テープ・フラグメントの途中では区切らないので、少し超えることがある。
+    /// * `dry_run` - 真なら、書き出しも消しもしない。
+    pub fn compact(
+        fragments_directory: &str,
+        output_directory: &str,
+        box_size: usize,
+        dry_run: bool,
+        app: &Application,
+    ) -> CompactionReport {
+        let mut report = CompactionReport::new();
+
+        // 読めて、テープが全部 検査に通ったテープ・フラグメント。
+        let mut fragments: Vec<(String, Vec<RpmTape>)> = Vec::new();
+        for fragment_file in FragmentCompactor::list_fragment_files(fragments_directory) {
+            let tapes = match FragmentCompactor::read_fragment_file(&fragment_file) {
+                Ok(tapes) => tapes,
+                Err(err) => {
+                    report.kept.push((fragment_file, err.to_string()));
+                    continue;
+                }
+            };
+
+            let ng_verdicts: Vec<RpmTapeVerdict> = tapes
+                .iter()
+                .enumerate()
+                .map(|(index, tape)| RpmTapeValidator::validate_tape(index, tape, app))
+                .filter(|verdict| !verdict.is_ok())
+                .collect();
+            if ng_verdicts.is_empty() {
+                fragments.push((fragment_file, tapes));
+            } else {
+                report.kept.push((
+                    fragment_file.to_string(),
+                    format!("Tape check failed. NG tapes: {}.", ng_verdicts.len()),
+                ));
+                report.ng_verdicts.extend(
+                    ng_verdicts
+                        .into_iter()
+                        .map(|verdict| (fragment_file.to_string(), verdict)),
+                );
+            }
+        }
+
+        // テープ・フラグメントを丸ごと、箱が埋まるまで詰める。
+        let mut fragments = fragments.into_iter().peekable();
+        while fragments.peek().is_some() {
+            let mut rpm_tape_box = RpmTapeBox::new();
+            let mut fragment_files = Vec::new();
+            while rpm_tape_box.tape_box.len() < box_size.max(1) {
+                match fragments.next() {
+                    Some((fragment_file, tapes)) => {
+                        for tape in tapes {
+                            rpm_tape_box.push(tape);
+                        }
+                        fragment_files.push(fragment_file);
+                    }
+                    None => break,
+                }
+            }
+
+            let compacted_box = if dry_run {
+                CompactedBox {
+                    file: "".to_string(),
+                    tapes: rpm_tape_box.tape_box.len(),
+                    error: None,
+                }
+            } else {
+                FragmentCompactor::write_box(&rpm_tape_box, output_directory, app)
+            };
+
+            // 書けた箱に入ったテープ・フラグメントだけを消す。
+            for fragment_file in fragment_files {
+                if !compacted_box.is_ok() {
+                    report
+                        .kept
+                        .push((fragment_file, "Tape box write failed.".to_string()));
+                    continue;
+                }
+                if !dry_run {
+                    if let Err(err) = fs::remove_file(&fragment_file) {
+                        report.kept.push((fragment_file, err.to_string()));
+                        continue;
+                    }
+                }
+                report.deleted.push(fragment_file);
+            }
+            report.boxes.push(compacted_box);
+        }
+
+        report
+    }
+
+    // #####
+    // # L #
+    // #####
+
+    /// ディレクトリーの .tapesfrag ファイルを、名前順に並べる。
+    fn list_fragment_files(directory: &str) -> Vec<String> {
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            // ディレクトリーが無ければ、テープ・フラグメントも無い。
+            Err(_err) => return Vec::new(),
+        };
+
+        let mut files: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .is_some_and(|extension| extension == FragmentCompactor::EXTENSION)
+            })
+            .collect();
+        files.sort();
+        files
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect()
+    }
+
+    // #####
+    // # R #
+    // #####
+
+    /// テープ・フラグメントを読む。
+    /// テープ・フラグメントは、末尾にカンマの付いたテープの JSON が並んでいるだけなので、
+    /// テープ・ボックスの JSON に包んでから読む。古い版のテープも、最新の版に上げる。
+    pub fn read_fragment_file(fragment_file: &str) -> Result<Vec<RpmTape>, ParseError> {
+        let contents = fs::read_to_string(fragment_file)
+            .map_err(|err| ParseError::from_io("RPM", fragment_file, &err))?;
+
+        // 包みは別の行に置いて、エラーの行番号を テープ・フラグメントの行番号に戻せるようにする。
+        let body = contents.trim_end().trim_end_matches(',');
+        let box_text = format!("{{\"tape_box\":[\n{}\n]}}", body);
+
+        let mut value: Value = serde_json::from_str(&box_text).map_err(|err| {
+            let mut parse_error = ParseError::from_json("RPM", fragment_file, &box_text, &err);
+            parse_error.line = parse_error.line.saturating_sub(1);
+            parse_error
+        })?;
+        RpmMigration::migrate(&mut value, fragment_file)?;
+        let rpm_tape_box: RpmTapeBox = serde_json::from_value(value)
+            .map_err(|err| ParseError::new("RPM", &err.to_string()).with_file(fragment_file))?;
+
+        Ok(rpm_tape_box.tape_box)
+    }
+
+    // #####
+    // # W #
+    // #####
+
+    /// テープ・ボックスを書いて、読み直す。読み直せなければ 書いた箱は消す。
+    fn write_box(
+        rpm_tape_box: &RpmTapeBox,
+        output_directory: &str,
+        app: &Application,
+    ) -> CompactedBox {
+        let file = RpmTapeBox::create_file_full_name_in(Path::new(output_directory), app);
+        rpm_tape_box.write(&file, app);
+
+        let mut compacted_box = CompactedBox {
+            file: file.to_string(),
+            tapes: rpm_tape_box.tape_box.len(),
+            error: None,
+        };
+
+        // 書いたものを読み直す。
+        match RpmTapeBox::from_box_file(&file, app) {
+            Ok(ref written_box) if written_box.tape_box.len() == compacted_box.tapes => {}
+            Ok(written_box) => {
+                compacted_box.error = Some(format!(
+                    "Tapes differ. Written: {}, Read: {}.",
+                    compacted_box.tapes,
+                    written_box.tape_box.len()
+                ));
+            }
+            Err(err) => compacted_box.error = Some(err.to_string()),
+        }
+
+        if !compacted_box.is_ok() {
+            // 読み直せない箱は、トレーニングに使わせない。
+            if let Err(err) = fs::remove_file(&file) {
+                app.comm
+                    .println(&format!("Remove fail. '{}'. {}", file, err));
+            }
+        }
+
+        compacted_box
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use audio_compo::audio_rack::*;
+    use audio_compo::cassette_deck::*;
+    use conv::converter::*;
+    use instrument::position::*;
+    use media::cassette_tape::CassetteTape;
+    use sheet_music_format::tape_label::*;
+
+    /// USI の１行を、テープ・フラグメントに書き出す。
+    fn write_fragment(directory: &str, name: &str, line: &str, app: &Application) -> String {
+        let fragment = Path::new(directory).join(name).display().to_string();
+        let mut position = Position::new_honshogi_origin(app);
+        let mut rack = AudioRack::new(app);
+        let mut tape = CassetteTape::new_facing_right(app);
+        tape.set_file_full_name_without_extension(&fragment);
+        rack.add_tape_to_tape_box(Slot::Learning, tape, app);
+        rack.seek_of_next_tape(Slot::Learning, app);
+        Converter::convert_usi_line(line, &TapeLabel::new(), &mut rack, &mut position, app)
+            .unwrap_or_else(|err| panic!("{}", err));
+        format!("{}.{}", fragment, FragmentCompactor::EXTENSION)
+    }
+
+    /// テスト用のディレクトリーを、空っぽにして作り直す。
+    fn clean_directory(name: &str) -> String {
+        let file = Application::write_test_file(&format!("{}/.keep", name), b"");
+        let directory = Path::new(&file)
+            .parent()
+            .unwrap_or_else(|| panic!("No parent directory. File: '{}'.", file))
+            .to_path_buf();
+        fs::remove_dir_all(&directory).unwrap_or_else(|err| panic!("{}", err));
+        fs::create_dir_all(&directory).unwrap_or_else(|err| panic!("{}", err));
+        directory.display().to_string()
+    }
+
+    /// 出力ディレクトリーの箱に入っているテープの本数。
+    fn count_boxed_tapes(directory: &str, app: &Application) -> usize {
+        fs::read_dir(directory)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .flatten()
+            .map(|entry| {
+                RpmTapeBox::from_box_file(&entry.path().display().to_string(), app)
+                    .unwrap_or_else(|err| panic!("{}", err))
+                    .tape_box
+                    .len()
+            })
+            .sum()
+    }
+
+    #[test]
+    fn keeps_only_fragment_with_ng_tape() {
+        let app = Application::new_for_test();
+        let fragments = clean_directory("fragment_compactor/keep/fragments");
+        let output = clean_directory("fragment_compactor/keep/output");
+
+        let good1 = write_fragment(&fragments, "a", "startpos moves 7g7f 3c3d", &app);
+        let good2 = write_fragment(&fragments, "c", "startpos moves 2g2f 8c8d", &app);
+        // ７七に歩の無いところから動かすテープにする。
+        let bad = Path::new(&fragments)
+            .join("b.tapesfrag")
+            .display()
+            .to_string();
+        let text = fs::read_to_string(&good1).unwrap_or_else(|err| panic!("{}", err));
+        fs::write(&bad, text.replace("[1] 77 76 [1]", "[1] 76 75 [1]"))
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        let report = FragmentCompactor::compact(&fragments, &output, 1, false, &app);
+
+        // 箱は、よいテープ・フラグメントの分だけ書く。
+        assert_eq!(report.boxes.len(), 2);
+        assert!(report.boxes.iter().all(CompactedBox::is_ok));
+        assert_eq!(report.deleted, vec![good1.to_string(), good2.to_string()]);
+        assert_eq!(report.kept.len(), 1);
+        assert_eq!(report.kept[0].0, bad);
+        assert_eq!(report.ng_verdicts.len(), 1);
+        assert_eq!(report.ng_verdicts[0].0, bad);
+        assert!(!report.is_ok());
+
+        assert!(!Path::new(&good1).exists());
+        assert!(Path::new(&bad).exists());
+        assert!(!Path::new(&good2).exists());
+        assert_eq!(count_boxed_tapes(&output, &app), 2);
+    }
+
+    #[test]
+    fn does_not_duplicate_tapes_on_rerun() {
+        let app = Application::new_for_test();
+        let fragments = clean_directory("fragment_compactor/rerun/fragments");
+        let output = clean_directory("fragment_compactor/rerun/output");
+
+        let good = write_fragment(&fragments, "a", "startpos moves 7g7f 3c3d", &app);
+        let bad = Path::new(&fragments)
+            .join("b.tapesfrag")
+            .display()
+            .to_string();
+        fs::write(&bad, "{\"label\":").unwrap_or_else(|err| panic!("{}", err));
+
+        let first = FragmentCompactor::compact(&fragments, &output, 10, false, &app);
+        assert_eq!(first.boxes.len(), 1);
+        assert_eq!(first.deleted, vec![good]);
+        assert_eq!(first.kept.len(), 1);
+
+        // 残したテープ・フラグメントしか無いので、箱は増えない。
+        let second = FragmentCompactor::compact(&fragments, &output, 10, false, &app);
+        assert!(second.boxes.is_empty());
+        assert!(second.deleted.is_empty());
+        assert_eq!(second.kept.len(), 1);
+        assert_eq!(count_boxed_tapes(&output, &app), 1);
+    }
+
+    #[test]
+    fn packs_whole_fragments_into_box() {
+        let app = Application::new_for_test();
+        let fragments = clean_directory("fragment_compactor/pack/fragments");
+        let output = clean_directory("fragment_compactor/pack/output");
+
+        // ２本入りのテープ・フラグメントは、箱の大きさが１でも分けない。
+        let two = write_fragment(&fragments, "a", "startpos moves 7g7f 3c3d", &app);
+        let text = fs::read_to_string(&two).unwrap_or_else(|err| panic!("{}", err));
+        fs::write(&two, format!("{}{}", text, text)).unwrap_or_else(|err| panic!("{}", err));
+        write_fragment(&fragments, "b", "startpos moves 2g2f 8c8d", &app);
+
+        let report = FragmentCompactor::compact(&fragments, &output, 1, true, &app);
+        assert_eq!(report.boxes.len(), 2);
+        assert_eq!(report.boxes[0].tapes, 2);
+        assert_eq!(report.boxes[1].tapes, 1);
+        assert_eq!(report.deleted.len(), 2);
+        // 試すだけなら、書きも消しもしない。
+        assert!(Path::new(&two).exists());
+        assert_eq!(count_boxed_tapes(&output, &app), 0);
+    }
+}
@@ -1 +1,5 @@
+pub mod archive_expander;
+pub mod batch_converter;
+pub mod conversion_report;
 pub mod converter;
+pub mod fragment_compactor;
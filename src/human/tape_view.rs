@@ -6,14 +6,24 @@ use studio::application::Application;
 
 pub struct TapeView {}
 impl TapeView {
+    /// # Arguments
+    ///
+    /// * `plies` - 見えたフェーズ・チェンジの手目を足していく。注釈の表示に使う。
     fn seek(
         rack: &mut AudioRack,
         slot: Slot,
         position: &mut Position,
+        plies: &mut Vec<i16>,
         app: &Application,
     ) -> (String, String, String) {
         let (_taken_overflow, _awareness, note_opt) = rack.seek_a_note(slot, &app);
         if let Some(note) = note_opt {
+            if let Some(ply) = note.get_ope().get_phase_change() {
+                if !plies.contains(&ply) {
+                    plies.push(ply);
+                }
+            }
+
             (
                 note.to_human_presentable_id_5width(),
                 note.to_human_presentable_ope_5width(position.get_board_size(), &app),
@@ -48,17 +58,20 @@ impl TapeView {
                 "#>".to_string()
             };
 
+            // 見えた手目。
+            let mut plies = Vec::new();
+
             // 1セルは 5width。
-            let (id10, op10, fc10) = TapeView::seek(rack, slot, position, app);
-            let (id11, op11, fc11) = TapeView::seek(rack, slot, position, app);
-            let (id12, op12, fc12) = TapeView::seek(rack, slot, position, app);
-            let (id13, op13, fc13) = TapeView::seek(rack, slot, position, app);
-            let (id14, op14, fc14) = TapeView::seek(rack, slot, position, app);
-            let (id15, op15, fc15) = TapeView::seek(rack, slot, position, app);
-            let (id16, op16, fc16) = TapeView::seek(rack, slot, position, app);
-            let (id17, op17, fc17) = TapeView::seek(rack, slot, position, app);
-            let (id18, op18, fc18) = TapeView::seek(rack, slot, position, app);
-            let (id19, op19, fc19) = TapeView::seek(rack, slot, position, app);
+            let (id10, op10, fc10) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id11, op11, fc11) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id12, op12, fc12) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id13, op13, fc13) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id14, op14, fc14) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id15, op15, fc15) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id16, op16, fc16) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id17, op17, fc17) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id18, op18, fc18) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id19, op19, fc19) = TapeView::seek(rack, slot, position, &mut plies, app);
 
             // 10個戻る。
             rack.look_back_caret(slot, &app);
@@ -66,16 +79,16 @@ impl TapeView {
                 rack.seek_a_note(slot, &app);
             }
 
-            let (id09, op09, fc09) = TapeView::seek(rack, slot, position, app);
-            let (id08, op08, fc08) = TapeView::seek(rack, slot, position, app);
-            let (id07, op07, fc07) = TapeView::seek(rack, slot, position, app);
-            let (id06, op06, fc06) = TapeView::seek(rack, slot, position, app);
-            let (id05, op05, fc05) = TapeView::seek(rack, slot, position, app);
-            let (id04, op04, fc04) = TapeView::seek(rack, slot, position, app);
-            let (id03, op03, fc03) = TapeView::seek(rack, slot, position, app);
-            let (id02, op02, fc02) = TapeView::seek(rack, slot, position, app);
-            let (id01, op01, fc01) = TapeView::seek(rack, slot, position, app);
-            let (id00, op00, fc00) = TapeView::seek(rack, slot, position, app);
+            let (id09, op09, fc09) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id08, op08, fc08) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id07, op07, fc07) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id06, op06, fc06) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id05, op05, fc05) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id04, op04, fc04) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id03, op03, fc03) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id02, op02, fc02) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id01, op01, fc01) = TapeView::seek(rack, slot, position, &mut plies, app);
+            let (id00, op00, fc00) = TapeView::seek(rack, slot, position, &mut plies, app);
 
             // 10個戻る。
             rack.look_back_caret(slot, &app);
@@ -161,6 +174,14 @@ impl TapeView {
             app.comm.println(
                 &format!("+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+ {} +-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+",caret_text),
             );
+
+            // 見えている手の、コメントと消費時間。
+            plies.sort();
+            for ply in plies {
+                if let Some(annotation) = rack.get_annotation_of_tape(slot, ply) {
+                    app.comm.println(&annotation.to_human_presentable());
+                }
+            }
         }
     }
 }
@@ -1744,8 +1744,8 @@ pub fn parse_sign_to_drop(line: &str, start: &mut usize) -> Option<PieceType> {
         }
     };
 
-    let v: Vec<char> = line.to_string().chars().collect();
-    let sign = v[*start];
+    // 駒の次が `*`。
+    let sign = v[*start + 1];
     if sign == '*' {
         *start += 2;
         Some(piece_type)
@@ -12,6 +12,7 @@ pub const DEFAULT_BOARD_SIZE: usize = (DEFAULT_FILE_LEN * DEFAULT_RANK_LEN) as u
 pub const HANDS_LEN: usize = 3 * 8;
 
 /// 指先。
+#[derive(Clone)]
 pub struct Fingertip {
     id_piece: IdentifiedPiece,
     previous_address: Address,
@@ -50,6 +51,8 @@ impl Fingertip {
     }
 }
 
+/// 局面。読み筋を並べる時は、写しを取って動かす。
+#[derive(Clone)]
 pub struct Position {
     phase: HalfPlayerPhaseObject,
     board_size: BoardSize,
@@ -117,7 +120,7 @@ impl Position {
         }
     }
 
-    /// USI position 読込時に使う。使ってない駒を盤上に置く。
+    /// USI position 読込時に使う。使ってない駒を、先後と成りをそろえて盤上に置く。
     pub fn activate_piece(&mut self, piece_opt: Option<Piece>, cell: Cell, app: &Application) {
         if let Some(piece) = piece_opt {
             let disactivate_piece = piece.to_disactivate();
@@ -127,7 +130,11 @@ impl Position {
                 .unwrap_or_else(|| panic!(app.comm.panic("Fail. activate_piece.")));
 
             let destination = self.board_size.cell_to_address(cell);
-            self.board[destination] = Some(id_piece);
+            self.board[destination] = Some(IdentifiedPiece::from_phase_pro_id(
+                piece.get_phase(),
+                is_promoted_piece(Some(piece)),
+                id_piece.get_id(),
+            ));
         }
     }
 
@@ -27,11 +27,15 @@
 /// extern crate は main.rs か lib.rs に入れる。
 /// 参考: https://github.com/serde-rs/json |シリアライズ、デシリアライズ。
 extern crate chrono;
+extern crate encoding_rs;
+extern crate flate2;
 extern crate getopts;
 extern crate rand;
 extern crate regex;
 extern crate serde;
 extern crate serde_json;
+extern crate tar;
+extern crate zip;
 pub mod audio_compo;
 pub mod conf;
 pub mod conv;
@@ -53,8 +57,10 @@ use lib_sub::*;
 use live::base_performer::*;
 use live::best_move_picker::*;
 use live::computer_performer::*;
+use live::edit_history::*;
 use live::ohashi_performer::*;
 use live::tuner::*;
+use media::learning_journal::*;
 use std::io;
 use studio::application::*;
 use studio::common::closed_interval::*;
@@ -67,11 +73,23 @@ pub fn main_loop() {
     // Position.
     let mut position = Position::new_honshogi_origin(&app);
 
+    // 前回、対局の途中で止まっていれば、そこまでのラーニング・テープを残す。
+    match LearningJournal::recover(&app) {
+        Ok(Some(box_file)) => app
+            .comm
+            .println(&format!("#Recovered learning tape to '{}'.", box_file)),
+        Ok(None) => {}
+        Err(err) => app.comm.println(&format!("#Journal recover fail. {}", err)),
+    }
+
     // Audio rack.
     let mut rack = AudioRack::new(&app);
 
     let mut best_move_picker = BestMovePicker::default();
 
+    // 手で編集したラーニング・テープの履歴。
+    let mut history = EditHistory::default();
+
     loop {
         // Standard input.
         // Be sure to add "info" before the output message.
@@ -118,19 +136,35 @@ pub fn main_loop() {
             || line.starts_with('|')
             || line.starts_with('[')
         {
-            BasePerformer::improvise_by_line(&line, &mut rack, true, &mut position, &app);
+            history.edit(&mut rack, &mut position, &app, |rack, position| {
+                BasePerformer::improvise_by_line(&line, rack, true, position, &app)
+            });
 
         // #####
         // # B #
         // #####
         } else if line == "b" {
-            Tuner::back_1_note(&mut rack, &mut position, &app);
+            Tuner::back_1_note(&mut rack, Slot::Learning, &mut position, &app);
         } else if line == "bb" {
-            Tuner::back_1_move(&mut rack, &mut position, &app);
+            Tuner::back_1_move(&mut rack, Slot::Learning, &mut position, &app);
         } else if line == "bbb" {
-            Tuner::back_10_move(&mut rack, &mut position, &app);
+            Tuner::back_10_move(&mut rack, Slot::Learning, &mut position, &app);
         } else if line == "bbbb" {
-            Tuner::back_400_move(&mut rack, &mut position, &app);
+            Tuner::back_400_move(&mut rack, Slot::Learning, &mut position, &app);
+        } else if line == "b-p" {
+            // プリンキパル・テープ（読み筋）を戻す。
+            Tuner::back_1_note(&mut rack, Slot::Principal, &mut position, &app);
+        } else if line == "bb-p" {
+            Tuner::back_1_move(&mut rack, Slot::Principal, &mut position, &app);
+        } else if line.starts_with("branch") {
+            // ラーニング・テープの枝。
+            if line == "branch" {
+                LibSub::branch(&line, &mut rack, position.get_board_size(), &app);
+            } else {
+                history.edit(&mut rack, &mut position, &app, |rack, position| {
+                    LibSub::branch(&line, rack, position.get_board_size(), &app)
+                });
+            }
         } else if line.starts_with("bo") {
             // Board.
 
@@ -150,27 +184,33 @@ pub fn main_loop() {
         // #####
         } else if line == "d" {
             // Delete 1mark.
-            BasePerformer::rollback_note(&mut rack, Slot::Training, &mut position, &app);
-        /*
+            history.edit(&mut rack, &mut position, &app, |rack, position| {
+                BasePerformer::rollback_note(rack, Slot::Training, position, &app)
+            });
         } else if line == "dd" {
             // Delete 1ply.
-            BasePerformer::delete_1move(&mut rack, &mut position, &app);
+            history.edit(&mut rack, &mut position, &app, |rack, position| {
+                BasePerformer::delete_1move(rack, position, &app)
+            });
             HumanInterface::bo(&rack, &position, &app);
         } else if line == "ddd" {
             // Delete 10ply.
-            for _i in 0..10 {
-                BasePerformer::delete_1move(&mut rack, &mut position, &app);
-            }
+            history.edit(&mut rack, &mut position, &app, |rack, position| {
+                for _i in 0..10 {
+                    BasePerformer::delete_1move(rack, position, &app);
+                }
+            });
 
             HumanInterface::bo(&rack, &position, &app);
         } else if line == "dddd" {
             // Delete 400ply.
-            for _i in 0..400 {
-                BasePerformer::delete_1move(&mut rack, &mut position, &app);
-            }
+            history.edit(&mut rack, &mut position, &app, |rack, position| {
+                for _i in 0..400 {
+                    BasePerformer::delete_1move(rack, position, &app);
+                }
+            });
 
             HumanInterface::bo(&rack, &position, &app);
-            */
         } else if line == "dump-tape-l" {
             if !rack.is_none_current_tape(Slot::Learning) {
                 HumanInterface::dump_tape(&rack, Slot::Learning, &position, &app);
@@ -194,17 +234,25 @@ pub fn main_loop() {
         // # F #
         // #####
         } else if line == "f" {
-            Tuner::replay_a_note(&mut rack, &mut position, &app);
+            Tuner::replay_a_note(&mut rack, Slot::Learning, &mut position, &app);
         } else if line == "ff" {
-            Tuner::forward_1_move(&mut rack, &mut position, &app);
+            Tuner::forward_1_move(&mut rack, Slot::Learning, &mut position, &app);
         } else if line == "fff" {
-            Tuner::forward_10_move(&mut rack, &mut position, &app);
+            Tuner::forward_10_move(&mut rack, Slot::Learning, &mut position, &app);
         } else if line == "ffff" {
-            Tuner::forward_400_move(&mut rack, &mut position, &app);
+            Tuner::forward_400_move(&mut rack, Slot::Learning, &mut position, &app);
+        } else if line == "f-p" {
+            // プリンキパル・テープ（読み筋）を進める。
+            Tuner::replay_a_note(&mut rack, Slot::Principal, &mut position, &app);
+        } else if line == "ff-p" {
+            Tuner::forward_1_move(&mut rack, Slot::Principal, &mut position, &app);
 
         // #####
         // # G #
         // #####
+        } else if line.starts_with("goto") {
+            // キャレット番地へ飛ぶ。
+            LibSub::goto(&line, &mut rack, &mut position, &app);
         } else if line.starts_with("go") {
             ComputerPerformer::go(&mut best_move_picker, &mut rack, &mut position, &app);
         } else if line.starts_with("gameover") {
@@ -261,7 +309,9 @@ pub fn main_loop() {
         // #####
         } else if line == "ohashi" {
             // Ohashi mode.
-            OhashiPerformer::improvise_ohashi_starting(&mut rack, &mut position, &app)
+            history.edit(&mut rack, &mut position, &app, |rack, position| {
+                OhashiPerformer::improvise_ohashi_starting(rack, position, &app)
+            });
 
         // #########
         // # Piece #
@@ -275,14 +325,20 @@ pub fn main_loop() {
             | line.starts_with('S')
             | line.starts_with('R')
         {
-            BasePerformer::improvise_by_line(&line, &mut rack, true, &mut position, &app);
+            history.edit(&mut rack, &mut position, &app, |rack, position| {
+                BasePerformer::improvise_by_line(&line, rack, true, position, &app)
+            });
 
         // #####
         // # P #
         // #####
+        } else if line.starts_with("ply") {
+            // 手目へ飛ぶ。
+            LibSub::ply(&line, &mut rack, &mut position, &app);
         } else if line.starts_with("position") {
             // 相手が指したあとの局面まで進める。
             LibSub::position(line, &mut rack, &mut position, &app);
+            history.clear();
 
         // #####
         // # Q #
@@ -295,6 +351,11 @@ pub fn main_loop() {
         // #####
         } else if line == "rack-info" {
             app.comm.println(&rack.to_human_presentable());
+        } else if line == "redo" {
+            // 元に戻した編集を、やり直す。
+            if !history.redo(&mut rack, &mut position, &app) {
+                app.comm.println("Nothing to redo.");
+            }
 
         // #####
         // # S #
@@ -320,6 +381,11 @@ pub fn main_loop() {
         // #####
         // # U #
         // #####
+        } else if line == "undo" {
+            // 最後の編集を、元に戻す。
+            if !history.undo(&mut rack, &mut position, &app) {
+                app.comm.println("Nothing to undo.");
+            }
         } else if line == "usi" {
             app.comm.activate_standard_output(true);
             app.comm.println("id name kifuwarabe-wcsc29 build.25");
@@ -327,6 +393,7 @@ pub fn main_loop() {
             app.comm.println("usiok");
         } else if line == "usinewgame" {
             LibSub::usi_new_game(&mut rack, &app);
+            history.clear();
         }
     }
 }
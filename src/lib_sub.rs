@@ -3,6 +3,7 @@ use audio_compo::cassette_deck::*;
 use instrument::half_player_phase::*;
 use instrument::piece_etc::*;
 use instrument::position::*;
+use live::tuner::*;
 use media::cassette_tape::*;
 use media::two_heads_vec::*;
 use sheet_music_format::kifu_rpm::rpm_tape_box::*;
@@ -18,11 +19,63 @@ use studio::common::caret::*;
 
 pub struct LibSub {}
 impl LibSub {
+    // #####
+    // # B #
+    // #####
+
+    /// ラーニング・テープの、キャレットの所で分かれる枝を一覧する。
+    /// `branch N` なら、N 番目の枝に乗り換える。
+    pub fn branch(line: &str, rack: &mut AudioRack, board_size: BoardSize, app: &Application) {
+        if let Some(nth) = line.split_whitespace().nth(1) {
+            match nth.parse::<usize>() {
+                Ok(nth) => {
+                    if rack.switch_branch_at_caret(Slot::Learning, nth, board_size, app) {
+                        app.comm.println(&format!("Switched to branch {}.", nth));
+                    } else {
+                        app.comm.println(&format!("Branch {} is not found.", nth));
+                    }
+                }
+                Err(err) => app.comm.println(&format!("Branch number? {}", err)),
+            }
+        } else {
+            let branches =
+                rack.to_human_presentable_of_branches_at_caret(Slot::Learning, board_size);
+            if branches.is_empty() {
+                app.comm.println("No branch.");
+            }
+            for (i, branch) in branches.iter().enumerate() {
+                app.comm.println(&format!("[{}] {}", i, branch));
+            }
+        }
+    }
+
+    // #####
+    // # G #
+    // #####
+
     pub fn gameover(rack: &mut AudioRack, board_size: BoardSize, app: &Application) {
         // TODO とりあえず、テープが１個入った　テープ・ボックス形式で書きだし☆（＾～＾）
         rack.write_tape_box(board_size, &app);
     }
 
+    /// `goto N` で、ラーニング・テープのキャレットを N 番地へ飛ばす。枝の分かれる所へ行く時など。
+    pub fn goto(line: &str, rack: &mut AudioRack, position: &mut Position, app: &Application) {
+        match line
+            .split_whitespace()
+            .nth(1)
+            .map(|caret| caret.parse::<i16>())
+        {
+            Some(Ok(caret)) => {
+                if !Tuner::seek_to_caret(rack, Slot::Learning, caret, position, app) {
+                    app.comm
+                        .println(&format!("Caret {} is out of the tape.", caret));
+                }
+            }
+            Some(Err(err)) => app.comm.println(&format!("Caret number? {}", err)),
+            None => app.comm.println("Usage: goto N"),
+        }
+    }
+
     // #####
     // # H #
     // #####
@@ -65,6 +118,19 @@ impl LibSub {
     // # P #
     // #####
 
+    /// `ply N` で、ラーニング・テープのキャレットを N 手目の指し手の頭へ飛ばす。
+    pub fn ply(line: &str, rack: &mut AudioRack, position: &mut Position, app: &Application) {
+        match line.split_whitespace().nth(1).map(|ply| ply.parse::<i16>()) {
+            Some(Ok(ply)) => {
+                if !Tuner::seek_to_ply(rack, Slot::Learning, ply, position, app) {
+                    app.comm.println(&format!("Ply {} is not found.", ply));
+                }
+            }
+            Some(Err(err)) => app.comm.println(&format!("Ply number? {}", err)),
+            None => app.comm.println("Usage: ply N"),
+        }
+    }
+
     pub fn position(
         line: String,
         rack: &mut AudioRack,
@@ -75,23 +141,66 @@ impl LibSub {
         let mut urecord_opt = None;
         let mut start = 0;
 
+        // 指定局面にリセットすると テープの中身は消えるので、元の筋を枝や注釈ごと取り外しておく☆（＾～＾）
+        let old_line = rack.take_line_of_tape(Slot::Learning);
+
         // 指定局面にリセットするぜ☆（＾～＾）
-        if Fen::parse_initial_position(&line, &mut start, position, rack, &app) {
+        let is_reset = match Fen::parse_initial_position(&line, &mut start, position, rack, app) {
+            Ok(is_reset) => is_reset,
+            Err(err) => {
+                // 読めない局面なら、局面は進めない☆（＾～＾）
+                app.comm.println(&format!("info string {}", err));
+                false
+            }
+        };
+        if is_reset {
             // USI の moves の文字列を、オブジェクトに直訳するぜ☆（＾～＾）局面は指定局面から動かさないぜ☆（＾～＾）
-            urecord_opt = UsiPosition::parse_usi_line_moves(
+            match UsiPosition::parse_usi_line_moves(
                 &line,
                 &mut start,
                 position.get_board_size(),
                 &app,
-            );
+            ) {
+                Ok(urecord) => urecord_opt = urecord,
+                Err(err) => {
+                    // 読めない指し手があれば、指し手は進めない☆（＾～＾）
+                    app.comm.println(&format!("info string {}", err));
+                }
+            }
         }
 
         // USI -> RPM 変換を作れていないので、ポジションをもう１回初期局面に戻してから、プレイアウトします。
         // TODO できれば USI -> RPM 変換したい。
+        let mut ply = 1;
         if let Some(urecord) = urecord_opt {
             // 差し替え。
             rack.clear_of_tapes(Slot::Training, &app);
-            UsiConverter::play_out_usi_tape(position, &urecord, rack, &app);
+            match UsiConverter::play_out_usi_tape(position, &urecord, rack, app) {
+                Ok(()) => ply += urecord.moves.len() as i16,
+                Err(err) => {
+                    // 局面に合わない指し手があれば、指定局面に戻して 指し手は進めない☆（＾～＾）
+                    app.comm.println(&format!("info string {}", err));
+                    let mut start = 0;
+                    if let Err(err) =
+                        Fen::parse_initial_position(&line, &mut start, position, rack, app)
+                    {
+                        app.comm.println(&format!("info string {}", err));
+                    }
+                }
+            }
+        }
+
+        if is_reset {
+            // 次に指す手の手目。
+            rack.set_ply(Slot::Learning, ply);
+
+            // 元の筋を継ぐ。待ったされた手は、注釈ごと枝として残る。
+            if let Some(line) = old_line {
+                rack.graft_line_of_tape(Slot::Learning, line);
+            }
+        } else if let Some(line) = old_line {
+            // リセットしなかったので、元の筋をそのまま戻す☆（＾～＾）
+            rack.put_back_line_of_tape(Slot::Learning, line);
         }
     }
 
@@ -204,5 +313,60 @@ impl LibSub {
         );
         rack.add_tape_to_tape_box(Slot::Learning, tape, &app);
         rack.seek_of_next_tape(Slot::Learning, &app);
+
+        // 対局が途中で止まっても、指したところまで残す。
+        rack.open_learning_journal(app);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 学習テープの中身を、枝も注釈も込みで文字列にする。
+    fn learning_tape_json(rack: &AudioRack, app: &Application) -> String {
+        let tape = rack
+            .get_current_tape(Slot::Learning)
+            .unwrap_or_else(|| panic!("Learning tape is none."));
+        tape.to_rpm(BoardSize::create_hon_shogi()).to_tape_json(app)
+    }
+
+    /// 読めない position を送っても、学習テープも局面も変わらない。
+    #[test]
+    fn keeps_learning_tape_on_malformed_position() {
+        let app = Application::new_for_test();
+        let mut position = Position::new_honshogi_origin(&app);
+        let mut rack = AudioRack::new(&app);
+        let tape = CassetteTape::new_facing_right(&app);
+        rack.add_tape_to_tape_box(Slot::Learning, tape, &app);
+        rack.seek_of_next_tape(Slot::Learning, &app);
+        LibSub::position(
+            "position startpos moves 7g7f 3c3d".to_string(),
+            &mut rack,
+            &mut position,
+            &app,
+        );
+        // 待ったして、元の筋を枝にしておく。
+        LibSub::position(
+            "position startpos moves 2g2f".to_string(),
+            &mut rack,
+            &mut position,
+            &app,
+        );
+        let learning_tape = rack
+            .get_current_tape(Slot::Learning)
+            .unwrap_or_else(|| panic!("Learning tape is none."));
+        assert!(!learning_tape.branches.is_empty());
+        let tape_json = learning_tape_json(&rack, &app);
+        let text = position.to_text();
+
+        for line in &[
+            "position sfen lnsgkgsnl/9/9/9/9/9/9/9/LNSGKGSNX b - 1",
+            "position unknown",
+        ] {
+            LibSub::position(line.to_string(), &mut rack, &mut position, &app);
+            assert_eq!(learning_tape_json(&rack, &app), tape_json, "{}", line);
+            assert_eq!(position.to_text(), text, "{}", line);
+        }
     }
 }
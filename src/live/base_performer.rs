@@ -103,18 +103,19 @@ impl BasePerformer {
             rack.is_facing_left_of_current_tape(Slot::Learning, &app),
         );
 
-        // #############
-        // # 末尾に追記 #
-        // #############
-        rack.push_note(Slot::Learning, rnote);
+        // ################
+        // # キャレットに記録 #
+        // ################
+        // 戻したところから違う手を指したら、元の続きは枝として残る。
+        // 対局中なら、止まっても残るようにジャーナルにも書く。
+        rack.record_note(Slot::Learning, rnote, position.get_board_size(), app);
     }
 
     // #####
     // # P #
     // #####
 
-    /// 棋譜のカーソルが指している要素を削除して、１つ戻る。
-    /// TODO ply が変わることがある。
+    /// ラーニング・テープの末端のノートを削除して、局面も１つ戻す。
     ///
     /// # Returns
     ///
@@ -124,14 +125,10 @@ impl BasePerformer {
         position: &mut Position,
         app: &Application,
     ) -> Option<ShogiNote> {
-        HumanInterface::bo(rack, position, &app);
-
-        if let Some(rpm_note) = rack.delete_1note(Slot::Learning, &app) {
-            let (_is_legal_touch, _piece_identify_opt) = position.touch_ope(
-                rack.is_facing_left_of_current_tape(Slot::Learning, &app),
-                &rpm_note.get_ope(),
-                &app,
-            );
+        if let Some(rpm_note) = rack.pop_note(Slot::Learning, position.get_board_size(), app) {
+            // 書いた時と逆の向きでタッチして戻す。
+            let (_is_legal_touch, _piece_identify_opt) =
+                position.touch_ope(!rpm_note.is_facing_left(), &rpm_note.get_ope(), app);
             Some(rpm_note)
         } else {
             None
@@ -139,8 +136,6 @@ impl BasePerformer {
     }
 
     /// 1手削除する。
-    ///
-    /// TODO ply が変わる。
     pub fn delete_1move(rack: &mut AudioRack, position: &mut Position, app: &Application) {
         let mut count = 0;
         // 開始前に達したら終了。
@@ -328,15 +323,15 @@ impl BasePerformer {
         // # 末尾から削除 #
         // ###############
         let note_l = rack
-            .pop_note(Slot::Learning)
+            .pop_note(Slot::Learning, position.get_board_size(), app)
             .unwrap_or_else(|| panic!(app.comm.panic("[#RollbackN: note_l fail]")));
 
         // #########
         // # 盤操作 #
         // #########
         if let (false, _) = position.touch_ope(
-            // 巻き戻しの方向。プレイヤーのターンに影響。
-            rack.is_facing_left_of_current_tape(Slot::Learning, &app),
+            // 巻き戻しの方向。書いた時と逆の向き。プレイヤーのターンに影響。
+            !note_l.is_facing_left(),
             &note_l.get_ope(),
             &app,
         ) {
@@ -23,12 +23,16 @@ pub struct BestMovePicker {
 
     // ここに 手筋 を追加していけだぜ☆（＾～＾）
     best_thread_buffer: BestThreadBuffer,
+
+    // 最善手を選んだ手筋だぜ☆（＾～＾）読み筋として、プリンキパル・テープに並べる。
+    principal_thread: BestThread,
 }
 impl BestMovePicker {
     pub fn default() -> Self {
         let mut instance = BestMovePicker {
             best_thread_map: HashMap::new(),
             best_thread_buffer: BestThreadBuffer::new(),
+            principal_thread: BestThread::new(),
         };
 
         instance.init_state();
@@ -48,6 +52,7 @@ impl BestMovePicker {
 
         // 現在の内容を破棄☆（＾～＾）
         self.best_thread_buffer.clear();
+        self.principal_thread = BestThread::new();
     }
 
     /// 現在の内容を確定し、次の手筋にチェンジするぜ☆（*＾～＾*）
@@ -75,6 +80,11 @@ impl BestMovePicker {
         }
     }
 
+    /// 最後に選んだ最善手の手筋。先頭が最善手。最善手が投了なら空っぽ。
+    pub fn get_principal_thread(&self) -> &BestThread {
+        &self.principal_thread
+    }
+
     pub fn get_max_note_len(&self) -> usize {
         let mut max = 0;
 
@@ -108,6 +118,9 @@ impl BestMovePicker {
         // 現局面を文字列として持っておく。
         let cur_pos_text = position.to_text();
 
+        // トレーニング・テープの絞り込み。起動時に読んである。空っぽなら全部使う。
+        let training_query = &app.kw29_conf.training_filter;
+
         // とりあえず テープ・ボックス・ファイルを１個読む。
         'tape_box_dir_loop: for tape_box_file in fs::read_dir(&app.kw29_conf.training)
             .unwrap_or_else(|err| panic!(app.comm.panic_io(&err)))
@@ -167,6 +180,14 @@ impl BestMovePicker {
             let mut debug_tape_count = -1;
             // テープを１本シーク☆（＾～＾）
             while rack.seek_of_next_tape(Slot::Training, &app) {
+                // 絞り込みに合わないテープは使わない。
+                if !rack
+                    .get_label_of_current_tape(Slot::Training)
+                    .is_some_and(|label| training_query.matches(label))
+                {
+                    continue;
+                }
+
                 debug_tape_count += 1;
                 if 0 <= debug_tape_count && debug_tape_count <= 0 {
                     // このテープだけテストするぜ☆（＾～＾）
@@ -354,10 +375,13 @@ impl BestMovePicker {
                                     break 'tape_box_dir_loop;
                                 };
 
+                                // 手筋に最善手が入っていれば、この手は相手の応手だぜ☆（＾～＾）
+                                let is_reply = !self.best_thread_buffer.is_empty();
+
                                 // パターンマッチには２種類ある☆（＾～＾）
                                 // 主体となる駒まで指定する場合と、主体となる駒を指定しない場合だぜ☆（＾～＾）
                                 // 手筋の各1ムーブ目は、主体となるピースのものであるか判定する☆（＾～＾）
-                                if self.best_thread_buffer.is_empty()
+                                if !is_reply
                                     && !self.match_subject_piece(
                                         *subject_piece_id,
                                         my_addr_obj,
@@ -396,6 +420,11 @@ impl BestMovePicker {
                                     rack.skip_a_move(Slot::Training, &app);
                                     HumanInterface::bo(rack, &position, &app);
                                     self.change_thread(*subject_piece_id, &app);
+
+                                    // 途切れた応手は、手筋に足さない☆（＾～＾）
+                                    if is_reply {
+                                        continue 'sequence_thread;
+                                    }
                                 }
 
                                 // 今探している駒の指し手のような感じはするみたいだな☆（＾～＾）
@@ -418,7 +447,12 @@ impl BestMovePicker {
                                 // 手筋の１手に追加☆（＾～＾）
                                 self.best_thread_buffer.push_move(best_move);
 
-                                // TODO 手筋の次の手を探したいが、ループがおかしいので抜けるぜ☆（＾～＾）
+                                // 最善手の次の相手の応手までは読んで、読み筋と ponder に使うぜ☆（＾～＾）
+                                if !is_reply {
+                                    continue 'sequence_thread;
+                                }
+
+                                // TODO 応手より先も探したいが、ループがおかしいので抜けるぜ☆（＾～＾）
                                 self.change_thread(*subject_piece_id, &app);
                                 continue 'sequence_thread;
                             } // Sequence thread.
@@ -535,7 +569,8 @@ impl BestMovePicker {
 
             // とりあえず１つチョイス☆（＾～＾）
             if !best_thread.is_empty() {
-                best_move_opt = Some(&best_thread.moves[0]);
+                best_move_opt = Some(best_thread.moves[0].usi_move);
+                self.principal_thread = best_thread.clone();
 
                 // 検索結果を見てみようぜ☆（＾～＾）
                 // Operation.
@@ -554,8 +589,8 @@ impl BestMovePicker {
 
         // 自分の駒ごとの、現局面にマッチする最長の手筋を更新していく。
 
-        if let Some(best_move) = best_move_opt {
-            best_move.usi_move
+        if let Some(best_umove) = best_move_opt {
+            best_umove
         } else {
             UsiMove::create_resign()
         }
@@ -1,10 +1,14 @@
 use audio_compo::audio_rack::*;
 use audio_compo::cassette_deck::*;
 use human::human_interface::*;
+use instrument::piece_etc::*;
 use instrument::position::*;
 use live::base_performer::*;
 use live::best_move_picker::*;
+use musician::best_thread::*;
 use sheet_music_format::kifu_usi::usi_converter::*;
+use sheet_music_format::kifu_usi::usi_move::*;
+use sound::shogi_note::*;
 use studio::application::*;
 
 pub struct ComputerPerformer {}
@@ -22,12 +26,38 @@ impl ComputerPerformer {
         rack.turn_caret_towards_positive_infinity(Slot::Learning, &app);
 
         let best_umove = best_move_picker.get_mut_best_move(rack, position, &app);
+
+        // 読み筋を プリンキパル・テープに並べて、指す手の注釈としてラーニング・テープにも残すぜ☆（＾～＾）
+        let principal_umoves = ComputerPerformer::record_principal_variation(
+            best_move_picker.get_principal_thread(),
+            rack,
+            position,
+            app,
+        );
+        let pv: Vec<String> = principal_umoves
+            .iter()
+            .map(|umove| umove.to_sign(app))
+            .collect();
+        if !pv.is_empty() {
+            let ply = rack.get_ply(Slot::Learning);
+            rack.set_principal_variation_of_tape(Slot::Learning, ply, &pv);
+        }
+
         // Examples.
         // println!("bestmove 7g7f");
+        // println!("bestmove 7g7f ponder 3c3d");
         // println!("bestmove win");
         // println!("bestmove resign");
-        app.comm
-            .println(&format!("bestmove {}", best_umove.to_sign(&app)));
+        if let Some(ponder) = pv.get(1) {
+            app.comm.println(&format!(
+                "bestmove {} ponder {}",
+                best_umove.to_sign(app),
+                ponder
+            ));
+        } else {
+            app.comm
+                .println(&format!("bestmove {}", best_umove.to_sign(app)));
+        }
 
         // USI を再翻訳して再生するぜ☆（＾～＾）
         let rnote_opes =
@@ -40,4 +70,112 @@ impl ComputerPerformer {
             HumanInterface::bo(rack, position, &app);
         }
     }
+    // #####
+    // # R #
+    // #####
+
+    /// 手筋を、プリンキパル・テープにノートで並べる。局面は写しの上で動かすので、変わらない。
+    /// 指せない手が出てきたら、そこから先は捨てる。
+    ///
+    /// # Returns
+    ///
+    /// 並べられた指し手。
+    pub fn record_principal_variation(
+        best_thread: &BestThread,
+        rack: &mut AudioRack,
+        position: &Position,
+        app: &Application,
+    ) -> Vec<UsiMove> {
+        rack.clear_principal_tape(app);
+
+        let mut principal_position = position.clone();
+        let mut principal_umoves = Vec::new();
+        for (ply, best_move) in (rack.get_ply(Slot::Learning)..).zip(&best_thread.moves) {
+            let mut rnotes = Vec::new();
+            for rnote_ope in
+                UsiConverter::convert_move(best_move.usi_move, &principal_position, ply, app)
+            {
+                let is_facing_left = rack.is_facing_left_of_current_tape(Slot::Principal, app);
+                let (is_legal_touch, id_piece_opt) =
+                    principal_position.touch_ope(is_facing_left, &rnote_ope, app);
+                if !is_legal_touch {
+                    if app.is_debug() {
+                        app.comm.println(&format!(
+                            "[#Principal variation cut: {}]",
+                            best_move.usi_move.to_sign(app)
+                        ));
+                    }
+                    return principal_umoves;
+                }
+
+                let id = id_piece_opt.and_then(|id_piece| {
+                    PieceIdentify::from_number(id_piece.get_id().get_number())
+                });
+                rnotes.push(ShogiNote::from_id_ope(id, rnote_ope, is_facing_left));
+            }
+
+            for rnote in rnotes {
+                rack.push_note(Slot::Principal, rnote);
+            }
+            principal_umoves.push(best_move.usi_move);
+        }
+
+        principal_umoves
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib_sub::LibSub;
+    use media::cassette_tape::CassetteTape;
+    use musician::best_move::BestMove;
+    use sheet_music_format::kifu_usi::fen::Fen;
+    use studio::address::Address;
+    use studio::board_size::BoardSize;
+
+    fn best_move(sign: &str, app: &Application) -> BestMove {
+        let mut start = 0;
+        let usi_move = Fen::parse_usi_1move(sign, &mut start, BoardSize::create_hon_shogi(), app)
+            .unwrap_or_else(|err| panic!("{}", err));
+        BestMove {
+            usi_move,
+            subject_pid: PieceIdentify::K00,
+            subject_addr: Address::from_raw(0),
+            capture_pid: None,
+            capture_addr: None,
+        }
+    }
+
+    #[test]
+    fn records_best_move_and_reply_as_principal_variation() {
+        let app = Application::new_for_test();
+        let mut position = Position::new_honshogi_origin(&app);
+        let mut rack = AudioRack::new(&app);
+        let tape = CassetteTape::new_facing_right(&app);
+        rack.add_tape_to_tape_box(Slot::Learning, tape, &app);
+        rack.seek_of_next_tape(Slot::Learning, &app);
+        LibSub::position(
+            "position startpos".to_string(),
+            &mut rack,
+            &mut position,
+            &app,
+        );
+        let origin = position.to_text();
+
+        // 最善手と、相手の応手。３手目は指せないので、読み筋から外す。
+        let thread = BestThread::from_buffer(vec![
+            best_move("7g7f", &app),
+            best_move("3c3d", &app),
+            best_move("7g7f", &app),
+        ]);
+        let umoves =
+            ComputerPerformer::record_principal_variation(&thread, &mut rack, &position, &app);
+
+        let pv: Vec<String> = umoves.iter().map(|umove| umove.to_sign(&app)).collect();
+        assert_eq!(pv, vec!["7g7f".to_string(), "3c3d".to_string()]);
+        // 局面は写しの上で動かしたので、変わらない。
+        assert_eq!(position.to_text(), origin);
+        assert!(rack.get_current_tape(Slot::Principal).is_some());
+    }
 }
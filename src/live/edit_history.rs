@@ -0,0 +1,326 @@
+use audio_compo::audio_rack::*;
+use audio_compo::cassette_deck::*;
+use human::human_interface::*;
+use instrument::position::*;
+use live::tuner::*;
+use media::tape_branch::*;
+use std::cmp;
+use studio::application::*;
+
+/// 編集する前か後ろの、スロット１つ分のキャレットと手目。
+#[derive(Clone, Copy)]
+struct CaretMark {
+    // テープ・ボックスの中の、何本目のテープか。
+    index: usize,
+    caret: i16,
+    facing_left: bool,
+    ply: i16,
+}
+
+/// ラーニング・テープの編集１つ分。書き換わったノートから先だけを覚えておく。
+struct NoteEdit {
+    /// 筋の、何番目のノートから先が書き換わったか。
+    fork: usize,
+    /// この手目から先の注釈を、筋の先と一緒に入れ替える。
+    first_ply: i16,
+    /// 編集する前の、fork から先の筋。
+    before: TapeBranch,
+    /// 編集した後の、fork から先の筋。
+    after: TapeBranch,
+    learning: (CaretMark, CaretMark),
+    /// d はトレーニング・テープのキャレットも動かす。
+    training: (Option<CaretMark>, Option<CaretMark>),
+}
+
+/// ラーニング・テープを手で編集した時の履歴。undo で元に戻し、redo でやり直す。
+/// 書き換わったノートから先の筋と、キャレットと手目だけを覚えておく。
+/// 局面は、書き換わった所までキャレットを戻してから 入れ替えたノートをタッチし直して戻す。
+#[derive(Default)]
+pub struct EditHistory {
+    undo_stack: Vec<NoteEdit>,
+    redo_stack: Vec<NoteEdit>,
+}
+impl EditHistory {
+    /// 覚えておく編集の数。古いものから忘れる。
+    const MAX_LEN: usize = 100;
+
+    // #####
+    // # C #
+    // #####
+
+    /// 局面を指定し直した時など、戻れなくなったら履歴を捨てる。
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    // #####
+    // # E #
+    // #####
+
+    /// ラーニング・テープを編集して、書き換わったノートを覚えておく。やり直しの履歴は捨てる。
+    pub fn edit<F>(
+        &mut self,
+        rack: &mut AudioRack,
+        position: &mut Position,
+        app: &Application,
+        edit: F,
+    ) where
+        F: FnOnce(&mut AudioRack, &mut Position),
+    {
+        let before = match (
+            rack.get_current_tape(Slot::Learning),
+            EditHistory::take_mark(rack, Slot::Learning, app),
+        ) {
+            (Some(mut tape), Some(mark)) => Some((tape.take_line(), mark)),
+            _ => None,
+        };
+        let training = EditHistory::take_mark(rack, Slot::Training, app);
+
+        edit(rack, position);
+
+        // ラーニング・テープが無ければ、編集もできないので覚えない。
+        let (mut before_line, learning) = match before {
+            Some(before) => before,
+            None => return,
+        };
+        let after_learning = match EditHistory::take_mark(rack, Slot::Learning, app) {
+            Some(mark) if mark.index == learning.index => mark,
+            _ => return,
+        };
+        let mut line = match rack.take_line_of_tape(Slot::Learning) {
+            Some(line) => line,
+            None => return,
+        };
+
+        // 同じノートが続く所までは書き換わっていない。キャレットより手前は 局面の戻しに使うので残す。
+        let same = TapeBranch::count_same_notes(&before_line.notes, &line.notes);
+        let fork = cmp::max(
+            0,
+            cmp::min(same as i16, cmp::min(learning.caret, after_learning.caret)),
+        ) as usize;
+        let first_ply = cmp::min(
+            TapeBranch::get_first_ply(&before_line.notes[fork..]),
+            TapeBranch::get_first_ply(&line.notes[fork..]),
+        )
+        .unwrap_or(i16::MAX);
+        let before_tail = before_line.split_off(fork, first_ply);
+        let after_tail = line.split_off(fork, first_ply);
+        let after = after_tail.clone();
+        line.append(after_tail);
+        rack.put_back_line_of_tape(Slot::Learning, line);
+
+        self.undo_stack.push(NoteEdit {
+            fork,
+            first_ply,
+            before: before_tail,
+            after,
+            learning: (learning, after_learning),
+            training: (training, EditHistory::take_mark(rack, Slot::Training, app)),
+        });
+        if EditHistory::MAX_LEN < self.undo_stack.len() {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+    }
+
+    // #####
+    // # R #
+    // #####
+
+    /// 元に戻した編集を、やり直す。
+    ///
+    /// # Returns
+    ///
+    /// やり直す編集が無ければ偽。
+    pub fn redo(
+        &mut self,
+        rack: &mut AudioRack,
+        position: &mut Position,
+        app: &Application,
+    ) -> bool {
+        match self.redo_stack.pop() {
+            Some(edit) => {
+                EditHistory::restore(
+                    &edit,
+                    &edit.after,
+                    edit.learning.1,
+                    edit.training.1,
+                    rack,
+                    position,
+                    app,
+                );
+                self.undo_stack.push(edit);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// ラーニング・テープの筋の先を tail に入れ替えて、キャレットと手目と局面を合わせる。
+    fn restore(
+        edit: &NoteEdit,
+        tail: &TapeBranch,
+        learning: CaretMark,
+        training: Option<CaretMark>,
+        rack: &mut AudioRack,
+        position: &mut Position,
+        app: &Application,
+    ) {
+        // 局面を指定し直してテープが替わっていれば、戻せない。
+        if rack.get_tape_index(Slot::Learning) != Some(learning.index) {
+            return;
+        }
+
+        // 書き換わった所までキャレットを戻して、局面も戻す。
+        let caret = cmp::min(rack.step_in_of_tape(Slot::Learning), edit.fork as i16);
+        Tuner::seek_to_caret(rack, Slot::Learning, caret, position, app);
+
+        if let Some(mut tape) = rack.get_current_tape(Slot::Learning) {
+            let mut line = tape.take_line();
+            line.split_off(edit.fork, edit.first_ply);
+            line.append(tail.clone());
+            tape.put_back_line(line);
+            rack.replace_current_tape(Slot::Learning, tape, position.get_board_size(), app);
+        }
+
+        // 入れ替えたノートをタッチして、局面を進める。
+        Tuner::seek_to_caret(rack, Slot::Learning, learning.caret, position, app);
+        EditHistory::turn_caret(rack, Slot::Learning, learning, app);
+
+        // go でトレーニング・テープを入れ替えていれば、そのスロットは戻さない。
+        if let Some(training) = training {
+            if rack.get_tape_index(Slot::Training) == Some(training.index) {
+                rack.seek_to_caret_of_tape(Slot::Training, training.caret, app);
+                EditHistory::turn_caret(rack, Slot::Training, training, app);
+            }
+        }
+        HumanInterface::bo(rack, position, app);
+    }
+
+    // #####
+    // # T #
+    // #####
+
+    /// テープが無ければ None。
+    fn take_mark(rack: &AudioRack, slot: Slot, app: &Application) -> Option<CaretMark> {
+        if rack.is_none_current_tape(slot) {
+            return None;
+        }
+
+        rack.get_tape_index(slot).map(|index| CaretMark {
+            index,
+            caret: rack.step_in_of_tape(slot),
+            facing_left: rack.is_facing_left_of_current_tape(slot, app),
+            ply: rack.get_ply(slot),
+        })
+    }
+
+    /// キャレットの向きと手目を、覚えておいたとおりにする。
+    fn turn_caret(rack: &mut AudioRack, slot: Slot, mark: CaretMark, app: &Application) {
+        if mark.facing_left {
+            rack.turn_caret_towards_negative_infinity(slot, app);
+        } else {
+            rack.turn_caret_towards_positive_infinity(slot, app);
+        }
+        rack.set_ply(slot, mark.ply);
+    }
+
+    // #####
+    // # U #
+    // #####
+
+    /// 最後の編集を、元に戻す。
+    ///
+    /// # Returns
+    ///
+    /// 元に戻す編集が無ければ偽。
+    pub fn undo(
+        &mut self,
+        rack: &mut AudioRack,
+        position: &mut Position,
+        app: &Application,
+    ) -> bool {
+        match self.undo_stack.pop() {
+            Some(edit) => {
+                EditHistory::restore(
+                    &edit,
+                    &edit.before,
+                    edit.learning.0,
+                    edit.training.0,
+                    rack,
+                    position,
+                    app,
+                );
+                self.redo_stack.push(edit);
+                true
+            }
+            None => false,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib_sub::*;
+    use live::base_performer::*;
+    use media::cassette_tape::*;
+
+    /// ラーニング・テープのノートと、局面。
+    fn snapshot(rack: &AudioRack, position: &Position) -> (String, String) {
+        let (_ids, opes) = rack.get_sign_of_current_tape(Slot::Learning, position.get_board_size());
+        (opes, position.to_text())
+    }
+
+    /// ノートを書いて d と dd で消しても、undo と redo でノートも局面も元どおりになる。
+    #[test]
+    fn undoes_and_redoes_notes_and_position() {
+        let app = Application::new_for_test();
+        let mut position = Position::new_honshogi_origin(&app);
+        let mut rack = AudioRack::new(&app);
+        rack.add_tape_to_tape_box(Slot::Learning, CassetteTape::new_facing_right(&app), &app);
+        rack.seek_of_next_tape(Slot::Learning, &app);
+        LibSub::position(
+            "position startpos moves 7g7f 3c3d".to_string(),
+            &mut rack,
+            &mut position,
+            &app,
+        );
+
+        let mut history = EditHistory::default();
+        let mut snapshots = vec![snapshot(&rack, &position)];
+        history.edit(&mut rack, &mut position, &app, |rack, position| {
+            BasePerformer::delete_1move(rack, position, &app)
+        });
+        snapshots.push(snapshot(&rack, &position));
+        history.edit(&mut rack, &mut position, &app, |rack, position| {
+            BasePerformer::rollback_note(rack, Slot::Training, position, &app)
+        });
+        snapshots.push(snapshot(&rack, &position));
+        history.edit(&mut rack, &mut position, &app, |rack, position| {
+            BasePerformer::rollback_note(rack, Slot::Training, position, &app)
+        });
+        snapshots.push(snapshot(&rack, &position));
+        // 消した所から、別の手を指し直す。
+        history.edit(&mut rack, &mut position, &app, |rack, position| {
+            BasePerformer::improvise_by_line("76 [1] [2] 83 84 [2]", rack, true, position, &app)
+        });
+        snapshots.push(snapshot(&rack, &position));
+        assert!(snapshots[4].0.ends_with("[1] 77 76 [1] [2] 83 84 [2]"));
+        for pair in snapshots.windows(2) {
+            assert_ne!(pair[0], pair[1]);
+        }
+
+        for expected in snapshots.iter().rev().skip(1) {
+            assert!(history.undo(&mut rack, &mut position, &app));
+            assert_eq!(&snapshot(&rack, &position), expected);
+        }
+        assert!(!history.undo(&mut rack, &mut position, &app));
+
+        for expected in snapshots.iter().skip(1) {
+            assert!(history.redo(&mut rack, &mut position, &app));
+            assert_eq!(&snapshot(&rack, &position), expected);
+        }
+        assert!(!history.redo(&mut rack, &mut position, &app));
+    }
+}
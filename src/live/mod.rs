@@ -1,5 +1,6 @@
 pub mod base_performer;
 pub mod best_move_picker;
 pub mod computer_performer;
+pub mod edit_history;
 pub mod ohashi_performer;
 pub mod tuner;
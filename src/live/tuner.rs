@@ -3,6 +3,7 @@ use audio_compo::cassette_deck::*;
 use human::human_interface::*;
 use instrument::position::*;
 use live::base_performer::*;
+use sound::shogi_note::ShogiNote;
 use studio::application::*;
 use studio::common::caret::*;
 
@@ -12,16 +13,21 @@ impl Tuner {
     // # B #
     // #####
 
-    pub fn back_1_note(rack: &mut AudioRack, position: &mut Position, app: &Application) {
+    pub fn back_1_note(
+        rack: &mut AudioRack,
+        slot: Slot,
+        position: &mut Position,
+        app: &Application,
+    ) {
         if app.is_debug() {
             app.comm.println("[#back_1_note]")
         }
 
         // ルックバックする。
-        rack.look_back_caret(Slot::Learning, &app);
+        rack.look_back_caret(slot, app);
 
         // 棋譜上で１つ進む。
-        let (taken_overflow, awareness, rnote_opt) = rack.seek_a_note(Slot::Learning, &app);
+        let (taken_overflow, awareness, rnote_opt) = rack.seek_a_note(slot, app);
 
         if let Some(rnote) = rnote_opt {
             if app.is_debug() {
@@ -39,7 +45,7 @@ impl Tuner {
 
             // 局面上でそのノートをタッチする。ログも出力する。
             if let (false, _) = position.touch_ope(
-                rack.is_facing_left_of_current_tape(Slot::Learning, &app),
+                rack.is_facing_left_of_current_tape(slot, app),
                 &rnote.get_ope(),
                 &app,
             ) {
@@ -60,20 +66,30 @@ impl Tuner {
         }
 
         // ルックバックする。
-        rack.look_back_caret(Slot::Learning, &app);
+        rack.look_back_caret(slot, app);
     }
 
-    pub fn back_1_move(rack: &mut AudioRack, position: &mut Position, app: &Application) {
-        rack.turn_caret_towards_negative_infinity(Slot::Learning, &app);
-        BasePerformer::replay_a_move(rack, Slot::Learning, position, &app);
+    pub fn back_1_move(
+        rack: &mut AudioRack,
+        slot: Slot,
+        position: &mut Position,
+        app: &Application,
+    ) {
+        rack.turn_caret_towards_negative_infinity(slot, app);
+        BasePerformer::replay_a_move(rack, slot, position, app);
         HumanInterface::bo(rack, &position, &app);
     }
 
-    pub fn back_10_move(rack: &mut AudioRack, position: &mut Position, app: &Application) {
-        rack.turn_caret_towards_negative_infinity(Slot::Learning, &app);
+    pub fn back_10_move(
+        rack: &mut AudioRack,
+        slot: Slot,
+        position: &mut Position,
+        app: &Application,
+    ) {
+        rack.turn_caret_towards_negative_infinity(slot, app);
         for _i in 0..10 {
             let (sought_move_result, _rmove) =
-                BasePerformer::replay_a_move(rack, Slot::Learning, position, &app);
+                BasePerformer::replay_a_move(rack, slot, position, app);
             match sought_move_result {
                 SoughtMoveResult::Aware => {}
                 _ => {
@@ -84,11 +100,16 @@ impl Tuner {
         HumanInterface::bo(rack, &position, &app);
     }
 
-    pub fn back_400_move(rack: &mut AudioRack, position: &mut Position, app: &Application) {
-        rack.turn_caret_towards_negative_infinity(Slot::Learning, &app);
+    pub fn back_400_move(
+        rack: &mut AudioRack,
+        slot: Slot,
+        position: &mut Position,
+        app: &Application,
+    ) {
+        rack.turn_caret_towards_negative_infinity(slot, app);
         for _i in 0..400 {
             let (sought_move_result, _rmove) =
-                BasePerformer::replay_a_move(rack, Slot::Learning, position, &app);
+                BasePerformer::replay_a_move(rack, slot, position, app);
             match sought_move_result {
                 SoughtMoveResult::Aware => {}
                 _ => {
@@ -103,25 +124,40 @@ impl Tuner {
     // # F #
     // #####
 
-    pub fn forward_1_move(rack: &mut AudioRack, position: &mut Position, app: &Application) {
+    pub fn forward_1_move(
+        rack: &mut AudioRack,
+        slot: Slot,
+        position: &mut Position,
+        app: &Application,
+    ) {
         // 非合法タッチは自動で戻します。
-        rack.turn_caret_towards_positive_infinity(Slot::Learning, &app);
-        BasePerformer::replay_a_move(rack, Slot::Learning, position, &app);
+        rack.turn_caret_towards_positive_infinity(slot, app);
+        BasePerformer::replay_a_move(rack, slot, position, app);
         HumanInterface::bo(rack, &position, &app);
     }
 
-    pub fn forward_10_move(rack: &mut AudioRack, position: &mut Position, app: &Application) {
-        rack.turn_caret_towards_positive_infinity(Slot::Learning, &app);
+    pub fn forward_10_move(
+        rack: &mut AudioRack,
+        slot: Slot,
+        position: &mut Position,
+        app: &Application,
+    ) {
+        rack.turn_caret_towards_positive_infinity(slot, app);
         for _i in 0..10 {
-            BasePerformer::replay_a_move(rack, Slot::Learning, position, &app);
+            BasePerformer::replay_a_move(rack, slot, position, app);
         }
         HumanInterface::bo(rack, &position, &app);
     }
 
-    pub fn forward_400_move(rack: &mut AudioRack, position: &mut Position, app: &Application) {
-        rack.turn_caret_towards_positive_infinity(Slot::Learning, &app);
+    pub fn forward_400_move(
+        rack: &mut AudioRack,
+        slot: Slot,
+        position: &mut Position,
+        app: &Application,
+    ) {
+        rack.turn_caret_towards_positive_infinity(slot, app);
         for _i in 0..400 {
-            BasePerformer::replay_a_move(rack, Slot::Learning, position, &app);
+            BasePerformer::replay_a_move(rack, slot, position, app);
         }
         HumanInterface::bo(rack, &position, &app);
     }
@@ -130,11 +166,16 @@ impl Tuner {
     // # R #
     // #####
 
-    pub fn replay_a_note(rack: &mut AudioRack, position: &mut Position, app: &Application) {
-        rack.turn_caret_towards_positive_infinity(Slot::Learning, &app);
-        if let (_taken_overflow, _awareness, Some(rnote)) = rack.seek_a_note(Slot::Learning, &app) {
+    pub fn replay_a_note(
+        rack: &mut AudioRack,
+        slot: Slot,
+        position: &mut Position,
+        app: &Application,
+    ) {
+        rack.turn_caret_towards_positive_infinity(slot, app);
+        if let (_taken_overflow, _awareness, Some(rnote)) = rack.seek_a_note(slot, app) {
             if let (false, _) = position.touch_ope(
-                rack.is_facing_left_of_current_tape(Slot::Learning, &app),
+                rack.is_facing_left_of_current_tape(slot, app),
                 &rnote.get_ope(),
                 &app,
             ) {
@@ -152,6 +193,71 @@ impl Tuner {
         rack.look_back_caret(Slot::Training, &app);
     }
 
+    // #####
+    // # S #
+    // #####
+
+    /// キャレットを caret 番地へ飛ばして、通り過ぎたノートを局面でタッチする。
+    /// １手ずつ指し直さないので、遠くへ飛んでも速い。
+    ///
+    /// # Returns
+    ///
+    /// 飛べたら真。
+    pub fn seek_to_caret(
+        rack: &mut AudioRack,
+        slot: Slot,
+        caret: i16,
+        position: &mut Position,
+        app: &Application,
+    ) -> bool {
+        let passed = rack.seek_to_caret_of_tape(slot, caret, app);
+        Tuner::touch_passed_notes(rack, slot, passed, position, app)
+    }
+
+    /// キャレットを ply 手目の指し手の頭へ飛ばして、通り過ぎたノートを局面でタッチする。
+    ///
+    /// # Returns
+    ///
+    /// 飛べたら真。
+    pub fn seek_to_ply(
+        rack: &mut AudioRack,
+        slot: Slot,
+        ply: i16,
+        position: &mut Position,
+        app: &Application,
+    ) -> bool {
+        let passed = rack.seek_to_ply_of_tape(slot, ply, app);
+        Tuner::touch_passed_notes(rack, slot, passed, position, app)
+    }
+
+    // #####
+    // # T #
+    // #####
+
+    fn touch_passed_notes(
+        rack: &mut AudioRack,
+        slot: Slot,
+        passed: Option<Vec<ShogiNote>>,
+        position: &mut Position,
+        app: &Application,
+    ) -> bool {
+        match passed {
+            Some(notes) => {
+                // 戻る方へ飛んだ時は キャレットが左を向いているので、タッチも逆さになる。
+                let is_facing_left = rack.is_facing_left_of_current_tape(slot, app);
+                for note in notes {
+                    if let (false, _) = position.touch_ope(is_facing_left, &note.get_ope(), app) {
+                        // タッチできないのはおかしい。
+                        app.comm.println("Touch fail.");
+                    }
+                }
+                HumanInterface::bo(rack, position, app);
+                true
+            }
+            None => false,
+        }
+    }
+
     /*
     pub fn rollback_move(
         rack: &mut AudioRack,
@@ -1,8 +1,10 @@
 extern crate rand;
 use conf::kifuwarabe_wcsc29_master_config::*;
+use media::tape_branch::*;
 use media::two_heads_vec::*;
 use rand::Rng;
 use sheet_music_format::kifu_rpm::rpm_tape::*;
+use sheet_music_format::move_annotation::MoveAnnotation;
 use sheet_music_format::tape_label::TapeLabel;
 use sound::shogi_move::ShogiMove;
 use sound::shogi_note::*;
@@ -18,11 +20,16 @@ use studio::common::closed_interval::ClosedInterval;
 
 /// 説明 https://ch.nicovideo.jp/kifuwarabe/blomaga/ar1752788
 /// 説明 https://ch.nicovideo.jp/kifuwarabe/blomaga/ar1753122
+#[derive(Clone)]
 pub struct CassetteTape {
     pub fragment_file_name: String,
     pub caret: Caret,
     pub label: TapeLabel,
     pub tracks: TwoHeadsVec,
+    /// 指し手ごとのコメントと消費時間。手目の順。
+    pub annotations: Vec<MoveAnnotation>,
+    /// 変化などの枝。キャレットが進むのは tracks の筋だけで、枝に乗り換えると tracks と入れ替わる。
+    pub branches: Vec<TapeBranch>,
 }
 impl CassetteTape {
     // ###############
@@ -37,6 +44,8 @@ impl CassetteTape {
             caret: Caret::new_facing_right_caret(),
             label: TapeLabel::new(),
             tracks: TwoHeadsVec::default(),
+            annotations: Vec::new(),
+            branches: Vec::new(),
         }
     }
 
@@ -62,6 +71,8 @@ impl CassetteTape {
     pub fn clear_tape_body(&mut self, _app: &Application) {
         self.caret.clear_facing_right();
         self.tracks.clear();
+        self.annotations.clear();
+        self.branches.clear();
     }
 
     /// ランダムにファイル名を付けるぜ☆（*＾～＾*）
@@ -83,10 +94,100 @@ impl CassetteTape {
             .to_string()
     }
 
+    // #####
+    // # F #
+    // #####
+
+    /// 筋の fork 番目のノートから先を切り取って、枝にする。
+    /// 切り取った所から先で分かれていた枝は、その枝の枝になる。
+    fn fork(&mut self, fork: usize) {
+        let notes = self.tracks.split_off_positive_notes(fork);
+        if notes.is_empty() {
+            return;
+        }
+
+        let annotations = TapeBranch::split_off_annotations(&mut self.annotations, &notes);
+        let (branches, rest): (Vec<TapeBranch>, Vec<TapeBranch>) = self
+            .branches
+            .drain(..)
+            .partition(|branch| fork < branch.fork);
+        self.branches = rest;
+        self.branches.push(TapeBranch {
+            fork,
+            notes,
+            annotations,
+            branches,
+        });
+    }
+
     // #####
     // # G #
     // #####
 
+    /// キャレットの所で分かれる枝の、branches の中の番号。
+    /// 手目の区切りは どの筋でも同じなので、キャレットの先に続く区切りの後ろで分かれる枝も入れる。
+    pub fn get_branch_indexes_at_caret(&self) -> Vec<usize> {
+        let caret = self.caret.step_in();
+        if caret < 0 {
+            return Vec::new();
+        }
+
+        let first = caret as usize;
+        let last = first
+            + self
+                .tracks
+                .get_positive_notes()
+                .iter()
+                .skip(first)
+                .take_while(|note| note.is_phase_change())
+                .count();
+        self.branches
+            .iter()
+            .enumerate()
+            .filter(|(_index, branch)| first <= branch.fork && branch.fork <= last)
+            .map(|(index, _branch)| index)
+            .collect()
+    }
+
+    /// 取り外しておいた元の筋を、今の筋に継ぐ。
+    /// 元の筋の 今の筋と違うところから先は、枝にして残す。
+    pub fn graft_line(&mut self, mut line: TapeBranch) {
+        let same = TapeBranch::count_same_notes(&line.notes, self.tracks.get_positive_notes());
+        let notes = line.notes.split_off(same);
+        let annotations = TapeBranch::split_off_annotations(&mut line.annotations, &notes);
+
+        // 今の筋の注釈があれば、そっちを残す。
+        for annotation in line.annotations {
+            if self.get_annotation(annotation.ply).is_none() {
+                self.annotations.push(annotation);
+            }
+        }
+        self.annotations.sort_by_key(|annotation| annotation.ply);
+
+        let (branches, rest): (Vec<TapeBranch>, Vec<TapeBranch>) = line
+            .branches
+            .into_iter()
+            .partition(|branch| same < branch.fork);
+        self.branches.extend(rest);
+        if notes.is_empty() {
+            self.branches.extend(branches);
+        } else {
+            self.branches.push(TapeBranch {
+                fork: same,
+                notes,
+                annotations,
+                branches,
+            });
+        }
+
+        self.trim_branches();
+    }
+
+    /// 手目の注釈。
+    pub fn get_annotation(&self, ply: i16) -> Option<&MoveAnnotation> {
+        MoveAnnotation::find(&self.annotations, ply)
+    }
+
     /// 範囲はキャレット番地で示す☆（＾～＾）
     /// ０に背を向けた２つのキャレットがあると仮定し、両端はピークを指すキャレット☆（＾～＾）
     pub fn get_span_caret_facing_outward(&self) -> ClosedInterval {
@@ -176,14 +277,124 @@ impl CassetteTape {
     pub fn push_note(&mut self, note: ShogiNote) {
         self.tracks.push_note(note);
     }
-    pub fn pop_note(&mut self) -> Option<ShogiNote> {
-        self.tracks.pop_note()
+    /// 正の方のテープの末端のノートを削除。キャレットがテープの外に出たら、末端に戻す。
+    pub fn pop_note(&mut self, app: &Application) -> Option<ShogiNote> {
+        let note = self.tracks.pop_note();
+
+        let len = self.tracks.get_positive_notes().len() as i16;
+        if len < self.caret.step_in() {
+            let facing_left = self.caret.is_facing_left();
+            self.caret = Caret::new_facing_right_caret_with_number(len);
+            if facing_left {
+                self.caret.look_back(app);
+            }
+        }
+
+        note
+    }
+    /// 取り外しておいた筋を、枝も注釈も 取り外す前のとおりに戻す。テープは空っぽのはず。
+    pub fn put_back_line(&mut self, mut line: TapeBranch) {
+        self.tracks.append_positive_notes(&mut line.notes);
+        self.annotations = line.annotations;
+        self.branches = line.branches;
+    }
+
+    // #####
+    // # R #
+    // #####
+
+    /// キャレットの所にノートを書いて、キャレットを進める。
+    /// キャレットが筋の途中にあって 続きと違うノートなら、続きは枝にして残す。
+    /// 同じノートなら、書かずに進むだけ。そのノートで始まる枝があれば、その枝に乗り換える。
+    /// キャレットが右を向いて 正の方のテープの上にいなければ、末尾に書き足すだけで キャレットは動かさない。
+    ///
+    /// # Returns
+    ///
+    /// キャレットから先の筋が変わったら真。
+    pub fn record_note(&mut self, note: ShogiNote, app: &Application) -> bool {
+        let caret = self.caret.step_in();
+        let len = self.tracks.get_positive_notes().len() as i16;
+        if self.caret.is_facing_left() || caret < 0 || len < caret {
+            self.tracks.push_note(note);
+            return false;
+        }
+
+        let mut is_changed = false;
+        if caret == len {
+            self.tracks.push_note(note);
+            // 同じノートで始まる枝は、分かれる所が先にずれる。
+            self.trim_branches();
+        } else if self.tracks.get_positive_notes()[caret as usize] != note {
+            let fork = caret as usize;
+            match self
+                .branches
+                .iter()
+                .position(|branch| branch.fork == fork && branch.notes.first() == Some(&note))
+            {
+                Some(index) => self.switch_branch(index),
+                None => {
+                    self.fork(fork);
+                    self.tracks.push_note(note);
+                }
+            }
+            is_changed = true;
+        }
+
+        self.seek_a_note(app);
+        is_changed
     }
 
     // #####
     // # S #
     // #####
 
+    /// キャレットを、正の方のテープの caret 番地へ飛ばす。
+    /// 進む方へ飛んだら右向き、戻る方へ飛んだら左向きになる。
+    ///
+    /// # Returns
+    ///
+    /// 通り過ぎたノート。局面で触る順。キャレットか行き先が正の方のテープの上に無ければ None。
+    pub fn seek_to_caret(&mut self, caret: i16, app: &Application) -> Option<Vec<ShogiNote>> {
+        let notes = self.tracks.get_positive_notes();
+        let len = notes.len() as i16;
+        let current = self.caret.step_in();
+        if current < 0 || caret < 0 || len < caret {
+            return None;
+        }
+
+        // テープの末端より先に出ていても、通り過ぎるノートは無い。
+        let current = cmp::min(current, len);
+        let passed = if current <= caret {
+            notes[current as usize..caret as usize].to_vec()
+        } else {
+            notes[caret as usize..current as usize]
+                .iter()
+                .rev()
+                .cloned()
+                .collect()
+        };
+
+        let facing_left = caret < current;
+        self.caret = Caret::new_facing_right_caret_with_number(caret);
+        if facing_left {
+            self.caret.turn_towards_negative_infinity(app);
+        }
+
+        Some(passed)
+    }
+
+    /// キャレットを、ply 手目の指し手の頭へ飛ばす。
+    ///
+    /// # Returns
+    ///
+    /// 通り過ぎたノート。局面で触る順。その手目が無ければ None。
+    pub fn seek_to_ply(&mut self, ply: i16, app: &Application) -> Option<Vec<ShogiNote>> {
+        match self.tracks.get_caret_of_ply(ply) {
+            Some(caret) => self.seek_to_caret(caret, app),
+            None => None,
+        }
+    }
+
     /// # Returns
     ///
     /// (taken overflow, awareness, note)
@@ -191,10 +402,51 @@ impl CassetteTape {
         self.tracks.seek_a_note(&mut self.caret, &app)
     }
 
+    /// 枝に乗り換える。今の筋の 分かれる所から先は、枝にして残す。キャレットは動かさない。
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - branches の中の番号。
+    pub fn switch_branch(&mut self, index: usize) {
+        let mut branch = self.branches.remove(index);
+        self.fork(branch.fork);
+
+        self.tracks.append_positive_notes(&mut branch.notes);
+        self.annotations.append(&mut branch.annotations);
+        self.annotations.sort_by_key(|annotation| annotation.ply);
+        self.branches.append(&mut branch.branches);
+    }
+
     pub fn set_file_full_name_without_extension(&mut self, file_name_without_extension: &str) {
         self.fragment_file_name = format!("{}.tapesfrag", file_name_without_extension).to_string();
     }
 
+    /// 指し手ごとの注釈を書く。空っぽの注釈は捨てる。
+    pub fn set_annotations(&mut self, annotations: &[MoveAnnotation]) {
+        self.annotations = annotations
+            .iter()
+            .filter(|annotation| !annotation.is_empty())
+            .cloned()
+            .collect();
+    }
+
+    /// 手目の注釈に、読み筋を書く。その手目の注釈が無ければ足す。
+    pub fn set_principal_variation(&mut self, ply: i16, pv: &[String]) {
+        match self
+            .annotations
+            .iter()
+            .position(|annotation| annotation.ply == ply)
+        {
+            Some(index) => self.annotations[index].pv = pv.to_vec(),
+            None => {
+                let mut annotation = MoveAnnotation::new(ply);
+                annotation.pv = pv.to_vec();
+                self.annotations.push(annotation);
+                self.annotations.sort_by_key(|annotation| annotation.ply);
+            }
+        }
+    }
+
     /// テープのラベルを書く。
     pub fn set_label(&mut self, tape_label: &TapeLabel) {
         self.label = tape_label.clone();
@@ -233,10 +485,63 @@ impl CassetteTape {
         self.tracks.to_sign(board_size)
     }
 
+    /// 筋を、枝も注釈も丸ごと取り外す。テープは空っぽになる。キャレットは動かさない。
+    pub fn take_line(&mut self) -> TapeBranch {
+        TapeBranch {
+            fork: 0,
+            notes: self.tracks.split_off_positive_notes(0),
+            annotations: mem::take(&mut self.annotations),
+            branches: mem::take(&mut self.branches),
+        }
+    }
+
+    /// 分かれた所から先が 筋と同じ枝は、分かれる所を先にずらす。筋と全部同じ枝は捨てる。
+    fn trim_branches(&mut self) {
+        let mut trimmed = Vec::new();
+        let mut branches = mem::take(&mut self.branches);
+        while let Some(mut branch) = branches.pop() {
+            let same = match self.tracks.get_positive_notes().get(branch.fork..) {
+                Some(line_notes) => TapeBranch::count_same_notes(&branch.notes, line_notes),
+                None => 0,
+            };
+            if same == 0 {
+                trimmed.push(branch);
+                continue;
+            }
+
+            let fork = branch.fork + same;
+            branch.fork = fork;
+            branch.notes.drain(..same);
+            // ずらした所までに分かれていた枝の枝は、筋から分かれる枝になる。
+            let (on_line, rest): (Vec<TapeBranch>, Vec<TapeBranch>) = branch
+                .branches
+                .drain(..)
+                .partition(|child| child.fork <= fork);
+            branch.branches = rest;
+            branches.extend(on_line);
+            if branch.notes.is_empty() {
+                branches.append(&mut branch.branches);
+            } else {
+                branches.push(branch);
+            }
+        }
+
+        trimmed.sort_by_key(|branch| branch.fork);
+        self.branches = trimmed;
+    }
+
     pub fn to_rpm(&self, board_size: BoardSize) -> RpmTape {
+        let mut tracks = self.tracks.to_rpm_tracks(board_size);
+        tracks.annotation = self.annotations.clone();
+        tracks.branch = self
+            .branches
+            .iter()
+            .map(|branch| branch.to_rpm(board_size))
+            .collect();
+
         RpmTape {
             label: self.label.clone(),
-            tracks: self.tracks.to_rpm_tracks(board_size),
+            tracks,
         }
     }
 
@@ -1,10 +1,16 @@
 extern crate rand;
 use audio_compo::cassette_deck::Slot;
 use media::cassette_tape::*;
+use media::tape_branch::*;
+use sheet_music_format::kifu_rpm::rpm_tape::RpmTape;
 use sheet_music_format::kifu_rpm::rpm_tape_box::*;
+use sheet_music_format::kifu_rpm::rpm_tape_reader::*;
+use sheet_music_format::move_annotation::MoveAnnotation;
+use sheet_music_format::parse_error::ParseError;
 use sheet_music_format::tape_label::TapeLabel;
 use sound::shogi_move::ShogiMove;
 use sound::shogi_note::ShogiNote;
+use std::collections::HashMap;
 use std::*;
 use studio::application::Application;
 use studio::board_size::*;
@@ -12,6 +18,15 @@ use studio::common::caret::Awareness;
 use studio::common::caret::Caret;
 use studio::common::closed_interval::ClosedInterval;
 
+/// 箱の中のテープの在りか。
+#[derive(Clone, Copy)]
+enum StoredTape {
+    /// tapes の何番目か。
+    Loaded(usize),
+    /// どのファイルの何番目のテープか。捨てたテープと、まだ読んでいないテープ。
+    InFile(usize, usize),
+}
+
 /// 保存したいときは RPM棋譜 に変換して、そっちで保存しろだぜ☆（＾～＾）
 pub struct CassetteTapeBox {
     // このテープボックスの役割。
@@ -20,7 +35,16 @@ pub struct CassetteTapeBox {
     file_name: String,
 
     // イテレーターを使いたいので public にしてある。
+    // ファイルから読んだテープは、通り過ぎたら捨てるので 先頭の released_tapes 本が抜けている。
     pub tapes: Vec<CassetteTape>,
+    // 通り過ぎて捨てたテープの本数。tapes[0] は、この番号のテープ。
+    released_tapes: usize,
+
+    // テープ・ボックス・ファイルと、テープを作る盤のサイズ。
+    // テープは シークで届いた時に１本ずつ読んで、tapes の後ろに足す。捨てたテープを読み直せるように、読み終わっても閉じない。
+    sources: Vec<(RpmTapeReader, BoardSize)>,
+    // 足したテープが、どのファイルの何番目のテープか。ファイルから読んでいなければ None。
+    origins: Vec<Option<(usize, usize)>>,
 
     // テープ間のキャレット。
     caret_of_tapes: Caret,
@@ -47,6 +71,9 @@ impl CassetteTapeBox {
             role_as_slot: slot,
             file_name: "".to_string(),
             tapes: Vec::new(),
+            released_tapes: 0,
+            sources: Vec::new(),
+            origins: Vec::new(),
             caret_of_tapes: Caret::new_facing_right_caret(),
             awareness_of_tapes: Awareness::new(),
             ply: 1,
@@ -60,6 +87,13 @@ impl CassetteTapeBox {
     /// ◆テープを追加するぜ☆（＾～＾）
     pub fn add_tape(&mut self, tape: CassetteTape, _app: &Application) {
         self.tapes.push(tape);
+        self.origins.push(None);
+    }
+
+    /// ◆テープ・ボックス・ファイルを、まだ読まずに追加するぜ☆（＾～＾）
+    /// テープは シークで届いた時に１本ずつ読む。
+    pub fn add_tapes_from_reader(&mut self, reader: RpmTapeReader, board_size: BoardSize) {
+        self.sources.push((reader, board_size));
     }
 
     // #####
@@ -73,10 +107,13 @@ impl CassetteTapeBox {
                 .println(&format!("[#Clear tape box: {:?}]", self.role_as_slot));
         }
         self.tapes.clear();
+        self.released_tapes = 0;
+        self.sources.clear();
+        self.origins.clear();
         self.caret_of_tapes.clear_facing_right();
     }
     pub fn clear_tape_body(&mut self, app: &Application) {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].clear_tape_body(&app)
         } else {
             panic!(
@@ -94,7 +131,7 @@ impl CassetteTapeBox {
     ///
     /// 削除したノート。
     pub fn delete_1note(&mut self, app: &Application) -> Option<ShogiNote> {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             let tape = &mut self.tapes[index];
 
             let (new_tape, removed_note_opt) = tape.tracks.new_truncated_tape(&tape.caret, &app);
@@ -117,13 +154,115 @@ impl CassetteTapeBox {
     // # G #
     // #####
 
+    /// 箱の並びで最初のテープの名前。捨てたテープは ファイルから読む。読めるテープが無ければ None。
+    pub fn get_name_of_first_tape(&mut self) -> Option<String> {
+        self.get_stored_tapes()
+            .into_iter()
+            .find_map(|stored| self.read_name_of_stored_tape(stored))
+    }
+
+    /// 箱の並びで最後のテープの名前。まだ読んでいないテープは ファイルから読む。読めるテープが無ければ None。
+    pub fn get_name_of_last_tape(&mut self) -> Option<String> {
+        self.get_stored_tapes()
+            .into_iter()
+            .rev()
+            .find_map(|stored| self.read_name_of_stored_tape(stored))
+    }
+
+    /// 箱のテープの並び。ファイルから読んだテープは ファイルの順のまま、ファイルから読んでいないテープは その後ろ。
+    fn get_stored_tapes(&self) -> Vec<StoredTape> {
+        // 読んであるテープが、どのファイルの何番目のテープか。
+        let mut loaded = HashMap::new();
+        let mut added = Vec::new();
+        for (index, origin) in self.origins[self.released_tapes..].iter().enumerate() {
+            match *origin {
+                Some(origin) => {
+                    loaded.insert(origin, index);
+                }
+                None => added.push(StoredTape::Loaded(index)),
+            }
+        }
+
+        let mut stored_tapes = Vec::new();
+        for (source, (reader, _board_size)) in self.sources.iter().enumerate() {
+            for index in 0..reader.len() {
+                stored_tapes.push(match loaded.get(&(source, index)) {
+                    Some(&loaded_index) => StoredTape::Loaded(loaded_index),
+                    None => StoredTape::InFile(source, index),
+                });
+            }
+        }
+        stored_tapes.extend(added);
+        stored_tapes
+    }
+
+    /// 今のテープの、手目の注釈。
+    pub fn get_annotation_of_current_tape(&self, ply: i16) -> Option<MoveAnnotation> {
+        if let Some(index) = self.get_current_index() {
+            self.tapes[index].get_annotation(ply).cloned()
+        } else {
+            None
+        }
+    }
+
+    /// 今のテープの、注釈全部。テープが無ければ空っぽ。
+    pub fn get_annotations_of_current_tape(&self) -> Vec<MoveAnnotation> {
+        if let Some(index) = self.get_current_index() {
+            self.tapes[index].annotations.clone()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// 今のテープの、tapes の中の位置。捨てたテープの分だけ、テープの番号とずれる。
+    /// 捨てたテープを指していれば None。
+    fn get_current_index(&self) -> Option<usize> {
+        self.awareness_of_tapes
+            .index
+            .and_then(|index| index.checked_sub(self.released_tapes))
+    }
+
+    /// 今のテープ。テープが無ければ None。
+    pub fn get_current_tape(&self) -> Option<&CassetteTape> {
+        match self.get_current_index() {
+            Some(index) => Some(&self.tapes[index]),
+            None => None,
+        }
+    }
+
+    /// 今のテープのラベル。テープが無ければ None。
+    pub fn get_label_of_current_tape(&self) -> Option<&TapeLabel> {
+        self.get_current_tape().map(|tape| &tape.label)
+    }
+
+    /// 今のテープの、正の方のノート。テープが無ければ空っぽ。
+    pub fn get_notes_of_current_tape(&self) -> Vec<ShogiNote> {
+        if let Some(index) = self.get_current_index() {
+            self.tapes[index].tracks.get_positive_notes().to_vec()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// 取り外しておいた元の筋を、今のテープの筋に継ぐ。
+    pub fn graft_line_of_current_tape(&mut self, line: TapeBranch) {
+        if let Some(index) = self.get_current_index() {
+            self.tapes[index].graft_line(line);
+        } else {
+            panic!(
+                "#graft_line_of_current_tape: Please seek tapes. It is none. Slot: '{:?}'.",
+                self.role_as_slot
+            );
+        }
+    }
+
     pub fn get_file_name(&self) -> String {
         self.file_name.to_string()
     }
 
     /// -と+方向の長さがある☆（＾～＾）
     pub fn get_current_tape_len(&self) -> ClosedInterval {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].get_span_caret_facing_outward()
         } else {
             panic!(
@@ -134,7 +273,7 @@ impl CassetteTapeBox {
     }
 
     pub fn get_sign_of_current_tape(&self, board_size: BoardSize) -> (String, String) {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].to_sign(board_size)
         } else {
             panic!(
@@ -157,7 +296,7 @@ impl CassetteTapeBox {
     // #####
 
     pub fn insert_note(&mut self, note: ShogiNote, board_size: BoardSize, app: &Application) {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].insert_note(note, board_size, &app);
         } else {
             panic!(
@@ -168,7 +307,7 @@ impl CassetteTapeBox {
     }
 
     pub fn is_facing_left_of_current_tape(&self) -> bool {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].caret.is_facing_left()
         } else {
             panic!(
@@ -180,7 +319,7 @@ impl CassetteTapeBox {
 
     // キャレットがピークを指しているか☆（＾～＾）
     pub fn is_peak_of_current_tape(&self) -> bool {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].is_peak()
         } else {
             panic!(
@@ -191,7 +330,7 @@ impl CassetteTapeBox {
     }
     // キャレットが次、オーバーフローするか☆（＾～＾）
     pub fn is_before_caret_overflow_of_tape(&self) -> bool {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].is_before_caret_overflow()
         } else {
             panic!(
@@ -202,7 +341,7 @@ impl CassetteTapeBox {
     }
 
     pub fn is_empty_tapes(&self) -> bool {
-        self.tapes.is_empty()
+        self.len_tapes() == 0
     }
 
     pub fn is_none_current_tape(&self) -> bool {
@@ -214,7 +353,7 @@ impl CassetteTapeBox {
     // #####
 
     pub fn look_back_caret(&mut self, app: &Application) {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].caret.look_back(&app);
         } else {
             panic!(
@@ -225,7 +364,7 @@ impl CassetteTapeBox {
     }
 
     pub fn turn_caret_towards_negative_infinity(&mut self, app: &Application) {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].caret.turn_towards_negative_infinity(&app);
         } else {
             panic!(
@@ -236,7 +375,7 @@ impl CassetteTapeBox {
     }
 
     pub fn turn_caret_towards_positive_infinity(&mut self, app: &Application) {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].caret.turn_towards_positive_infinity(&app);
         } else {
             panic!(
@@ -246,8 +385,16 @@ impl CassetteTapeBox {
         }
     }
 
+    /// 箱に入っているテープの本数。捨てたテープも、まだ読んでいないテープも数える。
+    /// 読めなくて飛ばしたテープは数えない。
     pub fn len_tapes(&self) -> usize {
-        self.tapes.len()
+        self.released_tapes
+            + self.tapes.len()
+            + self
+                .sources
+                .iter()
+                .map(|(reader, _board_size)| reader.len_unread())
+                .sum::<usize>()
     }
 
     // #####
@@ -256,7 +403,7 @@ impl CassetteTapeBox {
 
     /// 正の方のテープの末端にノートを追加。
     pub fn push_note(&mut self, note: ShogiNote) {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].push_note(note)
         } else {
             panic!(
@@ -267,9 +414,9 @@ impl CassetteTapeBox {
     }
 
     /// 正の方のテープの末端にノートを追加。
-    pub fn pop_note(&mut self) -> Option<ShogiNote> {
-        if let Some(index) = self.awareness_of_tapes.index {
-            self.tapes[index].pop_note()
+    pub fn pop_note(&mut self, app: &Application) -> Option<ShogiNote> {
+        if let Some(index) = self.get_current_index() {
+            self.tapes[index].pop_note(app)
         } else {
             panic!(
                 "#Box.Pop note: Please seek tapes. It is none. Slot: '{:?}'.",
@@ -277,10 +424,21 @@ impl CassetteTapeBox {
             );
         }
     }
+    /// 取り外しておいた筋を、今のテープに 取り外す前のとおりに戻す。
+    pub fn put_back_line_of_current_tape(&mut self, line: TapeBranch) {
+        if let Some(index) = self.get_current_index() {
+            self.tapes[index].put_back_line(line);
+        } else {
+            panic!(
+                "#put_back_line_of_current_tape: Please seek tapes. It is none. Slot: '{:?}'.",
+                self.role_as_slot
+            );
+        }
+    }
 
     /*
     pub fn push_note_to_positive_of_current_tape(&mut self, note: ShogiNote) {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].tracks.positive_notes.push(note);
         } else {
             panic!(
@@ -290,7 +448,7 @@ impl CassetteTapeBox {
         }
     }
     pub fn push_note_to_negative_of_current_tape(&mut self, note: ShogiNote) {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].tracks.negative_notes.push(note);
         } else {
             panic!(
@@ -301,6 +459,101 @@ impl CassetteTapeBox {
     }
     */
 
+    // #####
+    // # R #
+    // #####
+
+    /// 箱の中のテープの名前。ファイルの中のテープは、ファイルから読む。読めなければ None。
+    fn read_name_of_stored_tape(&mut self, stored: StoredTape) -> Option<String> {
+        match stored {
+            StoredTape::Loaded(index) => Some(self.tapes[index].label.get_name()),
+            StoredTape::InFile(source, index) => self.sources[source]
+                .0
+                .read_tape(index)
+                .ok()
+                .map(|tape_j| tape_j.label.get_name()),
+        }
+    }
+
+    /// 箱の中のテープを、RPM棋譜に直す。ファイルの中のテープは、ファイルから読む。
+    fn read_stored_tape(
+        &mut self,
+        stored: StoredTape,
+        board_size: BoardSize,
+    ) -> Result<RpmTape, ParseError> {
+        match stored {
+            StoredTape::Loaded(index) => Ok(self.tapes[index].to_rpm(board_size)),
+            StoredTape::InFile(source, index) => self.sources[source].0.read_tape(index),
+        }
+    }
+
+    /// まだ読んでいないテープを１本読んで、テープに直して 後ろに足す。
+    /// 読めないテープは、ログに書いて飛ばす。
+    ///
+    /// # Returns
+    ///
+    /// 読めたら真。もう読むテープが無ければ偽。
+    fn read_next_tape(&mut self, app: &Application) -> bool {
+        while let Some(source) = self
+            .sources
+            .iter()
+            .position(|(reader, _board_size)| 0 < reader.len_unread())
+        {
+            let (ref mut reader, board_size) = self.sources[source];
+            let index = reader.len() - reader.len_unread();
+            match reader.next() {
+                Some(Ok(tape_j)) => {
+                    // テープを追加中。テープを追加しても、キャレットは進まない☆（*＾～＾*）
+                    self.tapes.push(tape_j.to_object(board_size, app));
+                    self.origins.push(Some((source, index)));
+                    return true;
+                }
+                Some(Err(err)) => {
+                    app.comm.println(&format!("#Skip tape. {}", err));
+                }
+                None => {}
+            }
+        }
+        false
+    }
+
+    /// 通り過ぎた、ファイルから読んだテープを捨てる。要る時は ファイルから読み直せる。
+    fn release_passed_tapes(&mut self) {
+        let current = self.awareness_of_tapes.index.unwrap_or(0);
+        while self.released_tapes < current && self.origins[self.released_tapes].is_some() {
+            self.tapes.remove(0);
+            self.released_tapes += 1;
+        }
+    }
+
+    /// 今のテープを、キャレットごと丸ごと差し替える。
+    pub fn replace_current_tape(&mut self, tape: CassetteTape) {
+        if let Some(index) = self.get_current_index() {
+            self.tapes[index] = tape;
+        } else {
+            panic!(
+                "#replace_current_tape: Please seek tapes. It is none. Slot: '{:?}'.",
+                self.role_as_slot
+            );
+        }
+    }
+
+    /// キャレットの所にノートを書いて、キャレットを進める。
+    ///
+    /// # Returns
+    ///
+    /// キャレットから先の筋が変わったら真。
+    pub fn record_note(&mut self, note: ShogiNote, app: &Application) -> bool {
+        if let Some(index) = self.get_current_index() {
+            self.tapes[index].record_note(note, app)
+        } else {
+            panic!(
+                "#Box.Record note: Please seek tapes. It is none. Slot: '{:?}'.",
+                self.role_as_slot
+            );
+        }
+    }
+
     // #####
     // # S #
     // #####
@@ -311,7 +564,7 @@ impl CassetteTapeBox {
 
     /// テープのラベルを書く。
     pub fn set_label_of_tape(&mut self, label: &TapeLabel) {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].set_label(label);
         } else {
             panic!(
@@ -321,6 +574,51 @@ impl CassetteTapeBox {
         }
     }
 
+    pub fn set_annotations_of_tape(&mut self, annotations: &[MoveAnnotation]) {
+        if let Some(index) = self.get_current_index() {
+            self.tapes[index].set_annotations(annotations);
+        } else {
+            panic!(
+                "#set_annotations_of_tape: Please seek tapes. It is none. Slot: '{:?}'.",
+                self.role_as_slot
+            );
+        }
+    }
+
+    pub fn set_principal_variation_of_tape(&mut self, ply: i16, pv: &[String]) {
+        if let Some(index) = self.get_current_index() {
+            self.tapes[index].set_principal_variation(ply, pv);
+        } else {
+            panic!(
+                "#set_principal_variation_of_tape: Please seek tapes. It is none. Slot: '{:?}'.",
+                self.role_as_slot
+            );
+        }
+    }
+
+    /// キャレットの所で分かれる枝の nth 番目に乗り換える。
+    ///
+    /// # Returns
+    ///
+    /// 乗り換えたら真。そんな枝が無ければ偽。
+    pub fn switch_branch_at_caret(&mut self, nth: usize) -> bool {
+        if let Some(index) = self.get_current_index() {
+            let tape = &mut self.tapes[index];
+            match tape.get_branch_indexes_at_caret().get(nth) {
+                Some(branch_index) => {
+                    tape.switch_branch(*branch_index);
+                    true
+                }
+                None => false,
+            }
+        } else {
+            panic!(
+                "#switch_branch_at_caret: Please seek tapes. It is none. Slot: '{:?}'.",
+                self.role_as_slot
+            );
+        }
+    }
+
     /// ◆次のテープを利用するぜ☆（＾～＾）
     /// 次のテープが無ければ、おわり☆（＾ｑ＾）
     ///
@@ -332,7 +630,12 @@ impl CassetteTapeBox {
             app.comm.println("[#Box.seek_of_tapes: 開始]");
         }
 
-        if self.tapes.is_empty() {
+        if (self.released_tapes + self.tapes.len()) as i16 <= self.caret_of_tapes.step_in() {
+            // 読んだテープの終わりなら、まだ読んでいないテープを１本読む。
+            self.read_next_tape(app);
+        }
+
+        if self.released_tapes + self.tapes.len() == 0 {
             // テープが無いなら。
             if app.is_debug() {
                 app.comm.println("[#Seek of tapes:テープが無い]");
@@ -340,7 +643,7 @@ impl CassetteTapeBox {
             return false;
         }
 
-        if self.tapes.len() as i16 <= self.caret_of_tapes.step_in() {
+        if (self.released_tapes + self.tapes.len()) as i16 <= self.caret_of_tapes.step_in() {
             // 今回はテープの終わりなら。
             if app.is_debug() {
                 app.comm.println("[#Seek of tapes:今回はテープの終わり]");
@@ -349,6 +652,7 @@ impl CassetteTapeBox {
         }
 
         self.awareness_of_tapes = self.caret_of_tapes.seek_a_note(&app);
+        self.release_passed_tapes();
         if app.is_debug() {
             app.comm.println(&format!(
                 "[#Seek of tapes: {}]",
@@ -367,7 +671,7 @@ impl CassetteTapeBox {
     ///
     /// (taken overflow, awareness, note)
     pub fn seek_a_note(&mut self, app: &Application) -> (bool, Awareness, Option<ShogiNote>) {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].seek_a_note(&app)
         } else {
             panic!(
@@ -381,7 +685,7 @@ impl CassetteTapeBox {
     ///
     /// (taken overflow, move)
     pub fn skip_a_move(&mut self, app: &Application) -> (bool, ShogiMove) {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].skip_a_move(&app)
         } else {
             panic!(
@@ -401,7 +705,7 @@ impl CassetteTapeBox {
         caret: &mut Caret,
         app: &Application,
     ) -> (bool, Awareness, Option<ShogiNote>) {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].seek_a_note_with_othre_caret(caret, &app)
         } else {
             panic!(
@@ -411,8 +715,34 @@ impl CassetteTapeBox {
         }
     }
 
+    /// 今のテープのキャレットを、caret 番地へ飛ばす。
+    ///
+    /// # Returns
+    ///
+    /// 通り過ぎたノート。局面で触る順。テープが無いか、飛べなければ None。
+    pub fn seek_to_caret_of_tape(
+        &mut self,
+        caret: i16,
+        app: &Application,
+    ) -> Option<Vec<ShogiNote>> {
+        match self.get_current_index() {
+            Some(index) => self.tapes[index].seek_to_caret(caret, app),
+            None => None,
+        }
+    }
+    /// 今のテープのキャレットを、ply 手目の指し手の頭へ飛ばす。
+    ///
+    /// # Returns
+    ///
+    /// 通り過ぎたノート。局面で触る順。テープが無いか、その手目が無ければ None。
+    pub fn seek_to_ply_of_tape(&mut self, ply: i16, app: &Application) -> Option<Vec<ShogiNote>> {
+        match self.get_current_index() {
+            Some(index) => self.tapes[index].seek_to_ply(ply, app),
+            None => None,
+        }
+    }
     pub fn step_in_of_tape(&self) -> i16 {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].caret.step_in()
         } else {
             panic!(
@@ -426,16 +756,30 @@ impl CassetteTapeBox {
     // # T #
     // #####
 
-    pub fn to_rpm(&self, board_size: BoardSize) -> RpmTapeBox {
+    /// 箱のテープを全部、RPM棋譜に直す。捨てたテープと まだ読んでいないテープは ファイルから読む。
+    /// ファイルから読んだテープは ファイルの順のまま、ファイルから読んでいないテープは その後ろに並べる。
+    /// 読めないテープは、ログに書いて飛ばす。
+    pub fn to_rpm(&mut self, board_size: BoardSize, app: &Application) -> RpmTapeBox {
         let mut tape_box = RpmTapeBox::new();
-
-        for tape in &self.tapes {
-            tape_box.push(tape.to_rpm(board_size));
+        for stored in self.get_stored_tapes() {
+            match self.read_stored_tape(stored, board_size) {
+                Ok(tape_j) => tape_box.push(tape_j),
+                Err(err) => app.comm.println(&format!("#Skip tape. {}", err)),
+            }
         }
 
         tape_box
     }
 
+    /// 今のテープの筋を、枝も注釈も丸ごと取り外す。テープが無ければ None。
+    pub fn take_line_of_current_tape(&mut self) -> Option<TapeBranch> {
+        if let Some(index) = self.get_current_index() {
+            Some(self.tapes[index].take_line())
+        } else {
+            None
+        }
+    }
+
     /// このテープ・ボックスのデバッグ情報表示。人間向け。
     pub fn to_human_presentable(&self) -> String {
         if let Some(index) = self.awareness_of_tapes.index {
@@ -449,7 +793,7 @@ impl CassetteTapeBox {
                 }
                 .to_string(),
                 self.file_name,
-                self.tapes.len(),
+                self.len_tapes(),
                 index
             )
             .to_string()
@@ -464,7 +808,7 @@ impl CassetteTapeBox {
         board_size: BoardSize,
         app: &Application,
     ) -> String {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             use audio_compo::cassette_deck::Slot::*;
             format!(
                 "[{}-Box: Tape index: {}, Tape: {}]",
@@ -474,7 +818,7 @@ impl CassetteTapeBox {
                     Principal => "P",
                 }
                 .to_string(),
-                index,
+                index + self.released_tapes,
                 self.tapes[index].to_human_presentable(board_size, &app)
             )
             .to_string()
@@ -483,9 +827,22 @@ impl CassetteTapeBox {
         }
     }
 
+    /// 今のテープの、キャレットの所で分かれる枝の表示。人間向け。
+    pub fn to_human_presentable_of_branches_at_caret(&self, board_size: BoardSize) -> Vec<String> {
+        if let Some(index) = self.get_current_index() {
+            let tape = &self.tapes[index];
+            tape.get_branch_indexes_at_caret()
+                .iter()
+                .map(|branch_index| tape.branches[*branch_index].to_human_presentable(board_size))
+                .collect()
+        } else {
+            Vec::new()
+        }
+    }
+
     /// 現在聴いているテープのキャレットのデバッグ情報表示。人間向け。
     pub fn to_human_presentable_of_caret(&self, app: &Application) -> String {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             format!(
                 "{:?}-box {}",
                 self.role_as_slot,
@@ -503,7 +860,7 @@ impl CassetteTapeBox {
 
     /// このテープを、テープ・フラグメント書式で書きだすぜ☆（＾～＾）
     pub fn write_current_tapes_fragment(&self, board_size: BoardSize, app: &Application) {
-        if let Some(index) = self.awareness_of_tapes.index {
+        if let Some(index) = self.get_current_index() {
             self.tapes[index].write_tape_fragment(board_size, &app)
         } else {
             panic!(
@@ -514,8 +871,149 @@ impl CassetteTapeBox {
     }
 
     /// このテープ・ボックスを書きだすぜ☆（＾～＾）
-    pub fn write_tape_box(&self, board_size: BoardSize, app: &Application) {
-        let rpm_tape_box = self.to_rpm(board_size);
+    pub fn write_tape_box(&mut self, board_size: BoardSize, app: &Application) {
+        let rpm_tape_box = self.to_rpm(board_size, app);
         rpm_tape_box.write(&self.file_name, &app);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ▲７六歩 を指したテープ３本のうち、２本目が読めない箱のファイル。
+    fn write_box_with_bad_tape() -> String {
+        let mut rpm_tape_box = RpmTapeBox::new();
+        for name in &["a", "b", "c"] {
+            let mut tape = RpmTape::new();
+            tape.label.set_name(name);
+            tape.tracks.id = "-1 23 23 -1".to_string();
+            tape.tracks.ope = "[1] 77 76 [1]".to_string();
+            tape.tracks.facing = ". . . .".to_string();
+            rpm_tape_box.push(tape);
+        }
+        let mut value = serde_json::to_value(&rpm_tape_box).unwrap_or_else(|err| panic!("{}", err));
+        value["tape_box"][1]["label"] = serde_json::json!(1);
+        Application::write_test_file(
+            "cassette-tape-box/bad-tape-box.json",
+            value.to_string().as_bytes(),
+        )
+    }
+
+    /// 読めないテープは飛ばし、通り過ぎたテープは捨てても、箱の本数と書き出しは 箱全体のもの。
+    #[test]
+    fn skips_bad_tape_and_releases_passed_tapes() {
+        let app = Application::new_for_test();
+        let board_size = BoardSize::create_hon_shogi();
+        let box_file = write_box_with_bad_tape();
+
+        let mut tape_box = CassetteTapeBox::new_empty_tape_box(Slot::Training, &app);
+        tape_box.add_tapes_from_reader(
+            RpmTapeReader::open(&box_file).unwrap_or_else(|err| panic!("{}", err)),
+            board_size,
+        );
+        assert_eq!(tape_box.len_tapes(), 3);
+
+        assert!(tape_box.seek_of_tapes(&app));
+        assert_eq!(
+            tape_box
+                .get_label_of_current_tape()
+                .map(TapeLabel::get_name),
+            Some("a".to_string())
+        );
+
+        // b は読めないので飛ばして c。a は捨てる。
+        assert!(tape_box.seek_of_tapes(&app));
+        assert_eq!(tape_box.get_tape_index(), Some(1));
+        assert_eq!(
+            tape_box
+                .get_label_of_current_tape()
+                .map(TapeLabel::get_name),
+            Some("c".to_string())
+        );
+        assert_eq!(tape_box.tapes.len(), 1);
+        assert_eq!(tape_box.len_tapes(), 2);
+
+        let names: Vec<String> = tape_box
+            .to_rpm(board_size, &app)
+            .tape_box
+            .iter()
+            .map(|tape| tape.label.get_name())
+            .collect();
+        assert_eq!(names, vec!["a", "c"]);
+
+        assert!(!tape_box.seek_of_tapes(&app));
+    }
+
+    /// 読みかけの箱に テープを足して書き出しても、ファイルのテープの順は変わらず、足したテープは後ろ。
+    #[test]
+    fn writes_tapes_in_original_order() {
+        let app = Application::new_for_test();
+        let board_size = BoardSize::create_hon_shogi();
+        let mut rpm_tape_box = RpmTapeBox::new();
+        for name in &["a", "b", "c"] {
+            let mut tape = RpmTape::new();
+            tape.label.set_name(name);
+            tape.tracks.id = "-1 23 23 -1".to_string();
+            tape.tracks.ope = "[1] 77 76 [1]".to_string();
+            tape.tracks.facing = ". . . .".to_string();
+            rpm_tape_box.push(tape);
+        }
+        let box_file = Application::write_test_file(
+            "cassette-tape-box/ordered-tape-box.json",
+            serde_json::to_string(&rpm_tape_box)
+                .unwrap_or_else(|err| panic!("{}", err))
+                .as_bytes(),
+        );
+
+        let mut tape_box = CassetteTapeBox::new_empty_tape_box(Slot::Learning, &app);
+        tape_box.add_tapes_from_reader(
+            RpmTapeReader::open(&box_file).unwrap_or_else(|err| panic!("{}", err)),
+            board_size,
+        );
+        assert!(tape_box.seek_of_tapes(&app));
+        let mut tape = CassetteTape::new_facing_right(&app);
+        tape.label.set_name("x");
+        tape_box.add_tape(tape, &app);
+        // x に移って、a は捨てる。
+        assert!(tape_box.seek_of_tapes(&app));
+        assert_eq!(
+            tape_box
+                .get_label_of_current_tape()
+                .map(TapeLabel::get_name),
+            Some("x".to_string())
+        );
+
+        assert_eq!(tape_box.get_name_of_first_tape(), Some("a".to_string()));
+        assert_eq!(tape_box.get_name_of_last_tape(), Some("x".to_string()));
+
+        tape_box.to_rpm(board_size, &app).write(&box_file, &app);
+        let names: Vec<String> = RpmTapeBox::from_box_file(&box_file, &app)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .tape_box
+            .iter()
+            .map(|tape| tape.label.get_name())
+            .collect();
+        assert_eq!(names, vec!["a", "b", "c", "x"]);
+    }
+
+    /// 捨てたテープを指していても、パニックにならずに None。
+    #[test]
+    fn returns_none_for_released_tape() {
+        let app = Application::new_for_test();
+        let board_size = BoardSize::create_hon_shogi();
+        let box_file = write_box_with_bad_tape();
+
+        let mut tape_box = CassetteTapeBox::new_empty_tape_box(Slot::Training, &app);
+        tape_box.add_tapes_from_reader(
+            RpmTapeReader::open(&box_file).unwrap_or_else(|err| panic!("{}", err)),
+            board_size,
+        );
+        assert!(tape_box.seek_of_tapes(&app));
+        assert!(tape_box.seek_of_tapes(&app));
+        // a を捨てたまま、a を指す。
+        tape_box.awareness_of_tapes.index = Some(0);
+        assert!(tape_box.get_current_index().is_none());
+        assert!(tape_box.get_label_of_current_tape().is_none());
+    }
+}
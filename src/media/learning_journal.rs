@@ -0,0 +1,184 @@
+use media::two_heads_vec::NONE_VALUE;
+use sheet_music_format::kifu_rpm::rpm_tape::*;
+use sheet_music_format::kifu_rpm::rpm_tape_box::*;
+use sheet_music_format::kifu_rpm::rpm_tape_tracks::*;
+use sheet_music_format::tape_label::*;
+use sound::shogi_note::ShogiNote;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::path::Path;
+use studio::application::Application;
+use studio::board_size::BoardSize;
+
+/// 対局中のラーニング・テープのジャーナル。
+/// テープ・ボックスは対局が終わった時にしか書かないので、途中でエンジンが止まっても
+/// 指したところまでは残るように、ノートを１つずつ追記していく。
+///
+/// ```text
+/// box {書き込み先のテープ・ボックスのファイル名}
+/// {背番号} {操作} {向き}
+/// ...
+/// ```
+///
+/// 対局が終わってテープ・ボックスを書いたら消す。起動した時に残っていれば、
+/// 途中で止まった対局なので テープに戻して テープ・ボックスに足す。
+pub struct LearningJournal {
+    file: File,
+    box_file: String,
+    journal_file: String,
+}
+impl LearningJournal {
+    const BOX_PREFIX: &'static str = "box ";
+
+    // #####
+    // # A #
+    // #####
+
+    /// ノートを１つ追記する。
+    pub fn append(&mut self, note: &ShogiNote, board_size: BoardSize) -> io::Result<()> {
+        writeln!(
+            self.file,
+            "{} {} {}",
+            note.get_id().map_or(NONE_VALUE, |pid| pid.get_number()),
+            note.get_ope().to_sign(board_size),
+            ShogiNote::convert_facing_left_str(note.is_facing_left())
+        )?;
+        self.file.flush()
+    }
+
+    // #####
+    // # C #
+    // #####
+
+    /// テープを消した時は、ジャーナルも見出しだけにする。
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.write_header()
+    }
+
+    /// テープ・ボックスを書き終えたので、ジャーナルを消す。
+    pub fn close(self) -> io::Result<()> {
+        let journal_file = self.journal_file.to_string();
+        drop(self.file);
+        fs::remove_file(journal_file)
+    }
+
+    // #####
+    // # O #
+    // #####
+
+    /// ジャーナルを作り直す。
+    ///
+    /// # Arguments
+    ///
+    /// * `box_file` - 対局が途中で止まった時に、テープを足すテープ・ボックス。
+    pub fn open(box_file: &str, app: &Application) -> io::Result<Self> {
+        let journal_file = app.kw29_conf.get_learning_journal_file(app);
+        if let Some(parent) = Path::new(&journal_file).parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&journal_file)?;
+
+        let mut journal = LearningJournal {
+            file,
+            box_file: box_file.to_string(),
+            journal_file,
+        };
+        journal.write_header()?;
+        Ok(journal)
+    }
+
+    // #####
+    // # R #
+    // #####
+
+    /// 途中で止まった対局のジャーナルが残っていれば、テープに戻してテープ・ボックスに足し、ジャーナルを消す。
+    /// テープには、途中で止まった印を付ける。
+    ///
+    /// # Returns
+    ///
+    /// テープを足したテープ・ボックスのファイル名。ジャーナルが無いか、ノートが無ければ None。
+    pub fn recover(app: &Application) -> io::Result<Option<String>> {
+        let journal_file = app.kw29_conf.get_learning_journal_file(app);
+        if !Path::new(&journal_file).exists() {
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(&journal_file)?;
+        let mut lines = contents.lines();
+        let box_file = match lines
+            .next()
+            .and_then(|line| line.get(LearningJournal::BOX_PREFIX.len()..))
+        {
+            Some(box_file) if !box_file.is_empty() => box_file.to_string(),
+            _ => RpmTapeBox::create_file_full_name(&app.kw29_conf, app),
+        };
+
+        // 書いている途中で止まった最後の行は、３つ揃っていなければ捨てる。
+        let mut id_vec = Vec::new();
+        let mut ope_vec = Vec::new();
+        let mut facing_vec = Vec::new();
+        for line in lines {
+            let tokens: Vec<&str> = line.split(' ').collect();
+            if tokens.len() != 3 {
+                continue;
+            }
+            id_vec.push(tokens[0]);
+            ope_vec.push(tokens[1]);
+            facing_vec.push(tokens[2]);
+        }
+
+        let written = if ope_vec.is_empty() {
+            None
+        } else {
+            let mut label = TapeLabel::new();
+            label.set_incomplete(true);
+
+            let mut tracks = RpmTapeTracks::new();
+            tracks.id = id_vec.join(" ");
+            tracks.ope = ope_vec.join(" ");
+            tracks.facing = facing_vec.join(" ");
+
+            // 元の箱が読めなければ、新しい箱に書く。
+            let (box_file, mut rpm_tape_box) = match RpmTapeBox::from_box_file(&box_file, app) {
+                Ok(rpm_tape_box) => (box_file, rpm_tape_box),
+                Err(err) => {
+                    app.comm.println(&format!("#Journal: {}", err));
+                    (
+                        RpmTapeBox::create_file_full_name(&app.kw29_conf, app),
+                        RpmTapeBox::new(),
+                    )
+                }
+            };
+            rpm_tape_box.push(RpmTape { label, tracks });
+            rpm_tape_box.write(&box_file, app);
+            Some(box_file)
+        };
+
+        fs::remove_file(&journal_file)?;
+        Ok(written)
+    }
+
+    // #####
+    // # W #
+    // #####
+
+    fn write_header(&mut self) -> io::Result<()> {
+        writeln!(
+            self.file,
+            "{}{}",
+            LearningJournal::BOX_PREFIX,
+            self.box_file
+        )?;
+        self.file.flush()
+    }
+}
@@ -1,3 +1,5 @@
 pub mod cassette_tape;
 pub mod cassette_tape_box;
+pub mod learning_journal;
+pub mod tape_branch;
 pub mod two_heads_vec;
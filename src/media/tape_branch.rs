@@ -0,0 +1,134 @@
+use media::two_heads_vec::*;
+use sheet_music_format::kifu_rpm::rpm_tape_branch::RpmTapeBranch;
+use sheet_music_format::move_annotation::MoveAnnotation;
+use sound::shogi_note::ShogiNote;
+use studio::board_size::*;
+
+/// テープの枝。変化、検討の筋、別の手筋など。
+/// 親の筋の fork 番目のノートから先を、notes に入れ替えたものが この枝の筋。
+/// 枝が持てるのは、正の方のテープだけ。
+#[derive(Clone)]
+pub struct TapeBranch {
+    /// 分かれる所。親の筋の、正の方のテープのノートの番号。
+    pub fork: usize,
+    /// 分かれた所から先のノート。
+    pub notes: Vec<ShogiNote>,
+    /// 分かれた所から先の、指し手ごとの注釈。
+    pub annotations: Vec<MoveAnnotation>,
+    /// この枝から、さらに分かれる枝。fork は親の筋と同じく、筋の先頭から数える。
+    pub branches: Vec<TapeBranch>,
+}
+impl TapeBranch {
+    // #####
+    // # A #
+    // #####
+
+    /// split_off で切り離した筋の先を、筋の後ろに付け直す。
+    pub fn append(&mut self, mut tail: TapeBranch) {
+        self.notes.append(&mut tail.notes);
+        self.annotations.append(&mut tail.annotations);
+        self.annotations.sort_by_key(|annotation| annotation.ply);
+        self.branches.append(&mut tail.branches);
+    }
+
+    // #####
+    // # C #
+    // #####
+
+    /// 先頭から同じノートが いくつ続くか。
+    pub fn count_same_notes(notes: &[ShogiNote], other_notes: &[ShogiNote]) -> usize {
+        notes
+            .iter()
+            .zip(other_notes.iter())
+            .take_while(|(note, other_note)| note == other_note)
+            .count()
+    }
+
+    // #####
+    // # G #
+    // #####
+
+    /// この枝の最初の手目。分かれた所から先の、最初のフェーズ・チェンジの手目。
+    pub fn get_first_ply(notes: &[ShogiNote]) -> Option<i16> {
+        notes.iter().find_map(|note| note.get_ply())
+    }
+
+    /// 分かれた所から先の注釈を、注釈の並びから抜き出す。
+    pub fn split_off_annotations(
+        annotations: &mut Vec<MoveAnnotation>,
+        notes: &[ShogiNote],
+    ) -> Vec<MoveAnnotation> {
+        match TapeBranch::get_first_ply(notes) {
+            Some(first_ply) => {
+                let (branch_annotations, rest): (Vec<MoveAnnotation>, Vec<MoveAnnotation>) =
+                    annotations
+                        .drain(..)
+                        .partition(|annotation| first_ply <= annotation.ply);
+                *annotations = rest;
+                branch_annotations
+            }
+            None => Vec::new(),
+        }
+    }
+
+    // #####
+    // # S #
+    // #####
+
+    /// 筋の fork 番目のノートから先を切り離す。first_ply 手目から先の注釈と、fork から先で分かれる枝も付いてくる。
+    pub fn split_off(&mut self, fork: usize, first_ply: i16) -> TapeBranch {
+        let (annotations, rest): (Vec<MoveAnnotation>, Vec<MoveAnnotation>) = self
+            .annotations
+            .drain(..)
+            .partition(|annotation| first_ply <= annotation.ply);
+        self.annotations = rest;
+        let (branches, rest): (Vec<TapeBranch>, Vec<TapeBranch>) = self
+            .branches
+            .drain(..)
+            .partition(|branch| fork <= branch.fork);
+        self.branches = rest;
+
+        TapeBranch {
+            fork,
+            notes: self.notes.split_off(fork),
+            annotations,
+            branches,
+        }
+    }
+
+    // #####
+    // # T #
+    // #####
+
+    pub fn to_rpm(&self, board_size: BoardSize) -> RpmTapeBranch {
+        let mut tracks =
+            TwoHeadsVec::from_vector(self.notes.to_vec(), Vec::new()).to_rpm_tracks(board_size);
+        tracks.annotation = self.annotations.clone();
+        tracks.branch = self
+            .branches
+            .iter()
+            .map(|branch| branch.to_rpm(board_size))
+            .collect();
+
+        RpmTapeBranch {
+            fork: self.fork,
+            tracks,
+        }
+    }
+
+    /// Human presentable.
+    pub fn to_human_presentable(&self, board_size: BoardSize) -> String {
+        let opes: Vec<String> = self
+            .notes
+            .iter()
+            .map(|note| note.get_ope().to_sign(board_size))
+            .collect();
+        format!(
+            "[Branch: Fork: {}, Notes: {}, Branches: {}. {}]",
+            self.fork,
+            self.notes.len(),
+            self.branches.len(),
+            opes.join(" ")
+        )
+    }
+}
@@ -1,20 +1,23 @@
 use sheet_music_format::kifu_rpm::rpm_tape_tracks::*;
 use sound::shogi_move::*;
 use sound::shogi_note::*;
+use std::collections::BTreeMap;
 use std::*;
 use studio::application::Application;
 use studio::board_size::*;
 use studio::common::caret::*;
 use studio::common::closed_interval::ClosedInterval;
 
-const NONE_VALUE: i8 = -1;
+pub const NONE_VALUE: i8 = -1;
 
 /// Reversible physical move.
 /// 説明 https://ch.nicovideo.jp/kifuwarabe/blomaga/ar1752788
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct TwoHeadsVec {
     positive_notes: Vec<ShogiNote>,
     negative_notes: Vec<ShogiNote>,
+    /// 手目の索引。正の方のテープで、その手目の指し手の頭のキャレット番地。
+    ply_index: BTreeMap<i16, i16>,
 }
 impl fmt::Display for TwoHeadsVec {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -35,14 +38,18 @@ impl TwoHeadsVec {
         Self {
             positive_notes: Vec::new(),
             negative_notes: Vec::new(),
+            ply_index: BTreeMap::new(),
         }
     }
 
     pub fn from_vector(positive_v: Vec<ShogiNote>, negative_v: Vec<ShogiNote>) -> Self {
-        Self {
+        let mut tvec = Self {
             positive_notes: positive_v,
             negative_notes: negative_v,
-        }
+            ply_index: BTreeMap::new(),
+        };
+        tvec.index_plies_from(0);
+        tvec
     }
 
     /// キャレット位置にノートを挿入した新しいオブジェクトを返します。
@@ -160,16 +167,27 @@ impl TwoHeadsVec {
 
     /// 連結。
     pub fn append_tape_to_right(&mut self, tape_to_empty: &mut Self) {
+        let len = self.positive_notes.len();
         self.positive_notes
             .append(&mut tape_to_empty.negative_notes);
         self.positive_notes
             .append(&mut tape_to_empty.positive_notes);
+        tape_to_empty.ply_index.clear();
+        self.index_plies_from(len);
     }
     pub fn append_tape_to_left(&mut self, tape_to_empty: &mut Self) {
         self.negative_notes
             .append(&mut tape_to_empty.positive_notes);
         self.negative_notes
             .append(&mut tape_to_empty.negative_notes);
+        tape_to_empty.ply_index.clear();
+    }
+
+    /// 正の方のテープの末端に、ノートを全部追加。
+    pub fn append_positive_notes(&mut self, notes: &mut Vec<ShogiNote>) {
+        let len = self.positive_notes.len();
+        self.positive_notes.append(notes);
+        self.index_plies_from(len);
     }
 
     // #####
@@ -179,6 +197,7 @@ impl TwoHeadsVec {
     pub fn clear(&mut self) {
         self.positive_notes.clear();
         self.negative_notes.clear();
+        self.ply_index.clear();
     }
 
     // #####
@@ -204,6 +223,41 @@ impl TwoHeadsVec {
         self.positive_notes.len() as i16 - 1
     }
 
+    /// 正の方のテープのノート。
+    pub fn get_positive_notes(&self) -> &[ShogiNote] {
+        &self.positive_notes
+    }
+
+    /// 手目の指し手の頭のキャレット番地。無ければ None。
+    pub fn get_caret_of_ply(&self, ply: i16) -> Option<i16> {
+        self.ply_index.get(&ply).cloned()
+    }
+
+    // #####
+    // # I #
+    // #####
+
+    /// 正の方のテープの from 番目から先のノートを、手目の索引に載せる。
+    /// 指し手は `[手目]` で挟まれているので、閉じる方の後ろは 次の手目の頭としても載せる。
+    fn index_plies_from(&mut self, from: usize) {
+        for (i, note) in self.positive_notes.iter().enumerate().skip(from) {
+            if let Some(ply) = note.get_ply() {
+                let caret = i as i16;
+                match self.ply_index.get(&ply).cloned() {
+                    // 開く方。前の手目の閉じる方の後ろとして、もう載っていることもある。
+                    None => {
+                        self.ply_index.insert(ply, caret);
+                    }
+                    Some(head) if head == caret => {}
+                    // 閉じる方。
+                    Some(_head) => {
+                        self.ply_index.entry(ply + 1).or_insert(caret + 1);
+                    }
+                }
+            }
+        }
+    }
+
     // #####
     // # N #
     // #####
@@ -271,17 +325,29 @@ impl TwoHeadsVec {
     /// 正の方のテープの末端にノートを追加。
     pub fn push_note(&mut self, note: ShogiNote) {
         self.positive_notes.push(note);
+        self.index_plies_from(self.positive_notes.len() - 1);
     }
 
     /// 正の方のテープの末端のノートを削除。
     pub fn pop_note(&mut self) -> Option<ShogiNote> {
-        self.positive_notes.pop()
+        let note = self.positive_notes.pop();
+        self.unindex_plies_from(self.positive_notes.len());
+        note
     }
 
     // #####
     // # S #
     // #####
 
+    /// 正の方のテープの、at 番目から先のノートを切り取る。
+    pub fn split_off_positive_notes(&mut self, at: usize) -> Vec<ShogiNote> {
+        if self.positive_notes.len() <= at {
+            return Vec::new();
+        }
+        self.unindex_plies_from(at);
+        self.positive_notes.split_off(at)
+    }
+
     /// キャレットは必ず１つ進みます。
     /// 0 は、正の数とします。（マイナスゼロは無いです）
     /// Noneを返したら、オーバーフローしています。
@@ -665,6 +731,9 @@ impl TwoHeadsVec {
             // 操作は、半角スペース１個区切り。
             ope: operations.trim_start().to_string(),
             facing: facing_left.to_string(),
+            // 注釈と枝は、テープの方で付ける。
+            annotation: Vec::new(),
+            branch: Vec::new(),
         }
     }
 
@@ -706,4 +775,15 @@ impl TwoHeadsVec {
 
         dump
     }
+
+    // #####
+    // # U #
+    // #####
+
+    /// 正の方のテープの from 番目から先を切り落とす時に、手目の索引から外す。
+    /// from 番地は 切り落とした後の末端なので、そこを頭とする手目は残す。
+    fn unindex_plies_from(&mut self, from: usize) {
+        let from = from as i16;
+        self.ply_index.retain(|_ply, caret| *caret <= from);
+    }
 }
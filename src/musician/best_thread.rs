@@ -3,7 +3,7 @@ use studio::application::Application;
 use studio::board_size::BoardSize;
 
 /// 手筋１個分。読み筋。
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct BestThread {
     pub moves: Vec<BestMove>,
 }
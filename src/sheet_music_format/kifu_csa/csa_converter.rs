@@ -6,6 +6,7 @@ use live::base_performer::*;
 use live::ohashi_performer::*;
 use sheet_music_format::kifu_csa::csa_move::*;
 use sheet_music_format::kifu_csa::csa_tape::*;
+use sheet_music_format::parse_error::*;
 use sound::shogi_note_operation::*;
 use studio::address::*;
 use studio::application::Application;
@@ -13,12 +14,17 @@ use studio::application::Application;
 pub struct CsaConverter {}
 impl CsaConverter {
     /// 変換には、初期局面が必要。
+    /// 局面に合わない指し手があれば、そこで止めてエラーを返す。
+    ///
+    /// # Returns
+    ///
+    /// 指した手の数。投了は数えない。
     pub fn play_out_csa_tape(
         ctape: &CsaTape,
         rack: &mut AudioRack,
         position: &mut Position,
         app: &Application,
-    ) {
+    ) -> Result<usize, ParseError> {
         if app.is_debug() {
             app.comm.println("[#play_out_csa_tape:開始]");
         }
@@ -38,14 +44,29 @@ impl CsaConverter {
             }
 
             // 盤を動かしていく。
-            CsaConverter::convert_csa_move(cmove, rack, position, ply, &app);
+            CsaConverter::convert_csa_move(cmove, rack, position, ply, app)?;
 
             ply += 1;
         }
 
+        if ctape.resign {
+            // 投了。
+            let rnote_opes = [
+                ShogiNoteOpe::change_phase(ply),
+                ShogiNoteOpe::resign(),
+                ShogiNoteOpe::change_phase(ply),
+            ];
+            for rnote_ope in rnote_opes.iter() {
+                BasePerformer::improvise_note_ope_no_log(rack, rnote_ope, false, position, app);
+                HumanInterface::bo(rack, position, app);
+            }
+        }
+
         if app.is_debug() {
             app.comm.println("[#play_out_csa_tape:終了]");
         }
+
+        Ok(ctape.moves.len())
     }
 
     /// １ノートずつ盤を動かしながら、ノートを作ります。
@@ -55,7 +76,23 @@ impl CsaConverter {
         position: &mut Position,
         ply: i16,
         app: &Application,
-    ) {
+    ) -> Result<(), ParseError> {
+        // 動かす駒が無ければ、局面に合わない指し手。
+        let is_movable = match cmove.source {
+            Some(source) => position.get_id_piece(source).is_some(),
+            None => cmove.get_drop().is_some_and(|drop| {
+                position
+                    .peek_hand(Piece::from_ph_pt(cmove.phase, drop))
+                    .is_some()
+            }),
+        };
+        if !is_movable {
+            return Err(
+                ParseError::new("CSA", &format!("No piece to move. Ply: {}.", ply))
+                    .with_text(&cmove.to_csa_text()),
+            );
+        }
+
         /*
         if app.is_debug() {
             app.comm.println(&format!(
@@ -333,5 +370,7 @@ impl CsaConverter {
             ));
         }
         */
+
+        Ok(())
     }
 }
@@ -1,7 +1,7 @@
 use instrument::half_player_phase::*;
 use instrument::piece_etc::*;
+use sheet_music_format::parse_error::*;
 use studio::address::*;
-use studio::application::Application;
 use studio::parser::*;
 
 pub struct CsaMove {
@@ -12,65 +12,72 @@ pub struct CsaMove {
     pub koma: Option<PieceType>,
 }
 impl CsaMove {
-    pub fn parse(line: &str, app: &Application) -> Option<CsaMove> {
+    /// 指し手の行を読む。 "+2726FU" など。
+    ///
+    /// # Returns
+    ///
+    /// '%CHUDAN' など 指し手でなければ None。読めなければ、何文字目が悪かったかを返す。
+    pub fn parse(line: &str) -> Result<Option<CsaMove>, ParseError> {
         // comm.println(&format!("Parse   : line: '{}'.", line));
 
         if line.len() < 7 {
-            return None;
+            return Ok(None);
         };
 
-        let ch0 = line
-            .chars()
-            .nth(0)
-            .unwrap_or_else(|| panic!(app.comm.panic("Fail. ch0.")));
-        let ch1 = line
-            .chars()
-            .nth(1)
-            .unwrap_or_else(|| panic!(app.comm.panic("Fail. ch1.")));
-        let ch2 = line
-            .chars()
-            .nth(2)
-            .unwrap_or_else(|| panic!(app.comm.panic("Fail. ch2.")));
-        let ch3 = line
-            .chars()
-            .nth(3)
-            .unwrap_or_else(|| panic!(app.comm.panic("Fail. ch3.")));
-        let ch4 = line
-            .chars()
-            .nth(4)
-            .unwrap_or_else(|| panic!(app.comm.panic("Fail. ch4.")));
-        let str5 = &line[5..=6];
+        let chars: Vec<char> = line.chars().collect();
+        let error = |column: usize, message: &str| -> ParseError {
+            ParseError::new("CSA", message)
+                .with_column(column)
+                .with_text(line)
+        };
 
-        if ch0 == '%' {
+        if chars[0] == '%' {
             // '%CHUDAN' かも。
-            return None;
+            return Ok(None);
+        }
+
+        let phase = match chars[0] {
+            '+' => HalfPlayerPhaseValue::First,
+            '-' => HalfPlayerPhaseValue::Second,
+            _ => return Err(error(1, "Unexpected phase.")),
+        };
+
+        // 筋と段は 1～9。打つ時は移動元が 00。
+        let is_number = |nth: usize, min: char| -> bool {
+            chars.get(nth).is_some_and(|ch| min <= *ch && *ch <= '9')
+        };
+        let is_drop = chars.get(1) == Some(&'0') && chars.get(2) == Some(&'0');
+        for nth in 1..5 {
+            if !((is_drop && nth < 3) || is_number(nth, '1')) {
+                return Err(error(nth + 1, "Unexpected cell."));
+            }
         }
 
-        let src_opt = if ch1 == '0' {
+        let str5: String = chars.iter().skip(5).take(2).collect();
+        let piece_type = CsaMove::koma_to_piece_type(&str5);
+        if piece_type.is_none() {
+            return Err(error(6, "Unexpected piece."));
+        }
+
+        let src_opt = if is_drop {
             // drop.
             None
         } else {
             Some(Cell::from_file_rank(
-                Parser::file_char_to_i8(ch1),
-                Parser::rank_char_to_i8(ch2),
+                Parser::file_char_to_i8(chars[1]),
+                Parser::rank_char_to_i8(chars[2]),
             ))
         };
 
-        let piece_type = CsaMove::koma_to_piece_type(str5);
-
-        Some(CsaMove {
-            phase: match ch0 {
-                '+' => HalfPlayerPhaseValue::First,
-                '-' => HalfPlayerPhaseValue::Second,
-                _ => panic!("Unexpected phase: '{}'.", ch1),
-            },
+        Ok(Some(CsaMove {
+            phase,
             source: src_opt,
             destination: Cell::from_file_rank(
-                Parser::file_char_to_i8(ch3),
-                Parser::rank_char_to_i8(ch4),
+                Parser::file_char_to_i8(chars[3]),
+                Parser::rank_char_to_i8(chars[4]),
             ),
             koma: piece_type,
-        })
+        }))
     }
 
     pub fn is_drop(&self) -> bool {
@@ -106,9 +113,52 @@ impl CsaMove {
             "UM" => Some(PB),
             "RY" => Some(PR),
             "TO" => Some(PP),
-            // _ => { None },
-            _ => panic!("Unexpected koma: '{}'.", koma),
+            _ => None,
+        }
+    }
+
+    pub fn piece_type_to_koma(piece_type: PieceType) -> String {
+        use instrument::piece_etc::PieceType::*;
+        match piece_type {
+            K | PK => "OU",
+            G | PG => "KI",
+            S => "GI",
+            N => "KE",
+            L => "KY",
+            B => "KA",
+            R => "HI",
+            P => "FU",
+            PS => "NG",
+            PN => "NK",
+            PL => "NY",
+            PB => "UM",
+            PR => "RY",
+            PP => "TO",
         }
+        .to_string()
+    }
+
+    /// 棋譜ファイルに書く指し手。 "+7776FU" など。打つときの移動元は "00"。
+    pub fn to_csa_text(&self) -> String {
+        format!(
+            "{}{}{}{}{}",
+            match self.phase {
+                HalfPlayerPhaseValue::Second => "-",
+                _ => "+",
+            },
+            if let Some(src) = self.source {
+                format!("{}{}", src.get_file(), src.get_rank())
+            } else {
+                "00".to_string()
+            },
+            self.destination.get_file(),
+            self.destination.get_rank(),
+            if let Some(km) = self.koma {
+                CsaMove::piece_type_to_koma(km)
+            } else {
+                "".to_string()
+            }
+        )
     }
 
     pub fn to_human_presentable(&self) -> String {
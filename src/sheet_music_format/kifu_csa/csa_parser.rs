@@ -1,9 +1,8 @@
-use regex::Regex;
 use sheet_music_format::kifu_csa::csa_move::*;
 use sheet_music_format::kifu_csa::csa_tape::*;
+use sheet_music_format::parse_error::*;
+use sheet_music_format::record_encoding::*;
 use std::ffi::OsStr;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
 use std::path::Path;
 use std::*;
 use studio::application::Application;
@@ -17,26 +16,65 @@ PI
 +
 +2726FU
 T8
+'コメントは、直前の指し手に付ける。
 -8384FU
 T0
+%TORYO
+     */
+/* Example 複数の対局
+V2.2
+N+Apery
+N-名人コブラ
+PI
++
++2726FU
+%TORYO
+/
+V2.2
+N+名人コブラ
+N-Apery
+PI
++
++7776FU
+%TORYO
      */
 pub struct CsaParser {}
 impl CsaParser {
-    pub fn from_file(file: &str, app: &Application) -> CsaTape {
+    /// '/' だけの行で区切られた対局を、１つずつテープにする。
+    /// 対局が２つ以上あれば、テープ名の後ろに対局の番号を付ける。
+    /// 読めない行があれば、ファイル名と行番号を付けたエラーを返す。
+    pub fn from_file(file: &str, app: &Application) -> Result<Vec<CsaTape>, ParseError> {
+        let mut tapes = Vec::new();
         let mut tape = CsaTape::new();
 
         let file_stem = Path::new(&file)
             .file_stem()
             .and_then(OsStr::to_str)
-            .unwrap_or_else(|| panic!(app.comm.panic("Fail. get_file_stem_from_file_path.")));
+            .ok_or_else(|| ParseError::new("CSA", "No file stem.").with_file(file))?;
         tape.get_mut_tape_label().set_name(file_stem);
 
+        // 対局の中での行番号。
         let mut num = 0;
-        for line_result in
-            BufReader::new(File::open(file).unwrap_or_else(|err| panic!(app.comm.panic_io(&err))))
-                .lines()
-        {
-            let line = line_result.unwrap_or_else(|err| panic!(app.comm.panic_io(&err)));
+        // 対局の中に、空行ではない行があったか。
+        let mut has_content = false;
+        // Shift_JIS のファイルも多い。文字コードを判定して読む。
+        let lines = RecordEncoding::read_lines(file, app)
+            .map_err(|err| ParseError::from_io("CSA", file, &err))?;
+        // ファイルの中での行番号は 1 から。
+        for (i, line) in lines.iter().enumerate() {
+            if line.trim() == "/" {
+                // 対局の区切り。
+                tapes.push(tape);
+                tape = CsaTape::new();
+                tape.get_mut_tape_label().set_name(file_stem);
+                num = 0;
+                has_content = false;
+                continue;
+            }
+
+            if !line.trim().is_empty() {
+                has_content = true;
+            }
 
             if num == 0 && line.starts_with('V') {
                 // 最初の行で V で始まれば バージョン番号と予想。
@@ -46,46 +84,238 @@ impl CsaParser {
             {
                 // 7文字以上で、先頭が +, -, % で始まれば　指し手。
                 // print!("{}  ", line);
-                if let Some(csa_move) = CsaMove::parse(&line, &app) {
+                let csa_move = CsaMove::parse(line)
+                    .map_err(|err| err.with_file(file).with_line(i + 1, line))?;
+                if let Some(csa_move) = csa_move {
                     tape.push_move(csa_move);
                 }
-            } else if line.starts_with("$START_TIME:") {
+            } else if line.starts_with('T') {
+                // 消費時間。秒。直前の指し手に付ける。
+                if let Ok(consumed) = line['T'.len_utf8()..].trim().parse::<u32>() {
+                    let ply = tape.get_annotation_ply();
+                    tape.get_mut_annotation(ply).consumed = Some(consumed);
+                }
+            } else if line.starts_with('\'') {
+                // コメント。直前の指し手に付ける。初手より前なら、初期局面へのコメント。
+                let ply = tape.get_annotation_ply();
+                tape.get_mut_annotation(ply)
+                    .push_comment(&line['\''.len_utf8()..]);
+            } else if line.starts_with("%TORYO") {
+                // 投了。
+                tape.resign = true;
+            } else if let Some(start_time) = line.strip_prefix("$START_TIME:") {
                 // ```
                 // $START_TIME:2018/05/05 09:44:47
                 // ```
-                // 対局年月日と開始時刻。
-                let re = Regex::new(r"\$START_TIME:(.+) (.+)")
-                    .unwrap_or_else(|f| panic!(app.comm.panic(&f.to_string())));
-                let matched = re
-                    .captures(&line)
-                    .unwrap_or_else(|| panic!(app.comm.panic("Fail. regex parse.")));
-                tape.get_mut_tape_label()
-                    .set_game_date(&matched.get(1).map_or("", |m| m.as_str()));
-                tape.get_mut_tape_label()
-                    .set_start_time(&matched.get(2).map_or("", |m| m.as_str()));
-            } else if line.starts_with("N+") {
+                // 対局年月日と開始時刻。時刻は省略されることがある。
+                let label = tape.get_mut_tape_label();
+                label.set_game_date_and_start_time(start_time);
+                let game_date = label.get_game_date();
+                if game_date.is_empty()
+                    || !game_date.chars().all(|ch| ch.is_ascii_digit() || ch == '/')
+                {
+                    return Err(ParseError::new("CSA", "Unexpected $START_TIME.")
+                        .with_file(file)
+                        .with_line(i + 1, line));
+                }
+            } else if let Some(end_time) = line.strip_prefix("$END_TIME:") {
+                // 終了時刻。
+                tape.get_mut_tape_label().set_end_time(end_time);
+            } else if let Some(event) = line.strip_prefix("$EVENT:") {
+                // 棋戦名。
+                tape.get_mut_tape_label().set_event(event);
+            } else if let Some(site) = line.strip_prefix("$SITE:") {
+                // 対局場所。
+                tape.get_mut_tape_label().set_place(site);
+            } else if let Some(opening) = line.strip_prefix("$OPENING:") {
+                // 戦型。
+                tape.get_mut_tape_label().set_battle_type(opening);
+            } else if let Some(player1) = line.strip_prefix("N+") {
                 // 先手プレイヤー名。
-                let re = Regex::new(r"N+(.*)")
-                    .unwrap_or_else(|f| panic!(app.comm.panic(&f.to_string())));
-                let matched = re
-                    .captures(&line)
-                    .unwrap_or_else(|| panic!(app.comm.panic("Fail. regex parse.")));
-                let matched_text = matched.get(1).map_or("", |m| m.as_str());
-                tape.get_mut_tape_label().set_player1(&matched_text);
-            } else if line.starts_with("N-") {
+                tape.get_mut_tape_label().set_player1(player1);
+            } else if let Some(player2) = line.strip_prefix("N-") {
                 // 後手プレイヤー名。
-                let re = Regex::new(r"N-(.*)")
-                    .unwrap_or_else(|f| panic!(app.comm.panic(&f.to_string())));
-                let matched = re
-                    .captures(&line)
-                    .unwrap_or_else(|| panic!(app.comm.panic("Fail. regex parse.")));
-                let matched_text = matched.get(1).map_or("", |m| m.as_str());
-                tape.get_mut_tape_label().set_player2(&matched_text);
+                tape.get_mut_tape_label().set_player2(player2);
+            } else if line.starts_with('$') {
+                // 知らないヘッダー。 "$TIME_LIMIT:..." など。
+                let key = line.split(':').next().unwrap_or("");
+                tape.warnings
+                    .push(format!("Line {}: Unknown header key: '{}'.", i + 1, key));
             }
 
             num += 1;
         }
 
-        tape
+        // 最後の '/' の後ろに、対局が無いこともある。空行しか無ければ 対局ではない。
+        // ヘッダーがあれば、指し手が無くても ラベル付きの空っぽのテープにする。
+        if tapes.is_empty() || has_content {
+            tapes.push(tape);
+        }
+
+        // 指し手の無い対局は、読み飛ばさずに知らせる。
+        let games = tapes.len();
+        for (i, tape) in tapes.iter_mut().enumerate() {
+            if tape.moves.is_empty() {
+                let message = if 1 < games {
+                    format!("Game {}: No moves.", i + 1)
+                } else {
+                    "No moves.".to_string()
+                };
+                tape.warnings.push(message);
+            }
+        }
+
+        if 1 < tapes.len() {
+            for (i, tape) in tapes.iter_mut().enumerate() {
+                tape.get_mut_tape_label()
+                    .set_name(&format!("{} #{}", file_stem, i + 1));
+            }
+        }
+
+        Ok(tapes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(name: &str, text: &str) -> Vec<CsaTape> {
+        let app = Application::new_for_test();
+        let file = Application::write_test_file(name, text.as_bytes());
+        CsaParser::from_file(&file, &app).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    #[test]
+    fn reads_consumed_time_and_comments() {
+        let tapes = parse(
+            "csa_parser/annotation.csa",
+            "V2.2
+N+Apery
+N-名人コブラ
+$START_TIME:2018/05/05 09:44:47
+PI
++
+'初期局面へのコメント。
++2726FU
+T8
+'１手目へのコメント。
+-8384FU
+T0
+%TORYO
+",
+        );
+
+        assert_eq!(tapes.len(), 1);
+        let tape = &tapes[0];
+        assert_eq!(tape.get_tape_label().get_player1(), "Apery");
+        assert_eq!(tape.get_tape_label().get_player2(), "名人コブラ");
+        assert_eq!(tape.get_tape_label().get_game_date(), "2018/05/05");
+        assert_eq!(tape.moves.len(), 2);
+        assert!(tape.resign);
+
+        let find = |ply: i16| {
+            tape.annotations
+                .iter()
+                .find(|annotation| annotation.ply == ply)
+                .unwrap_or_else(|| panic!("No annotation. Ply: {}.", ply))
+        };
+        assert_eq!(find(0).comments, vec!["初期局面へのコメント。"]);
+        assert_eq!(find(1).consumed, Some(8));
+        assert_eq!(find(1).comments, vec!["１手目へのコメント。"]);
+        assert_eq!(find(2).consumed, Some(0));
+    }
+
+    #[test]
+    fn splits_games_and_keeps_last_game_without_moves() {
+        let tapes = parse(
+            "csa_parser/games.csa",
+            "V2.2
+N+Apery
+N-名人コブラ
+PI
++
++2726FU
+%TORYO
+/
+V2.2
+N+名人コブラ
+N-Apery
+PI
++
++7776FU
+-3334FU
+%TORYO
+/
+V2.2
+N+Apery
+N-名人コブラ
+PI
++
+",
+        );
+
+        assert_eq!(tapes.len(), 3);
+        assert_eq!(tapes[0].get_tape_label().get_name(), "games #1");
+        assert_eq!(tapes[0].moves.len(), 1);
+        assert_eq!(tapes[1].get_tape_label().get_player1(), "名人コブラ");
+        assert_eq!(tapes[1].moves.len(), 2);
+
+        // 指し手の無い対局も、ラベル付きのテープにして 知らせる。
+        assert_eq!(tapes[2].get_tape_label().get_name(), "games #3");
+        assert_eq!(tapes[2].get_tape_label().get_player1(), "Apery");
+        assert!(tapes[2].moves.is_empty());
+        assert_eq!(tapes[2].warnings, vec!["Game 3: No moves."]);
+    }
+
+    #[test]
+    fn ignores_blank_lines_after_last_separator() {
+        let tapes = parse(
+            "csa_parser/trailing-separator.csa",
+            "V2.2
+PI
++
++2726FU
+%TORYO
+/
+
+",
+        );
+
+        assert_eq!(tapes.len(), 1);
+        assert_eq!(tapes[0].get_tape_label().get_name(), "trailing-separator");
+        assert!(tapes[0].warnings.is_empty());
+    }
+
+    #[test]
+    fn reports_line_of_unexpected_move() {
+        let app = Application::new_for_test();
+        let file = Application::write_test_file(
+            "csa_parser/broken-move.csa",
+            "V2.2\nPI\n+\n+2726FU\n/\nPI\n+\n+27X6FU\n".as_bytes(),
+        );
+
+        let err = match CsaParser::from_file(&file, &app) {
+            Ok(_tapes) => panic!("Unexpected success."),
+            Err(err) => err,
+        };
+        assert_eq!(err.line, 8);
+        assert_eq!(err.text, "+27X6FU");
+    }
+
+    #[test]
+    fn reports_line_of_malformed_start_time() {
+        let app = Application::new_for_test();
+        let file = Application::write_test_file(
+            "csa_parser/broken-start-time.csa",
+            "V2.2\n$START_TIME:\nPI\n+\n+2726FU\n".as_bytes(),
+        );
+
+        let err = match CsaParser::from_file(&file, &app) {
+            Ok(_tapes) => panic!("Unexpected success."),
+            Err(err) => err,
+        };
+        assert_eq!(err.line, 2);
+        assert_eq!(err.text, "$START_TIME:");
     }
 }
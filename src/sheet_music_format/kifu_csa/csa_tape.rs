@@ -2,23 +2,44 @@ use instrument::half_player_phase::*;
 use instrument::position::*;
 use sheet_music_format::kifu_csa::csa_move::*;
 use sheet_music_format::kifu_csa::csa_parser::*;
+use sheet_music_format::kifu_rpm::rpm_move::*;
+use sheet_music_format::kifu_rpm::rpm_tape::*;
+use sheet_music_format::move_annotation::MoveAnnotation;
+use sheet_music_format::parse_error::*;
 use sheet_music_format::tape_label::*;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::prelude::*;
+use std::path::Path;
 use std::*;
 use studio::application::Application;
+use studio::board_size::*;
 
 #[derive(Default)]
 pub struct CsaTape {
     // 指し手。
     pub moves: Vec<CsaMove>,
 
+    // 投了で終わったか。
+    pub resign: bool,
+
+    // コメントと消費時間。手目の順。0手目は初期局面へのコメント。
+    pub annotations: Vec<MoveAnnotation>,
+
     // テープ・ラベル。
     tape_label: TapeLabel,
+
+    // 読み飛ばしたもの。知らないヘッダーなど。
+    pub warnings: Vec<String>,
 }
 impl CsaTape {
     pub fn new() -> CsaTape {
         CsaTape {
             moves: Vec::new(),
+            resign: false,
+            annotations: Vec::new(),
             tape_label: TapeLabel::new(),
+            warnings: Vec::new(),
         }
     }
 
@@ -26,14 +47,49 @@ impl CsaTape {
     // # F #
     // #####
 
-    pub fn from_file(file: &str, app: &Application) -> CsaTape {
+    /// ファイル読取。１つのファイルに、対局がいくつか入っていることがある。
+    pub fn from_file(file: &str, app: &Application) -> Result<Vec<CsaTape>, ParseError> {
         CsaParser::from_file(&file, &app)
     }
 
+    /// RPMテープ１本を、CSA に書き直す。
+    pub fn from_rpm_tape(rtape: &RpmTape, board_size: BoardSize, app: &Application) -> CsaTape {
+        let mut ctape = CsaTape::new();
+        ctape.tape_label = rtape.label.clone();
+        ctape.annotations = rtape.tracks.annotation.clone();
+
+        for rmove in RpmMove::parse_rpm_tape(rtape, board_size, app) {
+            if rmove.resign {
+                ctape.resign = true;
+                break;
+            }
+
+            // CSA の駒は、動かした後の駒。
+            let piece_type = rmove
+                .piece_type
+                .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. rmove.piece_type.")));
+            ctape.moves.push(CsaMove {
+                phase: rmove.phase,
+                source: rmove.source,
+                destination: rmove
+                    .destination
+                    .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. rmove.destination."))),
+                koma: Some(promote_piece_type(piece_type, rmove.promotion)),
+            });
+        }
+
+        ctape
+    }
+
     // #####
     // # G #
     // #####
 
+    /// 次に読むコメントや消費時間が付く手目。最後に読んだ指し手か、投了。
+    pub fn get_annotation_ply(&self) -> i16 {
+        self.moves.len() as i16 + if self.resign { 1 } else { 0 }
+    }
+
     pub fn get_current_phase(&self) -> HalfPlayerPhaseValue {
         match self.moves.len() % 2 {
             0 => HalfPlayerPhaseValue::First,
@@ -41,6 +97,28 @@ impl CsaTape {
         }
     }
 
+    /// 手目の注釈。無ければ作る。
+    pub fn get_mut_annotation(&mut self, ply: i16) -> &mut MoveAnnotation {
+        let index = match self
+            .annotations
+            .iter()
+            .position(|annotation| annotation.ply == ply)
+        {
+            Some(index) => index,
+            None => {
+                self.annotations.push(MoveAnnotation::new(ply));
+                self.annotations.len() - 1
+            }
+        };
+
+        &mut self.annotations[index]
+    }
+
+    // テープ・ラベル。
+    pub fn get_tape_label(&self) -> &TapeLabel {
+        &self.tape_label
+    }
+
     // テープ・ラベル。
     pub fn get_mut_tape_label(&mut self) -> &mut TapeLabel {
         &mut self.tape_label
@@ -53,7 +131,6 @@ impl CsaTape {
     pub fn make_move(&mut self, cmove: CsaMove, position: &mut Position, app: &Application) {
         if cmove.is_drop() {
             // TODO drop
-
         } else {
             let source_id_piece_opt = position.remove_id_piece(
                 cmove
@@ -97,4 +174,99 @@ impl CsaTape {
 
         text
     }
+
+    /// CSA のテキスト。
+    pub fn to_csa_text(&self) -> String {
+        let label = &self.tape_label;
+        let mut text = "V2.2\n".to_string();
+
+        let start = format!("{} {}", label.get_game_date(), label.get_start_time());
+        let pairs = [
+            ("N+", label.get_player1()),
+            ("N-", label.get_player2()),
+            ("$EVENT:", label.get_event()),
+            ("$SITE:", label.get_place()),
+            ("$START_TIME:", start.trim().to_string()),
+            ("$END_TIME:", label.get_end_time()),
+            ("$OPENING:", label.get_battle_type()),
+        ];
+        for (key, value) in pairs.iter() {
+            if !value.is_empty() {
+                text = format!("{}{}{}\n", text, key, value);
+            }
+        }
+
+        // 平手の初期局面。先手から。
+        text = format!("{}PI\n+\n", text);
+        text = format!("{}{}", text, self.annotation_to_csa_text(0));
+
+        for (i, cmove) in self.moves.iter().enumerate() {
+            text = format!("{}{}\n", text, cmove.to_csa_text());
+            text = format!("{}{}", text, self.annotation_to_csa_text(i as i16 + 1));
+        }
+
+        if self.resign {
+            text = format!("{}%TORYO\n", text);
+            text = format!(
+                "{}{}",
+                text,
+                self.annotation_to_csa_text(self.moves.len() as i16 + 1)
+            );
+        }
+
+        text
+    }
+
+    /// 指し手の後ろに付く、消費時間とコメントの行。
+    fn annotation_to_csa_text(&self, ply: i16) -> String {
+        let mut text = String::new();
+
+        if let Some(annotation) = MoveAnnotation::find(&self.annotations, ply) {
+            if let Some(consumed) = annotation.consumed {
+                text = format!("{}T{}\n", text, consumed);
+            }
+
+            for comment in &annotation.comments {
+                text = format!("{}'{}\n", text, comment);
+            }
+        }
+
+        text
+    }
+
+    // #####
+    // # W #
+    // #####
+
+    /// ファイル単位で書きだすぜ☆（＾～＾）
+    pub fn write(&self, file_name: &str, app: &Application) {
+        if app.is_debug() {
+            app.comm
+                .println(&format!("#Write csa to '{}'...", file_name));
+        }
+
+        let path = Path::new(&file_name);
+
+        // ディレクトリー作成。
+        if let Some(parent) = path.parent() {
+            match fs::create_dir_all(parent) {
+                Ok(_x) => {}
+                Err(err) => panic!("{}", err),
+            }
+        } else {
+            panic!("Create directory fail. {}", file_name);
+        }
+
+        // 全文上書き☆（＾～＾）
+        let mut file_obj = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)
+            .unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)));
+
+        if let Err(e) = write!(file_obj, "{}", self.to_csa_text()) {
+            eprintln!("Couldn't write to file: {}", e);
+        }
+    }
 }
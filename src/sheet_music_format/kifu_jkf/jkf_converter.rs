@@ -0,0 +1,207 @@
+use audio_compo::audio_rack::*;
+use human::human_interface::*;
+use instrument::half_player_phase::*;
+use instrument::position::*;
+use live::base_performer::*;
+use live::ohashi_performer::*;
+use sheet_music_format::kifu_jkf::jkf_move::*;
+use sheet_music_format::kifu_jkf::jkf_tape::*;
+use sheet_music_format::parse_error::*;
+use sound::shogi_note_operation::*;
+use studio::address::*;
+use studio::application::Application;
+
+pub struct JkfConverter {}
+impl JkfConverter {
+    /// 変換には、初期局面が必要。
+    ///
+    /// # Arguments
+    ///
+    /// * `jline` - 本譜か、変化を広げた１本道の手順。
+    ///
+    /// # Returns
+    ///
+    /// 指した手の数。投了は数えない。
+    pub fn play_out_jkf_line(
+        jtape: &JkfTape,
+        jline: &JkfLine,
+        rack: &mut AudioRack,
+        position: &mut Position,
+        app: &Application,
+    ) -> Result<usize, ParseError> {
+        if !jtape.is_hirate() {
+            return Err(ParseError::new("JKF", "Initial preset is not HIRATE."));
+        }
+
+        // 大橋流を指すところから☆（*＾～＾*）
+        OhashiPerformer::improvise_ohashi_starting(rack, position, app);
+
+        let mut ply = 1;
+        for jmove_format in &jline.moves {
+            let rnote_opes = JkfConverter::convert_move(jmove_format, position, ply, app)?;
+            if rnote_opes.is_empty() {
+                // 投了以外の終局。
+                break;
+            }
+
+            for rnote_ope in rnote_opes {
+                BasePerformer::improvise_note_ope_no_log(rack, &rnote_ope, false, position, app);
+                HumanInterface::bo(rack, position, app);
+            }
+
+            if jmove_format.is_resign() {
+                break;
+            }
+
+            ply += 1;
+        }
+
+        Ok((ply - 1) as usize)
+    }
+
+    /// 変換には、現局面が必要。指し手も投了も無ければ、空っぽを返す。
+    ///
+    /// 駒台の番地は、局面の手番ではなく JKF の color から決める☆（＾～＾）
+    pub fn convert_move(
+        jmove_format: &JkfMoveFormat,
+        position: &Position,
+        ply: i16,
+        _app: &Application,
+    ) -> Result<Vec<ShogiNoteOpe>, ParseError> {
+        let mut rmoves = Vec::new();
+
+        if jmove_format.is_resign() {
+            rmoves.push(ShogiNoteOpe::change_phase(ply));
+            rmoves.push(ShogiNoteOpe::resign());
+            rmoves.push(ShogiNoteOpe::change_phase(ply));
+            return Ok(rmoves);
+        }
+
+        let jmove = match jmove_format.move_ {
+            Some(ref jmove) => jmove,
+            None => return Ok(rmoves),
+        };
+
+        let phase_value = if jmove.color == 0 {
+            HalfPlayerPhaseValue::First
+        } else {
+            HalfPlayerPhaseValue::Second
+        };
+
+        // ####################
+        // # (0) Change phase #
+        // ####################
+        {
+            rmoves.push(ShogiNoteOpe::change_phase(ply));
+        }
+
+        let destination_address = Address::from_cell(jmove.to.to_cell(), position.get_board_size());
+
+        match jmove.from {
+            None => {
+                // 1,4 は駒を打つ(drop)動きの場合
+
+                // #################
+                // # (1d) Hand off #
+                // #################
+                {
+                    let drop = jmove.get_piece_type().ok_or_else(|| {
+                        ParseError::new("JKF", &format!("Unexpected piece. Ply: {}.", ply))
+                            .with_text(&jmove.piece)
+                    })?;
+                    let hand_off =
+                        ShogiNoteOpe::from_address(Address::from_hand_ph_pt(phase_value, drop));
+                    rmoves.push(hand_off);
+                }
+
+                // ################
+                // # (4d) Hand on #
+                // ################
+                {
+                    let hand_on = ShogiNoteOpe::from_address(destination_address);
+                    rmoves.push(hand_on);
+                }
+            }
+            Some(from) => {
+                // 駒を進める動きの場合
+                if let Some(capture_id_piece) =
+                    position.get_id_piece_by_address(destination_address.get_index())
+                {
+                    // 1～4は、駒を取る(capture)動き。
+
+                    // #################
+                    // # (1c) Hand off #
+                    // #################
+                    {
+                        let hand_off = ShogiNoteOpe::from_address(destination_address);
+                        rmoves.push(hand_off);
+                    }
+
+                    // #################
+                    // # (2) Hand turn #
+                    // #################
+                    if capture_id_piece.is_promoted() {
+                        let hand_turn = ShogiNoteOpe::turn_over();
+                        rmoves.push(hand_turn);
+                    }
+
+                    // ###################
+                    // # (3) Hand rotate #
+                    // ###################
+                    {
+                        let hand_rotate = ShogiNoteOpe::rotate();
+                        rmoves.push(hand_rotate);
+                    }
+
+                    // ################
+                    // # (4c) Hand on #
+                    // ################
+                    {
+                        let up = capture_id_piece.get_type();
+                        let hand_on =
+                            ShogiNoteOpe::from_address(Address::from_hand_ph_pt(phase_value, up));
+                        rmoves.push(hand_on);
+                    }
+                }
+
+                // 5～7は、盤上の駒を進める動き。
+
+                // #################
+                // # (5) Board off #
+                // #################
+                {
+                    let board_off = ShogiNoteOpe::from_address(Address::from_cell(
+                        from.to_cell(),
+                        position.get_board_size(),
+                    ));
+                    rmoves.push(board_off);
+                }
+
+                // #######################
+                // # (6) Board turn over #
+                // #######################
+                if jmove.promote.unwrap_or(false) {
+                    let board_turn = ShogiNoteOpe::turn_over();
+                    rmoves.push(board_turn);
+                }
+
+                // ################
+                // # (7) Board on #
+                // ################
+                {
+                    let board_on = ShogiNoteOpe::from_address(destination_address);
+                    rmoves.push(board_on);
+                }
+            }
+        }
+
+        // ####################
+        // # (8) Change phase #
+        // ####################
+        {
+            rmoves.push(ShogiNoteOpe::change_phase(ply));
+        }
+
+        Ok(rmoves)
+    }
+}
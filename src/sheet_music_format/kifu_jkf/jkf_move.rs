@@ -0,0 +1,335 @@
+use instrument::half_player_phase::*;
+use instrument::piece_etc::*;
+use serde::*;
+use sheet_music_format::kifu_csa::csa_move::*;
+use sheet_music_format::kifu_rpm::rpm_move::*;
+use sheet_music_format::move_annotation::MoveAnnotation;
+use studio::address::*;
+use studio::application::Application;
+
+/// JKF の升目。筋が x、段が y。
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct JkfPlace {
+    pub x: i8,
+    pub y: i8,
+}
+impl JkfPlace {
+    pub fn from_cell(cell: Cell) -> Self {
+        JkfPlace {
+            x: cell.get_file(),
+            y: cell.get_rank(),
+        }
+    }
+
+    pub fn to_cell(self) -> Cell {
+        Cell::from_file_rank(self.x, self.y)
+    }
+}
+
+/// JKF の時間。時は省略されることがある。
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct JkfTimeFormat {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub h: Option<i32>,
+    pub m: i32,
+    pub s: i32,
+}
+
+impl JkfTimeFormat {
+    pub fn from_seconds(seconds: u32, with_hour: bool) -> Self {
+        let seconds = seconds as i32;
+        if with_hour {
+            JkfTimeFormat {
+                h: Some(seconds / 3600),
+                m: seconds / 60 % 60,
+                s: seconds % 60,
+            }
+        } else {
+            JkfTimeFormat {
+                h: None,
+                m: seconds / 60,
+                s: seconds % 60,
+            }
+        }
+    }
+
+    pub fn to_seconds(&self) -> u32 {
+        (self.h.unwrap_or(0) * 3600 + self.m * 60 + self.s) as u32
+    }
+}
+
+/// JKF の消費時間。その手の時間と、累計。
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct JkfTime {
+    pub now: JkfTimeFormat,
+    pub total: JkfTimeFormat,
+}
+
+/// JKF の指し手。駒は CSA の駒の記号。
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct JkfMove {
+    // 先手 0、後手 1。
+    pub color: i8,
+
+    // 打つときは無い。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<JkfPlace>,
+
+    pub to: JkfPlace,
+
+    // 動かす前の駒。
+    pub piece: String,
+
+    // 「同」。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub same: Option<bool>,
+
+    // 成るなら true、不成なら false、どちらでもなければ無し。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub promote: Option<bool>,
+
+    // 取った駒。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capture: Option<String>,
+
+    // 「右」「直」などの相対位置。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relative: Option<String>,
+}
+impl JkfMove {
+    /// RPMテープから読み取った指し手から作る。
+    ///
+    /// # Arguments
+    ///
+    /// * `previous` - 直前の指し手。「同」の判定に使う。
+    pub fn from_rpm_move(rmove: &RpmMove, previous: Option<&RpmMove>, app: &Application) -> Self {
+        let destination = rmove
+            .destination
+            .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. rmove.destination.")));
+
+        let same = if let Some(prev) = previous {
+            if let Some(prev_dst) = prev.destination {
+                if prev_dst == destination {
+                    Some(true)
+                } else {
+                    None
+                }
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        JkfMove {
+            color: match rmove.phase {
+                HalfPlayerPhaseValue::Second => 1,
+                _ => 0,
+            },
+            from: rmove.source.map(JkfPlace::from_cell),
+            to: JkfPlace::from_cell(destination),
+            piece: CsaMove::piece_type_to_koma(
+                rmove
+                    .piece_type
+                    .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. rmove.piece_type."))),
+            ),
+            same,
+            promote: if rmove.promotion {
+                Some(true)
+            } else if JkfMove::is_promotable(rmove, destination) {
+                // 不成。
+                Some(false)
+            } else {
+                None
+            },
+            capture: rmove.capture.map(CsaMove::piece_type_to_koma),
+            relative: None,
+        }
+    }
+
+    pub fn get_piece_type(&self) -> Option<PieceType> {
+        CsaMove::koma_to_piece_type(&self.piece)
+    }
+
+    /// 成れる指し手か。盤上の 成っていない駒が、敵陣に入るか 敵陣から出る時に成れる。
+    fn is_promotable(rmove: &RpmMove, destination: Cell) -> bool {
+        use instrument::piece_etc::PieceType::*;
+        let source = match rmove.source {
+            Some(source) => source,
+            None => return false,
+        };
+        match rmove.piece_type {
+            Some(R) | Some(B) | Some(S) | Some(N) | Some(L) | Some(P) => {}
+            _ => return false,
+        }
+
+        let is_enemy_camp = |cell: Cell| match rmove.phase {
+            HalfPlayerPhaseValue::Second => 7 <= cell.get_rank(),
+            _ => cell.get_rank() <= 3,
+        };
+        is_enemy_camp(source) || is_enemy_camp(destination)
+    }
+}
+
+/// JKF の moves の要素１つ。最初の要素は指し手を持たず、初期局面へのコメントなどを入れる。
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct JkfMoveFormat {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comments: Option<Vec<String>>,
+
+    #[serde(rename = "move", skip_serializing_if = "Option::is_none")]
+    pub move_: Option<JkfMove>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<JkfTime>,
+
+    // "TORYO" などの特殊な終局。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub special: Option<String>,
+
+    // 変化。この要素の代わりに指せる手順の一覧。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forks: Option<Vec<Vec<JkfMoveFormat>>>,
+}
+impl JkfMoveFormat {
+    pub fn from_move(jmove: JkfMove) -> Self {
+        JkfMoveFormat {
+            move_: Some(jmove),
+            ..Default::default()
+        }
+    }
+
+    pub fn from_special(special: &str) -> Self {
+        JkfMoveFormat {
+            special: Some(special.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// コメントと消費時間を書き込む。
+    pub fn set_annotation(&mut self, annotation: &MoveAnnotation) {
+        if !annotation.comments.is_empty() {
+            self.comments = Some(annotation.comments.clone());
+        }
+
+        if let (Some(consumed), Some(total)) = (annotation.consumed, annotation.total) {
+            self.time = Some(JkfTime {
+                now: JkfTimeFormat::from_seconds(consumed, false),
+                total: JkfTimeFormat::from_seconds(total, true),
+            });
+        }
+    }
+
+    /// 同じ指し手か。コメントや時間は比べない。
+    pub fn is_same_move(&self, other: &JkfMoveFormat) -> bool {
+        self.move_ == other.move_ && self.special == other.special
+    }
+
+    /// コメントと消費時間。
+    pub fn to_annotation(&self, ply: i16) -> MoveAnnotation {
+        let mut annotation = MoveAnnotation::new(ply);
+
+        if let Some(ref comments) = self.comments {
+            annotation.comments = comments.clone();
+        }
+
+        if let Some(ref time) = self.time {
+            annotation.consumed = Some(time.now.to_seconds());
+            annotation.total = Some(time.total.to_seconds());
+        }
+
+        annotation
+    }
+
+    pub fn is_resign(&self) -> bool {
+        match self.special {
+            Some(ref special) => special == "TORYO",
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rpm_move(
+        ply: i16,
+        source: (i8, i8),
+        destination: (i8, i8),
+        piece_type: PieceType,
+    ) -> RpmMove {
+        RpmMove {
+            ply,
+            phase: if ply % 2 == 0 {
+                HalfPlayerPhaseValue::Second
+            } else {
+                HalfPlayerPhaseValue::First
+            },
+            source: Some(Cell::from_file_rank(source.0, source.1)),
+            destination: Some(Cell::from_file_rank(destination.0, destination.1)),
+            piece_type: Some(piece_type),
+            promotion: false,
+            drop: false,
+            capture: None,
+            resign: false,
+        }
+    }
+
+    #[test]
+    fn promotion_is_written_as_true() {
+        let app = Application::new_for_test();
+        let mut rmove = rpm_move(1, (2, 4), (2, 3), PieceType::P);
+        rmove.promotion = true;
+
+        assert_eq!(
+            JkfMove::from_rpm_move(&rmove, None, &app).promote,
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn declined_promotion_is_written_as_false() {
+        let app = Application::new_for_test();
+
+        // ▲2三銀不成。敵陣に入る。
+        let rmove = rpm_move(1, (3, 4), (2, 3), PieceType::S);
+        assert_eq!(
+            JkfMove::from_rpm_move(&rmove, None, &app).promote,
+            Some(false)
+        );
+
+        // △8八角不成。後手の敵陣は ７～９段目。
+        let rmove = rpm_move(2, (2, 2), (8, 8), PieceType::B);
+        assert_eq!(
+            JkfMove::from_rpm_move(&rmove, None, &app).promote,
+            Some(false)
+        );
+
+        // ▲3八銀不成。敵陣から出る。
+        let rmove = rpm_move(3, (2, 2), (3, 8), PieceType::S);
+        assert_eq!(
+            JkfMove::from_rpm_move(&rmove, None, &app).promote,
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn move_that_cannot_promote_has_no_promotion() {
+        let app = Application::new_for_test();
+
+        // 敵陣に触れない。
+        let rmove = rpm_move(1, (7, 7), (7, 6), PieceType::P);
+        assert_eq!(JkfMove::from_rpm_move(&rmove, None, &app).promote, None);
+
+        // 金は成れない。
+        let rmove = rpm_move(1, (3, 4), (2, 3), PieceType::G);
+        assert_eq!(JkfMove::from_rpm_move(&rmove, None, &app).promote, None);
+
+        // 打つ手は成れない。
+        let mut rmove = rpm_move(1, (3, 4), (2, 3), PieceType::S);
+        rmove.source = None;
+        rmove.drop = true;
+        assert_eq!(JkfMove::from_rpm_move(&rmove, None, &app).promote, None);
+    }
+}
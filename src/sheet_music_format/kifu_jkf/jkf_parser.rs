@@ -0,0 +1,51 @@
+use sheet_music_format::kifu_jkf::jkf_tape::*;
+use sheet_music_format::parse_error::*;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use studio::application::Application;
+
+/* Example
+{
+  "header": {
+    "先手": "Bonanza",
+    "後手": "渡辺明",
+    "開始日時": "2007/03/21"
+  },
+  "initial": { "preset": "HIRATE" },
+  "moves": [
+    {},
+    { "move": { "from": { "x": 7, "y": 7 }, "to": { "x": 7, "y": 6 }, "color": 0, "piece": "FU" } },
+    { "move": { "from": { "x": 8, "y": 3 }, "to": { "x": 8, "y": 4 }, "color": 1, "piece": "FU" },
+      "forks": [[ { "move": { "from": { "x": 3, "y": 3 }, "to": { "x": 3, "y": 4 }, "color": 1, "piece": "FU" } } ]] },
+    { "special": "TORYO" }
+  ]
+}
+ */
+/// JKFファイルは JSON なので serde に読ませる。
+pub struct JkfParser {}
+impl JkfParser {
+    /// JSON として読めなければ、行と文字の位置を付けたエラーを返す。
+    pub fn from_file(file: &str, _app: &Application) -> Result<JkfTape, ParseError> {
+        let mut contents = String::new();
+        File::open(file)
+            .and_then(|mut file_obj| file_obj.read_to_string(&mut contents))
+            .map_err(|err| ParseError::from_io("JKF", file, &err))?;
+
+        // BOM が付いていることがある。
+        let contents = contents.trim_start_matches('\u{feff}');
+        let mut tape: JkfTape = serde_json::from_str(contents)
+            .map_err(|f| ParseError::from_json("JKF", file, contents, &f))?;
+
+        let file_stem = Path::new(&file)
+            .file_stem()
+            .and_then(OsStr::to_str)
+            .ok_or_else(|| ParseError::new("JKF", "No file stem.").with_file(file))?;
+        tape.get_mut_tape_label().set_name(file_stem);
+        tape.get_mut_tape_label().set_format("JKF");
+        tape.set_tape_label_from_header();
+
+        Ok(tape)
+    }
+}
@@ -0,0 +1,623 @@
+use media::tape_branch::*;
+use serde::*;
+use sheet_music_format::kifu_jkf::jkf_move::*;
+use sheet_music_format::kifu_jkf::jkf_parser::*;
+use sheet_music_format::kifu_rpm::rpm_move::*;
+use sheet_music_format::kifu_rpm::rpm_tape::*;
+use sheet_music_format::kifu_rpm::rpm_tape_box::*;
+use sheet_music_format::kifu_rpm::rpm_tape_branch::RpmTapeBranch;
+use sheet_music_format::kifu_rpm::rpm_tape_tracks::RpmTapeTracks;
+use sheet_music_format::move_annotation::MoveAnnotation;
+use sheet_music_format::parse_error::*;
+use sheet_music_format::tape_label::*;
+use sound::shogi_note::ShogiNote;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::prelude::*;
+use std::path::Path;
+use studio::application::Application;
+use studio::board_size::*;
+
+/// JKF の初期局面。平手なら preset は "HIRATE"。
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct JkfInitial {
+    pub preset: String,
+
+    // preset が "OTHER" のときの局面。読み取るだけで、まだ使わない。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+/// 変化も含めた、１本道の手順。
+pub struct JkfLine {
+    /// 本譜なら 0。変化なら、分かれた手目。
+    pub branch_ply: i16,
+    /// 分かれる元の手順の番号。本譜は 0。
+    pub parent: usize,
+    /// 初期局面の要素は含まない。
+    pub moves: Vec<JkfMoveFormat>,
+}
+
+/// JSON Kifu Format のファイルに対応。
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct JkfTape {
+    pub header: BTreeMap<String, String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initial: Option<JkfInitial>,
+
+    pub moves: Vec<JkfMoveFormat>,
+
+    // テープ・ラベル。ヘッダーから作る。
+    #[serde(skip)]
+    tape_label: TapeLabel,
+
+    // 読み飛ばしたもの。知らないヘッダーなど。
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+}
+impl JkfTape {
+    pub fn new() -> JkfTape {
+        JkfTape {
+            header: BTreeMap::new(),
+            initial: None,
+            moves: Vec::new(),
+            tape_label: TapeLabel::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    // #####
+    // # F #
+    // #####
+
+    /// ファイル読取。
+    pub fn from_file(file: &str, app: &Application) -> Result<JkfTape, ParseError> {
+        JkfParser::from_file(file, app)
+    }
+
+    /// RPMテープ１本を、JKF に書き直す。
+    pub fn from_rpm_tape(rtape: &RpmTape, board_size: BoardSize, app: &Application) -> JkfTape {
+        let mut jtape = JkfTape::new();
+        jtape.set_header_from_tape_label(&rtape.label);
+        jtape.initial = Some(JkfInitial {
+            preset: "HIRATE".to_string(),
+            data: None,
+        });
+
+        let notes = JkfTape::to_notes(&rtape.tracks, board_size, app);
+        jtape.moves = JkfTape::to_move_formats(&notes, &rtape.tracks.annotation, board_size, app);
+
+        // テープの枝は、分かれた手目の forks に差し込む。
+        jtape.insert_branches(
+            &notes,
+            &rtape.tracks.annotation,
+            &rtape.tracks.branch,
+            board_size,
+            app,
+        );
+
+        jtape
+    }
+
+    /// テープ・ボックスを、JKF に書き直す。
+    /// 変化のテープは、分かれる元のテープの forks に差し込む。差し込めなければ、別の JKF にする。
+    pub fn from_rpm_tape_box(
+        rbox: &RpmTapeBox,
+        board_size: BoardSize,
+        app: &Application,
+    ) -> Vec<JkfTape> {
+        let mut jtapes: Vec<JkfTape> = Vec::new();
+
+        // テープ名から、差し込み先の JKF の番号を引く。
+        let mut roots: HashMap<String, usize> = HashMap::new();
+
+        for rtape in &rbox.tape_box {
+            let jtape = JkfTape::from_rpm_tape(rtape, board_size, app);
+
+            if rtape.label.is_variation() {
+                if let Some(&root) = roots.get(&rtape.label.get_branch_from()) {
+                    let branch_ply = rtape.label.get_branch_ply().parse::<i16>().unwrap_or(0);
+                    if jtapes[root].insert_fork(&jtape.moves[1..], branch_ply) {
+                        roots.insert(rtape.label.get_name(), root);
+                        continue;
+                    }
+                }
+            }
+
+            roots.insert(rtape.label.get_name(), jtapes.len());
+            jtapes.push(jtape);
+        }
+
+        jtapes
+    }
+
+    // #####
+    // # G #
+    // #####
+
+    // テープ・ラベル。
+    pub fn get_tape_label(&self) -> &TapeLabel {
+        &self.tape_label
+    }
+
+    // テープ・ラベル。
+    pub fn get_mut_tape_label(&mut self) -> &mut TapeLabel {
+        &mut self.tape_label
+    }
+
+    // #####
+    // # I #
+    // #####
+
+    /// テープの枝を、分かれた手目の forks に差し込む。枝の枝も。
+    ///
+    /// # Arguments
+    ///
+    /// * `notes` - 枝の分かれる元の筋。
+    /// * `annotations` - 元の筋の注釈。
+    fn insert_branches(
+        &mut self,
+        notes: &[ShogiNote],
+        annotations: &[MoveAnnotation],
+        branches: &[RpmTapeBranch],
+        board_size: BoardSize,
+        app: &Application,
+    ) {
+        let moves = JkfTape::to_move_formats(notes, annotations, board_size, app);
+
+        for branch in branches {
+            // 枝の筋は、分かれる所までの元の筋に 枝のノートを継いだもの。注釈も同じ。
+            let mut fork_notes = JkfTape::to_notes(&branch.tracks, board_size, app);
+            let first_ply = TapeBranch::get_first_ply(&fork_notes);
+            let mut branch_annotations: Vec<MoveAnnotation> = annotations
+                .iter()
+                .filter(|annotation| first_ply.is_none_or(|ply| annotation.ply < ply))
+                .cloned()
+                .collect();
+            branch_annotations.extend_from_slice(&branch.tracks.annotation);
+            let mut branch_notes = notes[..cmp::min(branch.fork, notes.len())].to_vec();
+            branch_notes.append(&mut fork_notes);
+            let branch_moves =
+                JkfTape::to_move_formats(&branch_notes, &branch_annotations, board_size, app);
+
+            // 分かれた手目は、元の筋と 違う指し手になる所。
+            let branch_ply = branch_moves
+                .iter()
+                .zip(moves.iter())
+                .skip(1)
+                .take_while(|(jmove_format, other)| jmove_format.is_same_move(other))
+                .count()
+                + 1;
+            if branch_moves.len() <= branch_ply
+                || !self.insert_fork(&branch_moves[1..], branch_ply as i16)
+            {
+                app.comm
+                    .println(&format!("#Skip branch. Fork: {}.", branch.fork));
+                continue;
+            }
+
+            self.insert_branches(
+                &branch_notes,
+                &branch_annotations,
+                &branch.tracks.branch,
+                board_size,
+                app,
+            );
+        }
+    }
+
+    /// 初手からの手順を、分かれた手目の forks として差し込む。
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - 初手からの手順。
+    /// * `branch_ply` - 分かれた手目。
+    ///
+    /// # Returns
+    ///
+    /// 差し込めたら真。
+    pub fn insert_fork(&mut self, line: &[JkfMoveFormat], branch_ply: i16) -> bool {
+        if self.moves.is_empty() {
+            return false;
+        }
+
+        JkfTape::insert_fork_to(&mut self.moves[1..], 1, line, branch_ply)
+    }
+
+    /// # Arguments
+    ///
+    /// * `list` - 本譜か、変化の手順。
+    /// * `first_ply` - list の先頭の手目。
+    fn insert_fork_to(
+        list: &mut [JkfMoveFormat],
+        first_ply: i16,
+        line: &[JkfMoveFormat],
+        branch_ply: i16,
+    ) -> bool {
+        for (i, jmove_format) in list.iter_mut().enumerate() {
+            let ply = first_ply + i as i16;
+            let expected = match line.get((ply - 1) as usize) {
+                Some(expected) => expected,
+                None => return false,
+            };
+
+            if ply == branch_ply {
+                jmove_format
+                    .forks
+                    .get_or_insert_with(Vec::new)
+                    .push(line[(ply - 1) as usize..].to_vec());
+                return true;
+            }
+
+            if !jmove_format.is_same_move(expected) {
+                // 変化の中を探す。
+                if let Some(ref mut forks) = jmove_format.forks {
+                    for fork in forks.iter_mut() {
+                        let found = match fork.first() {
+                            Some(first) => first.is_same_move(expected),
+                            None => false,
+                        };
+                        if found {
+                            return JkfTape::insert_fork_to(fork, ply, line, branch_ply);
+                        }
+                    }
+                }
+                return false;
+            }
+        }
+
+        false
+    }
+
+    /// 平手か。初期局面が省略されていれば平手とする。
+    pub fn is_hirate(&self) -> bool {
+        match self.initial {
+            Some(ref initial) => initial.preset == "HIRATE",
+            None => true,
+        }
+    }
+
+    // #####
+    // # S #
+    // #####
+
+    /// ヘッダーから、テープ・ラベルを作る。
+    pub fn set_tape_label_from_header(&mut self) {
+        for (key, value) in &self.header {
+            match key.as_str() {
+                "先手" | "下手" => self.tape_label.set_player1(value),
+                "後手" | "上手" => self.tape_label.set_player2(value),
+                "開始日時" => {
+                    // 日付と時刻は 空白で区切られていることがある。
+                    let mut tokens = value.splitn(2, ' ');
+                    self.tape_label
+                        .set_game_date(tokens.next().unwrap_or("").trim());
+                    self.tape_label
+                        .set_start_time(tokens.next().unwrap_or("").trim());
+                }
+                "終了日時" => self.tape_label.set_end_time(value),
+                "場所" => self.tape_label.set_place(value),
+                "棋戦" => self.tape_label.set_event(value),
+                "持ち時間" => self.tape_label.set_time_system(value),
+                "手合割" => self.tape_label.set_handicap(value),
+                "戦型" => self.tape_label.set_battle_type(value),
+                _ => self
+                    .warnings
+                    .push(format!("Unknown header key: '{}'.", key)),
+            }
+        }
+    }
+
+    /// テープ・ラベルから、ヘッダーを作る。空っぽの項目は書かない。
+    pub fn set_header_from_tape_label(&mut self, label: &TapeLabel) {
+        let start = format!("{} {}", label.get_game_date(), label.get_start_time());
+        let pairs = [
+            ("先手", label.get_player1()),
+            ("後手", label.get_player2()),
+            ("開始日時", start.trim().to_string()),
+            ("終了日時", label.get_end_time()),
+            ("場所", label.get_place()),
+            ("棋戦", label.get_event()),
+            ("持ち時間", label.get_time_system()),
+            ("手合割", label.get_handicap()),
+            ("戦型", label.get_battle_type()),
+        ];
+
+        for (key, value) in pairs.iter() {
+            if !value.is_empty() {
+                self.header.insert(key.to_string(), value.to_string());
+            }
+        }
+
+        self.tape_label = label.clone();
+    }
+
+    // #####
+    // # T #
+    // #####
+
+    /// 手順の、指し手ごとのコメントと消費時間。手目の順。
+    pub fn to_annotations(&self, line: &JkfLine) -> Vec<MoveAnnotation> {
+        let mut annotations = Vec::new();
+
+        // 最初の要素は、初期局面のためのもの。
+        if let Some(initial) = self.moves.first() {
+            annotations.push(initial.to_annotation(0));
+        }
+
+        for (i, jmove_format) in line.moves.iter().enumerate() {
+            annotations.push(jmove_format.to_annotation((i + 1) as i16));
+        }
+
+        annotations
+            .into_iter()
+            .filter(|annotation| !annotation.is_empty())
+            .collect()
+    }
+
+    /// 本譜と、全ての変化を、１本道の手順に広げる。本譜が先頭。
+    pub fn to_lines(&self) -> Vec<JkfLine> {
+        let mut lines = Vec::new();
+
+        // 最初の要素は、初期局面のためのもの。
+        let body = if self.moves.is_empty() {
+            &self.moves[..]
+        } else {
+            &self.moves[1..]
+        };
+        JkfTape::collect_lines(&[], body, 0, 0, &mut lines);
+
+        lines
+    }
+
+    fn collect_lines(
+        prefix: &[JkfMoveFormat],
+        body: &[JkfMoveFormat],
+        branch_ply: i16,
+        parent: usize,
+        lines: &mut Vec<JkfLine>,
+    ) {
+        let mut moves = prefix.to_vec();
+        moves.extend_from_slice(body);
+        let number = lines.len();
+        lines.push(JkfLine {
+            branch_ply,
+            parent,
+            moves,
+        });
+
+        for (i, jmove_format) in body.iter().enumerate() {
+            if let Some(ref forks) = jmove_format.forks {
+                let mut fork_prefix = prefix.to_vec();
+                fork_prefix.extend_from_slice(&body[..i]);
+                let fork_ply = (fork_prefix.len() + 1) as i16;
+
+                for fork in forks {
+                    JkfTape::collect_lines(&fork_prefix, fork, fork_ply, number, lines);
+                }
+            }
+        }
+    }
+
+    pub fn to_json(&self, app: &Application) -> String {
+        serde_json::to_string(self).unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())))
+    }
+
+    /// ノートの筋を、JKF の指し手に書き直す。最初の要素は、初期局面のためのもの。
+    fn to_move_formats(
+        notes: &[ShogiNote],
+        annotations: &[MoveAnnotation],
+        board_size: BoardSize,
+        app: &Application,
+    ) -> Vec<JkfMoveFormat> {
+        let mut annotations = annotations.to_vec();
+        MoveAnnotation::fill_total(&mut annotations);
+
+        let mut moves = Vec::new();
+        let mut initial = JkfMoveFormat::default();
+        if let Some(annotation) = MoveAnnotation::find(&annotations, 0) {
+            initial.set_annotation(annotation);
+        }
+        moves.push(initial);
+
+        let rmoves = RpmMove::parse_notes(notes, board_size);
+        for (i, rmove) in rmoves.iter().enumerate() {
+            let mut jmove_format = if rmove.resign {
+                JkfMoveFormat::from_special("TORYO")
+            } else {
+                let previous = if 0 < i { rmoves.get(i - 1) } else { None };
+                JkfMoveFormat::from_move(JkfMove::from_rpm_move(rmove, previous, app))
+            };
+
+            if let Some(annotation) = MoveAnnotation::find(&annotations, rmove.ply) {
+                jmove_format.set_annotation(annotation);
+            }
+            moves.push(jmove_format);
+
+            if rmove.resign {
+                break;
+            }
+        }
+
+        moves
+    }
+
+    /// トラックのノート。空っぽのトラックなら、ノートも無い。
+    fn to_notes(
+        tracks: &RpmTapeTracks,
+        board_size: BoardSize,
+        app: &Application,
+    ) -> Vec<ShogiNote> {
+        if tracks.ope.is_empty() {
+            Vec::new()
+        } else {
+            tracks.to_positive_vec(board_size, app)
+        }
+    }
+
+    // #####
+    // # W #
+    // #####
+
+    /// ファイル単位で書きだすぜ☆（＾～＾）
+    pub fn write(&self, file_name: &str, app: &Application) {
+        if app.is_debug() {
+            app.comm
+                .println(&format!("#Write jkf to '{}'...", file_name));
+        }
+
+        let path = Path::new(&file_name);
+
+        // ディレクトリー作成。
+        if let Some(parent) = path.parent() {
+            match fs::create_dir_all(parent) {
+                Ok(_x) => {}
+                Err(err) => panic!("{}", err),
+            }
+        } else {
+            panic!("Create directory fail. {}", file_name);
+        }
+
+        // 全文上書き☆（＾～＾）
+        let mut file_obj = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)
+            .unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)));
+
+        if let Err(e) = writeln!(file_obj, "{}", self.to_json(app)) {
+            eprintln!("Couldn't write to file: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use audio_compo::audio_rack::*;
+    use audio_compo::cassette_deck::*;
+    use conv::conversion_report::*;
+    use conv::converter::*;
+    use conv::fragment_compactor::*;
+    use instrument::position::*;
+    use media::cassette_tape::CassetteTape;
+
+    /// 本譜は ７六歩、３四歩、２六歩。２手目に ８四歩 の変化がある。
+    const FORKED_JKF: &str = r#"{
+"header": {"先手": "A", "後手": "B"},
+"moves": [
+{},
+{"move": {"color": 0, "from": {"x": 7, "y": 7}, "to": {"x": 7, "y": 6}, "piece": "FU"}},
+{"move": {"color": 1, "from": {"x": 3, "y": 3}, "to": {"x": 3, "y": 4}, "piece": "FU"},
+ "forks": [[
+  {"move": {"color": 1, "from": {"x": 8, "y": 3}, "to": {"x": 8, "y": 4}, "piece": "FU"}},
+  {"move": {"color": 0, "from": {"x": 2, "y": 7}, "to": {"x": 2, "y": 6}, "piece": "FU"}}
+ ]]},
+{"move": {"color": 0, "from": {"x": 2, "y": 7}, "to": {"x": 2, "y": 6}, "piece": "FU"}}
+]
+}"#;
+
+    /// JKF を変換して、テープ・フラグメントに書いたテープを読み直す。
+    fn convert_to_rpm_tapes(name: &str, app: &Application) -> Vec<RpmTape> {
+        let file =
+            Application::write_test_file(&format!("jkf_tape/{}.jkf", name), FORKED_JKF.as_bytes());
+        let fragment = Application::write_test_file(&format!("jkf_tape/{}", name), b"");
+        let fragment_file = format!("{}.tapesfrag", fragment);
+        let _ = fs::remove_file(&fragment_file);
+
+        let mut position = Position::new_honshogi_origin(app);
+        let mut rack = AudioRack::new(app);
+        let mut tape = CassetteTape::new_facing_right(app);
+        tape.set_file_full_name_without_extension(&fragment);
+        rack.add_tape_to_tape_box(Slot::Learning, tape, app);
+        rack.seek_of_next_tape(Slot::Learning, app);
+
+        let mut report = FileReport::new(&file);
+        Converter::convert(file, &mut rack, &mut position, &mut report, app)
+            .unwrap_or_else(|err| panic!("{}", err));
+        FragmentCompactor::read_fragment_file(&fragment_file)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// 書き出した JKF の、本譜と２手目の変化が 元の JKF と同じ指し手か。
+    fn assert_same_as_forked_jkf(jtape: &JkfTape) {
+        let expected: JkfTape =
+            serde_json::from_str(FORKED_JKF).unwrap_or_else(|err| panic!("{}", err));
+        let same_moves = |moves: &[JkfMoveFormat], expected_moves: &[JkfMoveFormat]| {
+            moves.len() == expected_moves.len()
+                && moves
+                    .iter()
+                    .zip(expected_moves.iter())
+                    .all(|(jmove_format, other)| jmove_format.is_same_move(other))
+        };
+
+        assert!(same_moves(&jtape.moves, &expected.moves));
+        let forks = jtape.moves[2]
+            .forks
+            .as_ref()
+            .unwrap_or_else(|| panic!("No forks."));
+        let expected_forks = expected.moves[2]
+            .forks
+            .as_ref()
+            .unwrap_or_else(|| panic!("No expected forks."));
+        assert_eq!(forks.len(), 1);
+        assert!(same_moves(&forks[0], &expected_forks[0]));
+    }
+
+    /// 変化の手順は、分かれた手目の forks に差し込むと 元の JKF に戻る。
+    #[test]
+    fn inserts_variation_line_into_forks() {
+        let mut jtape: JkfTape =
+            serde_json::from_str(FORKED_JKF).unwrap_or_else(|err| panic!("{}", err));
+        let lines = jtape.to_lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].branch_ply, 2);
+        assert_eq!(lines[1].parent, 0);
+
+        jtape.moves[2].forks = None;
+        assert!(jtape.insert_fork(&lines[1].moves, lines[1].branch_ply));
+        assert_same_as_forked_jkf(&jtape);
+
+        // 違う手順からは分かれない。
+        assert!(!jtape.insert_fork(&lines[1].moves[1..], 3));
+    }
+
+    /// 変化のテープは、テープ・ボックスから JKF に戻すと forks に戻る。
+    #[test]
+    fn keeps_forks_of_variation_tapes_through_rpm() {
+        let app = Application::new_for_test();
+        let rtapes = convert_to_rpm_tapes("variation-tapes", &app);
+        assert_eq!(rtapes.len(), 2);
+
+        let mut rbox = RpmTapeBox::new();
+        for rtape in rtapes {
+            rbox.push(rtape);
+        }
+        let jtapes = JkfTape::from_rpm_tape_box(&rbox, BoardSize::create_hon_shogi(), &app);
+
+        assert_eq!(jtapes.len(), 1);
+        assert_same_as_forked_jkf(&jtapes[0]);
+    }
+
+    /// テープの枝は、JKF に書き出すと forks になる。
+    #[test]
+    fn writes_tape_branches_as_forks() {
+        let app = Application::new_for_test();
+        let board_size = BoardSize::create_hon_shogi();
+        let rtapes = convert_to_rpm_tapes("tape-branches", &app);
+
+        // 変化のテープを、本譜のテープの枝にする。
+        let mut tape = rtapes[0].to_object(board_size, &app);
+        let mut variation = rtapes[1].to_object(board_size, &app);
+        tape.graft_line(variation.take_line());
+        assert_eq!(tape.branches.len(), 1);
+
+        let jtape = JkfTape::from_rpm_tape(&tape.to_rpm(board_size), board_size, &app);
+        assert_same_as_forked_jkf(&jtape);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod jkf_converter;
+pub mod jkf_move;
+pub mod jkf_parser;
+pub mod jkf_tape;
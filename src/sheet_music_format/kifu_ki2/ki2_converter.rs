@@ -0,0 +1,110 @@
+use audio_compo::audio_rack::*;
+use human::human_interface::*;
+use instrument::position::*;
+use live::base_performer::*;
+use live::ohashi_performer::*;
+use sheet_music_format::kifu_ki2::ki2_tape::*;
+use sheet_music_format::kifu_kif::kif_converter::*;
+use sheet_music_format::parse_error::*;
+use studio::application::Application;
+
+pub struct Ki2Converter {}
+impl Ki2Converter {
+    /// 変換には、初期局面が必要。
+    /// 移動元の升は、１手ずつ局面を動かしながら決める。
+    ///
+    /// # Returns
+    ///
+    /// 指した手の数。投了は数えない。
+    pub fn play_out_ki2_tape(
+        ktape: &Ki2Tape,
+        rack: &mut AudioRack,
+        position: &mut Position,
+        app: &Application,
+    ) -> Result<usize, ParseError> {
+        // 大橋流を指すところから☆（*＾～＾*）
+        OhashiPerformer::improvise_ohashi_starting(rack, position, app);
+
+        let mut previous = None;
+        let mut ply = 1;
+        for ki2_move in &ktape.moves {
+            let kmove = ki2_move.to_kif_move(position, ply, previous)?;
+
+            let rnote_opes =
+                KifConverter::convert_move(&kmove, position, ply, app).map_err(|err| {
+                    // KIF の指し手ではなく、書かれていた KI2 の指し手を示す。
+                    ParseError {
+                        format: "KI2".to_string(),
+                        line: ki2_move.line,
+                        column: ki2_move.column,
+                        text: ki2_move.text.to_string(),
+                        ..err
+                    }
+                })?;
+            for rnote_ope in rnote_opes {
+                BasePerformer::improvise_note_ope_no_log(rack, &rnote_ope, false, position, app);
+                HumanInterface::bo(rack, position, app);
+            }
+
+            if kmove.is_resign {
+                break;
+            }
+
+            previous = kmove.destination;
+            ply += 1;
+        }
+
+        Ok((ply - 1) as usize)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use audio_compo::cassette_deck::Slot;
+    use media::cassette_tape::CassetteTape;
+
+    fn play_out(name: &str, text: &str) -> Result<usize, ParseError> {
+        let app = Application::new_for_test();
+        let file = Application::write_test_file(name, text.as_bytes());
+        let ktape = Ki2Tape::from_file(&file, &app).unwrap_or_else(|err| panic!("{}", err));
+
+        let mut rack = AudioRack::new(&app);
+        rack.add_tape_to_tape_box(Slot::Learning, CassetteTape::new_facing_right(&app), &app);
+        rack.seek_of_next_tape(Slot::Learning, &app);
+        let mut position = Position::new_honshogi_origin(&app);
+        Ki2Converter::play_out_ki2_tape(&ktape, &mut rack, &mut position, &app)
+    }
+
+    #[test]
+    fn finds_sources_from_position() {
+        // 「同」、「右」、成り、取った駒を打つ。
+        let moves = play_out(
+            "ki2_converter/sources.ki2",
+            "▲７六歩    △３四歩    ▲２二角成  △同　銀    ▲５八金右
+△４五角打  ▲４八銀
+まで7手で先手の勝ち
+",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+        // 投了は数えない。
+        assert_eq!(moves, 7);
+    }
+
+    #[test]
+    fn reports_move_with_ambiguous_source() {
+        // ５八へ行ける金は ２枚ある。
+        let err = match play_out(
+            "ki2_converter/ambiguous.ki2",
+            "▲７六歩    △３四歩\n▲５八金\n",
+        ) {
+            Ok(_moves) => panic!("Unexpected success."),
+            Err(err) => err,
+        };
+
+        assert_eq!(err.format, "KI2");
+        assert_eq!(err.line, 2);
+        assert_eq!(err.column, 1);
+        assert_eq!(err.text, "▲５八金");
+    }
+}
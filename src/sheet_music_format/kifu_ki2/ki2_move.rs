@@ -0,0 +1,386 @@
+use instrument::half_player_phase::*;
+use instrument::piece_etc::*;
+use instrument::position::*;
+use regex::Regex;
+use sheet_music_format::kifu_kif::kif_move::*;
+use sheet_music_format::move_annotation::MoveAnnotation;
+use sheet_music_format::parse_error::*;
+use studio::address::*;
+use studio::application::Application;
+
+/// KI2 の指し手。移動元の升は書かれていないので、局面を見て決める。
+/// "▲７六歩" "△同　歩" "▲５八金右" "▲２二角成" "▲４五角打" など。
+#[derive(Clone)]
+pub struct Ki2Move {
+    pub destination: Option<Cell>,
+    pub is_same: bool,
+    // 動かす前の駒。
+    pub piece: Option<JsaPieceType>,
+    // "右" "左" "直"。
+    pub relative: String,
+    // "上" "引" "寄"。
+    pub motion: String,
+    pub is_promote: bool,
+    pub is_drop: bool,
+    pub is_resign: bool,
+    // コメント。
+    pub annotation: MoveAnnotation,
+    // 棋譜ファイルの何行目の、何文字目に書かれていたか。1 から。エラーに使う。
+    pub line: usize,
+    pub column: usize,
+    // 書かれていた指し手。 "▲５八金右" など。
+    pub text: String,
+}
+impl Ki2Move {
+    pub fn new_resign(ply: i16) -> Self {
+        Ki2Move {
+            destination: None,
+            is_same: false,
+            piece: None,
+            relative: "".to_string(),
+            motion: "".to_string(),
+            is_promote: false,
+            is_drop: false,
+            is_resign: true,
+            annotation: MoveAnnotation::new(ply),
+            line: 0,
+            column: 0,
+            text: "".to_string(),
+        }
+    }
+
+    // #####
+    // # C #
+    // #####
+
+    /// 駒の利きで、移動元から移動先へ行けるか。
+    pub fn can_reach(
+        position: &Position,
+        piece_type: PieceType,
+        phase_value: HalfPlayerPhaseValue,
+        source: Cell,
+        destination: Cell,
+    ) -> bool {
+        if source == destination {
+            return false;
+        }
+
+        let dx = destination.get_file() - source.get_file();
+        // 前へ進むほど大きい。先手は段が小さくなる方が前。
+        let dy = Ki2Move::get_forward(phase_value, source, destination);
+        let is_king_step = dx.abs() <= 1 && dy.abs() <= 1;
+
+        use instrument::piece_etc::PieceType::*;
+        match piece_type {
+            K | PK => is_king_step,
+            G | PG | PS | PN | PL | PP => {
+                (dy == 1 && dx.abs() <= 1) || (dy == 0 && dx.abs() == 1) || (dy == -1 && dx == 0)
+            }
+            S => (dy == 1 && dx.abs() <= 1) || (dy == -1 && dx.abs() == 1),
+            N => dy == 2 && dx.abs() == 1,
+            P => dy == 1 && dx == 0,
+            L => dx == 0 && 0 < dy && Ki2Move::is_path_clear(position, source, destination),
+            R => (dx == 0 || dy == 0) && Ki2Move::is_path_clear(position, source, destination),
+            PR => {
+                is_king_step
+                    || ((dx == 0 || dy == 0)
+                        && Ki2Move::is_path_clear(position, source, destination))
+            }
+            B => dx.abs() == dy.abs() && Ki2Move::is_path_clear(position, source, destination),
+            PB => {
+                is_king_step
+                    || (dx.abs() == dy.abs()
+                        && Ki2Move::is_path_clear(position, source, destination))
+            }
+        }
+    }
+
+    // #####
+    // # G #
+    // #####
+
+    /// 前へ進んだ段数。下がれば負。
+    fn get_forward(phase_value: HalfPlayerPhaseValue, source: Cell, destination: Cell) -> i8 {
+        match phase_value {
+            HalfPlayerPhaseValue::Second => destination.get_rank() - source.get_rank(),
+            _ => source.get_rank() - destination.get_rank(),
+        }
+    }
+
+    // #####
+    // # I #
+    // #####
+
+    /// 移動元と移動先の間の升が、全て空いていれば真。
+    fn is_path_clear(position: &Position, source: Cell, destination: Cell) -> bool {
+        let step_file = (destination.get_file() - source.get_file()).signum();
+        let step_rank = (destination.get_rank() - source.get_rank()).signum();
+
+        let mut file = source.get_file() + step_file;
+        let mut rank = source.get_rank() + step_rank;
+        while file != destination.get_file() || rank != destination.get_rank() {
+            if position
+                .get_id_piece(Cell::from_file_rank(file, rank))
+                .is_some()
+            {
+                return false;
+            }
+            file += step_file;
+            rank += step_rank;
+        }
+
+        true
+    }
+
+    // #####
+    // # P #
+    // #####
+
+    /// 1行に書かれた指し手を全部読む。 "▲７六歩    △３四歩    ▲２六歩" など。
+    ///
+    /// # Arguments
+    ///
+    /// * `first_ply` - 行の最初の指し手の手目。
+    /// * `line_number` - 棋譜ファイルの何行目か。1 から。
+    pub fn parse_line(
+        line: &str,
+        first_ply: i16,
+        line_number: usize,
+        app: &Application,
+    ) -> Vec<Ki2Move> {
+        // "同　歩" の全角空白は、指し手の区切りではない。
+        let re = Regex::new(
+            r"[▲△☗☖](?:([１２３４５６７８９1-9])([一二三四五六七八九])|同　?)(成銀|成桂|成香|全|圭|杏|歩|香|桂|銀|金|玉|王|角|飛|と|馬|龍|竜)([右左直]?)([上引寄]?)(不成|成|打)?",
+        )
+        .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
+
+        let mut moves = Vec::new();
+        for caps in re.captures_iter(line) {
+            let matched = caps.get(0).map_or("", |m| m.as_str());
+            let column = line[..caps.get(0).map_or(0, |m| m.start())].chars().count() + 1;
+
+            let destination = match (caps.get(1), caps.get(2)) {
+                (Some(file), Some(rank)) => Some(Cell::from_file_rank(
+                    Ki2Move::parse_file(file.as_str()),
+                    Ki2Move::parse_rank(rank.as_str()),
+                )),
+                _ => None,
+            };
+
+            let action = caps.get(6).map_or("", |m| m.as_str());
+
+            moves.push(Ki2Move {
+                destination,
+                is_same: destination.is_none(),
+                piece: Ki2Move::parse_piece(caps.get(3).map_or("", |m| m.as_str())),
+                relative: caps.get(4).map_or("", |m| m.as_str()).to_string(),
+                motion: caps.get(5).map_or("", |m| m.as_str()).to_string(),
+                is_promote: action == "成",
+                is_drop: action == "打",
+                is_resign: false,
+                annotation: MoveAnnotation::new(first_ply + moves.len() as i16),
+                line: line_number,
+                column,
+                text: matched.to_string(),
+            });
+        }
+
+        moves
+    }
+
+    fn parse_file(text: &str) -> i8 {
+        match text {
+            "１" | "1" => 1,
+            "２" | "2" => 2,
+            "３" | "3" => 3,
+            "４" | "4" => 4,
+            "５" | "5" => 5,
+            "６" | "6" => 6,
+            "７" | "7" => 7,
+            "８" | "8" => 8,
+            _ => 9,
+        }
+    }
+
+    fn parse_rank(text: &str) -> i8 {
+        match text {
+            "一" => 1,
+            "二" => 2,
+            "三" => 3,
+            "四" => 4,
+            "五" => 5,
+            "六" => 6,
+            "七" => 7,
+            "八" => 8,
+            _ => 9,
+        }
+    }
+
+    fn parse_piece(text: &str) -> Option<JsaPieceType> {
+        use instrument::piece_etc::JsaPieceType::*;
+        match text {
+            "歩" => Some(P),
+            "香" => Some(L),
+            "桂" => Some(N),
+            "銀" => Some(S),
+            "金" => Some(G),
+            "玉" | "王" => Some(K),
+            "角" => Some(B),
+            "飛" => Some(R),
+            "と" => Some(PP),
+            "成香" | "杏" => Some(PL),
+            "成桂" | "圭" => Some(PN),
+            "成銀" | "全" => Some(PS),
+            "馬" => Some(PB),
+            "龍" | "竜" => Some(PR),
+            _ => None,
+        }
+    }
+
+    // #####
+    // # T #
+    // #####
+
+    /// 局面を見て移動元を決め、KIF の指し手にする。
+    ///
+    /// 駒台の番地と同じく、手番は手目から決める☆（＾～＾）奇数手が先手。
+    ///
+    /// # Arguments
+    ///
+    /// * `previous` - 直前の指し手の移動先。「同」に使う。
+    ///
+    /// # Returns
+    ///
+    /// 移動元が決まらなければ、書かれていた行と文字の位置を付けたエラー。
+    pub fn to_kif_move(
+        &self,
+        position: &Position,
+        ply: i16,
+        previous: Option<Cell>,
+    ) -> Result<KifMove, ParseError> {
+        let error = |message: String| -> ParseError {
+            ParseError::new("KI2", &message)
+                .with_line(self.line, &self.text)
+                .with_column(self.column)
+        };
+
+        let mut kmove = KifMove {
+            destination: None,
+            is_same: false,
+            piece: self.piece,
+            is_promote: self.is_promote,
+            is_drop: false,
+            source: None,
+            is_resign: self.is_resign,
+            annotation: self.annotation.clone(),
+        };
+        if self.is_resign {
+            return Ok(kmove);
+        }
+
+        let destination = if self.is_same {
+            previous
+        } else {
+            self.destination
+        }
+        .ok_or_else(|| error(format!("No destination. Ply: {}.", ply)))?;
+        kmove.destination = Some(destination);
+
+        let phase_value = if ply % 2 == 0 {
+            HalfPlayerPhaseValue::Second
+        } else {
+            HalfPlayerPhaseValue::First
+        };
+        let piece_type = PieceType::from_jsa_piece_type(
+            self.piece
+                .ok_or_else(|| error(format!("No piece. Ply: {}.", ply)))?,
+        );
+
+        // 移動先へ行ける、手番の駒。
+        let board_size = position.get_board_size();
+        let mut candidates = Vec::new();
+        if !self.is_drop {
+            for rank in 1..=board_size.get_rank_len() {
+                for file in 1..=board_size.get_file_len() {
+                    let source = Cell::from_file_rank(file, rank);
+                    if let Some(id_piece) = position.get_id_piece(source) {
+                        if !id_piece.get_phase().is_half()
+                            && id_piece.get_phase().get_state() == phase_value
+                            && id_piece.get_type() == piece_type
+                            && Ki2Move::can_reach(
+                                position,
+                                piece_type,
+                                phase_value,
+                                source,
+                                destination,
+                            )
+                        {
+                            candidates.push(source);
+                        }
+                    }
+                }
+            }
+        }
+
+        if candidates.is_empty() {
+            // 盤上に行ける駒が無ければ、打つ。
+            if position
+                .peek_hand(Piece::from_ph_pt(phase_value, piece_type))
+                .is_none()
+            {
+                return Err(error(format!("No piece can move or drop. Ply: {}.", ply)));
+            }
+            kmove.is_drop = true;
+            return Ok(kmove);
+        }
+
+        // 上、引、寄。
+        match self.motion.as_str() {
+            "上" => {
+                candidates.retain(|src| 0 < Ki2Move::get_forward(phase_value, *src, destination))
+            }
+            "引" => {
+                candidates.retain(|src| Ki2Move::get_forward(phase_value, *src, destination) < 0)
+            }
+            "寄" => {
+                candidates.retain(|src| Ki2Move::get_forward(phase_value, *src, destination) == 0)
+            }
+            _ => {}
+        }
+
+        // 右、左、直。先手から見て右は 筋の小さい方、後手から見て右は 筋の大きい方。
+        let rightmost = |candidates: &[Cell], right: bool| -> Option<Cell> {
+            let to_right = |src: &Cell| -> i8 {
+                match phase_value {
+                    HalfPlayerPhaseValue::Second => src.get_file(),
+                    _ => -src.get_file(),
+                }
+            };
+            if right {
+                candidates.iter().cloned().max_by_key(to_right)
+            } else {
+                candidates.iter().cloned().min_by_key(to_right)
+            }
+        };
+        match self.relative.as_str() {
+            "直" => candidates.retain(|src| {
+                src.get_file() == destination.get_file()
+                    && Ki2Move::get_forward(phase_value, *src, destination) == 1
+            }),
+            "右" => candidates = rightmost(&candidates, true).into_iter().collect(),
+            "左" => candidates = rightmost(&candidates, false).into_iter().collect(),
+            _ => {}
+        }
+
+        if candidates.len() != 1 {
+            return Err(error(format!(
+                "Source is not unique. Ply: {}, Candidates: {}.",
+                ply,
+                candidates.len()
+            )));
+        }
+
+        kmove.source = Some(candidates[0]);
+        Ok(kmove)
+    }
+}
@@ -0,0 +1,155 @@
+use sheet_music_format::kifu_ki2::ki2_move::*;
+use sheet_music_format::kifu_ki2::ki2_tape::*;
+use sheet_music_format::kifu_kif::kif_parser::*;
+use sheet_music_format::parse_error::*;
+use sheet_music_format::record_encoding::*;
+use std::ffi::OsStr;
+use std::path::Path;
+use studio::application::Application;
+
+/* Example
+開始日時：2007/03/21
+棋戦：大和証券杯特別対局
+戦型：四間飛車
+手合割：平手
+先手：Bonanza
+後手：渡辺明
+▲７六歩    △８四歩    ▲６八飛    △３四歩    ▲６六歩
+*コメントは、直前の指し手に付ける。
+△同　歩    ▲５八金右
+まで112手で後手の勝ち
+ */
+/// KI2 は、移動元の升を書かない棋譜。ヘッダーは KIF と同じ。
+pub struct Ki2Parser {}
+impl Ki2Parser {
+    /// ファイルを読めなければ、エラーを返す。
+    /// 移動元の升は 局面を動かしながら決めるので、指し手のエラーは変換の時に分かる。
+    pub fn from_file(file: &str, app: &Application) -> Result<Ki2Tape, ParseError> {
+        let mut tape = Ki2Tape::new();
+
+        let file_stem = Path::new(&file)
+            .file_stem()
+            .and_then(OsStr::to_str)
+            .ok_or_else(|| ParseError::new("KI2", "No file stem.").with_file(file))?;
+        tape.get_mut_tape_label().set_name(file_stem);
+
+        // Shift_JIS のファイルも多い。文字コードを判定して読む。
+        let lines = RecordEncoding::read_lines(file, app)
+            .map_err(|err| ParseError::from_io("KI2", file, &err))?;
+        for (num, line) in lines.into_iter().enumerate() {
+            if num == 0 && line.contains("棋譜ファイル") {
+                // 最初の行で「棋譜ファイル」の文字があれば、バージョン番号が含まれていると予想。
+                tape.get_mut_tape_label().set_format(&line);
+            } else if KifParser::parse_header_line(&line, tape.get_mut_tape_label()) {
+                // ヘッダー。
+            } else if line.starts_with('*') {
+                // コメント。直前の指し手に付ける。
+                tape.push_comment(&line['*'.len_utf8()..]);
+            } else if line.starts_with("変化：") {
+                // 変化はまだ読まない。本譜で終わり。
+                break;
+            } else if line.starts_with("まで") {
+                // "まで76手で先手の勝ち" は投了。時間切れや反則は 投了ではない。
+                if line.contains("勝ち") && !line.contains("切れ") && !line.contains("反則") {
+                    let ply = tape.moves.len() as i16 + 1;
+                    tape.moves.push(Ki2Move::new_resign(ply));
+                }
+            } else if let Some(key) = KifParser::parse_unknown_header_key(&line) {
+                // 知らないヘッダー。
+                tape.warnings
+                    .push(format!("Line {}: Unknown header key: '{}'.", num + 1, key));
+            } else {
+                let first_ply = tape.moves.len() as i16 + 1;
+                tape.moves
+                    .extend(Ki2Move::parse_line(&line, first_ply, num + 1, app));
+            }
+        }
+
+        // これでテープはできあがり。
+        Ok(tape)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instrument::piece_etc::JsaPieceType;
+    use studio::address::Cell;
+
+    fn parse(name: &str, text: &str) -> Ki2Tape {
+        let app = Application::new_for_test();
+        let file = Application::write_test_file(name, text.as_bytes());
+        Ki2Parser::from_file(&file, &app).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    #[test]
+    fn reads_header_and_moves() {
+        let tape = parse(
+            "ki2_parser/header.ki2",
+            "開始日時：2007/03/21
+先手：Bonanza
+後手：渡辺明
+▲７六歩    △３四歩    ▲２二角成
+△同　銀    ▲５八金右  △４五角打
+まで6手で後手の勝ち
+",
+        );
+
+        let label = tape.get_tape_label();
+        assert_eq!(label.get_name(), "header");
+        assert_eq!(label.get_game_date(), "2007/03/21");
+        assert_eq!(label.get_player1(), "Bonanza");
+        assert_eq!(label.get_player2(), "渡辺明");
+
+        // 投了も１手。
+        assert_eq!(tape.moves.len(), 7);
+        assert_eq!(tape.moves[0].destination.map(Cell::to_scalar), Some(76));
+        assert!(tape.moves[0].piece == Some(JsaPieceType::P));
+        assert!(tape.moves[2].is_promote);
+        // 「同」は 移動先を書かない。
+        assert!(tape.moves[3].is_same);
+        assert!(tape.moves[3].destination.is_none());
+        assert_eq!(tape.moves[4].relative, "右");
+        assert!(tape.moves[5].is_drop);
+        assert!(tape.moves[6].is_resign);
+
+        // 行と文字の位置は エラーに使う。
+        assert_eq!(tape.moves[4].line, 5);
+        assert_eq!(tape.moves[4].column, 9);
+        assert_eq!(tape.moves[4].text, "▲５八金右");
+        assert_eq!(tape.moves[4].annotation.ply, 5);
+    }
+
+    #[test]
+    fn attaches_comments_and_stops_at_variation() {
+        let tape = parse(
+            "ki2_parser/comment.ki2",
+            "*初期局面へのコメント。
+▲７六歩    △３四歩
+*２手目へのコメント。
+変化：2手
+△８四歩
+",
+        );
+
+        assert_eq!(tape.comments, vec!["初期局面へのコメント。"]);
+        assert_eq!(tape.moves.len(), 2);
+        assert!(tape.moves[0].annotation.comments.is_empty());
+        assert_eq!(
+            tape.moves[1].annotation.comments,
+            vec!["２手目へのコメント。"]
+        );
+    }
+
+    #[test]
+    fn does_not_resign_on_time_out() {
+        let tape = parse(
+            "ki2_parser/time-out.ki2",
+            "▲７六歩    △３四歩
+まで2手で時間切れにより先手の勝ち
+",
+        );
+
+        assert_eq!(tape.moves.len(), 2);
+        assert!(!tape.moves[1].is_resign);
+    }
+}
@@ -0,0 +1,91 @@
+use sheet_music_format::kifu_ki2::ki2_move::*;
+use sheet_music_format::kifu_ki2::ki2_parser::*;
+use sheet_music_format::move_annotation::MoveAnnotation;
+use sheet_music_format::parse_error::*;
+use sheet_music_format::tape_label::*;
+use studio::application::Application;
+
+/// KI2 のファイルに対応。変化はまだ読まない。
+#[derive(Default)]
+pub struct Ki2Tape {
+    // 指し手。本譜。
+    pub moves: Vec<Ki2Move>,
+
+    // 初手より前のコメント。初期局面へのもの。
+    pub comments: Vec<String>,
+
+    // テープ・ラベル。
+    tape_label: TapeLabel,
+
+    // 読み飛ばしたもの。知らないヘッダーなど。
+    pub warnings: Vec<String>,
+}
+impl Ki2Tape {
+    pub fn new() -> Ki2Tape {
+        Ki2Tape {
+            moves: Vec::new(),
+            comments: Vec::new(),
+            tape_label: TapeLabel::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    // #####
+    // # F #
+    // #####
+
+    /// ファイル読取。
+    pub fn from_file(file: &str, app: &Application) -> Result<Ki2Tape, ParseError> {
+        Ki2Parser::from_file(file, app)
+    }
+
+    // #####
+    // # G #
+    // #####
+
+    // テープ・ラベル。
+    pub fn get_tape_label(&self) -> &TapeLabel {
+        &self.tape_label
+    }
+
+    // テープ・ラベル。
+    pub fn get_mut_tape_label(&mut self) -> &mut TapeLabel {
+        &mut self.tape_label
+    }
+
+    // #####
+    // # P #
+    // #####
+
+    /// コメントを追加。直前に読んだ指し手に付ける。
+    /// 初手より前なら、初期局面へのコメント。
+    pub fn push_comment(&mut self, comment: &str) {
+        if let Some(mov) = self.moves.last_mut() {
+            mov.annotation.push_comment(comment);
+        } else {
+            self.comments.push(comment.to_string());
+        }
+    }
+
+    // #####
+    // # T #
+    // #####
+
+    /// 指し手ごとのコメント。手目の順。
+    pub fn to_annotations(&self) -> Vec<MoveAnnotation> {
+        let mut annotations = Vec::new();
+
+        let mut opening = MoveAnnotation::new(0);
+        opening.comments = self.comments.clone();
+        annotations.push(opening);
+
+        for kmove in &self.moves {
+            annotations.push(kmove.annotation.clone());
+        }
+
+        annotations
+            .into_iter()
+            .filter(|annotation| !annotation.is_empty())
+            .collect()
+    }
+}
@@ -0,0 +1,4 @@
+pub mod ki2_converter;
+pub mod ki2_move;
+pub mod ki2_parser;
+pub mod ki2_tape;
@@ -1,49 +1,108 @@
 use audio_compo::audio_rack::*;
 use human::human_interface::*;
+use instrument::half_player_phase::*;
 use instrument::piece_etc::*;
 use instrument::position::*;
 use live::base_performer::*;
 use live::ohashi_performer::*;
 use sheet_music_format::kifu_kif::kif_move::*;
 use sheet_music_format::kifu_kif::kif_tape::*;
+use sheet_music_format::parse_error::*;
 use sound::shogi_note_operation::*;
 use studio::address::*;
 use studio::application::Application;
 
 pub struct KifConverter {}
 impl KifConverter {
-    /// 変換には、初期局面が必要。
+    /// 変換には、初期局面が必要。本譜だけ変換する。
+    ///
+    /// # Returns
+    ///
+    /// 指した手の数。投了は数えない。
     pub fn play_out_kifu_tape(
         ktape: &KifTape,
         rack: &mut AudioRack,
         position: &mut Position,
         app: &Application,
-    ) {
+    ) -> Result<usize, ParseError> {
+        KifConverter::play_out_kif_moves(&ktape.moves, rack, position, app)
+    }
+
+    /// 変換には、初期局面が必要。
+    ///
+    /// # Arguments
+    ///
+    /// * `kmoves` - 本譜か、変化を広げた初手からの手順。
+    ///
+    /// # Returns
+    ///
+    /// 指した手の数。投了は数えない。
+    pub fn play_out_kif_moves(
+        kmoves: &[KifMove],
+        rack: &mut AudioRack,
+        position: &mut Position,
+        app: &Application,
+    ) -> Result<usize, ParseError> {
         // 大橋流を指すところから☆（*＾～＾*）
         OhashiPerformer::improvise_ohashi_starting(rack, position, &app);
 
         let mut ply = 1;
-        for kmove in &ktape.moves {
-            let rnote_opes = KifConverter::convert_move(kmove, position, ply, &app);
+        for kmove in kmoves {
+            let rnote_opes = KifConverter::convert_move(kmove, position, ply, app)?;
+            if rnote_opes.is_empty() {
+                // 中断など、投了以外の終局。
+                break;
+            }
 
             for rnote_ope in rnote_opes {
                 BasePerformer::improvise_note_ope_no_log(rack, &rnote_ope, false, position, &app);
                 HumanInterface::bo(rack, position, &app);
             }
 
+            if kmove.is_resign {
+                break;
+            }
+
             ply += 1;
         }
+
+        Ok((ply - 1) as usize)
     }
 
-    /// 変換には、現局面が必要。
+    /// 変換には、現局面が必要。指し手も投了も無ければ、空っぽを返す。
+    ///
+    /// 駒台の番地は、局面の手番ではなく 手目から決める☆（＾～＾）奇数手が先手。
+    /// 局面に合わない指し手なら、エラーを返す。
     pub fn convert_move(
         kmove: &KifMove,
         position: &Position,
         ply: i16,
         app: &Application,
-    ) -> Vec<ShogiNoteOpe> {
+    ) -> Result<Vec<ShogiNoteOpe>, ParseError> {
         let mut rmoves = Vec::new();
 
+        if kmove.is_resign {
+            rmoves.push(ShogiNoteOpe::change_phase(ply));
+            rmoves.push(ShogiNoteOpe::resign());
+            rmoves.push(ShogiNoteOpe::change_phase(ply));
+            return Ok(rmoves);
+        }
+
+        if kmove.destination.is_none() {
+            return Ok(rmoves);
+        }
+
+        let error = |message: &str| -> ParseError {
+            ParseError::new("KIF", &format!("{} Ply: {}.", message, ply))
+                .with_text(&kmove.to_kif_text())
+        };
+
+        let phase_value = if ply % 2 == 0 {
+            HalfPlayerPhaseValue::Second
+        } else {
+            HalfPlayerPhaseValue::First
+        };
+
         // ####################
         // # (0) Change phase #
         // ####################
@@ -67,15 +126,14 @@ impl KifConverter {
             {
                 let piece_type = jsa_piece_type_to_perfect(kmove.piece);
                 let piece = Piece::from_ph_pt(
-                    position.get_phase().get_state(),
-                    piece_type.unwrap_or_else(|| panic!(app.comm.panic("Fail. piece_type."))),
+                    phase_value,
+                    piece_type.ok_or_else(|| error("No piece to drop."))?,
                 );
                 let drop = position.peek_hand(piece);
 
                 let hand_off = ShogiNoteOpe::from_address(Address::from_hand_ph_pt(
-                    position.get_phase().get_state(),
-                    drop.unwrap_or_else(|| panic!(app.comm.panic("Fail. drop.")))
-                        .get_type(),
+                    phase_value,
+                    drop.ok_or_else(|| error("No piece in hand."))?.get_type(),
                 ));
                 rmoves.push(hand_off);
             }
@@ -123,10 +181,8 @@ impl KifConverter {
                 // ################
                 {
                     let up = capture_id_piece.get_type();
-                    let hand_on = ShogiNoteOpe::from_address(Address::from_hand_ph_pt(
-                        position.get_phase().get_state(),
-                        up,
-                    ));
+                    let hand_on =
+                        ShogiNoteOpe::from_address(Address::from_hand_ph_pt(phase_value, up));
                     rmoves.push(hand_on);
                 }
             }
@@ -138,9 +194,7 @@ impl KifConverter {
             // #################
             {
                 let board_off = ShogiNoteOpe::from_address(Address::from_cell(
-                    kmove
-                        .source
-                        .unwrap_or_else(|| panic!(app.comm.panic("Fail. kmove.source."))),
+                    kmove.source.ok_or_else(|| error("No source."))?,
                     position.get_board_size(),
                 ));
                 rmoves.push(board_off);
@@ -183,6 +237,6 @@ impl KifConverter {
             rmoves.push(ShogiNoteOpe::change_phase(ply));
         }
 
-        rmoves
+        Ok(rmoves)
     }
 }
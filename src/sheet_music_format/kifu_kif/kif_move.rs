@@ -1,8 +1,12 @@
 use instrument::piece_etc::*;
 use regex::Regex;
+use sheet_music_format::kifu_rpm::rpm_move::*;
+use sheet_music_format::move_annotation::MoveAnnotation;
+use sheet_music_format::parse_error::*;
 use studio::address::*;
 use studio::application::Application;
 
+#[derive(Clone)]
 pub struct KifMove {
     pub destination: Option<Cell>,
     pub is_same: bool,
@@ -10,8 +14,112 @@ pub struct KifMove {
     pub is_promote: bool,
     pub is_drop: bool,
     pub source: Option<Cell>,
+    pub is_resign: bool,
+    // コメントと消費時間。
+    pub annotation: MoveAnnotation,
 }
 impl KifMove {
+    /// RPMテープから読み取った指し手から作る。
+    ///
+    /// # Arguments
+    ///
+    /// * `previous` - 直前の指し手。「同」の判定に使う。
+    pub fn from_rpm_move(rmove: &RpmMove, previous: Option<&RpmMove>) -> Self {
+        let is_same = match (previous, rmove.destination) {
+            (Some(prev), Some(dst)) => prev.destination == Some(dst),
+            _ => false,
+        };
+
+        KifMove {
+            destination: rmove.destination,
+            is_same,
+            piece: rmove.piece_type.map(KifMove::piece_type_to_jsa),
+            is_promote: rmove.promotion,
+            is_drop: rmove.drop,
+            source: rmove.source,
+            is_resign: rmove.resign,
+            annotation: MoveAnnotation::new(rmove.ply),
+        }
+    }
+
+    pub fn piece_type_to_jsa(piece_type: PieceType) -> JsaPieceType {
+        use instrument::piece_etc::PieceType::*;
+        match piece_type {
+            K | PK => JsaPieceType::K,
+            R => JsaPieceType::R,
+            PR => JsaPieceType::PR,
+            B => JsaPieceType::B,
+            PB => JsaPieceType::PB,
+            G | PG => JsaPieceType::G,
+            S => JsaPieceType::S,
+            PS => JsaPieceType::PS,
+            N => JsaPieceType::N,
+            PN => JsaPieceType::PN,
+            L => JsaPieceType::L,
+            PL => JsaPieceType::PL,
+            P => JsaPieceType::P,
+            PP => JsaPieceType::PP,
+        }
+    }
+
+    pub fn jsa_piece_type_to_kanji(piece: JsaPieceType) -> String {
+        use instrument::piece_etc::JsaPieceType::*;
+        match piece {
+            K => "玉",
+            R => "飛",
+            PR => "龍",
+            B => "角",
+            PB => "馬",
+            G => "金",
+            S => "銀",
+            PS => "成銀",
+            N => "桂",
+            PN => "成桂",
+            L => "香",
+            PL => "成香",
+            P => "歩",
+            PP => "と",
+        }
+        .to_string()
+    }
+
+    /// 棋譜ファイルに書く指し手。 "７六歩(77)" など。
+    pub fn to_kif_text(&self) -> String {
+        if self.is_resign {
+            return "投了".to_string();
+        }
+
+        let mut text = if self.is_same {
+            "同　".to_string()
+        } else if let Some(dst) = self.destination {
+            format!(
+                "{}{}",
+                ["１", "２", "３", "４", "５", "６", "７", "８", "９"]
+                    [(dst.get_file() - 1) as usize],
+                ["一", "二", "三", "四", "五", "六", "七", "八", "九"]
+                    [(dst.get_rank() - 1) as usize]
+            )
+        } else {
+            "".to_string()
+        };
+
+        if let Some(piece) = self.piece {
+            text = format!("{}{}", text, KifMove::jsa_piece_type_to_kanji(piece));
+        }
+
+        if self.is_promote {
+            text = format!("{}成", text);
+        }
+
+        if self.is_drop {
+            text = format!("{}打", text);
+        } else if let Some(src) = self.source {
+            text = format!("{}({}{})", text, src.get_file(), src.get_rank());
+        }
+
+        text
+    }
+
     pub fn to_sign(&self) -> String {
         let mut sign = "".to_string();
 
@@ -33,13 +141,34 @@ impl KifMove {
         sign
     }
 
-    pub fn parse(line: &str, app: &Application) -> Option<KifMove> {
-        let re = Regex::new(r"\s*\d+ ((.*))*\([ 0-9:/]+\)")
-            .unwrap_or_else(|f| panic!(app.comm.panic(&f.to_string())));
+    /// 指し手の行を読む。 "   1 ７六歩(77)   ( 0:01/00:00:01)" など。
+    ///
+    /// # Returns
+    ///
+    /// 読めなければ、何文字目が悪かったかを返す。
+    pub fn parse(line: &str, app: &Application) -> Result<KifMove, ParseError> {
+        // 消費時間は省略されることがある。
+        let re =
+            Regex::new(r"^\s*(\d+) (.*?)\s*(\(\s*(\d+):(\d+)/\s*(\d+):(\d+):(\d+)\))?\s*\+?\s*$")
+                .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
         let caps = re
             .captures(line)
-            .unwrap_or_else(|| panic!(app.comm.panic("Fail. parse.")));
-        let sign = caps.get(1).map_or("", |m| m.as_str());
+            .ok_or_else(|| ParseError::new("KIF", "Not a move.").with_text(line))?;
+        let ply = caps
+            .get(1)
+            .map_or("", |m| m.as_str())
+            .parse::<i16>()
+            .map_err(|f| ParseError::new("KIF", &f.to_string()).with_text(line))?;
+        let sign = caps.get(2).map_or("", |m| m.as_str());
+
+        // 指し手の何文字目が悪かったか。行の先頭から数える。
+        let sign_column = line[..caps.get(2).map_or(0, |m| m.start())].chars().count();
+        let error = |nth: usize, message: &str| -> ParseError {
+            ParseError::new("KIF", message)
+                .with_column(sign_column + nth + 1)
+                .with_text(sign)
+        };
+        let chars: Vec<char> = sign.chars().collect();
 
         let mut mv = KifMove {
             destination: None,
@@ -48,8 +177,27 @@ impl KifMove {
             is_promote: false,
             is_drop: false,
             source: None,
+            is_resign: false,
+            annotation: MoveAnnotation::new(ply),
         };
 
+        if caps.get(3).is_some() {
+            // ( 0:16/ 0:00:16) その手の分秒、累計の時分秒。
+            let number = |i: usize| -> Result<u32, ParseError> {
+                caps.get(i)
+                    .map_or("", |m| m.as_str())
+                    .parse::<u32>()
+                    .map_err(|f| ParseError::new("KIF", &f.to_string()).with_text(line))
+            };
+            mv.annotation.consumed = Some(number(4)? * 60 + number(5)?);
+            mv.annotation.total = Some(number(6)? * 3600 + number(7)? * 60 + number(8)?);
+        }
+
+        if sign.starts_with("投了") {
+            mv.is_resign = true;
+            return Ok(mv);
+        }
+
         /*
         Phase   0  1   2   3   4   5  6   7  8  9
                筋  段  同  駒  成  打  (  筋  段  )
@@ -61,298 +209,115 @@ impl KifMove {
                  */
         let mut nth = 0;
 
-        let dfile = if 0 < sign.len() - nth {
-            // Phase 0.
-            let ch = sign
-                .chars()
-                .nth(nth)
-                .unwrap_or_else(|| panic!(app.comm.panic("Fail. sing.")))
-                .to_string();
-            nth += 1;
-            match ch.as_str() {
-                "１" => Some(1),
-                "２" => Some(2),
-                "３" => Some(3),
-                "４" => Some(4),
-                "５" => Some(5),
-                "６" => Some(6),
-                "７" => Some(7),
-                "８" => Some(8),
-                "９" => Some(9),
-                _ => {
-                    nth -= 1;
-                    None
-                }
-            }
-        } else {
-            None
+        // Phase 0.
+        let dfile = match chars.get(nth) {
+            Some('１') => Some(1),
+            Some('２') => Some(2),
+            Some('３') => Some(3),
+            Some('４') => Some(4),
+            Some('５') => Some(5),
+            Some('６') => Some(6),
+            Some('７') => Some(7),
+            Some('８') => Some(8),
+            Some('９') => Some(9),
+            _ => None,
         };
-
-        mv.destination = if 0 < sign.len() - nth {
-            // Phase 1.
-            let ch = sign
-                .chars()
-                .nth(nth)
-                .unwrap_or_else(|| panic!(app.comm.panic("Fail. sign.")))
-                .to_string();
+        if dfile.is_some() {
             nth += 1;
-            match ch.as_str() {
-                "一" => Some(Cell::from_file_rank(
-                    dfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. dfile."))),
-                    1,
-                )),
-                "二" => Some(Cell::from_file_rank(
-                    dfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. dfile."))),
-                    2,
-                )),
-                "三" => Some(Cell::from_file_rank(
-                    dfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. dfile."))),
-                    3,
-                )),
-                "四" => Some(Cell::from_file_rank(
-                    dfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. dfile."))),
-                    4,
-                )),
-                "五" => Some(Cell::from_file_rank(
-                    dfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. dfile."))),
-                    5,
-                )),
-                "六" => Some(Cell::from_file_rank(
-                    dfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. dfile."))),
-                    6,
-                )),
-                "七" => Some(Cell::from_file_rank(
-                    dfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. dfile."))),
-                    7,
-                )),
-                "八" => Some(Cell::from_file_rank(
-                    dfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. dfile."))),
-                    8,
-                )),
-                "九" => Some(Cell::from_file_rank(
-                    dfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. dfile."))),
-                    9,
-                )),
-                _ => {
-                    nth -= 1;
-                    None
-                }
+        }
+
+        // Phase 1.
+        let drank = match chars.get(nth) {
+            Some('一') => Some(1),
+            Some('二') => Some(2),
+            Some('三') => Some(3),
+            Some('四') => Some(4),
+            Some('五') => Some(5),
+            Some('六') => Some(6),
+            Some('七') => Some(7),
+            Some('八') => Some(8),
+            Some('九') => Some(9),
+            _ => None,
+        };
+        mv.destination = match (dfile, drank) {
+            (Some(file), Some(rank)) => {
+                nth += 1;
+                Some(Cell::from_file_rank(file, rank))
             }
-        } else {
-            None
+            (None, Some(_rank)) => return Err(error(nth, "Rank without file.")),
+            (Some(_file), None) => return Err(error(nth, "File without rank.")),
+            (None, None) => None,
         };
 
-        if 0 < sign.len() - nth {
-            // Phase 2.
-            let ch = sign
-                .chars()
-                .nth(nth)
-                .unwrap_or_else(|| panic!(app.comm.panic("Fail. sign.")))
-                .to_string();
+        // Phase 2.
+        if chars.get(nth) == Some(&'同') {
             nth += 1;
-            mv.is_same = match ch.as_str() {
-                "同" => {
-                    let ch = sign
-                        .chars()
-                        .nth(nth)
-                        .unwrap_or_else(|| panic!(app.comm.panic("Fail. sign.")))
-                        .to_string();
+            match chars.get(nth) {
+                Some('　') => {
                     nth += 1;
-                    match ch.as_str() {
-                        "　" => true,
-                        _ => panic!("Unexpected same ch: '{}'.", ch),
-                    }
+                    mv.is_same = true;
                 }
-                _ => {
-                    nth -= 1;
-                    false
-                }
-            };
+                _ => return Err(error(nth, "Unexpected same.")),
+            }
         }
 
-        if 0 < sign.len() - nth {
-            // Phase 3.
-            let ch = sign
-                .chars()
-                .nth(nth)
-                .unwrap_or_else(|| panic!(app.comm.panic("Fail. sign.")))
-                .to_string();
-            nth += 1;
-            use instrument::piece_etc::JsaPieceType::*;
-            mv.piece = match ch.as_str() {
-                "歩" => Some(P),
-                "香" => Some(L),
-                "桂" => Some(N),
-                "銀" => Some(S),
-                "金" => Some(G),
-                "玉" => Some(K),
-                "角" => Some(B),
-                "飛" => Some(R),
-                "と" => Some(PP),
-                "馬" => Some(PB),
-                "龍" => Some(PR),
-                "成" => {
-                    let ch = sign
-                        .chars()
-                        .nth(nth)
-                        .unwrap_or_else(|| panic!(app.comm.panic("Fail. sign.")))
-                        .to_string();
-                    nth += 1;
-                    match ch.as_str() {
-                        "香" => Some(PL),
-                        "桂" => Some(PN),
-                        "銀" => Some(PS),
-                        _ => panic!("Unexpected promoted ch: '{}'.", ch),
-                    }
-                }
-                _ => {
-                    nth -= 1;
-                    None
+        // Phase 3.
+        use instrument::piece_etc::JsaPieceType::*;
+        mv.piece = match chars.get(nth) {
+            Some('歩') => Some(P),
+            Some('香') => Some(L),
+            Some('桂') => Some(N),
+            Some('銀') => Some(S),
+            Some('金') => Some(G),
+            Some('玉') => Some(K),
+            Some('角') => Some(B),
+            Some('飛') => Some(R),
+            Some('と') => Some(PP),
+            Some('馬') => Some(PB),
+            Some('龍') => Some(PR),
+            Some('成') => {
+                nth += 1;
+                match chars.get(nth) {
+                    Some('香') => Some(PL),
+                    Some('桂') => Some(PN),
+                    Some('銀') => Some(PS),
+                    _ => return Err(error(nth, "Unexpected promoted piece.")),
                 }
-            };
-        }
-
-        if 0 < sign.len() - nth {
-            // Phase 4.
-            let ch = sign
-                .chars()
-                .nth(nth)
-                .unwrap_or_else(|| panic!(app.comm.panic("Fail. sign.")))
-                .to_string();
+            }
+            _ => None,
+        };
+        if mv.piece.is_some() {
             nth += 1;
-            mv.is_promote = match ch.as_str() {
-                "成" => true,
-                _ => {
-                    nth -= 1;
-                    false
-                }
-            };
         }
 
-        if 0 < sign.len() - nth {
-            // Phase 5.
-            let ch = sign
-                .chars()
-                .nth(nth)
-                .unwrap_or_else(|| panic!(app.comm.panic("Fail. sign.")))
-                .to_string();
+        // Phase 4.
+        if chars.get(nth) == Some(&'成') {
             nth += 1;
-            mv.is_drop = match ch.as_str() {
-                "打" => true,
-                _ => {
-                    nth -= 1;
-                    false
-                }
-            };
+            mv.is_promote = true;
         }
 
-        if 0 < sign.len() - nth {
-            // Phase 6.
-            let ch = sign
-                .chars()
-                .nth(nth)
-                .unwrap_or_else(|| panic!(app.comm.panic("Fail. sign.")))
-                .to_string();
+        // Phase 5.
+        if chars.get(nth) == Some(&'打') {
             nth += 1;
-            match ch.as_str() {
-                "(" => {}
-                _ => nth -= 1,
-            };
+            mv.is_drop = true;
         }
 
-        let sfile = if 0 < sign.len() - nth {
-            // Phase 7.
-            let ch = sign
-                .chars()
-                .nth(nth)
-                .unwrap_or_else(|| panic!(app.comm.panic("Fail. sfile.")))
-                .to_string();
+        // Phase 6.
+        if chars.get(nth) == Some(&'(') {
             nth += 1;
-            match ch.as_str() {
-                "1" => Some(1),
-                "2" => Some(2),
-                "3" => Some(3),
-                "4" => Some(4),
-                "5" => Some(5),
-                "6" => Some(6),
-                "7" => Some(7),
-                "8" => Some(8),
-                "9" => Some(9),
-                _ => {
-                    nth -= 1;
-                    None
-                }
-            }
-        } else {
-            None
-        };
+        }
 
-        mv.source = if 0 < sign.len() - nth {
-            // Phase 8.
-            let ch = sign
-                .chars()
-                .nth(nth)
-                .unwrap_or_else(|| panic!(app.comm.panic("Fail. sign.")))
-                .to_string();
-            /*nth += 1;*/
-            match ch.as_str() {
-                "1" => Some(Cell::from_file_rank(
-                    sfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. sfile."))),
-                    1,
-                )),
-                "2" => Some(Cell::from_file_rank(
-                    sfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. sfile."))),
-                    2,
-                )),
-                "3" => Some(Cell::from_file_rank(
-                    sfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. sfile."))),
-                    3,
-                )),
-                "4" => Some(Cell::from_file_rank(
-                    sfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. sfile."))),
-                    4,
-                )),
-                "5" => Some(Cell::from_file_rank(
-                    sfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. sfile."))),
-                    5,
-                )),
-                "6" => Some(Cell::from_file_rank(
-                    sfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. sfile."))),
-                    6,
-                )),
-                "7" => Some(Cell::from_file_rank(
-                    sfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. sfile."))),
-                    7,
-                )),
-                "8" => Some(Cell::from_file_rank(
-                    sfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. sfile."))),
-                    8,
-                )),
-                "9" => Some(Cell::from_file_rank(
-                    sfile.unwrap_or_else(|| panic!(app.comm.panic("Fail. sfile."))),
-                    9,
-                )),
-                _ => {
-                    /*nth -= 1;*/
-                    None
-                }
+        // Phase 7, 8.
+        let sfile = chars.get(nth).and_then(|ch| ch.to_digit(10));
+        let srank = chars.get(nth + 1).and_then(|ch| ch.to_digit(10));
+        mv.source = match (sfile, srank) {
+            (Some(file), Some(rank)) if 0 < file && 0 < rank => {
+                Some(Cell::from_file_rank(file as i8, rank as i8))
             }
-        } else {
-            None
+            (Some(_file), _) => return Err(error(nth, "Unexpected source.")),
+            _ => None,
         };
 
-        /*
-        if 0 < sign.len() - nth {
-            // Phase 9.
-            let ch = sign.chars().nth(nth).unwrap_or_else(|| panic!(app.comm.panic("Fail. sign."))).to_string();
-            /* nth += 1; */
-            match ch.as_str() {
-                ")" => {},
-                _ => {/*nth -= 1*/},
-            };
-        }
-        */
-
-        Some(mv)
+        Ok(mv)
     }
 }
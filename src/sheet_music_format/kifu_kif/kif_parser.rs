@@ -1,9 +1,10 @@
 use regex::Regex;
 use sheet_music_format::kifu_kif::kif_move::*;
 use sheet_music_format::kifu_kif::kif_tape::*;
+use sheet_music_format::parse_error::*;
+use sheet_music_format::record_encoding::*;
+use sheet_music_format::tape_label::*;
 use std::ffi::OsStr;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
 use std::path::Path;
 use std::*;
 use studio::application::Application;
@@ -19,6 +20,7 @@ use studio::application::Application;
 後手：コンピュータ L1
 手数----指手---------消費時間--
    1 ２六歩(27)   ( 0:16/ 0:00:16)
+*コメントは、直前の指し手に付ける。
    2 ８四歩(83)   ( 0:01/ 0:00:01)
    3 ２五歩(26)   ( 0:01/ 0:00:17)
    4 ８五歩(84)   ( 0:01/ 0:00:02)
@@ -42,86 +44,80 @@ use studio::application::Application;
    2 ８四歩(83)   ( 2:32/00:02:32)
    3 ６六歩(67)   ( 0:01/00:00:02)
  */
+/* Example 変化
+手数----指手---------消費時間--
+   1 ７六歩(77)   ( 0:00/00:00:00)
+   2 ３四歩(33)   ( 0:00/00:00:00)+
+   3 ２二角成(88) ( 0:00/00:00:00)
+
+変化：2手
+   2 ８四歩(83)   ( 0:00/00:00:00)
+   3 ２六歩(27)   ( 0:00/00:00:00)
+ */
 /// Kifファイルには色んなパターンがあるようだ。
 /// 柿木将棋 V1.89 棋譜ファイル
 pub struct KifParser {}
 impl KifParser {
-    pub fn from_file(file: &str, app: &Application) -> KifTape {
+    /// 読めない行があれば、ファイル名と行番号を付けたエラーを返す。
+    pub fn from_file(file: &str, app: &Application) -> Result<KifTape, ParseError> {
         let mut tape = KifTape::new();
 
         let file_stem = Path::new(&file)
             .file_stem()
             .and_then(OsStr::to_str)
-            .unwrap_or_else(|| panic!(app.comm.panic("Fail. get_file_stem_from_file_path.")));
+            .ok_or_else(|| ParseError::new("KIF", "No file stem.").with_file(file))?;
         tape.get_mut_tape_label().set_name(file_stem);
 
-        let mut num = 0;
-        for result in
-            BufReader::new(File::open(file).unwrap_or_else(|err| panic!(app.comm.panic_io(&err))))
-                .lines()
-        {
-            let line = result.unwrap_or_else(|err| panic!(app.comm.panic_io(&err)));
+        // Shift_JIS のファイルも多い。文字コードを判定して読む。
+        let lines = RecordEncoding::read_lines(file, app)
+            .map_err(|err| ParseError::from_io("KIF", file, &err))?;
+        let variation_re = Regex::new(r"変化：\s*(\d+)手")
+            .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
+        for (num, line) in lines.into_iter().enumerate() {
+            // 行番号は 1 から。
+            let error = |message: &str| -> ParseError {
+                ParseError::new("KIF", message)
+                    .with_file(file)
+                    .with_line(num + 1, &line)
+            };
 
             if num == 0 && line.contains("棋譜ファイル") {
                 // 最初の行で「棋譜ファイル」の文字があれば、バージョン番号が含まれていると予想。
                 tape.get_mut_tape_label().set_format(&line);
-            } else if line.starts_with("# 対  局  日：") {
-                let re = Regex::new(r"# 対  局  日：(.*)")
-                    .unwrap_or_else(|f| panic!(app.comm.panic(&f.to_string())));
-                let matched = re
-                    .captures(&line)
-                    .unwrap_or_else(|| panic!(app.comm.panic("Fail. regex parse.")));
-                let matched_text = matched.get(1).map_or("", |m| m.as_str());
-                tape.get_mut_tape_label().set_game_date(&matched_text);
-            } else if line.starts_with("棋戦：") {
-                let re = Regex::new(r"棋戦：(.*)")
-                    .unwrap_or_else(|f| panic!(app.comm.panic(&f.to_string())));
-                let matched = re
+            } else if KifParser::parse_header_line(&line, tape.get_mut_tape_label()) {
+                // ヘッダー。
+            } else if line.starts_with('*') {
+                // コメント。直前の指し手に付ける。
+                tape.push_comment(&line['*'.len_utf8()..]);
+            } else if line.starts_with("変化：") {
+                // 変化。ここから先の指し手は、この変化のもの。
+                let matched = variation_re
                     .captures(&line)
-                    .unwrap_or_else(|| panic!(app.comm.panic("Fail. regex parse.")));
-                let matched_text = matched.get(1).map_or("", |m| m.as_str());
-                tape.get_mut_tape_label().set_event(&matched_text);
-            } else if line.starts_with("先手：") {
-                // 先手プレイヤー名。
-                let re = Regex::new(r"先手：(.*)")
-                    .unwrap_or_else(|f| panic!(app.comm.panic(&f.to_string())));
-                let matched = re
-                    .captures(&line)
-                    .unwrap_or_else(|| panic!(app.comm.panic("Fail. regex parse.")));
-                let matched_text = matched.get(1).map_or("", |m| m.as_str());
-                tape.get_mut_tape_label().set_player1(&matched_text);
-            } else if line.starts_with("後手：") {
-                // 後手プレイヤー名。
-                let re = Regex::new(r"後手：(.*)")
-                    .unwrap_or_else(|f| panic!(app.comm.panic(&f.to_string())));
-                let matched = re
-                    .captures(&line)
-                    .unwrap_or_else(|| panic!(app.comm.panic("Fail. regex parse.")));
-                let matched_text = matched.get(1).map_or("", |m| m.as_str());
-                tape.get_mut_tape_label().set_player2(&matched_text);
+                    .ok_or_else(|| error("Unexpected variation."))?;
+                let branch_ply = matched
+                    .get(1)
+                    .map_or("", |m| m.as_str())
+                    .parse::<i16>()
+                    .map_err(|f| error(&f.to_string()))?;
+                tape.push_variation(branch_ply);
+            } else if let Some(key) = KifParser::parse_unknown_header_key(&line) {
+                // 知らないヘッダー。
+                tape.warnings
+                    .push(format!("Line {}: Unknown header key: '{}'.", num + 1, key));
             } else if 4 < line.len() {
                 // 4文字以上で。
-                // 先頭の空白を省き。
-                let mut first_ch = line.trim_start().to_string();
-                first_ch = first_ch
+                // 先頭の空白を省き、数字で始まる行は　指し手。それ以外の行は無視。
+                let is_digit = line
+                    .trim_start()
                     .chars()
-                    .nth(0)
-                    .unwrap_or_else(|| panic!(app.comm.panic("Fail. n0.")))
-                    .to_string();
-                match first_ch.parse::<i8>() {
-                    Ok(_x) => {
-                        // 数字で始まる行は　指し手。
-                        if let Some(kif_move) = KifMove::parse(&line, &app) {
-                            tape.push_move(kif_move);
-                        }
-                    }
-                    Err(_err) => {
-                        // この行は無視。
-                    }
+                    .next()
+                    .is_some_and(|ch| ch.is_ascii_digit());
+                if is_digit {
+                    let kif_move = KifMove::parse(&line, app)
+                        .map_err(|err| err.with_file(file).with_line(num + 1, &line))?;
+                    tape.push_move(kif_move);
                 }
             }
-
-            num += 1;
         }
 
         // '同'を解決する。
@@ -135,6 +131,212 @@ impl KifParser {
         }
 
         // これでテープはできあがり。
-        tape
+        Ok(tape)
+    }
+
+    /// "キー：値" の形の行なら、キーを返す。指し手の行は除く。
+    /// parse_header_line で読めなかった行に使う。
+    pub fn parse_unknown_header_key(line: &str) -> Option<String> {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with(|ch: char| ch.is_ascii_digit()) {
+            return None;
+        }
+
+        line.find('：')
+            .map(|i| line[..i].trim().to_string())
+            .filter(|key| !key.is_empty())
+    }
+
+    /// KIF と KI2 に共通のヘッダー行。テープ・ラベルに書き込む。
+    ///
+    /// # Returns
+    ///
+    /// ヘッダー行なら真。
+    pub fn parse_header_line(line: &str, label: &mut TapeLabel) -> bool {
+        if let Some(game_date) = line.strip_prefix("# 対  局  日：") {
+            label.set_game_date(game_date);
+        } else if let Some(start) = line.strip_prefix("開始日時：") {
+            // 日付と時刻は 空白で区切られていることがある。
+            label.set_game_date_and_start_time(start);
+        } else if let Some(end_time) = line.strip_prefix("終了日時：") {
+            label.set_end_time(end_time);
+        } else if let Some(place) = line.strip_prefix("場所：") {
+            label.set_place(place);
+        } else if let Some(time_system) = line.strip_prefix("持ち時間：") {
+            label.set_time_system(time_system);
+        } else if let Some(handicap) = line.strip_prefix("手合割：") {
+            label.set_handicap(handicap);
+        } else if let Some(battle_type) = line.strip_prefix("戦型：") {
+            label.set_battle_type(battle_type);
+        } else if let Some(event) = line.strip_prefix("棋戦：") {
+            label.set_event(event);
+        } else if let Some(player1) = line.strip_prefix("先手：") {
+            // 先手プレイヤー名。
+            label.set_player1(player1);
+        } else if let Some(player2) = line.strip_prefix("後手：") {
+            // 後手プレイヤー名。
+            label.set_player2(player2);
+        } else {
+            return false;
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use studio::address::Cell;
+
+    /// 升目を ７六 なら 76 にする。
+    fn to_scalar(cell: Option<Cell>) -> Option<i8> {
+        cell.map(Cell::to_scalar)
+    }
+
+    fn parse(name: &str, text: &str) -> KifTape {
+        let app = Application::new_for_test();
+        let file = Application::write_test_file(name, text.as_bytes());
+        KifParser::from_file(&file, &app).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    #[test]
+    fn reads_header_and_moves() {
+        let tape = parse(
+            "kif_parser/header.kif",
+            "# ----  Kifu for Windows V6.00 棋譜ファイル  ----
+開始日時：2007/03/21 10:00
+手合割：平手
+先手：Bonanza
+後手：渡辺明
+手数----指手---------消費時間--
+   1 ７六歩(77)   ( 0:01/00:00:01)
+   2 ８四歩(83)   ( 2:32/00:02:32)
+   3 ２二角成(88) ( 0:01/00:00:02)
+   4 同　銀(31)   ( 0:01/00:02:33)
+",
+        );
+
+        let label = tape.get_tape_label();
+        assert_eq!(label.get_name(), "header");
+        assert_eq!(label.get_game_date(), "2007/03/21");
+        assert_eq!(label.get_start_time(), "10:00");
+        assert_eq!(label.get_player1(), "Bonanza");
+        assert_eq!(label.get_player2(), "渡辺明");
+
+        assert_eq!(tape.moves.len(), 4);
+        assert_eq!(to_scalar(tape.moves[0].destination), Some(76));
+        assert_eq!(to_scalar(tape.moves[0].source), Some(77));
+        assert!(tape.moves[2].is_promote);
+        // 「同」は 直前の指し手の行き先。
+        assert!(tape.moves[3].is_same);
+        assert_eq!(to_scalar(tape.moves[3].destination), Some(22));
+        assert_eq!(tape.moves[1].annotation.consumed, Some(152));
+    }
+
+    #[test]
+    fn reads_variations_as_lines() {
+        let tape = parse(
+            "kif_parser/variation.kif",
+            "手数----指手---------消費時間--
+   1 ７六歩(77)   ( 0:00/00:00:00)
+   2 ３四歩(33)   ( 0:00/00:00:00)+
+   3 ２二角成(88) ( 0:00/00:00:00)
+
+変化：2手
+   2 ８四歩(83)   ( 0:00/00:00:00)
+   3 ２六歩(27)   ( 0:00/00:00:00)+
+
+変化：3手
+   3 ６八銀(79)   ( 0:00/00:00:00)
+
+変化：2手
+   2 ４四歩(43)   ( 0:00/00:00:00)
+",
+        );
+
+        assert_eq!(tape.moves.len(), 3);
+        assert_eq!(tape.variations.len(), 3);
+
+        let lines = tape.to_lines();
+        assert_eq!(lines.len(), 4);
+
+        // 本譜。
+        assert_eq!(lines[0].branch_ply, 0);
+        assert_eq!(lines[0].moves.len(), 3);
+
+        // 2手目から分かれる。
+        assert_eq!(lines[1].branch_ply, 2);
+        assert_eq!(lines[1].parent, 0);
+        assert_eq!(lines[1].moves.len(), 3);
+        assert_eq!(to_scalar(lines[1].moves[0].destination), Some(76));
+        assert_eq!(to_scalar(lines[1].moves[1].destination), Some(84));
+
+        // 直前の変化の 3手目から分かれる。
+        assert_eq!(lines[2].branch_ply, 3);
+        assert_eq!(lines[2].parent, 1);
+        assert_eq!(to_scalar(lines[2].moves[1].destination), Some(84));
+        assert_eq!(to_scalar(lines[2].moves[2].destination), Some(68));
+
+        // 2手目の変化は、また本譜から分かれる。
+        assert_eq!(lines[3].branch_ply, 2);
+        assert_eq!(lines[3].parent, 0);
+        assert_eq!(lines[3].moves.len(), 2);
+    }
+
+    #[test]
+    fn attaches_comments_to_moves() {
+        let tape = parse(
+            "kif_parser/comment.kif",
+            "手数----指手---------消費時間--
+*初期局面へのコメント。
+   1 ７六歩(77)   ( 0:00/00:00:00)
+*１手目へのコメント。
+   2 ３四歩(33)   ( 0:00/00:00:00)+
+
+変化：2手
+*変化の１手目より前のコメント。
+   2 ８四歩(83)   ( 0:00/00:00:00)
+*変化の２手目へのコメント。
+",
+        );
+
+        assert_eq!(tape.comments, vec!["初期局面へのコメント。"]);
+        assert_eq!(
+            tape.moves[0].annotation.comments,
+            vec!["１手目へのコメント。"]
+        );
+        assert!(tape.moves[1].annotation.comments.is_empty());
+
+        // 変化の１手目より前のコメントは、変化の１手目に付く。
+        assert_eq!(
+            tape.variations[0].moves[0].annotation.comments,
+            vec![
+                "変化の１手目より前のコメント。",
+                "変化の２手目へのコメント。"
+            ]
+        );
+
+        let lines = tape.to_lines();
+        let annotations = tape.to_annotations(&lines[1]);
+        assert_eq!(annotations[0].ply, 0);
+        assert_eq!(annotations[1].ply, 1);
+        assert_eq!(annotations[2].ply, 2);
+        assert_eq!(annotations[2].comments.len(), 2);
+    }
+
+    #[test]
+    fn reports_line_of_unexpected_variation() {
+        let app = Application::new_for_test();
+        let file = Application::write_test_file(
+            "kif_parser/broken-variation.kif",
+            "   1 ７六歩(77)   ( 0:00/00:00:00)\n変化：二手\n".as_bytes(),
+        );
+
+        let err = match KifParser::from_file(&file, &app) {
+            Ok(_tape) => panic!("Unexpected success."),
+            Err(err) => err,
+        };
+        assert_eq!(err.line, 2);
     }
 }
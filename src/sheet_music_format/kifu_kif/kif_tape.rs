@@ -1,22 +1,65 @@
 use sheet_music_format::kifu_kif::kif_move::*;
 use sheet_music_format::kifu_kif::kif_parser::*;
+use sheet_music_format::kifu_rpm::rpm_move::*;
+use sheet_music_format::kifu_rpm::rpm_tape::*;
+use sheet_music_format::move_annotation::MoveAnnotation;
+use sheet_music_format::parse_error::*;
 use sheet_music_format::tape_label::*;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::prelude::*;
+use std::path::Path;
 use std::*;
 use studio::application::Application;
+use studio::board_size::*;
+
+/// 変化。本譜か、別の変化から分かれる。
+pub struct KifVariation {
+    /// 分かれた手目。
+    pub branch_ply: i16,
+    /// 分かれる元。0 なら本譜、1 以上なら variations[parent - 1]。
+    pub parent: usize,
+    /// 分かれた手目からの指し手。
+    pub moves: Vec<KifMove>,
+    /// 変化の１手目より前のコメント。１手目を読んだら、１手目に付ける。
+    pub pending_comments: Vec<String>,
+}
+
+/// 初手から指し直せる、１本道の手順。
+pub struct KifLine {
+    /// 本譜なら 0。変化なら、分かれた手目。
+    pub branch_ply: i16,
+    /// 分かれる元の手順の番号。本譜は 0。
+    pub parent: usize,
+    /// 初手からの指し手。
+    pub moves: Vec<KifMove>,
+}
 
 #[derive(Default)]
 pub struct KifTape {
-    // 指し手。
+    // 指し手。本譜。
     pub moves: Vec<KifMove>,
 
+    // 変化。出てきた順。
+    pub variations: Vec<KifVariation>,
+
+    // 初手より前のコメント。初期局面へのもの。
+    pub comments: Vec<String>,
+
     // テープ・ラベル。
     tape_label: TapeLabel,
+
+    // 読み飛ばしたもの。知らないヘッダーなど。
+    pub warnings: Vec<String>,
 }
 impl KifTape {
     pub fn new() -> KifTape {
         KifTape {
             moves: Vec::new(),
+            variations: Vec::new(),
+            comments: Vec::new(),
             tape_label: TapeLabel::new(),
+            warnings: Vec::new(),
         }
     }
 
@@ -25,15 +68,49 @@ impl KifTape {
     // #####
 
     /// ファイル読取。
-    pub fn from_file(file: &str, app: &Application) -> KifTape {
+    pub fn from_file(file: &str, app: &Application) -> Result<KifTape, ParseError> {
         // .kif形式には、バージョンがいろいろあるようだ。
         KifParser::from_file(file, &app)
     }
 
+    /// RPMテープ１本を、KIF に書き直す。
+    pub fn from_rpm_tape(rtape: &RpmTape, board_size: BoardSize, app: &Application) -> KifTape {
+        let mut ktape = KifTape::new();
+        ktape.tape_label = rtape.label.clone();
+
+        let mut annotations = rtape.tracks.annotation.clone();
+        MoveAnnotation::fill_total(&mut annotations);
+
+        if let Some(annotation) = MoveAnnotation::find(&annotations, 0) {
+            ktape.comments = annotation.comments.clone();
+        }
+
+        let rmoves = RpmMove::parse_rpm_tape(rtape, board_size, app);
+        for (i, rmove) in rmoves.iter().enumerate() {
+            let previous = if 0 < i { rmoves.get(i - 1) } else { None };
+            let mut kmove = KifMove::from_rpm_move(rmove, previous);
+            if let Some(annotation) = MoveAnnotation::find(&annotations, rmove.ply) {
+                kmove.annotation = annotation.clone();
+            }
+            ktape.moves.push(kmove);
+
+            if rmove.resign {
+                break;
+            }
+        }
+
+        ktape
+    }
+
     // #####
     // # G #
     // #####
 
+    // テープ・ラベル。
+    pub fn get_tape_label(&self) -> &TapeLabel {
+        &self.tape_label
+    }
+
     // テープ・ラベル。
     pub fn get_mut_tape_label(&mut self) -> &mut TapeLabel {
         &mut self.tape_label
@@ -43,8 +120,227 @@ impl KifTape {
     // # P #
     // #####
 
-    /// 指し手を追加。
-    pub fn push_move(&mut self, mov: KifMove) {
-        self.moves.push(mov);
+    /// コメントを追加。直前に読んだ指し手に付ける。
+    /// 初手より前なら、初期局面へのコメント。
+    /// 変化の１手目より前なら、変化の１手目に付ける。
+    pub fn push_comment(&mut self, comment: &str) {
+        if let Some(variation) = self.variations.last_mut() {
+            if let Some(mov) = variation.moves.last_mut() {
+                mov.annotation.push_comment(comment);
+            } else {
+                variation.pending_comments.push(comment.to_string());
+            }
+        } else if let Some(mov) = self.moves.last_mut() {
+            mov.annotation.push_comment(comment);
+        } else {
+            self.comments.push(comment.to_string());
+        }
+    }
+
+    /// 指し手を追加。変化を読んでいる途中なら、その変化に追加。
+    pub fn push_move(&mut self, mut mov: KifMove) {
+        if let Some(variation) = self.variations.last_mut() {
+            for comment in variation.pending_comments.drain(..) {
+                mov.annotation.push_comment(&comment);
+            }
+            variation.moves.push(mov);
+        } else {
+            self.moves.push(mov);
+        }
+    }
+
+    /// 変化を始める。分かれる元は、直前に読んだ手順から遡って探す。
+    ///
+    /// 変化は深さ優先で並んでいるので、分かれた手目より後で始まる手順は 分かれる元ではない。
+    pub fn push_variation(&mut self, branch_ply: i16) {
+        let mut parent = self.variations.len();
+        while 0 < parent && branch_ply <= self.variations[parent - 1].branch_ply {
+            parent = self.variations[parent - 1].parent;
+        }
+
+        self.variations.push(KifVariation {
+            branch_ply,
+            parent,
+            moves: Vec::new(),
+            pending_comments: Vec::new(),
+        });
+    }
+
+    // #####
+    // # T #
+    // #####
+
+    /// 手順の、指し手ごとのコメントと消費時間。手目の順。
+    pub fn to_annotations(&self, line: &KifLine) -> Vec<MoveAnnotation> {
+        let mut annotations = Vec::new();
+
+        let mut opening = MoveAnnotation::new(0);
+        opening.comments = self.comments.clone();
+        annotations.push(opening);
+
+        for (i, kmove) in line.moves.iter().enumerate() {
+            let mut annotation = kmove.annotation.clone();
+            annotation.ply = (i + 1) as i16;
+            annotations.push(annotation);
+        }
+
+        annotations
+            .into_iter()
+            .filter(|annotation| !annotation.is_empty())
+            .collect()
+    }
+
+    /// 本譜と、全ての変化を、初手からの手順に広げる。本譜が先頭。
+    pub fn to_lines(&self) -> Vec<KifLine> {
+        let mut lines = vec![KifLine {
+            branch_ply: 0,
+            parent: 0,
+            moves: self.moves.clone(),
+        }];
+
+        for variation in &self.variations {
+            let mut moves: Vec<KifMove> = lines[variation.parent]
+                .moves
+                .iter()
+                .take((variation.branch_ply - 1) as usize)
+                .cloned()
+                .collect();
+            moves.extend(variation.moves.iter().cloned());
+
+            // '同'を解決する。
+            let mut pre_cell = None;
+            for mov in &mut moves {
+                if mov.is_same {
+                    mov.destination = pre_cell;
+                }
+
+                pre_cell = mov.destination;
+            }
+
+            lines.push(KifLine {
+                branch_ply: variation.branch_ply,
+                parent: variation.parent,
+                moves,
+            });
+        }
+
+        lines
+    }
+
+    /// KIF のテキスト。変化も書く。
+    pub fn to_kif_text(&self) -> String {
+        let label = &self.tape_label;
+        let mut text = "# ----  kifuwarabe-wcsc29 棋譜ファイル  ----\n".to_string();
+
+        let start = format!("{} {}", label.get_game_date(), label.get_start_time());
+        let pairs = [
+            ("開始日時", start.trim().to_string()),
+            ("終了日時", label.get_end_time()),
+            ("棋戦", label.get_event()),
+            ("戦型", label.get_battle_type()),
+            ("場所", label.get_place()),
+            ("持ち時間", label.get_time_system()),
+            ("手合割", label.get_handicap()),
+            ("先手", label.get_player1()),
+            ("後手", label.get_player2()),
+        ];
+        for (key, value) in pairs.iter() {
+            if !value.is_empty() {
+                text = format!("{}{}：{}\n", text, key, value);
+            }
+        }
+
+        text = format!("{}手数----指手---------消費時間--\n", text);
+        for comment in &self.comments {
+            text = format!("{}*{}\n", text, comment);
+        }
+
+        text = format!("{}{}", text, KifTape::moves_to_kif_text(&self.moves, 1));
+
+        for variation in &self.variations {
+            text = format!(
+                "{}\n変化：{}手\n{}",
+                text,
+                variation.branch_ply,
+                KifTape::moves_to_kif_text(&variation.moves, variation.branch_ply)
+            );
+        }
+
+        text
+    }
+
+    /// # Arguments
+    ///
+    /// * `first_ply` - moves の先頭の手目。
+    fn moves_to_kif_text(moves: &[KifMove], first_ply: i16) -> String {
+        let mut text = String::new();
+
+        for (i, kmove) in moves.iter().enumerate() {
+            let sign = kmove.to_kif_text();
+
+            // 全角は２桁と数えて、指し手の幅をそろえる。
+            let width: usize = sign
+                .chars()
+                .map(|ch| if ch.is_ascii() { 1 } else { 2 })
+                .sum();
+            let padding = if width < 13 { 13 - width } else { 1 };
+
+            let time = match (kmove.annotation.consumed, kmove.annotation.total) {
+                (Some(consumed), Some(total)) => format!(
+                    "{}({:>2}:{:02}/{:02}:{:02}:{:02})",
+                    " ".repeat(padding),
+                    consumed / 60,
+                    consumed % 60,
+                    total / 3600,
+                    total / 60 % 60,
+                    total % 60
+                ),
+                _ => "".to_string(),
+            };
+
+            text = format!("{}{:>4} {}{}\n", text, first_ply + i as i16, sign, time);
+
+            for comment in &kmove.annotation.comments {
+                text = format!("{}*{}\n", text, comment);
+            }
+        }
+
+        text
+    }
+
+    // #####
+    // # W #
+    // #####
+
+    /// ファイル単位で書きだすぜ☆（＾～＾）
+    pub fn write(&self, file_name: &str, app: &Application) {
+        if app.is_debug() {
+            app.comm
+                .println(&format!("#Write kif to '{}'...", file_name));
+        }
+
+        let path = Path::new(&file_name);
+
+        // ディレクトリー作成。
+        if let Some(parent) = path.parent() {
+            match fs::create_dir_all(parent) {
+                Ok(_x) => {}
+                Err(err) => panic!("{}", err),
+            }
+        } else {
+            panic!("Create directory fail. {}", file_name);
+        }
+
+        // 全文上書き☆（＾～＾）
+        let mut file_obj = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)
+            .unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)));
+
+        if let Err(e) = write!(file_obj, "{}", self.to_kif_text()) {
+            eprintln!("Couldn't write to file: {}", e);
+        }
     }
 }
@@ -1,3 +1,12 @@
+pub mod rpm_binary;
+pub mod rpm_deduplicator;
+pub mod rpm_migration;
+pub mod rpm_move;
 pub mod rpm_tape;
 pub mod rpm_tape_box;
+pub mod rpm_tape_box_index;
+pub mod rpm_tape_branch;
+pub mod rpm_tape_diff;
+pub mod rpm_tape_reader;
 pub mod rpm_tape_tracks;
+pub mod rpm_tape_validator;
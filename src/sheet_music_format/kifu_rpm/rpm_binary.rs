@@ -0,0 +1,499 @@
+use sheet_music_format::kifu_rpm::rpm_migration::*;
+use sheet_music_format::kifu_rpm::rpm_tape::*;
+use sheet_music_format::kifu_rpm::rpm_tape_tracks::RpmTapeTracks;
+use sheet_music_format::parse_error::*;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// テープ・ボックスのバイナリ形式。 JSON 形式と同じものを、小さく書く。
+///
+/// 数は全てリトル・エンディアン。
+///
+/// ```text
+/// ファイル = "RPMB" 版(u32) テープの数(u32) テープ*
+/// テープ   = 長さ(u32) ラベルの長さ(u32) ラベル(JSON) 注釈の長さ(u32) 注釈(JSON) ノートの数(u32) ノート* [枝の長さ(u32) 枝(JSON)]
+/// ノート   = 背番号(i8) 操作と向き(u8) [手目(u16)]
+/// ```
+///
+/// 枝は、分かれた筋が無ければ書かない。
+///
+/// 操作と向きの 1バイトは、最上位ビットが向き (1 なら 'L')、残りの 7ビットが操作。
+///
+/// | 操作 | 中身 |
+/// | --- | --- |
+/// | 0～80 | 盤上の升。 (筋 - 1) * 9 + (段 - 1)。 |
+/// | 81～112 | 駒台。 HAND_SIGNS の番号。 `0+B` のように + の付いたものは 16 を足す。 |
+/// | 113 | `+` |
+/// | 114 | `-` |
+/// | 115 | `\|` |
+/// | 116 | `[手目]`。後ろに手目を u16 で続ける。 |
+/// | 117 | `%resign` |
+pub struct RpmBinary {}
+impl RpmBinary {
+    pub const EXTENSION: &'static str = "rpmb";
+    const MAGIC: &'static [u8; 4] = b"RPMB";
+
+    const BOARD_LEN: u8 = 81;
+    const HAND_SIGNS: [&'static str; 16] = [
+        "K", "R", "B", "G", "S", "N", "L", "P", "k", "r", "b", "g", "s", "n", "l", "p",
+    ];
+    const OPE_TURN: u8 = 113;
+    const OPE_ROTATE: u8 = 114;
+    const OPE_PHASE: u8 = 115;
+    const OPE_PLY: u8 = 116;
+    const OPE_RESIGN: u8 = 117;
+    const FACING_LEFT: u8 = 0x80;
+
+    // #####
+    // # D #
+    // #####
+
+    /// ノート１つを読む。
+    ///
+    /// # Returns
+    ///
+    /// (背番号, 操作, 向き) のトラックの文字列。
+    fn decode_note(bytes: &[u8], pos: &mut usize) -> Result<(String, String, String), String> {
+        let id = RpmBinary::read_u8(bytes, pos)? as i8;
+        let code = RpmBinary::read_u8(bytes, pos)?;
+        let facing = if code & RpmBinary::FACING_LEFT == 0 {
+            "."
+        } else {
+            "L"
+        };
+
+        let ope = match code & !RpmBinary::FACING_LEFT {
+            cell if cell < RpmBinary::BOARD_LEN => format!("{}{}", cell / 9 + 1, cell % 9 + 1),
+            hand if hand < RpmBinary::OPE_TURN => {
+                let index = (hand - RpmBinary::BOARD_LEN) as usize;
+                format!(
+                    "0{}{}",
+                    if 16 <= index { "+" } else { "" },
+                    RpmBinary::HAND_SIGNS[index % 16]
+                )
+            }
+            RpmBinary::OPE_TURN => "+".to_string(),
+            RpmBinary::OPE_ROTATE => "-".to_string(),
+            RpmBinary::OPE_PHASE => "|".to_string(),
+            RpmBinary::OPE_PLY => format!("[{}]", RpmBinary::read_u16(bytes, pos)?),
+            RpmBinary::OPE_RESIGN => "%resign".to_string(),
+            unknown => {
+                return Err(format!(
+                    "Unexpected ope code {} at byte {}.",
+                    unknown,
+                    *pos - 1
+                ))
+            }
+        };
+
+        Ok((id.to_string(), ope, facing.to_string()))
+    }
+
+    /// テープ１本を読む。
+    pub fn decode_tape(bytes: &[u8]) -> Result<RpmTape, String> {
+        let mut pos = 0;
+
+        let label_len = RpmBinary::read_u32(bytes, &mut pos)? as usize;
+        let label = serde_json::from_slice(RpmBinary::read_bytes(bytes, &mut pos, label_len)?)
+            .map_err(|err| format!("Label: {}", err))?;
+
+        let annotation_len = RpmBinary::read_u32(bytes, &mut pos)? as usize;
+        let annotation = if annotation_len == 0 {
+            Vec::new()
+        } else {
+            serde_json::from_slice(RpmBinary::read_bytes(bytes, &mut pos, annotation_len)?)
+                .map_err(|err| format!("Annotation: {}", err))?
+        };
+
+        let notes = RpmBinary::read_u32(bytes, &mut pos)? as usize;
+        let mut id_vec = Vec::with_capacity(notes);
+        let mut ope_vec = Vec::with_capacity(notes);
+        let mut facing_vec = Vec::with_capacity(notes);
+        for _ in 0..notes {
+            let (id, ope, facing) = RpmBinary::decode_note(bytes, &mut pos)?;
+            id_vec.push(id);
+            ope_vec.push(ope);
+            facing_vec.push(facing);
+        }
+
+        let branch = if pos == bytes.len() {
+            Vec::new()
+        } else {
+            let branch_len = RpmBinary::read_u32(bytes, &mut pos)? as usize;
+            serde_json::from_slice(RpmBinary::read_bytes(bytes, &mut pos, branch_len)?)
+                .map_err(|err| format!("Branch: {}", err))?
+        };
+        if pos != bytes.len() {
+            return Err(format!("Tape has {} extra bytes.", bytes.len() - pos));
+        }
+
+        let mut tracks = RpmTapeTracks::new();
+        tracks.id = id_vec.join(" ");
+        tracks.ope = ope_vec.join(" ");
+        tracks.facing = facing_vec.join(" ");
+        tracks.annotation = annotation;
+        tracks.branch = branch;
+
+        Ok(RpmTape { label, tracks })
+    }
+
+    // #####
+    // # E #
+    // #####
+
+    /// ノート１つを書く。
+    fn encode_note(id: &str, ope: &str, facing: &str, out: &mut Vec<u8>) -> Result<(), String> {
+        let id: i8 = id
+            .parse()
+            .map_err(|_err| format!("Unexpected id note. '{}'.", id))?;
+
+        let facing = match facing {
+            "." => 0,
+            "L" => RpmBinary::FACING_LEFT,
+            _ => return Err(format!("Unexpected facing note. '{}'.", facing)),
+        };
+
+        let unexpected = || format!("Unexpected ope note. '{}'.", ope);
+        let chars: Vec<char> = ope.chars().collect();
+        let mut ply = None;
+        let code = match ope {
+            "+" => RpmBinary::OPE_TURN,
+            "-" => RpmBinary::OPE_ROTATE,
+            "|" => RpmBinary::OPE_PHASE,
+            "%resign" => RpmBinary::OPE_RESIGN,
+            _ if ope.starts_with('[') && ope.ends_with(']') => {
+                ply = Some(
+                    ope[1..ope.len() - 1]
+                        .parse::<u16>()
+                        .map_err(|_err| unexpected())?,
+                );
+                RpmBinary::OPE_PLY
+            }
+            _ if chars.len() == 2 && chars[0] != '0' => {
+                let file = chars[0].to_digit(10).ok_or_else(unexpected)?;
+                let rank = chars[1].to_digit(10).ok_or_else(unexpected)?;
+                if file == 0 || rank == 0 {
+                    return Err(unexpected());
+                }
+                ((file - 1) * 9 + (rank - 1)) as u8
+            }
+            _ if ope.starts_with('0') => {
+                let (promoted, sign) = match ope.strip_prefix("0+") {
+                    Some(sign) => (16, sign),
+                    None => (0, &ope[1..]),
+                };
+                let index = RpmBinary::HAND_SIGNS
+                    .iter()
+                    .position(|hand_sign| *hand_sign == sign)
+                    .ok_or_else(unexpected)?;
+                RpmBinary::BOARD_LEN + (promoted + index) as u8
+            }
+            _ => return Err(unexpected()),
+        };
+
+        out.push(id as u8);
+        out.push(code | facing);
+        if let Some(ply) = ply {
+            out.extend_from_slice(&ply.to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    /// テープ１本を書く。長さは付けない。
+    pub fn encode_tape(rtape: &RpmTape) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+
+        let label = serde_json::to_vec(&rtape.label).map_err(|err| err.to_string())?;
+        RpmBinary::write_u32(&mut out, label.len() as u32);
+        out.extend_from_slice(&label);
+
+        if rtape.tracks.annotation.is_empty() {
+            RpmBinary::write_u32(&mut out, 0);
+        } else {
+            let annotation =
+                serde_json::to_vec(&rtape.tracks.annotation).map_err(|err| err.to_string())?;
+            RpmBinary::write_u32(&mut out, annotation.len() as u32);
+            out.extend_from_slice(&annotation);
+        }
+
+        // 空っぽのテープは、トラックも空っぽ。
+        if rtape.tracks.ope.is_empty() {
+            RpmBinary::write_u32(&mut out, 0);
+        } else {
+            let id_vec: Vec<&str> = rtape.tracks.id.split(' ').collect();
+            let ope_vec: Vec<&str> = rtape.tracks.ope.split(' ').collect();
+            let facing_vec: Vec<&str> = rtape.tracks.facing.split(' ').collect();
+            if id_vec.len() != ope_vec.len() || facing_vec.len() != ope_vec.len() {
+                return Err(format!(
+                    "Track length differs. Id: {}, Ope: {}, Facing: {}.",
+                    id_vec.len(),
+                    ope_vec.len(),
+                    facing_vec.len()
+                ));
+            }
+
+            RpmBinary::write_u32(&mut out, ope_vec.len() as u32);
+            for (i, ope) in ope_vec.iter().enumerate() {
+                RpmBinary::encode_note(id_vec[i], ope, facing_vec[i], &mut out)
+                    .map_err(|message| format!("Caret: {}. {}", i, message))?;
+            }
+        }
+
+        // 枝は無ければ書かない。
+        if !rtape.tracks.branch.is_empty() {
+            let branch = serde_json::to_vec(&rtape.tracks.branch).map_err(|err| err.to_string())?;
+            RpmBinary::write_u32(&mut out, branch.len() as u32);
+            out.extend_from_slice(&branch);
+        }
+
+        Ok(out)
+    }
+
+    /// テープを全部、ファイルの中身にする。
+    pub fn encode_tape_box(tapes: &[RpmTape]) -> Result<Vec<u8>, ParseError> {
+        let mut out = Vec::new();
+        out.extend_from_slice(RpmBinary::MAGIC);
+        RpmBinary::write_u32(&mut out, RpmMigration::get_latest_version());
+        RpmBinary::write_u32(&mut out, tapes.len() as u32);
+
+        for (i, rtape) in tapes.iter().enumerate() {
+            let tape = RpmBinary::encode_tape(rtape)
+                .map_err(|message| ParseError::new("RPMB", &format!("Tape: {}. {}", i, message)))?;
+            RpmBinary::write_u32(&mut out, tape.len() as u32);
+            out.extend_from_slice(&tape);
+        }
+
+        Ok(out)
+    }
+
+    // #####
+    // # I #
+    // #####
+
+    /// 拡張子で、バイナリ形式か判定する。
+    pub fn is_binary_file(file: &str) -> bool {
+        Path::new(file)
+            .extension()
+            .is_some_and(|extension| extension == RpmBinary::EXTENSION)
+    }
+
+    // #####
+    // # R #
+    // #####
+
+    fn read_bytes<'a>(bytes: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], String> {
+        if bytes.len() < *pos + len {
+            return Err(format!("Unexpected end at byte {}.", bytes.len()));
+        }
+        let slice = &bytes[*pos..*pos + len];
+        *pos += len;
+        Ok(slice)
+    }
+
+    fn read_u8(bytes: &[u8], pos: &mut usize) -> Result<u8, String> {
+        Ok(RpmBinary::read_bytes(bytes, pos, 1)?[0])
+    }
+
+    fn read_u16(bytes: &[u8], pos: &mut usize) -> Result<u16, String> {
+        let slice = RpmBinary::read_bytes(bytes, pos, 2)?;
+        Ok(u16::from_le_bytes([slice[0], slice[1]]))
+    }
+
+    fn read_u32(bytes: &[u8], pos: &mut usize) -> Result<u32, String> {
+        let slice = RpmBinary::read_bytes(bytes, pos, 4)?;
+        Ok(u32::from_le_bytes([slice[0], slice[1], slice[2], slice[3]]))
+    }
+
+    // #####
+    // # W #
+    // #####
+
+    fn write_u32(out: &mut Vec<u8>, value: u32) {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// バイナリ形式のテープ・ボックスを、テープ１本ずつ読む。
+/// 開いた時は テープの長さだけ読んで飛ばし、テープの中身は読む時に読む。
+pub struct RpmBinaryReader {
+    file_name: String,
+    reader: BufReader<File>,
+    version: u32,
+    // テープの中身の、ファイルの先頭からの位置と長さ。
+    tapes: Vec<(u64, u32)>,
+}
+impl RpmBinaryReader {
+    /// ファイルを開いて、テープの位置を調べる。
+    pub fn open(file_name: &str) -> Result<Self, ParseError> {
+        let io_error = |err: std::io::Error| ParseError::from_io("RPMB", file_name, &err);
+        let error = |message: &str| ParseError::new("RPMB", message).with_file(file_name);
+
+        let mut reader = BufReader::new(File::open(file_name).map_err(io_error)?);
+
+        let mut header = [0; 12];
+        reader.read_exact(&mut header).map_err(io_error)?;
+        if &header[0..4] != RpmBinary::MAGIC {
+            return Err(error("Not a binary tape box."));
+        }
+        let mut pos = 4;
+        let version = RpmBinary::read_u32(&header, &mut pos).map_err(|message| error(&message))?;
+        if RpmMigration::get_latest_version() < version {
+            return Err(error(&format!(
+                "Tape box version {} is newer than supported version {}.",
+                version,
+                RpmMigration::get_latest_version()
+            )));
+        }
+        let count = RpmBinary::read_u32(&header, &mut pos).map_err(|message| error(&message))?;
+
+        let mut tapes = Vec::with_capacity(count as usize);
+        let mut offset = header.len() as u64;
+        for _ in 0..count {
+            let mut len = [0; 4];
+            reader.read_exact(&mut len).map_err(io_error)?;
+            let len = u32::from_le_bytes(len);
+            offset += 4;
+            tapes.push((offset, len));
+
+            offset += u64::from(len);
+            reader.seek(SeekFrom::Start(offset)).map_err(io_error)?;
+        }
+
+        Ok(RpmBinaryReader {
+            file_name: file_name.to_string(),
+            reader,
+            version,
+            tapes,
+        })
+    }
+
+    // #####
+    // # G #
+    // #####
+
+    pub fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    // #####
+    // # I #
+    // #####
+
+    pub fn is_empty(&self) -> bool {
+        self.tapes.is_empty()
+    }
+
+    // #####
+    // # L #
+    // #####
+
+    /// テープの本数。
+    pub fn len(&self) -> usize {
+        self.tapes.len()
+    }
+
+    // #####
+    // # R #
+    // #####
+
+    /// テープを１本読む。
+    pub fn read_tape(&mut self, index: usize) -> Result<RpmTape, ParseError> {
+        let file_name = &self.file_name;
+        let reader = &mut self.reader;
+        let (offset, len) = self.tapes[index];
+
+        let mut bytes = vec![0; len as usize];
+        reader
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| reader.read_exact(&mut bytes))
+            .map_err(|err| ParseError::from_io("RPMB", file_name, &err))?;
+
+        RpmBinary::decode_tape(&bytes).map_err(|message| {
+            ParseError::new("RPMB", &format!("Tape: {}. {}", index, message)).with_file(file_name)
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sheet_music_format::kifu_rpm::rpm_tape_box::RpmTapeBox;
+    use studio::application::Application;
+
+    /// ▲７六歩 を指して投了したテープ。注釈と枝も付ける。
+    fn sample_tape() -> RpmTape {
+        // 書いていないラベルの項目は、版 0 からの移行で埋める。
+        let tape = serde_json::json!({
+            "label": {"name": "sample", "player1": "羽生善治"},
+            "tracks": {
+                "id": "-1 23 23 -1 -1 -1",
+                "ope": "[1] 77 76 | 0+B %resign",
+                "facing": ". . . . L .",
+                "annotation": [{"ply": 1, "comments": ["初手。"], "consumed": 5}],
+                "branch": [{
+                    "fork": 1,
+                    "tracks": {"id": "23 23", "ope": "77 75", "facing": ". ."}
+                }]
+            }
+        });
+        let tape = RpmMigration::migrate_tape(tape, 0, "sample.json")
+            .unwrap_or_else(|err| panic!("{}", err));
+        serde_json::from_value(tape).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    #[test]
+    fn round_trips_tape() {
+        let tape = sample_tape();
+
+        let bytes = RpmBinary::encode_tape(&tape).unwrap_or_else(|err| panic!("{}", err));
+        let decoded = RpmBinary::decode_tape(&bytes).unwrap_or_else(|err| panic!("{}", err));
+
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap_or_default(),
+            serde_json::to_value(&tape).unwrap_or_default()
+        );
+    }
+
+    #[test]
+    fn round_trips_tape_box_file() {
+        let app = Application::new_for_test();
+        let mut rpm_tape_box = RpmTapeBox::new();
+        rpm_tape_box.push(sample_tape());
+        // 空っぽのテープも書ける。
+        rpm_tape_box.push(RpmTape::new());
+
+        let file = Application::write_test_file("rpm_binary/box.rpmb", b"");
+        rpm_tape_box.write(&file, &app);
+        let (read_box, version) =
+            RpmTapeBox::from_binary_file(&file).unwrap_or_else(|err| panic!("{}", err));
+
+        assert_eq!(version, RpmMigration::get_latest_version());
+        assert_eq!(
+            serde_json::to_value(&read_box).unwrap_or_default(),
+            serde_json::to_value(&rpm_tape_box).unwrap_or_default()
+        );
+
+        // 開いた時は テープの位置だけ調べる。
+        let mut reader = RpmBinaryReader::open(&file).unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(reader.len(), 2);
+        let tape = reader.read_tape(1).unwrap_or_else(|err| panic!("{}", err));
+        assert!(tape.tracks.ope.is_empty());
+    }
+
+    #[test]
+    fn reports_broken_notes() {
+        let mut tape = sample_tape();
+        tape.tracks.ope = "[1] 77 76 | 0+B 0X".to_string();
+        assert_eq!(
+            RpmBinary::encode_tape(&tape).err(),
+            Some("Caret: 5. Unexpected ope note. '0X'.".to_string())
+        );
+
+        // 最後の 1バイトが欠けている。
+        let mut bytes =
+            RpmBinary::encode_tape(&sample_tape()).unwrap_or_else(|err| panic!("{}", err));
+        bytes.pop();
+        assert!(RpmBinary::decode_tape(&bytes).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use sheet_music_format::kifu_rpm::rpm_tape::*;
+use std::collections::HashMap;
+
+/// 同じ対局のテープの集まり。
+pub struct DuplicateGroup {
+    // 残したテープの名前。ラベルを混ぜる前の名前。
+    pub kept: String,
+    // 混ぜて捨てたテープの名前。
+    pub merged: Vec<String>,
+}
+
+/// 同じ対局が KIF, CSA, KI2 など いくつもの棋譜から来ると、同じテープが重なる。
+/// 初期局面からの指し手の並びで テープを見分けて、重なったテープを１本にする。
+/// ラベルの違いは見ない。
+pub struct RpmDeduplicator {}
+impl RpmDeduplicator {
+    // #####
+    // # D #
+    // #####
+
+    /// 重なったテープを１本にする。順序は、最初に出てきた順。
+    ///
+    /// ラベルは全部混ぜる。トラックは 注釈の一番多いテープのものを残す。
+    ///
+    /// # Returns
+    ///
+    /// (重なりの無いテープ, ２本以上重なっていた集まり)
+    pub fn deduplicate(tapes: Vec<RpmTape>) -> (Vec<RpmTape>, Vec<DuplicateGroup>) {
+        let mut unique: Vec<RpmTape> = Vec::new();
+        let mut groups: Vec<DuplicateGroup> = Vec::new();
+        // 指紋 → unique と groups の番号。
+        let mut index_of: HashMap<String, usize> = HashMap::new();
+
+        for tape in tapes {
+            let fingerprint = RpmDeduplicator::get_fingerprint(&tape);
+            if let Some(&index) = index_of.get(&fingerprint) {
+                groups[index].merged.push(tape.label.get_name());
+
+                // テープ名は、最初のテープのものを残す。
+                let kept = &mut unique[index];
+                kept.label.merge(&tape.label);
+                if kept.tracks.annotation.len() < tape.tracks.annotation.len() {
+                    kept.tracks = tape.tracks;
+                }
+            } else {
+                index_of.insert(fingerprint, unique.len());
+                groups.push(DuplicateGroup {
+                    kept: tape.label.get_name(),
+                    merged: Vec::new(),
+                });
+                unique.push(tape);
+            }
+        }
+
+        let groups = groups
+            .into_iter()
+            .filter(|group| !group.merged.is_empty())
+            .collect();
+        (unique, groups)
+    }
+
+    // #####
+    // # G #
+    // #####
+
+    /// テープの指紋。初期局面の並べ方と、指し手の操作の並び。
+    ///
+    /// 背番号と向きは見ない。投了は、書いてある棋譜と書いてない棋譜があるので見ない。
+    pub fn get_fingerprint(tape: &RpmTape) -> String {
+        let notes: Vec<&str> = tape.tracks.ope.split(' ').collect();
+
+        let mut fingerprint: Vec<&str> = Vec::with_capacity(notes.len());
+        let mut i = 0;
+        while i < notes.len() {
+            // `[N] %resign [N]` の１手を飛ばす。
+            if notes[i].starts_with('[')
+                && notes.get(i + 1) == Some(&"%resign")
+                && notes.get(i + 2) == Some(&notes[i])
+            {
+                i += 3;
+                continue;
+            }
+            if notes[i] != "%resign" {
+                fingerprint.push(notes[i]);
+            }
+            i += 1;
+        }
+
+        fingerprint.join(" ")
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sheet_music_format::kifu_rpm::rpm_migration::RpmMigration;
+
+    /// 背番号と向きは ope に合わせて適当に埋めたテープ。
+    fn tape(label: serde_json::Value, ope: &str, annotations: usize) -> RpmTape {
+        let notes = ope.split(' ').count();
+        let annotation: Vec<serde_json::Value> = (0..annotations)
+            .map(|ply| serde_json::json!({"ply": ply + 1, "comments": ["注釈。"], "consumed": 1}))
+            .collect();
+        let tape = serde_json::json!({
+            "label": label,
+            "tracks": {
+                "id": vec!["-1"; notes].join(" "),
+                "ope": ope,
+                "facing": vec!["."; notes].join(" "),
+                "annotation": annotation,
+            }
+        });
+        let tape = RpmMigration::migrate_tape(tape, 0, "dedup.json")
+            .unwrap_or_else(|err| panic!("{}", err));
+        serde_json::from_value(tape).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    #[test]
+    fn ignores_resign_in_fingerprint() {
+        let with_resign = tape(serde_json::json!({}), "[1] 77 76 [1] [2] %resign [2]", 0);
+        let bare_resign = tape(serde_json::json!({}), "[1] 77 76 [1] %resign", 0);
+        let without_resign = tape(serde_json::json!({}), "[1] 77 76 [1]", 0);
+
+        let fingerprint = RpmDeduplicator::get_fingerprint(&without_resign);
+        assert_eq!(fingerprint, "[1] 77 76 [1]");
+        assert_eq!(RpmDeduplicator::get_fingerprint(&with_resign), fingerprint);
+        assert_eq!(RpmDeduplicator::get_fingerprint(&bare_resign), fingerprint);
+    }
+
+    #[test]
+    fn merges_duplicates_and_keeps_most_annotated_tracks() {
+        let kif = tape(
+            serde_json::json!({"name": "kif", "player1": "羽生", "event": "竜王戦"}),
+            "[1] 77 76 [1]",
+            0,
+        );
+        let other = tape(serde_json::json!({"name": "other"}), "[1] 27 26 [1]", 0);
+        let csa = tape(
+            serde_json::json!({"name": "csa", "player1": "羽生善治", "place": "東京"}),
+            "[1] 77 76 [1] [2] %resign [2]",
+            2,
+        );
+
+        let (unique, groups) = RpmDeduplicator::deduplicate(vec![kif, other, csa]);
+
+        // 最初に出てきた順。
+        assert_eq!(unique.len(), 2);
+        assert_eq!(unique[0].label.get_name(), "kif");
+        assert_eq!(unique[1].label.get_name(), "other");
+
+        // ラベルは混ぜて、長い方を残す。テープ名は残したテープのもの。
+        assert_eq!(unique[0].label.get_player1(), "羽生善治");
+        assert_eq!(unique[0].label.get_event(), "竜王戦");
+        assert_eq!(unique[0].label.get_place(), "東京");
+        // トラックは 注釈の多い方。
+        assert_eq!(unique[0].tracks.annotation.len(), 2);
+        assert_eq!(unique[0].tracks.ope, "[1] 77 76 [1] [2] %resign [2]");
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].kept, "kif");
+        assert_eq!(groups[0].merged, vec!["csa".to_string()]);
+    }
+}
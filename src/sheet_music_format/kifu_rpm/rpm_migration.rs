@@ -0,0 +1,262 @@
+use serde_json::{Map, Value};
+use sheet_music_format::parse_error::*;
+
+/// テープ・ボックスの版を、読み込む時に最新へ上げる。
+/// 版の無いテープ・ボックスは 版 0。
+///
+/// 書式を変える時は、MIGRATIONS の後ろに移行を１つ足す。
+/// 最新の版は MIGRATIONS の数 (`RpmMigration::get_latest_version()`) なので、書き出す版も それで１つ上がる。
+/// 古い版のテープ・ボックスは、１版ずつ順に上げていく。
+pub struct RpmMigration {}
+impl RpmMigration {
+    /// MIGRATIONS[n] は、版 n を 版 n+1 にする。
+    const MIGRATIONS: [fn(&mut Map<String, Value>); 3] = [
+        RpmMigration::migrate_0_to_1,
+        RpmMigration::migrate_1_to_2,
+        RpmMigration::migrate_2_to_3,
+    ];
+
+    /// 版 1 で、空っぽでも書き出すようになったテープ・ラベルの項目。
+    const LABEL_KEYS_1: [&'static str; 15] = [
+        "name",
+        "game_date",
+        "start_time",
+        "end_time",
+        "place",
+        "event",
+        "player1",
+        "player2",
+        "time_system",
+        "handicap",
+        "battle_type",
+        "format",
+        "branch_ply",
+        "branch_from",
+        "archive",
+    ];
+
+    /// 版 1 のトラック。注釈は省略できる。
+    const TRACK_KEYS_1: [&'static str; 3] = ["id", "ope", "facing"];
+
+    // #####
+    // # F #
+    // #####
+
+    /// 欠けている項目を 空っぽの文字列で埋める。
+    fn fill_keys(tape: &mut Map<String, Value>, name: &str, keys: &[&str]) {
+        let child = tape
+            .entry(name)
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(ref mut child) = *child {
+            for key in keys {
+                child
+                    .entry(*key)
+                    .or_insert_with(|| Value::String("".to_string()));
+            }
+        }
+    }
+
+    /// テープ・ボックスのテープを全部、手を加える。
+    fn for_each_tape(object: &mut Map<String, Value>, f: fn(&mut Map<String, Value>)) {
+        let tapes = object
+            .entry("tape_box")
+            .or_insert_with(|| Value::Array(Vec::new()));
+        if let Value::Array(ref mut tapes) = *tapes {
+            for tape in tapes.iter_mut() {
+                if let Value::Object(ref mut tape) = *tape {
+                    f(tape);
+                }
+            }
+        }
+    }
+
+    // #####
+    // # G #
+    // #####
+
+    /// 最新の版。
+    pub fn get_latest_version() -> u32 {
+        RpmMigration::MIGRATIONS.len() as u32
+    }
+
+    /// テープ・ボックスの JSON に書かれた版。無ければ 0。
+    pub fn get_version(value: &Value) -> u32 {
+        value
+            .get("version")
+            .and_then(Value::as_u64)
+            .map_or(0, |version| version as u32)
+    }
+
+    // #####
+    // # M #
+    // #####
+
+    /// テープ・ボックスの JSON を、最新の版に上げる。
+    ///
+    /// # Returns
+    ///
+    /// 上げる前の版。最新より新しい版は読めないのでエラー。
+    pub fn migrate(value: &mut Value, box_file: &str) -> Result<u32, ParseError> {
+        let version = RpmMigration::get_version(value);
+        if RpmMigration::get_latest_version() < version {
+            return Err(ParseError::new(
+                "RPM",
+                &format!(
+                    "Tape box version {} is newer than supported version {}.",
+                    version,
+                    RpmMigration::get_latest_version()
+                ),
+            )
+            .with_file(box_file));
+        }
+
+        let object = value.as_object_mut().ok_or_else(|| {
+            ParseError::new("RPM", "Tape box is not an object.").with_file(box_file)
+        })?;
+        // 同じフォルダーに置いた 索引などの JSON を、空っぽのテープ・ボックスにして潰さない。
+        if !object.contains_key("tape_box") {
+            return Err(
+                ParseError::new("RPM", "Not a tape box. 'tape_box' not found.").with_file(box_file),
+            );
+        }
+        for migration in RpmMigration::MIGRATIONS.iter().skip(version as usize) {
+            migration(object);
+        }
+        object.insert(
+            "version".to_string(),
+            Value::from(RpmMigration::get_latest_version()),
+        );
+
+        Ok(version)
+    }
+
+    /// テープ１本の JSON を、最新の版に上げる。テープ・ボックスを テープ１本ずつ読む時に使う。
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - テープ・ボックスに書かれていた版。
+    pub fn migrate_tape(tape: Value, version: u32, box_file: &str) -> Result<Value, ParseError> {
+        let mut object = Map::new();
+        object.insert("version".to_string(), Value::from(version));
+        object.insert("tape_box".to_string(), Value::Array(vec![tape]));
+
+        let mut value = Value::Object(object);
+        RpmMigration::migrate(&mut value, box_file)?;
+        Ok(value
+            .get_mut("tape_box")
+            .and_then(|tapes| tapes.get_mut(0))
+            .map_or(Value::Null, Value::take))
+    }
+
+    /// 版 0 → 版 1。
+    /// 版 0 のテープは、ラベルの項目や トラックが欠けていることがある。空っぽで埋める。
+    fn migrate_0_to_1(object: &mut Map<String, Value>) {
+        RpmMigration::for_each_tape(object, |tape| {
+            RpmMigration::fill_keys(tape, "label", &RpmMigration::LABEL_KEYS_1);
+            RpmMigration::fill_keys(tape, "tracks", &RpmMigration::TRACK_KEYS_1);
+        });
+    }
+
+    /// 版 1 → 版 2。
+    /// 版 2 で、テープ・ラベルに 途中で止まった対局の印 `incomplete` を足した。版 1 以前のテープは全部 最後まで指してある。
+    fn migrate_1_to_2(object: &mut Map<String, Value>) {
+        RpmMigration::for_each_tape(object, |tape| {
+            if let Some(Value::Object(ref mut label)) = tape.get_mut("label") {
+                label.entry("incomplete").or_insert(Value::Bool(false));
+            }
+        });
+    }
+
+    /// 版 2 → 版 3。
+    /// 版 3 で、トラックに 分かれた筋 `branch` を足した。枝は省略できるので、手を加えるところは無い。
+    fn migrate_2_to_3(_object: &mut Map<String, Value>) {}
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_version_0_to_latest() {
+        let mut value = serde_json::json!({
+            "tape_box": [
+                {
+                    "label": {"name": "old"},
+                    "tracks": {"ope": "1 2 3"}
+                }
+            ]
+        });
+
+        let version =
+            RpmMigration::migrate(&mut value, "old.json").unwrap_or_else(|err| panic!("{}", err));
+
+        assert_eq!(version, 0);
+        assert_eq!(
+            RpmMigration::get_version(&value),
+            RpmMigration::get_latest_version()
+        );
+        let tape = &value["tape_box"][0];
+        // 書いてあった項目は そのまま。
+        assert_eq!(tape["label"]["name"], "old");
+        assert_eq!(tape["tracks"]["ope"], "1 2 3");
+        // 欠けている項目は 埋める。
+        assert_eq!(tape["label"]["player1"], "");
+        assert_eq!(tape["label"]["incomplete"], false);
+        assert_eq!(tape["tracks"]["id"], "");
+    }
+
+    #[test]
+    fn keeps_incomplete_of_version_2() {
+        let mut value = serde_json::json!({
+            "version": 2,
+            "tape_box": [{"label": {"incomplete": true}, "tracks": {}}]
+        });
+
+        let version =
+            RpmMigration::migrate(&mut value, "v2.json").unwrap_or_else(|err| panic!("{}", err));
+
+        assert_eq!(version, 2);
+        assert_eq!(value["tape_box"][0]["label"]["incomplete"], true);
+    }
+
+    #[test]
+    fn rejects_newer_version() {
+        let mut value = serde_json::json!({
+            "version": RpmMigration::get_latest_version() + 1,
+            "tape_box": []
+        });
+
+        let err = match RpmMigration::migrate(&mut value, "new.json") {
+            Ok(_version) => panic!("Unexpected success."),
+            Err(err) => err,
+        };
+        assert_eq!(err.file, "new.json");
+        assert!(err.message.contains("newer than supported version"));
+    }
+
+    #[test]
+    fn migrates_one_tape() {
+        let tape = serde_json::json!({"label": {"name": "one"}, "tracks": {}});
+
+        let tape =
+            RpmMigration::migrate_tape(tape, 0, "box.json").unwrap_or_else(|err| panic!("{}", err));
+
+        assert_eq!(tape["label"]["name"], "one");
+        assert_eq!(tape["label"]["incomplete"], false);
+    }
+
+    #[test]
+    fn rejects_json_without_tape_box() {
+        let mut value = serde_json::json!({"boxes": [{"file": "a-tape-box.json"}]});
+
+        let err = match RpmMigration::migrate(&mut value, "learning-index.json") {
+            Ok(_version) => panic!("Unexpected success."),
+            Err(err) => err,
+        };
+        assert!(err.message.contains("'tape_box' not found"));
+        // 書き換えない。
+        assert_eq!(
+            value,
+            serde_json::json!({"boxes": [{"file": "a-tape-box.json"}]})
+        );
+    }
+}
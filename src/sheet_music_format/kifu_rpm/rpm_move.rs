@@ -0,0 +1,195 @@
+use instrument::half_player_phase::*;
+use instrument::piece_etc::*;
+use sheet_music_format::kifu_rpm::rpm_tape::*;
+use sound::shogi_note::ShogiNote;
+use std::collections::HashMap;
+use studio::address::*;
+use studio::application::Application;
+use studio::board_size::*;
+
+/// RPMテープから読み取った、棋譜の指し手１つ分。
+///
+/// 局面は使わずに、ノートの並びと背番号だけから復元する☆（＾～＾）
+/// 大橋流（Ply が 0 以下のフェーズ・チェンジで挟まれた部分）は指し手に含めない。
+#[derive(Clone, Copy, PartialEq)]
+pub struct RpmMove {
+    /// 何手目か。1 から始まる。
+    pub ply: i16,
+    /// 奇数手は先手、偶数手は後手とする。
+    pub phase: HalfPlayerPhaseValue,
+    /// 打つときは None。
+    pub source: Option<Cell>,
+    /// 投了のときは None。
+    pub destination: Option<Cell>,
+    /// 動かす前の駒の種類。
+    pub piece_type: Option<PieceType>,
+    /// 成ったか。
+    pub promotion: bool,
+    /// 打ったか。
+    pub drop: bool,
+    /// 取った駒の、取られる前の種類。
+    pub capture: Option<PieceType>,
+    /// 投了か。
+    pub resign: bool,
+}
+impl RpmMove {
+    fn new(ply: i16) -> Self {
+        RpmMove {
+            ply,
+            phase: if ply % 2 == 0 {
+                HalfPlayerPhaseValue::Second
+            } else {
+                HalfPlayerPhaseValue::First
+            },
+            source: None,
+            destination: None,
+            piece_type: None,
+            promotion: false,
+            drop: false,
+            capture: None,
+            resign: false,
+        }
+    }
+
+    /// テープ１本分の指し手を読み取る。
+    pub fn parse_rpm_tape(
+        rtape: &RpmTape,
+        board_size: BoardSize,
+        app: &Application,
+    ) -> Vec<RpmMove> {
+        if rtape.tracks.ope.is_empty() {
+            return Vec::new();
+        }
+
+        RpmMove::parse_notes(&rtape.tracks.to_positive_vec(board_size, app), board_size)
+    }
+
+    /// フェーズ・チェンジで挟まれたノートの塊を、１手ずつ読み取る。
+    pub fn parse_notes(notes: &[ShogiNote], board_size: BoardSize) -> Vec<RpmMove> {
+        let mut moves = Vec::new();
+
+        // 背番号ごとの、成っているかどうか。
+        let mut promoted_map: HashMap<i8, bool> = HashMap::new();
+
+        let mut body: Vec<ShogiNote> = Vec::new();
+        let mut opened_ply: Option<i16> = None;
+
+        for note in notes {
+            if let Some(ply) = note.get_ope().get_phase_change() {
+                if let Some(open) = opened_ply {
+                    // 閉じるフェーズ・チェンジ。
+                    if 0 < open {
+                        if let Some(rmove) =
+                            RpmMove::parse_body(open, &body, &mut promoted_map, board_size)
+                        {
+                            moves.push(rmove);
+                        }
+                    }
+                    body.clear();
+                    opened_ply = None;
+                } else {
+                    // 開くフェーズ・チェンジ。
+                    opened_ply = Some(ply);
+                }
+            } else if opened_ply.is_some() {
+                body.push(*note);
+            }
+        }
+
+        moves
+    }
+
+    /// フェーズ・チェンジに挟まれた中身を読む。
+    ///
+    /// * 打つ: 駒台, 盤上。
+    /// * 取る: 盤上(取られる駒), [+], -, 駒台, 盤上(動かす駒), [+], 盤上。
+    /// * 進む: 盤上(動かす駒), [+], 盤上。
+    fn parse_body(
+        ply: i16,
+        body: &[ShogiNote],
+        promoted_map: &mut HashMap<i8, bool>,
+        board_size: BoardSize,
+    ) -> Option<RpmMove> {
+        let mut rmove = RpmMove::new(ply);
+
+        if body.iter().any(|note| note.get_ope().is_resign()) {
+            rmove.resign = true;
+            return Some(rmove);
+        }
+
+        let first = body.first()?;
+        let first_address = first.get_ope().address?;
+
+        if let Some(piece) = first_address.get_hand_piece() {
+            // 打つ。
+            let destination = body.get(1)?.get_ope().address?;
+            rmove.drop = true;
+            rmove.piece_type = Some(PieceType::from_piece(piece));
+            rmove.destination = destination.to_cell(board_size);
+            if let Some(pid) = first.get_id() {
+                promoted_map.insert(pid.get_number(), false);
+            }
+            return Some(rmove);
+        }
+
+        let mut index = 0;
+        if body.iter().any(|note| note.get_ope().fingertip_rotate) {
+            // 取る。取られた駒は、駒台に置かれるまで読み飛ばす。
+            if let Some(pid) = first.get_id() {
+                let captured_promoted = *promoted_map.get(&pid.get_number()).unwrap_or(&false);
+                rmove.capture = Some(promote_piece_type(pid.get_piece_type(), captured_promoted));
+                promoted_map.insert(pid.get_number(), false);
+            }
+
+            while index < body.len() {
+                let ope = body[index].get_ope();
+                index += 1;
+                if let Some(address) = ope.address {
+                    if address.is_hand() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        // 進む。
+        let source_note = body.get(index)?;
+        rmove.source = source_note.get_ope().address?.to_cell(board_size);
+        if let Some(pid) = source_note.get_id() {
+            let promoted = *promoted_map.get(&pid.get_number()).unwrap_or(&false);
+            rmove.piece_type = Some(promote_piece_type(pid.get_piece_type(), promoted));
+
+            for note in &body[index + 1..] {
+                if note.get_ope().fingertip_turn {
+                    rmove.promotion = !rmove.promotion;
+                } else if let Some(address) = note.get_ope().address {
+                    rmove.destination = address.to_cell(board_size);
+                }
+            }
+
+            if rmove.promotion {
+                promoted_map.insert(pid.get_number(), !promoted);
+            }
+        }
+
+        Some(rmove)
+    }
+}
+
+/// 成っていれば、成り駒の種類にする。
+pub fn promote_piece_type(piece_type: PieceType, promoted: bool) -> PieceType {
+    if !promoted {
+        return piece_type;
+    }
+
+    use instrument::piece_etc::PieceType::*;
+    match piece_type {
+        R => PR,
+        B => PB,
+        S => PS,
+        N => PN,
+        L => PL,
+        P => PP,
+        _ => piece_type,
+    }
+}
@@ -37,11 +37,39 @@ impl RpmTape {
                 &app,
             ),
             caret: Caret::new_facing_right_caret(),
-            label: TapeLabel::new(),
+            label: self.label.clone(),
             tracks: TwoHeadsVec::from_vector(
                 self.tracks.to_positive_vec(board_size, &app),
                 Vec::new(),
             ),
+            annotations: self.tracks.annotation.clone(),
+            branches: self
+                .tracks
+                .branch
+                .iter()
+                .map(|branch| branch.to_object(board_size, app))
+                .collect(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// テープに直しても、ラベルは残る。トレーニング・テープの絞り込みに使う。
+    #[test]
+    fn keeps_label_in_object() {
+        let app = Application::new_for_test();
+        let mut tape = RpmTape::new();
+        tape.label.set_name("sample");
+        tape.label.set_handicap("平手");
+        tape.tracks.id = "-1 23 23 -1".to_string();
+        tape.tracks.ope = "[1] 77 76 [1]".to_string();
+        tape.tracks.facing = ". . . .".to_string();
+
+        let object = tape.to_object(BoardSize::create_hon_shogi(), &app);
+        assert_eq!(object.label.get_name(), "sample");
+        assert_eq!(object.label.get_handicap(), "平手");
+    }
+}
@@ -2,7 +2,12 @@ extern crate rand;
 use conf::kifuwarabe_wcsc29_master_config::KifuwarabeWcsc29MasterConfig;
 use rand::Rng;
 use serde::*;
+use serde_json::Value;
+use sheet_music_format::kifu_rpm::rpm_binary::*;
+use sheet_music_format::kifu_rpm::rpm_migration::*;
 use sheet_music_format::kifu_rpm::rpm_tape::*;
+use sheet_music_format::parse_error::*;
+use sheet_music_format::tape_label::TapeLabel;
 use std::fs;
 use std::fs::File;
 use std::fs::OpenOptions;
@@ -11,14 +16,19 @@ use std::path::Path;
 use studio::application::Application;
 
 /// テープ・ボックス json ファイルに対応。
+/// 拡張子が .rpmb なら、中身は RpmBinary のバイナリ形式。
 #[derive(Debug, Deserialize, Default, Serialize)]
 #[serde(rename_all = "snake_case")] // プロパティ名が JSON 側でスネークケースであることを指定
 pub struct RpmTapeBox {
+    // 書式の版。古い版は、読み込む時に RpmMigration で最新に上げる。
+    #[serde(default)]
+    pub version: u32,
     pub tape_box: Vec<RpmTape>,
 }
 impl RpmTapeBox {
     pub fn new() -> Self {
         RpmTapeBox {
+            version: RpmMigration::get_latest_version(),
             tape_box: Vec::new(),
         }
     }
@@ -28,29 +38,70 @@ impl RpmTapeBox {
     /// # Arguments
     ///
     /// * `box_file` - ファイル名。存在しないファイルの場合、新規作成。
-    pub fn from_box_file(box_file: &str, app: &Application) -> Self {
+    ///
+    /// # Returns
+    ///
+    /// 読めなければ、serde の教えてくれる行と文字の位置を付けたエラー。
+    pub fn from_box_file(box_file: &str, app: &Application) -> Result<Self, ParseError> {
+        RpmTapeBox::from_box_file_with_version(box_file, app).map(|(tape_box, _version)| tape_box)
+    }
+
+    /// バイナリ・ファイル読み取り。テープを全部読む。
+    ///
+    /// # Returns
+    ///
+    /// (テープ・ボックス, ファイルに書かれていた版)
+    pub fn from_binary_file(box_file: &str) -> Result<(Self, u32), ParseError> {
+        let mut reader = RpmBinaryReader::open(box_file)?;
+
+        let mut rpm_tape_box = RpmTapeBox::new();
+        for index in 0..reader.len() {
+            rpm_tape_box.push(reader.read_tape(index)?);
+        }
+        Ok((rpm_tape_box, reader.get_version()))
+    }
+
+    /// JSONファイル読み取り。古い版なら、最新の版に上げる。
+    /// 拡張子が .rpmb なら、バイナリ・ファイルとして読む。
+    ///
+    /// # Returns
+    ///
+    /// (最新の版のテープ・ボックス, ファイルに書かれていた版)
+    pub fn from_box_file_with_version(
+        box_file: &str,
+        app: &Application,
+    ) -> Result<(Self, u32), ParseError> {
         if app.is_debug() {
             app.comm.println(&format!("Box file name: '{}'.", box_file));
         }
 
         let path = Path::new(box_file);
+        if RpmBinary::is_binary_file(box_file) && path.exists() {
+            return RpmTapeBox::from_binary_file(box_file);
+        }
         match File::open(path) {
             Ok(mut file) => {
                 let mut contents = String::new();
-                match file.read_to_string(&mut contents) {
-                    Ok(x) => x,
-                    Err(err) => panic!("File open error. {:?}", err),
-                };
+                file.read_to_string(&mut contents)
+                    .map_err(|err| ParseError::from_io("RPM", box_file, &err))?;
 
                 // TODO 空っぽのファイルを読み込んでしまって、JSONのパースエラーになってしまうことがある☆（＾～＾）
                 // app.comm.println(&format!("Contents: '{}'.", contents));
 
-                match serde_json::from_str(&contents) {
-                    Ok(x) => x,
-                    Err(err) => {
-                        panic!(err);
+                let mut value: Value = serde_json::from_str(&contents)
+                    .map_err(|err| ParseError::from_json("RPM", box_file, &contents, &err))?;
+                let version = RpmMigration::migrate(&mut value, box_file)?;
+
+                // 読めなければ、元の文字列を読み直して 行と文字の位置を付ける。
+                let tape_box = serde_json::from_value(value).map_err(|err| {
+                    match serde_json::from_str::<RpmTapeBox>(&contents) {
+                        Err(err) => ParseError::from_json("RPM", box_file, &contents, &err),
+                        Ok(_tape_box) => {
+                            ParseError::new("RPM", &err.to_string()).with_file(box_file)
+                        }
                     }
-                }
+                })?;
+                Ok((tape_box, version))
             }
             Err(_err) => {
                 // 存在しないファイルの場合。
@@ -58,7 +109,8 @@ impl RpmTapeBox {
                 let rpm_tape_box = RpmTapeBox::new();
                 rpm_tape_box.write(box_file, &app);
 
-                rpm_tape_box
+                let version = rpm_tape_box.version;
+                Ok((rpm_tape_box, version))
             }
         }
     }
@@ -67,11 +119,73 @@ impl RpmTapeBox {
         self.tape_box.push(tape);
     }
 
+    /// 条件に合うテープだけを、新しい箱に移す。ラベルも トラックも そのまま。
+    pub fn filter<P>(self, predicate: P) -> RpmTapeBox
+    where
+        P: Fn(&RpmTape) -> bool,
+    {
+        let mut rpm_tape_box = RpmTapeBox::new();
+        rpm_tape_box.tape_box = self.tape_box.into_iter().filter(predicate).collect();
+        rpm_tape_box
+    }
+
+    /// いくつかの箱のテープを、１箱にまとめる。テープは 箱の順、箱の中の順に並べる。
+    pub fn merge(boxes: Vec<RpmTapeBox>) -> RpmTapeBox {
+        let mut rpm_tape_box = RpmTapeBox::new();
+        for other in boxes {
+            rpm_tape_box.tape_box.extend(other.tape_box);
+        }
+        rpm_tape_box
+    }
+
+    /// テープの本数で分ける。最後の箱だけは 少ないことがある。
+    pub fn split_by_count(self, size: usize) -> Vec<RpmTapeBox> {
+        let mut boxes = Vec::new();
+        let mut tapes = self.tape_box.into_iter();
+        loop {
+            let mut rpm_tape_box = RpmTapeBox::new();
+            rpm_tape_box.tape_box = tapes.by_ref().take(size.max(1)).collect();
+            if rpm_tape_box.tape_box.is_empty() {
+                break;
+            }
+            boxes.push(rpm_tape_box);
+        }
+        boxes
+    }
+
+    /// テープ・ラベルの項目の値で分ける。箱は 値が初めて出てきた順。
+    ///
+    /// # Returns
+    ///
+    /// (値, 箱) の並び。知らない項目名なら None。
+    pub fn split_by_label(self, key: &str) -> Option<Vec<(String, RpmTapeBox)>> {
+        TapeLabel::new().get_value(key)?;
+
+        let mut boxes: Vec<(String, RpmTapeBox)> = Vec::new();
+        for tape in self.tape_box {
+            let value = tape.label.get_value(key).unwrap_or_default();
+            match boxes.iter_mut().find(|(other, _box)| *other == value) {
+                Some((_value, rpm_tape_box)) => rpm_tape_box.push(tape),
+                None => {
+                    let mut rpm_tape_box = RpmTapeBox::new();
+                    rpm_tape_box.push(tape);
+                    boxes.push((value, rpm_tape_box));
+                }
+            }
+        }
+        Some(boxes)
+    }
+
     /// ランダムにファイル名を付けるぜ☆（*＾～＾*）
     pub fn create_file_full_name(
         kw29_conf: &KifuwarabeWcsc29MasterConfig,
         app: &Application,
     ) -> String {
+        RpmTapeBox::create_file_full_name_in(Path::new(&kw29_conf.learning), app)
+    }
+
+    /// 指定のディレクトリーに、ランダムにファイル名を付けるぜ☆（*＾～＾*）
+    pub fn create_file_full_name_in(directory: &Path, app: &Application) -> String {
         let mut rng = rand::thread_rng();
         let rand1: u64 = rng.gen();
         let rand2: u64 = rng.gen();
@@ -79,10 +193,10 @@ impl RpmTapeBox {
         let rand4: u64 = rng.gen();
         let file = format!("{}-{}-{}-{}-tape-box.json", rand1, rand2, rand3, rand4).to_string();
 
-        Path::new(&kw29_conf.learning)
+        directory
             .join(file)
             .to_str()
-            .unwrap_or_else(|| panic!(app.comm.panic("Fail. conf.learning.")))
+            .unwrap_or_else(|| panic!("{}", app.comm.panic("Fail. tape box directory.")))
             .to_string()
     }
 
@@ -105,6 +219,7 @@ impl RpmTapeBox {
     }
 
     /// テープ・ボックス単位で書きだすぜ☆（＾～＾）
+    /// 拡張子が .rpmb なら、バイナリ形式で書く。
     pub fn write(&self, file_name: &str, app: &Application) {
         if app.is_debug() {
             app.comm
@@ -123,20 +238,102 @@ impl RpmTapeBox {
             panic!("Create directory fail. {}", file_name);
         }
 
-        // 全文上書き☆（＾～＾）
-        let mut file_obj = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(path)
-            .unwrap_or_else(|err| panic!(app.comm.panic_io(&err)));
+        let contents = if RpmBinary::is_binary_file(file_name) {
+            RpmBinary::encode_tape_box(&self.tape_box)
+                .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())))
+        } else {
+            let json_text = serde_json::to_string(self)
+                .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
+            format!("{}\n", json_text).into_bytes()
+        };
 
-        let json_text =
-            serde_json::to_string(self).unwrap_or_else(|f| panic!(app.comm.panic(&f.to_string())));
+        // 全文上書き☆（＾～＾）書いている途中で止まっても 元の箱が残るように、
+        // 一時ファイルに書き切ってから 名前を付け替える。
+        let temp_file_name = format!("{}.tmp", file_name);
+        {
+            let mut file_obj = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&temp_file_name)
+                .unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)));
 
-        if let Err(e) = writeln!(file_obj, "{}", json_text) {
-            eprintln!("Couldn't write to file: {}", e);
+            file_obj
+                .write_all(&contents)
+                .and_then(|_| file_obj.sync_all())
+                .unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)));
         }
+        // 開いたままだと 名前を付け替えられない OS もあるので、閉じてから。
+        fs::rename(&temp_file_name, path)
+            .unwrap_or_else(|err| panic!("{}", app.comm.panic_io(&err)));
 
         // comm.println("#Sheet saved.");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// テープ名と 手合割だけ付けたテープを並べた箱。
+    fn tape_box(labels: &[(&str, &str)]) -> RpmTapeBox {
+        let mut rpm_tape_box = RpmTapeBox::new();
+        for (name, handicap) in labels {
+            let mut tape = RpmTape::new();
+            tape.label.set_name(name);
+            tape.label.set_handicap(handicap);
+            rpm_tape_box.push(tape);
+        }
+        rpm_tape_box
+    }
+
+    fn names(rpm_tape_box: &RpmTapeBox) -> Vec<String> {
+        rpm_tape_box
+            .tape_box
+            .iter()
+            .map(|tape| tape.label.get_name())
+            .collect()
+    }
+
+    /// 最後の箱だけが少なく、テープの順は変わらない。
+    #[test]
+    fn splits_by_count_keeping_order() {
+        let boxes =
+            tape_box(&[("a", ""), ("b", ""), ("c", ""), ("d", ""), ("e", "")]).split_by_count(2);
+        let names: Vec<Vec<String>> = boxes.iter().map(names).collect();
+        assert_eq!(names, vec![vec!["a", "b"], vec!["c", "d"], vec!["e"]]);
+    }
+
+    /// 0 本ずつでは分けられないので、１本ずつにする。空っぽの箱は作らない。
+    #[test]
+    fn splits_by_count_of_zero_into_single_tapes() {
+        assert_eq!(tape_box(&[("a", ""), ("b", "")]).split_by_count(0).len(), 2);
+        assert!(RpmTapeBox::new().split_by_count(3).is_empty());
+    }
+
+    /// 値が初めて出てきた順に箱を並べる。値の無いテープは 空っぽの値の箱に入れる。
+    #[test]
+    fn splits_by_label_in_order_of_first_value() {
+        let boxes = tape_box(&[("a", "平手"), ("b", "香落ち"), ("c", ""), ("d", "平手")])
+            .split_by_label("handicap")
+            .unwrap_or_else(|| panic!("Unknown key 'handicap'."));
+        let boxes: Vec<(String, Vec<String>)> = boxes
+            .iter()
+            .map(|(value, rpm_tape_box)| (value.clone(), names(rpm_tape_box)))
+            .collect();
+        assert_eq!(
+            boxes,
+            vec![
+                ("平手".to_string(), vec!["a".to_string(), "d".to_string()]),
+                ("香落ち".to_string(), vec!["b".to_string()]),
+                (String::new(), vec!["c".to_string()]),
+            ]
+        );
+    }
+
+    /// 知らない項目名なら 分けない。
+    #[test]
+    fn does_not_split_by_unknown_label() {
+        assert!(tape_box(&[("a", "平手")]).split_by_label("color").is_none());
+    }
+}
@@ -0,0 +1,115 @@
+use chrono::Utc;
+use serde::*;
+use sheet_music_format::parse_error::*;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use studio::application::Application;
+
+/// 満杯になって閉じたテープ・ボックス１箱の見出し。
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct RpmTapeBoxIndexEntry {
+    // テープ・ボックスのファイル名。
+    pub file: String,
+    // テープの本数。
+    pub tapes: usize,
+    // ファイルのバイト数。
+    pub bytes: u64,
+    // 最初と最後のテープ名。
+    pub first_tape: String,
+    pub last_tape: String,
+    // 閉じた日時。
+    pub closed_at: String,
+}
+
+/// 満杯になって閉じたテープ・ボックスの索引。ラーニング・フォルダーに置く。
+/// 閉じた箱は もう書き換えないので、中を開かずに 何が入っているか分かる。
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct RpmTapeBoxIndex {
+    pub boxes: Vec<RpmTapeBoxIndexEntry>,
+}
+impl RpmTapeBoxIndex {
+    pub fn new() -> Self {
+        RpmTapeBoxIndex::default()
+    }
+
+    // #####
+    // # F #
+    // #####
+
+    /// 索引ファイルを読む。無ければ空っぽの索引。
+    pub fn from_file(index_file: &str) -> Result<Self, ParseError> {
+        if !Path::new(index_file).exists() {
+            return Ok(RpmTapeBoxIndex::new());
+        }
+
+        let contents = fs::read_to_string(index_file)
+            .map_err(|err| ParseError::from_io("RPM", index_file, &err))?;
+        serde_json::from_str(&contents)
+            .map_err(|err| ParseError::from_json("RPM", index_file, &contents, &err))
+    }
+
+    // #####
+    // # P #
+    // #####
+
+    /// 閉じた箱を索引に足して、索引ファイルを書き直す。
+    pub fn push_closed_box(
+        index_file: &str,
+        mut entry: RpmTapeBoxIndexEntry,
+        app: &Application,
+    ) -> Result<(), ParseError> {
+        let mut index = RpmTapeBoxIndex::from_file(index_file)?;
+
+        entry.closed_at = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        index.boxes.push(entry);
+
+        let json_text = serde_json::to_string_pretty(&index)
+            .unwrap_or_else(|err| panic!("{}", app.comm.panic(&err.to_string())));
+        // 書いている途中で止まっても 元の索引が残るように、箱と同じく
+        // 一時ファイルに書き切ってから 名前を付け替える。
+        let temp_file_name = format!("{}.tmp", index_file);
+        File::create(&temp_file_name)
+            .and_then(|mut file_obj| {
+                file_obj.write_all(json_text.as_bytes())?;
+                file_obj.sync_all()
+            })
+            .and_then(|_| fs::rename(&temp_file_name, index_file))
+            .map_err(|err| ParseError::from_io("RPM", index_file, &err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 閉じた箱を足すたびに索引を書き直し、一時ファイルは残さない。
+    #[test]
+    fn pushes_closed_boxes_without_leaving_temp_file() {
+        let app = Application::new_for_test();
+        let index_file =
+            Application::write_test_file("rpm-tape-box-index/learning-index.json", b"");
+        fs::remove_file(&index_file).unwrap_or_else(|err| panic!("{}", err));
+
+        for file in &["a.rbox", "b.rbox"] {
+            let entry = RpmTapeBoxIndexEntry {
+                file: file.to_string(),
+                ..Default::default()
+            };
+            RpmTapeBoxIndex::push_closed_box(&index_file, entry, &app)
+                .unwrap_or_else(|err| panic!("{}", err));
+        }
+
+        let files: Vec<String> = RpmTapeBoxIndex::from_file(&index_file)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .boxes
+            .into_iter()
+            .map(|entry| entry.file)
+            .collect();
+        assert_eq!(files, vec!["a.rbox", "b.rbox"]);
+        assert!(!Path::new(&format!("{}.tmp", index_file)).exists());
+    }
+}
@@ -0,0 +1,30 @@
+use media::tape_branch::*;
+use serde::*;
+use sheet_music_format::kifu_rpm::rpm_tape_tracks::RpmTapeTracks;
+use studio::application::Application;
+use studio::board_size::*;
+
+/// テープの枝の JSON。枝の枝は、トラックの branch に入れ子で入る。
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")] // プロパティ名が JSON 側でスネークケースであることを指定
+pub struct RpmTapeBranch {
+    // 分かれる所。親の筋の、何番目のノートから先が入れ替わるか。
+    pub fork: usize,
+    // 分かれた所から先のトラック。
+    pub tracks: RpmTapeTracks,
+}
+impl RpmTapeBranch {
+    pub fn to_object(&self, board_size: BoardSize, app: &Application) -> TapeBranch {
+        TapeBranch {
+            fork: self.fork,
+            notes: self.tracks.to_positive_vec(board_size, app),
+            annotations: self.tracks.annotation.clone(),
+            branches: self
+                .tracks
+                .branch
+                .iter()
+                .map(|branch| branch.to_object(board_size, app))
+                .collect(),
+        }
+    }
+}
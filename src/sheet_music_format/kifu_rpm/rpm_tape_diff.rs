@@ -0,0 +1,240 @@
+use instrument::position::*;
+use media::cassette_tape::*;
+use sheet_music_format::kifu_kif::kif_move::*;
+use sheet_music_format::kifu_rpm::rpm_move::*;
+use sheet_music_format::kifu_rpm::rpm_tape::*;
+use sound::shogi_note::ShogiNote;
+use studio::application::Application;
+
+/// 何を単位に比べるか。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RpmTapeDiffMode {
+    // 指し手ごと。背番号や、駒を触る順の違いは見ない。
+    Move,
+    // ノートごと。コンバーターの タッチの並びの違いを調べる時に使う。
+    Note,
+}
+
+/// ２本のテープを並べて比べたもの。
+pub struct RpmTapeDiff {
+    pub mode: RpmTapeDiffMode,
+    // 行の見出し。指し手なら手目、ノートならキャレット番号。
+    pub headings: Vec<i16>,
+    // 左右のテープの行。指し手なら KIF の指し手、ノートならノートの表示。無ければ空っぽ。
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+    // 左右で違う行か。
+    pub differs: Vec<bool>,
+    // 初めて違った行。0 から。最後まで同じなら None。
+    pub divergence: Option<usize>,
+    // 初めて違った手目。
+    pub divergent_ply: Option<i16>,
+    // 初めて違う直前の局面。最後まで同じなら、終わりの局面。
+    pub position_text: String,
+}
+impl RpmTapeDiff {
+    // #####
+    // # D #
+    // #####
+
+    /// ２本のテープを比べる。
+    pub fn diff(
+        left: &RpmTape,
+        right: &RpmTape,
+        mode: RpmTapeDiffMode,
+        app: &Application,
+    ) -> RpmTapeDiff {
+        let board_size = Position::new_honshogi_origin(app).get_board_size();
+        let left_notes = left.tracks.to_positive_vec(board_size, app);
+        let right_notes = right.tracks.to_positive_vec(board_size, app);
+
+        match mode {
+            RpmTapeDiffMode::Move => RpmTapeDiff::diff_moves(&left_notes, &right_notes, app),
+            RpmTapeDiffMode::Note => RpmTapeDiff::diff_notes(&left_notes, &right_notes, app),
+        }
+    }
+
+    /// エンジンの持っているテープ同士を比べる。
+    pub fn diff_cassette_tapes(
+        left: &CassetteTape,
+        right: &CassetteTape,
+        mode: RpmTapeDiffMode,
+        app: &Application,
+    ) -> RpmTapeDiff {
+        let board_size = Position::new_honshogi_origin(app).get_board_size();
+        RpmTapeDiff::diff(
+            &left.to_rpm(board_size),
+            &right.to_rpm(board_size),
+            mode,
+            app,
+        )
+    }
+
+    /// 指し手に直して比べる。
+    fn diff_moves(
+        left_notes: &[ShogiNote],
+        right_notes: &[ShogiNote],
+        app: &Application,
+    ) -> RpmTapeDiff {
+        let board_size = Position::new_honshogi_origin(app).get_board_size();
+        let left_moves = RpmMove::parse_notes(left_notes, board_size);
+        let right_moves = RpmMove::parse_notes(right_notes, board_size);
+
+        let to_kif_text = |moves: &[RpmMove], i: usize| -> String {
+            moves.get(i).map_or("".to_string(), |rmove| {
+                let previous = if 0 < i { moves.get(i - 1) } else { None };
+                KifMove::from_rpm_move(rmove, previous).to_kif_text()
+            })
+        };
+
+        let mut diff = RpmTapeDiff::new(RpmTapeDiffMode::Move);
+        for i in 0..left_moves.len().max(right_moves.len()) {
+            let (left_move, right_move) = (left_moves.get(i), right_moves.get(i));
+            diff.push_row(
+                left_move.or(right_move).map_or(0, |rmove| rmove.ply),
+                to_kif_text(&left_moves, i),
+                to_kif_text(&right_moves, i),
+                left_move != right_move,
+            );
+        }
+        diff.divergent_ply = diff.divergence.map(|row| diff.headings[row]);
+
+        // 違う手を指す前まで、左のテープを進める。
+        let caret = match diff.divergent_ply {
+            Some(ply) => left_notes
+                .iter()
+                .position(|note| note.get_ply() == Some(ply))
+                .unwrap_or(left_notes.len()),
+            None => left_notes.len(),
+        };
+        diff.position_text = RpmTapeDiff::replay(&left_notes[..caret], app);
+
+        diff
+    }
+
+    /// ノートのまま比べる。
+    fn diff_notes(
+        left_notes: &[ShogiNote],
+        right_notes: &[ShogiNote],
+        app: &Application,
+    ) -> RpmTapeDiff {
+        let board_size = Position::new_honshogi_origin(app).get_board_size();
+        let to_text = |notes: &[ShogiNote], i: usize| -> String {
+            notes.get(i).map_or("".to_string(), |note| {
+                note.to_human_presentable(board_size, app)
+            })
+        };
+
+        let mut diff = RpmTapeDiff::new(RpmTapeDiffMode::Note);
+        for i in 0..left_notes.len().max(right_notes.len()) {
+            diff.push_row(
+                i as i16,
+                to_text(left_notes, i),
+                to_text(right_notes, i),
+                left_notes.get(i) != right_notes.get(i),
+            );
+        }
+
+        // 違うノートの手前で、最後に開いた手目。
+        let caret = diff
+            .divergence
+            .map_or(left_notes.len(), |row| row.min(left_notes.len()));
+        diff.divergent_ply = diff.divergence.and_then(|_row| {
+            left_notes[..caret]
+                .iter()
+                .rev()
+                .find_map(|note| note.get_ply())
+        });
+        diff.position_text = RpmTapeDiff::replay(&left_notes[..caret], app);
+
+        diff
+    }
+
+    // #####
+    // # N #
+    // #####
+
+    fn new(mode: RpmTapeDiffMode) -> Self {
+        RpmTapeDiff {
+            mode,
+            headings: Vec::new(),
+            left: Vec::new(),
+            right: Vec::new(),
+            differs: Vec::new(),
+            divergence: None,
+            divergent_ply: None,
+            position_text: String::new(),
+        }
+    }
+
+    // #####
+    // # P #
+    // #####
+
+    fn push_row(&mut self, heading: i16, left: String, right: String, differs: bool) {
+        if differs && self.divergence.is_none() {
+            self.divergence = Some(self.left.len());
+        }
+        self.headings.push(heading);
+        self.left.push(left);
+        self.right.push(right);
+        self.differs.push(differs);
+    }
+
+    // #####
+    // # R #
+    // #####
+
+    /// まっさらな局面で ノートを順にタッチして、局面を返す。
+    fn replay(notes: &[ShogiNote], app: &Application) -> String {
+        let mut position = Position::new_honshogi_origin(app);
+        for note in notes {
+            position.touch_ope(note.is_facing_left(), &note.get_ope(), app);
+        }
+        position.to_text()
+    }
+
+    // #####
+    // # T #
+    // #####
+
+    /// 左右２列に並べる。違う行には `*` を付ける。
+    pub fn to_human_presentable(&self) -> String {
+        // 全角文字は２文字分の幅で数える。
+        let width = |text: &str| -> usize {
+            text.chars()
+                .map(|ch| if ch.is_ascii() { 1 } else { 2 })
+                .sum()
+        };
+        let left_width = self.left.iter().map(|text| width(text)).max().unwrap_or(0);
+
+        let mut text = String::new();
+        for (i, left) in self.left.iter().enumerate() {
+            text.push_str(&format!(
+                "{} {:>4} {}{} | {}\n",
+                if self.differs[i] { "*" } else { " " },
+                self.headings[i],
+                left,
+                " ".repeat(left_width - width(left)),
+                self.right[i]
+            ));
+        }
+
+        match self.divergence {
+            None => text.push_str("Same.\n"),
+            Some(row) => {
+                text.push_str("First divergence:");
+                if self.mode == RpmTapeDiffMode::Note {
+                    text.push_str(&format!(" Caret: {}.", self.headings[row]));
+                }
+                if let Some(ply) = self.divergent_ply {
+                    text.push_str(&format!(" Ply: {}.", ply));
+                }
+                text.push('\n');
+            }
+        }
+        text.push_str(&self.position_text);
+
+        text
+    }
+}
@@ -0,0 +1,273 @@
+use serde_json::Value;
+use sheet_music_format::kifu_rpm::rpm_binary::*;
+use sheet_music_format::kifu_rpm::rpm_migration::*;
+use sheet_music_format::kifu_rpm::rpm_tape::*;
+use sheet_music_format::parse_error::*;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+/// JSON 形式のテープ・ボックスを、テープ１本ずつ読む。
+/// 開いた時は 括弧を数えて テープの位置だけ調べ、テープの中身は読む時に読む。
+pub struct RpmJsonReader {
+    file_name: String,
+    reader: BufReader<File>,
+    version: u32,
+    // テープの JSON の、ファイルの先頭からの位置と長さ。
+    tapes: Vec<(u64, u32)>,
+}
+impl RpmJsonReader {
+    /// ファイルを開いて、テープの位置を調べる。
+    pub fn open(file_name: &str) -> Result<Self, ParseError> {
+        let io_error = |err: std::io::Error| ParseError::from_io("RPM", file_name, &err);
+        let error = |message: &str| ParseError::new("RPM", message).with_file(file_name);
+
+        let mut reader = BufReader::new(File::open(file_name).map_err(io_error)?);
+        let mut scanner = RpmJsonScanner::default();
+        let mut buffer = [0; 64 * 1024];
+        let mut offset = 0;
+        loop {
+            let len = reader.read(&mut buffer).map_err(io_error)?;
+            if len == 0 {
+                break;
+            }
+            for byte in &buffer[..len] {
+                scanner.scan(*byte, offset);
+                offset += 1;
+            }
+        }
+
+        // tape_box が無ければ、空っぽの箱。
+        if !scanner.is_object {
+            return Err(error("Tape box is not an object."));
+        }
+        if scanner.depth != 0 || scanner.in_string {
+            return Err(error("Tape box is not closed."));
+        }
+
+        // 版の無いテープ・ボックスは 版 0。
+        let version = if scanner.version.is_empty() {
+            0
+        } else {
+            scanner
+                .version
+                .parse()
+                .map_err(|_err| error(&format!("Unexpected version '{}'.", scanner.version)))?
+        };
+        if RpmMigration::get_latest_version() < version {
+            return Err(error(&format!(
+                "Tape box version {} is newer than supported version {}.",
+                version,
+                RpmMigration::get_latest_version()
+            )));
+        }
+
+        Ok(RpmJsonReader {
+            file_name: file_name.to_string(),
+            reader,
+            version,
+            tapes: scanner.tapes,
+        })
+    }
+
+    // #####
+    // # G #
+    // #####
+
+    pub fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    // #####
+    // # I #
+    // #####
+
+    pub fn is_empty(&self) -> bool {
+        self.tapes.is_empty()
+    }
+
+    // #####
+    // # L #
+    // #####
+
+    /// テープの本数。
+    pub fn len(&self) -> usize {
+        self.tapes.len()
+    }
+
+    // #####
+    // # R #
+    // #####
+
+    /// テープを１本読む。古い版なら、最新の版に上げる。
+    pub fn read_tape(&mut self, index: usize) -> Result<RpmTape, ParseError> {
+        let file_name = &self.file_name;
+        let reader = &mut self.reader;
+        let (offset, len) = self.tapes[index];
+
+        let mut bytes = vec![0; len as usize];
+        reader
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| reader.read_exact(&mut bytes))
+            .map_err(|err| ParseError::from_io("RPM", file_name, &err))?;
+        let contents = String::from_utf8_lossy(&bytes);
+
+        let with_index = |mut err: ParseError| {
+            err.message = format!("Tape: {}. {}", index, err.message);
+            err
+        };
+        let value: Value = serde_json::from_str(&contents)
+            .map_err(|err| with_index(ParseError::from_json("RPM", file_name, &contents, &err)))?;
+        let value = RpmMigration::migrate_tape(value, self.version, file_name)?;
+        serde_json::from_value(value).map_err(|err| {
+            with_index(ParseError::new("RPM", &err.to_string()).with_file(file_name))
+        })
+    }
+}
+
+/// JSON を１バイトずつ見て、`tape_box` の配列の要素の位置を調べる。
+/// 文字列の中の括弧は数えない。
+#[derive(Default)]
+struct RpmJsonScanner {
+    // 括弧の深さ。テープ・ボックスの中が 1、tape_box の配列の中が 2。
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    // 深さ 1 で 最後に読んだ文字列と、その値を読んでいる項目名。
+    last_string: Vec<u8>,
+    key: Vec<u8>,
+    version: String,
+    // 一番外側が { } か。
+    is_object: bool,
+    in_tape_box: bool,
+    tape_start: Option<u64>,
+    tapes: Vec<(u64, u32)>,
+}
+impl RpmJsonScanner {
+    fn scan(&mut self, byte: u8, offset: u64) {
+        if self.in_string {
+            if self.escaped {
+                self.escaped = false;
+            } else if byte == b'\\' {
+                self.escaped = true;
+            } else if byte == b'"' {
+                self.in_string = false;
+                return;
+            }
+            if self.depth == 1 {
+                self.last_string.push(byte);
+            }
+            return;
+        }
+
+        match byte {
+            b'"' => {
+                self.in_string = true;
+                self.last_string.clear();
+            }
+            b':' if self.depth == 1 => self.key = self.last_string.clone(),
+            b',' if self.depth == 1 => self.key.clear(),
+            b'{' | b'[' => {
+                if self.in_tape_box && self.depth == 2 && self.tape_start.is_none() {
+                    self.tape_start = Some(offset);
+                }
+                if self.depth == 0 {
+                    self.is_object = byte == b'{';
+                }
+                if byte == b'[' && self.depth == 1 && self.key == b"tape_box" {
+                    self.in_tape_box = true;
+                }
+                self.depth += 1;
+            }
+            b'}' | b']' => {
+                self.depth = self.depth.saturating_sub(1);
+                if self.depth == 2 {
+                    if let Some(start) = self.tape_start.take() {
+                        self.tapes.push((start, (offset + 1 - start) as u32));
+                    }
+                } else if self.depth == 1 {
+                    self.in_tape_box = false;
+                }
+            }
+            b'0'..=b'9' if self.depth == 1 && self.key == b"version" => {
+                self.version.push(byte as char)
+            }
+            _ => {}
+        }
+    }
+}
+
+enum RpmTapeSource {
+    Binary(RpmBinaryReader),
+    Json(RpmJsonReader),
+}
+
+/// テープ・ボックスを、JSON でもバイナリでも テープ１本ずつ読む。
+/// 箱を丸ごと読まないので、大きな箱でも 読んだテープの分しか メモリーを使わない。
+pub struct RpmTapeReader {
+    source: RpmTapeSource,
+    // 次に読むテープの番号。
+    next: usize,
+}
+impl RpmTapeReader {
+    /// 拡張子が .rpmb なら バイナリ形式、それ以外は JSON 形式として開く。
+    pub fn open(file_name: &str) -> Result<Self, ParseError> {
+        let source = if RpmBinary::is_binary_file(file_name) {
+            RpmTapeSource::Binary(RpmBinaryReader::open(file_name)?)
+        } else {
+            RpmTapeSource::Json(RpmJsonReader::open(file_name)?)
+        };
+
+        Ok(RpmTapeReader { source, next: 0 })
+    }
+
+    // #####
+    // # I #
+    // #####
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // #####
+    // # L #
+    // #####
+
+    /// 箱に入っているテープの本数。
+    pub fn len(&self) -> usize {
+        match self.source {
+            RpmTapeSource::Binary(ref reader) => reader.len(),
+            RpmTapeSource::Json(ref reader) => reader.len(),
+        }
+    }
+
+    /// まだ読んでいないテープの本数。
+    pub fn len_unread(&self) -> usize {
+        self.len() - self.next
+    }
+
+    // #####
+    // # R #
+    // #####
+
+    /// テープを１本読む。
+    pub fn read_tape(&mut self, index: usize) -> Result<RpmTape, ParseError> {
+        match self.source {
+            RpmTapeSource::Binary(ref mut reader) => reader.read_tape(index),
+            RpmTapeSource::Json(ref mut reader) => reader.read_tape(index),
+        }
+    }
+}
+impl Iterator for RpmTapeReader {
+    type Item = Result<RpmTape, ParseError>;
+
+    /// 次のテープを読む。
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len() <= self.next {
+            return None;
+        }
+
+        let tape = self.read_tape(self.next);
+        self.next += 1;
+        Some(tape)
+    }
+}
@@ -1,5 +1,7 @@
 use instrument::piece_etc::*;
 use serde::*;
+use sheet_music_format::kifu_rpm::rpm_tape_branch::RpmTapeBranch;
+use sheet_music_format::move_annotation::MoveAnnotation;
 use sound::shogi_note::ShogiNote;
 use sound::shogi_note_operation::ShogiNoteOpe;
 use studio::application::*;
@@ -15,6 +17,12 @@ pub struct RpmTapeTracks {
     pub ope: String,
     // 向き。半角スペース１個区切り。'.' が正順、'L' で逆順。指し手を戻しているときは逆順で、真。
     pub facing: String,
+    // 注釈。指し手ごとのコメントと消費時間。無ければ省略。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub annotation: Vec<MoveAnnotation>,
+    // 枝。変化などの、もう１つの続き。無ければ省略。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub branch: Vec<RpmTapeBranch>,
 }
 impl RpmTapeTracks {
     pub fn new() -> Self {
@@ -22,6 +30,8 @@ impl RpmTapeTracks {
             id: "".to_string(),
             ope: "".to_string(),
             facing: "".to_string(),
+            annotation: Vec::new(),
+            branch: Vec::new(),
         }
     }
 
@@ -59,6 +69,19 @@ impl RpmTapeTracks {
             .to_string();
         }
 
+        for annotation in &self.annotation {
+            text = format!("{} {}", text, annotation.to_human_presentable());
+        }
+
+        for branch in &self.branch {
+            text = format!(
+                "{} [Branch: Fork: {}.{}]",
+                text,
+                branch.fork,
+                branch.tracks.to_human_presentable(app)
+            );
+        }
+
         text.to_string()
     }
 
@@ -0,0 +1,661 @@
+use instrument::half_player_phase::*;
+use instrument::piece_etc::*;
+use instrument::position::*;
+use regex::Regex;
+use sheet_music_format::kifu_ki2::ki2_move::*;
+use sheet_music_format::kifu_rpm::rpm_tape::*;
+use sheet_music_format::kifu_rpm::rpm_tape_box::*;
+use sound::shogi_note::ShogiNote;
+use sound::shogi_note_operation::ShogiNoteOpe;
+use studio::address::*;
+use studio::application::Application;
+use studio::board_size::*;
+use studio::common::caret::*;
+
+/// テープ１本を検査した結果。
+pub struct RpmTapeVerdict {
+    // テープ・ボックスの中の何本目か。0 から。
+    pub index: usize,
+    // テープ名。
+    pub name: String,
+    // ノートの数。
+    pub notes: usize,
+    // 最初におかしかったノートのキャレット番号。0 から。おかしくなければ None。
+    pub caret: Option<usize>,
+    // おかしかった理由。
+    pub message: String,
+}
+impl RpmTapeVerdict {
+    // #####
+    // # I #
+    // #####
+
+    pub fn is_ok(&self) -> bool {
+        self.caret.is_none()
+    }
+
+    // #####
+    // # T #
+    // #####
+
+    /// `NG #3 'name' Caret: 120. Touch is not complete. '0B'.` のように書く。
+    pub fn to_human_presentable(&self) -> String {
+        match self.caret {
+            Some(caret) => format!(
+                "NG #{} '{}' Caret: {}. {}",
+                self.index, self.name, caret, self.message
+            ),
+            None => format!("OK #{} '{}' Notes: {}.", self.index, self.name, self.notes),
+        }
+    }
+}
+
+/// 指し手１つ分のノート。キャレット番号と、触った駒も覚えておく。
+struct TouchedNote {
+    caret: usize,
+    note: ShogiNote,
+    // 触った後に指先か、置いた先にある駒。
+    id_piece: Option<IdentifiedPiece>,
+}
+
+/// テープ・ボックスのテープを、まっさらな局面で１本ずつ再生して検査する。
+/// BestMovePicker が読めないテープを、先に見つけるのに使う。
+///
+/// * 駒の保存 - 盤上、駒台、指先に、背番号 0～39 の駒が１つずつ。
+/// * タッチの完遂 - 全てのノートが完遂する。背番号のトラックと、触った駒が合う。
+/// * 合法 - 指し手が ShogiMove::to_best_move の読めるノートの並びになっている。
+///   手目の分かる１手目からは、手番の駒か、駒の利き、成れる所か、打てる所か、二歩も見る。王手放置は見ない。
+/// * 巻き戻し - 逆順にタッチして、初期局面に戻る。
+pub struct RpmTapeValidator {}
+impl RpmTapeValidator {
+    // #####
+    // # C #
+    // #####
+
+    /// 盤上、駒台、指先に、全ての背番号の駒が１つずつあるか。
+    fn check_conservation(position: &Position) -> Result<(), String> {
+        let mut counts = [0; 40];
+        let mut count = |id_piece: &IdentifiedPiece| {
+            counts[id_piece.get_id().get_number() as usize] += 1;
+        };
+
+        for id_piece in position.board.iter().flatten() {
+            count(id_piece);
+        }
+        for hand in position.hands.iter() {
+            for id_piece in hand {
+                count(id_piece);
+            }
+        }
+        if let Some(id_piece) = position.get_fingertip_idp() {
+            count(&id_piece);
+        }
+
+        for pid in PieceIdentify::iterator() {
+            match counts[pid.get_number() as usize] {
+                1 => {}
+                0 => {
+                    return Err(format!(
+                        "Piece is lost: {}.",
+                        pid.to_human_presentable_4width()
+                    ))
+                }
+                _ => {
+                    return Err(format!(
+                        "Piece is duplicated: {}.",
+                        pid.to_human_presentable_4width()
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 指し手１つのノートの並びと、合法かを見る。
+    ///
+    /// # Arguments
+    ///
+    /// * `ply` - 手目。分からない時や、駒を並べている時は 0 以下。
+    ///
+    /// # Returns
+    ///
+    /// おかしければ、おかしかったノートのキャレット番号と理由。
+    fn check_move(
+        notes: &[TouchedNote],
+        ply: i16,
+        position: &Position,
+        board_size: BoardSize,
+    ) -> Result<(), (usize, String)> {
+        if notes.is_empty() {
+            return Ok(());
+        }
+
+        let ope = |i: usize| notes[i].note.get_ope();
+        let is_board = |i: usize| {
+            i < notes.len()
+                && ope(i)
+                    .address
+                    .is_some_and(|address| address.is_on_board(board_size))
+        };
+        let is_hand =
+            |i: usize| i < notes.len() && ope(i).address.is_some_and(|address| address.is_hand());
+        let is_turn = |i: usize| i < notes.len() && ope(i).fingertip_turn;
+        let is_rotate = |i: usize| i < notes.len() && ope(i).fingertip_rotate;
+        let to_cell = |i: usize| {
+            board_size.address_to_cell(ope(i).address.map_or(0, |address| address.get_index()))
+        };
+        let last = notes.len() - 1;
+        let unexpected = |i: usize| -> (usize, String) {
+            (
+                notes[i.min(last)].caret,
+                format!(
+                    "Move does not follow the note order. '{}'.",
+                    notes[i.min(last)].note.get_ope().to_sign(board_size)
+                ),
+            )
+        };
+
+        // 投了。
+        if ope(0).is_resign() {
+            return if notes.len() == 1 {
+                Ok(())
+            } else {
+                Err(unexpected(1))
+            };
+        }
+
+        // 打つ。 駒台 → 盤上。
+        if is_hand(0) {
+            if !is_board(1) {
+                return Err(unexpected(1));
+            }
+            if notes.len() != 2 {
+                return Err(unexpected(2));
+            }
+            if ply < 1 {
+                return Ok(());
+            }
+            return RpmTapeValidator::check_drop(&notes[1], ply, position, to_cell(1))
+                .map_err(|message| (notes[1].caret, message));
+        }
+
+        // 動かす。 盤上 → [+] → 盤上。
+        // 取る。 盤上 → [+] → - → 駒台 → 盤上 → [+] → 盤上。
+        if !is_board(0) {
+            return Err(unexpected(0));
+        }
+        let mut i = 1;
+        if is_turn(i) {
+            i += 1;
+        }
+        // 取った駒を掴んだノート。
+        let mut capture = None;
+        if is_rotate(i) {
+            if !is_hand(i + 1) {
+                return Err(unexpected(i + 1));
+            }
+            if !is_board(i + 2) {
+                return Err(unexpected(i + 2));
+            }
+            capture = Some(0);
+            i += 2;
+        } else {
+            i = 0;
+        }
+
+        // 動かす駒を掴んだノート。
+        let source = i;
+        i += 1;
+        let is_promote = is_turn(i);
+        if is_promote {
+            i += 1;
+        }
+        if !is_board(i) {
+            return Err(unexpected(i));
+        }
+        if i != last {
+            return Err(unexpected(i + 1));
+        }
+        if let Some(capture) = capture {
+            if to_cell(capture) != to_cell(i) {
+                return Err((
+                    notes[i].caret,
+                    "Captured cell and destination differ.".to_string(),
+                ));
+            }
+        }
+
+        if ply < 1 {
+            return Ok(());
+        }
+        RpmTapeValidator::check_board_move(
+            notes,
+            ply,
+            position,
+            (source, to_cell(source)),
+            (i, to_cell(i)),
+            is_promote,
+            capture,
+        )
+        .map_err(|(index, message)| (notes[index].caret, message))
+    }
+
+    /// 盤上の駒を動かす指し手が、合法か。局面は指した後のもの。
+    fn check_board_move(
+        notes: &[TouchedNote],
+        ply: i16,
+        position: &Position,
+        (source, source_cell): (usize, Cell),
+        (destination, destination_cell): (usize, Cell),
+        is_promote: bool,
+        capture: Option<usize>,
+    ) -> Result<(), (usize, String)> {
+        let phase_value = RpmTapeValidator::get_phase_value_of_ply(ply);
+
+        // 掴んだ時の駒。成る前。
+        let id_piece = notes[source]
+            .id_piece
+            .ok_or_else(|| (source, "No piece to move.".to_string()))?;
+        if id_piece.get_phase().get_state() != phase_value {
+            return Err((
+                source,
+                format!(
+                    "Moved piece is not the player's. Ply: {}. '{}'.",
+                    ply,
+                    id_piece.to_human_presentable()
+                ),
+            ));
+        }
+
+        if let Some(capture) = capture {
+            let captured = notes[capture]
+                .id_piece
+                .ok_or_else(|| (capture, "No piece to capture.".to_string()))?;
+            if captured.get_phase().get_state() == phase_value {
+                return Err((
+                    capture,
+                    format!(
+                        "Captured piece is the player's. Ply: {}. '{}'.",
+                        ply,
+                        captured.to_human_presentable()
+                    ),
+                ));
+            }
+        }
+
+        let piece_type = id_piece.get_type();
+        if !Ki2Move::can_reach(
+            position,
+            piece_type,
+            phase_value,
+            source_cell,
+            destination_cell,
+        ) {
+            return Err((
+                destination,
+                format!(
+                    "Piece can not reach. Ply: {}. '{}' {} -> {}.",
+                    ply,
+                    id_piece.to_human_presentable(),
+                    source_cell.to_human_presentable(),
+                    destination_cell.to_human_presentable()
+                ),
+            ));
+        }
+
+        use instrument::piece_etc::PieceType::*;
+        if is_promote {
+            match piece_type {
+                R | B | S | N | L | P => {}
+                _ => {
+                    return Err((
+                        destination,
+                        format!(
+                            "Piece can not promote. Ply: {}. '{}'.",
+                            ply,
+                            id_piece.to_human_presentable()
+                        ),
+                    ))
+                }
+            }
+            if !RpmTapeValidator::is_promotion_zone(phase_value, source_cell)
+                && !RpmTapeValidator::is_promotion_zone(phase_value, destination_cell)
+            {
+                return Err((
+                    destination,
+                    format!("Promotion out of the zone. Ply: {}.", ply),
+                ));
+            }
+        } else if !RpmTapeValidator::can_move_further(phase_value, piece_type, destination_cell) {
+            return Err((
+                destination,
+                format!("Piece can not move further. Ply: {}.", ply),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 駒を打つ指し手が、合法か。局面は打った後のもの。
+    fn check_drop(
+        note: &TouchedNote,
+        ply: i16,
+        position: &Position,
+        destination: Cell,
+    ) -> Result<(), String> {
+        let phase_value = RpmTapeValidator::get_phase_value_of_ply(ply);
+        let id_piece = note
+            .id_piece
+            .ok_or_else(|| "No piece to drop.".to_string())?;
+        if id_piece.get_phase().get_state() != phase_value {
+            return Err(format!(
+                "Dropped piece is not the player's. Ply: {}. '{}'.",
+                ply,
+                id_piece.to_human_presentable()
+            ));
+        }
+
+        let piece_type = id_piece.get_type();
+        if !RpmTapeValidator::can_move_further(phase_value, piece_type, destination) {
+            return Err(format!("Piece can not move further. Ply: {}.", ply));
+        }
+
+        // 二歩。
+        if piece_type == PieceType::P {
+            for rank in 1..=position.get_board_size().get_rank_len() {
+                if rank == destination.get_rank() {
+                    continue;
+                }
+                if let Some(other) =
+                    position.get_id_piece(Cell::from_file_rank(destination.get_file(), rank))
+                {
+                    if other.get_phase().get_state() == phase_value
+                        && other.get_type() == PieceType::P
+                    {
+                        return Err(format!("Two pawns on a file. Ply: {}.", ply));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 行き所の無い駒でないか。
+    fn can_move_further(
+        phase_value: HalfPlayerPhaseValue,
+        piece_type: PieceType,
+        destination: Cell,
+    ) -> bool {
+        // 相手の陣の奥から数えた段。 1 が一番奥。
+        let rank = match phase_value {
+            HalfPlayerPhaseValue::Second => 10 - destination.get_rank(),
+            _ => destination.get_rank(),
+        };
+
+        match piece_type {
+            PieceType::P | PieceType::L => 1 < rank,
+            PieceType::N => 2 < rank,
+            _ => true,
+        }
+    }
+
+    // #####
+    // # G #
+    // #####
+
+    /// 手目から手番。奇数手が先手。
+    fn get_phase_value_of_ply(ply: i16) -> HalfPlayerPhaseValue {
+        if ply % 2 == 0 {
+            HalfPlayerPhaseValue::Second
+        } else {
+            HalfPlayerPhaseValue::First
+        }
+    }
+
+    // #####
+    // # I #
+    // #####
+
+    /// 相手の陣。先手なら 1～3 段。
+    fn is_promotion_zone(phase_value: HalfPlayerPhaseValue, cell: Cell) -> bool {
+        match phase_value {
+            HalfPlayerPhaseValue::Second => 7 <= cell.get_rank(),
+            _ => cell.get_rank() <= 3,
+        }
+    }
+
+    // #####
+    // # P #
+    // #####
+
+    /// トラックを読んで、ノートにする。 RpmTapeTracks::to_positive_vec と違い、パニックしない。
+    ///
+    /// # Returns
+    ///
+    /// 読めなければ、読めなかったノートのキャレット番号と理由。
+    fn parse_notes(
+        rtape: &RpmTape,
+        board_size: BoardSize,
+        app: &Application,
+    ) -> Result<Vec<ShogiNote>, (usize, String)> {
+        if rtape.tracks.ope.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let id_vec: Vec<&str> = rtape.tracks.id.split(' ').collect();
+        let ope_vec: Vec<&str> = rtape.tracks.ope.split(' ').collect();
+        let facing_vec: Vec<&str> = rtape.tracks.facing.split(' ').collect();
+        if id_vec.len() != ope_vec.len() || facing_vec.len() != ope_vec.len() {
+            return Err((
+                id_vec.len().min(ope_vec.len()).min(facing_vec.len()),
+                format!(
+                    "Track length differs. Id: {}, Ope: {}, Facing: {}.",
+                    id_vec.len(),
+                    ope_vec.len(),
+                    facing_vec.len()
+                ),
+            ));
+        }
+
+        let re = Regex::new(r"^(?:0\+?[KRBGSNLPkrbgsnlp]|[1-9][1-9]|\+|-|\||\[\d+\]|%resign)$")
+            .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
+
+        let mut notes = Vec::new();
+        for (i, ope_element) in ope_vec.iter().enumerate() {
+            let unexpected = |track: &str, token: &str| -> (usize, String) {
+                (i, format!("Unexpected {} note. '{}'.", track, token))
+            };
+
+            let pid = match id_vec[i].parse::<i8>() {
+                Ok(-1) => None,
+                Ok(number) => Some(
+                    PieceIdentify::from_number(number)
+                        .ok_or_else(|| unexpected("id", id_vec[i]))?,
+                ),
+                Err(_err) => return Err(unexpected("id", id_vec[i])),
+            };
+
+            if !re.is_match(ope_element) {
+                return Err(unexpected("ope", ope_element));
+            }
+            let mut caret = Caret::new_facing_right_caret();
+            let (_last_caret, note_ope_opt) =
+                ShogiNoteOpe::parse_1ope(ope_element, &mut caret, board_size, app);
+            let note_ope = note_ope_opt.ok_or_else(|| unexpected("ope", ope_element))?;
+
+            let is_facing_left = match facing_vec[i] {
+                "." => false,
+                "L" => true,
+                _ => return Err(unexpected("facing", facing_vec[i])),
+            };
+
+            notes.push(ShogiNote::from_id_ope(pid, note_ope, is_facing_left));
+        }
+
+        Ok(notes)
+    }
+
+    // #####
+    // # R #
+    // #####
+
+    /// テープを逆順にタッチして、初期局面に戻るか。
+    fn rewind(
+        notes: &[ShogiNote],
+        position: &mut Position,
+        origin_text: &str,
+        app: &Application,
+    ) -> Result<(), (usize, String)> {
+        let board_size = position.get_board_size();
+        for (caret, note) in notes.iter().enumerate().rev() {
+            let (complete, _id_piece) =
+                position.touch_ope(!note.is_facing_left(), &note.get_ope(), app);
+            if !complete {
+                return Err((
+                    caret,
+                    format!(
+                        "Rewind touch is not complete. '{}'.",
+                        note.get_ope().to_sign(board_size)
+                    ),
+                ));
+            }
+        }
+
+        if position.to_text() != origin_text {
+            return Err((
+                0,
+                "Rewind does not return to the origin position.".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// テープ１本を、まっさらな局面で再生する。
+    fn replay(rtape: &RpmTape, app: &Application) -> Result<usize, (usize, String)> {
+        let mut position = Position::new_honshogi_origin(app);
+        let origin_text = position.to_text();
+        let board_size = position.get_board_size();
+
+        let notes = RpmTapeValidator::parse_notes(rtape, board_size, app)?;
+
+        // フェーズ・チェンジで挟まれたノートが、指し手１つ。
+        let mut move_notes: Vec<TouchedNote> = Vec::new();
+        let mut ply = -1;
+        for (caret, note) in notes.iter().enumerate() {
+            let ope = note.get_ope();
+
+            // 駒台に置く時は、置く駒と駒台の番地が合うか。
+            if let (Some(address), Some(id_piece)) = (ope.address, position.get_fingertip_idp()) {
+                if address.is_hand()
+                    && address.get_hand_piece()
+                        != Some(Piece::from_ph_pt(
+                            id_piece.get_phase().get_state(),
+                            id_piece.get_type(),
+                        ))
+                {
+                    return Err((
+                        caret,
+                        format!(
+                            "Hand does not match the piece. '{}' '{}'.",
+                            ope.to_sign(board_size),
+                            id_piece.to_human_presentable()
+                        ),
+                    ));
+                }
+            }
+
+            let (complete, id_piece) = position.touch_ope(note.is_facing_left(), &ope, app);
+            if !complete {
+                return Err((
+                    caret,
+                    format!("Touch is not complete. '{}'.", ope.to_sign(board_size)),
+                ));
+            }
+            if let (Some(pid), Some(id_piece)) = (note.get_id(), id_piece) {
+                if pid != id_piece.get_id() {
+                    return Err((
+                        caret,
+                        format!(
+                            "Piece id differs. Track: {}, Touched: {}.",
+                            pid.to_human_presentable_4width(),
+                            id_piece.get_id().to_human_presentable_4width()
+                        ),
+                    ));
+                }
+            }
+            RpmTapeValidator::check_conservation(&position).map_err(|message| (caret, message))?;
+
+            if note.is_phase_change() {
+                if move_notes.is_empty() {
+                    // 指し手の始まり。
+                    ply = ope.get_phase_change().unwrap_or(-1);
+                } else {
+                    // 指し手の終わり。
+                    if position.get_fingertip_idp().is_some() {
+                        return Err((caret, "Piece is left in the fingertip.".to_string()));
+                    }
+                    RpmTapeValidator::check_move(&move_notes, ply, &position, board_size)?;
+                    move_notes.clear();
+                    ply = -1;
+                }
+            } else {
+                move_notes.push(TouchedNote {
+                    caret,
+                    note: *note,
+                    id_piece,
+                });
+            }
+        }
+
+        // 最後の指し手だけは、フェーズ・チェンジで閉じていなくてもいい。
+        if position.get_fingertip_idp().is_some() {
+            return Err((
+                notes.len().saturating_sub(1),
+                "Tape ends with a piece in the fingertip.".to_string(),
+            ));
+        }
+        RpmTapeValidator::check_move(&move_notes, ply, &position, board_size)?;
+
+        RpmTapeValidator::rewind(&notes, &mut position, &origin_text, app)?;
+
+        Ok(notes.len())
+    }
+
+    // #####
+    // # V #
+    // #####
+
+    /// テープ１本を検査する。
+    pub fn validate_tape(index: usize, rtape: &RpmTape, app: &Application) -> RpmTapeVerdict {
+        let mut verdict = RpmTapeVerdict {
+            index,
+            name: rtape.label.get_name(),
+            notes: 0,
+            caret: None,
+            message: "".to_string(),
+        };
+
+        match RpmTapeValidator::replay(rtape, app) {
+            Ok(notes) => verdict.notes = notes,
+            Err((caret, message)) => {
+                verdict.caret = Some(caret);
+                verdict.message = message;
+            }
+        }
+
+        verdict
+    }
+
+    /// テープ・ボックスのテープを全部検査する。
+    pub fn validate_tape_box(tape_box: &RpmTapeBox, app: &Application) -> Vec<RpmTapeVerdict> {
+        tape_box
+            .tape_box
+            .iter()
+            .enumerate()
+            .map(|(i, rtape)| RpmTapeValidator::validate_tape(i, rtape, app))
+            .collect()
+    }
+}
@@ -3,8 +3,10 @@ use audio_compo::cassette_deck::*;
 use instrument::piece_etc::*;
 use instrument::position::*;
 use live::ohashi_performer::*;
+use regex::Regex;
 use sheet_music_format::kifu_usi::usi_move::*;
 use sheet_music_format::kifu_usi::usi_position::*;
+use sheet_music_format::parse_error::*;
 use std::*;
 use studio::address::*;
 use studio::application::Application;
@@ -13,257 +15,369 @@ use studio::board_size::*;
 /// フォーサイス エドワーズ記法
 pub struct Fen {}
 impl Fen {
+    /// `position sfen ` の後ろの、盤面、手番、持ち駒、手目を読む。
+    /// 盤上に置かなかった駒は、駒台に残る。
+    ///
+    /// # Returns
+    ///
+    /// 読めなければ、何文字目の どの文字が悪かったかを返す。
     pub fn do_sfen(
         line: &str,
         start: &mut usize,
         position: &mut Position,
         app: &Application,
-    ) -> bool {
+    ) -> Result<(), ParseError> {
+        let error = |message: &str, index: usize| -> ParseError {
+            ParseError::new("USI", message)
+                .with_column(line[..index].chars().count() + 1)
+                .with_text(
+                    &line[index..]
+                        .chars()
+                        .next()
+                        .map_or(String::new(), String::from),
+                )
+        };
+
         // ゲームに使う駒がまだ決まっていないところから始めます。
-        position.repeat_empty_position(&app);
+        position.repeat_empty_position(app);
 
-        let rank = 9;
-        let mut file = 1;
+        // 盤面。１段目から、段ごとに ９筋から１筋へ。
+        let mut rank = 1;
+        let mut file = 9;
+        loop {
+            let sign = match line[*start..].chars().next() {
+                Some(sign) => sign,
+                None => return Err(error("Sfen not closed.", *start)),
+            };
+            match sign {
+                ' ' => break,
+                '1'..='9' => {
+                    file -= sign as i8 - '0' as i8;
+                    *start += 1;
+                }
+                '/' => {
+                    if file != 0 || rank == 9 {
+                        return Err(error("Unexpected rank end.", *start));
+                    }
+                    rank += 1;
+                    file = 9;
+                    *start += 1;
+                }
+                _ => {
+                    let index = *start;
+                    let piece = parse_sign_line_to_piece(line, start)
+                        .ok_or_else(|| error("Unexpected piece.", index))?;
+                    if file < 1 || position.get_hand_count(piece.to_disactivate()) == 0 {
+                        return Err(error("Too many pieces.", index));
+                    }
+                    position.activate_piece(Some(piece), Cell::from_file_rank(file, rank), app);
+                    file -= 1;
+                }
+            }
+            if file < 0 {
+                return Err(error("Too many cells.", *start - 1));
+            }
+        }
+        if file != 0 || rank != 9 {
+            return Err(error("Unexpected board end.", *start));
+        }
+        *start += 1;
 
-        let sign = line
-            .to_string()
-            .chars()
-            .next()
-            .unwrap_or_else(|| panic!(app.comm.panic("Fail. line.")));
-        let mut spaces = match sign {
-            '1' => 1,
-            '2' => 2,
-            '3' => 3,
-            '4' => 4,
-            '5' => 5,
-            '6' => 6,
-            '7' => 7,
-            '8' => 8,
-            '9' => 9,
-            '/' => -1,
-            _ => 0,
-        };
+        // 手番。
+        match line[*start..].chars().next() {
+            Some('b') | Some('w') => *start += 1,
+            _ => return Err(error("Unexpected phase.", *start)),
+        }
 
-        if spaces == 0 {
-            let piece_opt = parse_sign_line_to_piece(line, start);
-            position.activate_piece(piece_opt, Cell::from_file_rank(file, rank), &app);
-        /* file += 1; */
-        } else if spaces == -1 {
-            /* file = 1; */
-            /* rank = 9; */
+        // 持ち駒。 "-" か "2P3p" など。駒は もう駒台にある。
+        if !line[*start..].starts_with(' ') {
+            return Err(error("Hand not found.", *start));
+        }
+        *start += 1;
+        if line[*start..].starts_with('-') {
+            *start += 1;
         } else {
-            while spaces > 0 {
-                position.set_id_piece(Cell::from_file_rank(file, rank), None);
-                file += 1;
-                spaces -= 1;
+            loop {
+                match line[*start..].chars().next() {
+                    Some('0'..='9') => *start += 1,
+                    Some(' ') | None => break,
+                    Some(_sign) => {
+                        let index = *start;
+                        match parse_sign_line_to_piece(line, start) {
+                            Some(piece) if !is_promoted_piece(Some(piece)) => {}
+                            _ => return Err(error("Unexpected piece in hand.", index)),
+                        }
+                    }
+                }
             }
         }
-        true
+
+        // 手目。
+        let rest = &line[*start..];
+        let ply_len = rest.trim_start_matches(' ').len();
+        let digits = rest[rest.len() - ply_len..]
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .count();
+        if ply_len == rest.len() || digits == 0 {
+            return Err(error("Unexpected ply.", *start));
+        }
+        *start += rest.len() - ply_len + digits;
+
+        Ok(())
     }
 
-    // 本将棋のオリジン局面から、初期局面解析。
+    /// 本将棋のオリジン局面から、初期局面解析。
+    ///
+    /// # Returns
+    ///
+    /// `position startpos` でも `position sfen` でもなければ偽。
+    /// sfen が読めなければ、エラー。
     pub fn parse_initial_position(
         line: &str,
         start: &mut usize,
         position: &mut Position,
         rack: &mut AudioRack,
         app: &Application,
-    ) -> bool {
+    ) -> Result<bool, ParseError> {
         match UsiPosition::parse_startpos_test(line, start, &app.comm) {
             Some(is_startpos) => {
                 if is_startpos {
                     // 大橋流を指せるところまで、学習局面を戻す☆（＾～＾）
-                    position.repeat_origin_position(&app);
+                    position.repeat_origin_position(app);
 
                     // 大橋流で初期局面まで指す☆（＾～＾）
-                    rack.clear_tape_body(Slot::Learning, &app);
-                    OhashiPerformer::improvise_ohashi_starting(rack, position, &app);
-                    true
+                    rack.clear_tape_body(Slot::Learning, app);
+                    OhashiPerformer::improvise_ohashi_starting(rack, position, app);
                 } else {
-                    // 指定局面を、初期局面とする☆（＾～＾）
-                    Fen::do_sfen(line, start, position, &app)
+                    // 指定局面を、初期局面とする☆（＾～＾）読めなければ、局面は元のまま☆（＾～＾）
+                    let mut sfen_position = position.clone();
+                    Fen::do_sfen(line, start, &mut sfen_position, app)?;
+                    *position = sfen_position;
                 }
+                Ok(true)
             }
-            None => false,
+            None => Ok(false),
         }
     }
 
     /// ex.) Parses 7g7f.
+    ///
+    /// # Returns
+    ///
+    /// 指し手の形をしていなければ、何文字目が悪かったかを返す。
     pub fn parse_usi_1move(
         line: &str,
         start: &mut usize,
         board_size: BoardSize,
         app: &Application,
-    ) -> UsiMove {
-        let drop_opt = parse_sign_to_drop(line, start);
-        let source_opt = if drop_opt == None {
-            Some(Cell::from_file_rank(
-                parse_sign_to_file(line, start),
-                parse_sign_to_rank(line, start),
-            ))
-        } else {
-            None
-        };
-
-        let destination = Cell::from_file_rank(
-            parse_sign_to_file(line, start),
-            parse_sign_to_rank(line, start),
-        );
-
-        let promotion_flag = if drop_opt == None {
-            parse_sign_to_promotion(line, start)
-        } else {
-            false
-        };
+    ) -> Result<UsiMove, ParseError> {
+        // 先に形だけ確かめる。 "7g7f", "7g7f+", "B*4e"。
+        let re = Regex::new(r"^(?:[1-9][a-i]|[RBGSNLP]\*)[1-9][a-i]\+?(?:\s|$)")
+            .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
+        let rest = line.get(*start..).unwrap_or("");
+        if !re.is_match(rest) {
+            return Err(ParseError::new("USI", "Unexpected move.")
+                .with_column(line[..line.len() - rest.len()].chars().count() + 1)
+                .with_text(rest.split_whitespace().next().unwrap_or("")));
+        }
 
-        if let Some(drop) = drop_opt {
-            UsiMove::create_drop(destination, drop, board_size)
-        } else {
-            UsiMove::create_walk(
-                source_opt.unwrap_or_else(|| panic!(app.comm.panic("Fail. source_opt."))),
-                destination,
-                promotion_flag,
-                board_size,
-            )
+        match parse_sign_to_drop(line, start) {
+            Some(drop) => {
+                let destination = Cell::from_file_rank(
+                    parse_sign_to_file(line, start),
+                    parse_sign_to_rank(line, start),
+                );
+                Ok(UsiMove::create_drop(destination, drop, board_size))
+            }
+            None => {
+                let source = Cell::from_file_rank(
+                    parse_sign_to_file(line, start),
+                    parse_sign_to_rank(line, start),
+                );
+                let destination = Cell::from_file_rank(
+                    parse_sign_to_file(line, start),
+                    parse_sign_to_rank(line, start),
+                );
+                let promotion_flag = parse_sign_to_promotion(line, start);
+                Ok(UsiMove::create_walk(
+                    source,
+                    destination,
+                    promotion_flag,
+                    board_size,
+                ))
+            }
         }
     }
 }
 
-// フォーサイス エドワーズ記法に出てくる駒１つ分の読み込み。1～2文字。
+/// フォーサイス エドワーズ記法に出てくる駒１つ分の読み込み。1～2文字。
+/// 駒でなければ None。その時 start は進めない。
 pub fn parse_sign_line_to_piece(line: &str, start: &mut usize) -> Option<Piece> {
     use instrument::piece_etc::Piece::*;
 
-    // スタートが文字列の終端を読み終わっていれば、結果は空。
-    if line.len() <= *start as usize {
-        return None;
+    // 1文字目が + なら２文字。
+    let rest = line.get(*start..).unwrap_or("");
+    let (promoted, sign) = match rest.strip_prefix('+') {
+        Some(rest) => (true, rest.chars().next()),
+        None => (false, rest.chars().next()),
+    };
+
+    let piece = match (promoted, sign?) {
+        (true, 'R') => PR1,
+        (true, 'B') => PB1,
+        (true, 'S') => PS1,
+        (true, 'N') => PN1,
+        (true, 'L') => PL1,
+        (true, 'P') => PP1,
+        (true, 'r') => PR2,
+        (true, 'b') => PB2,
+        (true, 's') => PS2,
+        (true, 'n') => PN2,
+        (true, 'l') => PL2,
+        (true, 'p') => PP2,
+        (false, 'K') => K1,
+        (false, 'R') => R1,
+        (false, 'B') => B1,
+        (false, 'G') => G1,
+        (false, 'S') => S1,
+        (false, 'N') => N1,
+        (false, 'L') => L1,
+        (false, 'P') => P1,
+        (false, 'k') => K2,
+        (false, 'r') => R2,
+        (false, 'b') => B2,
+        (false, 'g') => G2,
+        (false, 's') => S2,
+        (false, 'n') => N2,
+        (false, 'l') => L2,
+        (false, 'p') => P2,
+        _ => return None,
+    };
+
+    *start += if promoted { 2 } else { 1 };
+    Some(piece)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instrument::half_player_phase::HalfPlayerPhaseValue;
+
+    fn parse(line: &str) -> (Result<bool, ParseError>, usize, Position) {
+        let app = Application::new_for_test();
+        let mut position = Position::new_honshogi_origin(&app);
+        let mut rack = AudioRack::new(&app);
+        let mut start = 0;
+        let result = Fen::parse_initial_position(line, &mut start, &mut position, &mut rack, &app);
+        (result, start, position)
     }
 
-    match &line[*start..=*start] {
-        "+" => {
-            // 1文字目が + なら２文字。
-            *start += 1;
-            match &line[*start..=*start] {
-                "R" => {
-                    *start += 1;
-                    Some(PR1)
-                }
-                "B" => {
-                    *start += 1;
-                    Some(PB1)
-                }
-                "S" => {
-                    *start += 1;
-                    Some(PS1)
-                }
-                "N" => {
-                    *start += 1;
-                    Some(PN1)
-                }
-                "L" => {
-                    *start += 1;
-                    Some(PL1)
-                }
-                "P" => {
-                    *start += 1;
-                    Some(PP1)
-                }
-                "r" => {
-                    *start += 1;
-                    Some(PR2)
-                }
-                "b" => {
-                    *start += 1;
-                    Some(PB2)
-                }
-                "s" => {
-                    *start += 1;
-                    Some(PS2)
-                }
-                "n" => {
-                    *start += 1;
-                    Some(PN2)
-                }
-                "l" => {
-                    *start += 1;
-                    Some(PL2)
-                }
-                "p" => {
-                    *start += 1;
-                    Some(PP2)
-                }
-                _ => panic!("Failed: Sfen unexpected piece."),
-            }
-        }
-        _ => {
-            // 1文字の符号。
-            *start += 1;
-            match &line[*start..=*start] {
-                "K" => {
-                    *start += 1;
-                    Some(K1)
-                }
-                "R" => {
-                    *start += 1;
-                    Some(R1)
-                }
-                "B" => {
-                    *start += 1;
-                    Some(B1)
-                }
-                "G" => {
-                    *start += 1;
-                    Some(G1)
-                }
-                "S" => {
-                    *start += 1;
-                    Some(S1)
-                }
-                "N" => {
-                    *start += 1;
-                    Some(N1)
-                }
-                "L" => {
-                    *start += 1;
-                    Some(L1)
-                }
-                "P" => {
-                    *start += 1;
-                    Some(P1)
-                }
-                "k" => {
-                    *start += 1;
-                    Some(K2)
-                }
-                "r" => {
-                    *start += 1;
-                    Some(R2)
-                }
-                "b" => {
-                    *start += 1;
-                    Some(B2)
-                }
-                "g" => {
-                    *start += 1;
-                    Some(G2)
-                }
-                "s" => {
-                    *start += 1;
-                    Some(S2)
-                }
-                "n" => {
-                    *start += 1;
-                    Some(N2)
-                }
-                "l" => {
-                    *start += 1;
-                    Some(L2)
-                }
-                "p" => {
-                    *start += 1;
-                    Some(P2)
-                }
-                _ => panic!("Failed: Sfen unexpected piece."),
-            }
-        }
+    #[test]
+    fn reads_sfen_board() {
+        let line = "position sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2 moves 3c3d";
+        let (result, start, position) = parse(line);
+
+        assert_eq!(result, Ok(true));
+        // 指し手の手前まで読む。
+        assert_eq!(&line[start..], " moves 3c3d");
+
+        let piece_type = |file, rank| {
+            position
+                .get_id_piece(Cell::from_file_rank(file, rank))
+                .map(|id_piece| id_piece.get_type())
+        };
+        assert!(piece_type(5, 1) == Some(PieceType::K));
+        assert!(piece_type(8, 2) == Some(PieceType::R));
+        assert!(piece_type(2, 8) == Some(PieceType::R));
+        assert!(piece_type(7, 6) == Some(PieceType::P));
+        assert!(piece_type(7, 7).is_none());
+        assert!(piece_type(5, 5).is_none());
+    }
+
+    #[test]
+    fn reads_sfen_hands_and_promoted_pieces() {
+        let (result, start, position) = parse("position sfen 8k/9/9/9/9/9/9/9/+R3K4 b 2Pp 51");
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(start, "position sfen 8k/9/9/9/9/9/9/9/+R3K4 b 2Pp 51".len());
+        let id_piece = position
+            .get_id_piece(Cell::from_file_rank(9, 9))
+            .unwrap_or_else(|| panic!("No piece."));
+        assert!(id_piece.get_type() == PieceType::PR);
+        assert_eq!(
+            id_piece.get_phase().get_state(),
+            HalfPlayerPhaseValue::First
+        );
+        let id_piece = position
+            .get_id_piece(Cell::from_file_rank(1, 1))
+            .unwrap_or_else(|| panic!("No piece."));
+        assert!(id_piece.get_type() == PieceType::K);
+        assert_eq!(
+            id_piece.get_phase().get_state(),
+            HalfPlayerPhaseValue::Second
+        );
+    }
+
+    #[test]
+    fn reports_column_of_unexpected_sfen() {
+        // 知らない駒。
+        let err =
+            parse("position sfen lnsgkgsnl/1r5x1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1")
+                .0
+                .err()
+                .unwrap_or_else(|| panic!("Unexpected success."));
+        assert_eq!(err.message, "Unexpected piece.");
+        assert_eq!(err.column, 28);
+        assert_eq!(err.text, "x");
+
+        // 盤面の途中で終わる。
+        let err = parse("position sfen ")
+            .0
+            .err()
+            .unwrap_or_else(|| panic!("Unexpected success."));
+        assert_eq!(err.message, "Sfen not closed.");
+        assert_eq!(err.column, 15);
+
+        // 筋が多すぎる。
+        let err = parse("position sfen 9/9/9/9/9/9/9/9/K9 b - 1")
+            .0
+            .err()
+            .unwrap_or_else(|| panic!("Unexpected success."));
+        assert_eq!(err.message, "Too many cells.");
+        assert_eq!(err.text, "9");
+    }
+
+    #[test]
+    fn ignores_line_without_position() {
+        let (result, start, _position) = parse("usinewgame");
+
+        assert_eq!(result, Ok(false));
+        assert_eq!(start, 0);
+    }
+
+    #[test]
+    fn reads_usi_moves() {
+        let app = Application::new_for_test();
+        let board_size = BoardSize::create_hon_shogi();
+
+        let mut start = 0;
+        let umove = Fen::parse_usi_1move("7g7f+", &mut start, board_size, &app)
+            .unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(umove.source.map(Cell::to_scalar), Some(77));
+        assert_eq!(umove.destination.map(Cell::to_scalar), Some(76));
+        assert!(umove.promotion);
+
+        let mut start = 0;
+        let umove = Fen::parse_usi_1move("P*5e", &mut start, board_size, &app)
+            .unwrap_or_else(|err| panic!("{}", err));
+        assert!(umove.get_drop() == Some(PieceType::P));
+        assert_eq!(umove.destination.map(Cell::to_scalar), Some(55));
+
+        let mut start = 0;
+        let err = Fen::parse_usi_1move("7g7z", &mut start, board_size, &app)
+            .err()
+            .unwrap_or_else(|| panic!("Unexpected success."));
+        assert_eq!(err.column, 1);
+        assert_eq!(err.text, "7g7z");
     }
 }
@@ -1,10 +1,13 @@
 use audio_compo::audio_rack::*;
 //use audio_compo::cassette_deck::*;
 use human::human_interface::*;
+use instrument::half_player_phase::*;
+use instrument::piece_etc::*;
 use instrument::position::*;
 use live::base_performer::*;
 use sheet_music_format::kifu_usi::usi_move::*;
 use sheet_music_format::kifu_usi::usi_tape::*;
+use sheet_music_format::parse_error::*;
 use sound::shogi_note_operation::*;
 use studio::address::*;
 use studio::application::Application;
@@ -15,27 +18,85 @@ impl UsiConverter {
     ///
     /// * 'position' - USIレコードと 初期局面を合わせてください。
     ///
+    /// # Returns
+    ///
+    /// 局面に合わない指し手があれば、そこで止めてエラーを返す。
     pub fn play_out_usi_tape(
         position: &mut Position,
         utape: &UsiTape,
         rack: &mut AudioRack,
         app: &Application,
-    ) {
+    ) -> Result<(), ParseError> {
         // 局面を動かしながら変換していく。
         let mut ply = 1;
         for umove in &utape.moves {
-            let rnote_opes = UsiConverter::convert_move(*umove, position, ply, &app);
+            UsiConverter::check_move(*umove, position, ply, app)?;
+
+            let rnote_opes = UsiConverter::convert_move(*umove, position, ply, app);
             //comm.println(&format!("Pmoves len: {}.", rpm_move.len()));
 
             for rnote_ope in rnote_opes {
-                BasePerformer::improvise_note_ope_no_log(rack, &rnote_ope, false, position, &app);
-                HumanInterface::bo(rack, position, &app);
+                BasePerformer::improvise_note_ope_no_log(rack, &rnote_ope, false, position, app);
+                HumanInterface::bo(rack, position, app);
             }
 
             ply += 1;
         }
+
+        Ok(())
+    }
+
+    /// 局面に合う指し手か。動かす駒が 手番の駒でなかったり、自分の駒を取ったりすれば エラー。
+    /// 手番は 手目から決める☆（＾～＾）奇数手が先手。
+    pub fn check_move(
+        umove: UsiMove,
+        position: &Position,
+        ply: i16,
+        app: &Application,
+    ) -> Result<(), ParseError> {
+        if umove.is_resign() {
+            return Ok(());
+        }
+
+        let phase_value = if ply % 2 == 0 {
+            HalfPlayerPhaseValue::Second
+        } else {
+            HalfPlayerPhaseValue::First
+        };
+        let is_own = |cell: Cell| {
+            position.get_id_piece(cell).is_some_and(|id_piece| {
+                !id_piece.get_phase().is_half() && id_piece.get_phase().get_state() == phase_value
+            })
+        };
+        let error = |message: &str| {
+            ParseError::new("USI", &format!("{} Ply: {}.", message, ply))
+                .with_text(&umove.to_sign(app))
+        };
+
+        let is_movable = match (umove.source, umove.get_drop()) {
+            (Some(source), _) => is_own(source),
+            (None, Some(drop)) => position
+                .peek_hand(Piece::from_ph_pt(phase_value, drop))
+                .is_some(),
+            (None, None) => false,
+        };
+        if !is_movable {
+            return Err(error("No piece to move."));
+        }
+
+        match umove.destination {
+            Some(destination)
+                if umove.is_drop() && position.get_id_piece(destination).is_some() =>
+            {
+                Err(error("Cell is not empty."))
+            }
+            Some(destination) if is_own(destination) => Err(error("Cannot capture own piece.")),
+            Some(_destination) => Ok(()),
+            None => Err(error("No destination.")),
+        }
     }
 
+    /// 駒台の番地は、局面の手番ではなく 手目から決める☆（＾～＾）奇数手が先手。
     pub fn convert_move(
         umove: UsiMove,
         position: &Position,
@@ -59,6 +120,12 @@ impl UsiConverter {
             return rpm_move;
         }
 
+        let phase_value = if ply % 2 == 0 {
+            HalfPlayerPhaseValue::Second
+        } else {
+            HalfPlayerPhaseValue::First
+        };
+
         let destination_address = Address::from_cell(
             umove
                 .destination
@@ -74,10 +141,8 @@ impl UsiConverter {
                 // # (1d) Hand off #
                 // #################
                 {
-                    let hand_off = ShogiNoteOpe::from_address(Address::from_hand_ph_pt(
-                        position.get_phase().get_state(),
-                        drop,
-                    ));
+                    let hand_off =
+                        ShogiNoteOpe::from_address(Address::from_hand_ph_pt(phase_value, drop));
                     rpm_move.push(hand_off);
                 }
 
@@ -125,10 +190,8 @@ impl UsiConverter {
                     // ################
                     {
                         let up = id_piece.get_type();
-                        let hand_on = ShogiNoteOpe::from_address(Address::from_hand_ph_pt(
-                            position.get_phase().get_state(),
-                            up,
-                        ));
+                        let hand_on =
+                            ShogiNoteOpe::from_address(Address::from_hand_ph_pt(phase_value, up));
                         rpm_move.push(hand_on);
                     }
                 }
@@ -1,4 +1,5 @@
 use sheet_music_format::kifu_usi::usi_tape::*;
+use sheet_music_format::parse_error::*;
 use std::*;
 use studio::application::Application;
 use studio::board_size::*;
@@ -7,6 +8,28 @@ use studio::parser::*;
 
 pub struct UsiPosition {}
 impl UsiPosition {
+    /// 平手の初期局面の sfen。
+    pub const STARTPOS_SFEN: &'static str =
+        "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+
+    /// 棋譜ファイルに書かれた１行を、 `position startpos ...` の形にそろえる。
+    /// 先頭の `position ` が省かれていれば付ける。平手の初期局面の sfen は startpos にする。
+    pub fn normalize_line(line: &str) -> String {
+        let line = line.trim();
+        let line = if line.starts_with("startpos") || line.starts_with("sfen ") {
+            format!("position {}", line)
+        } else {
+            line.to_string()
+        };
+
+        let hirate = format!("position sfen {}", UsiPosition::STARTPOS_SFEN);
+        if line.starts_with(&hirate) {
+            format!("position startpos{}", &line[hirate.len()..])
+        } else {
+            line
+        }
+    }
+
     /// startpos か、 sfen か、それ以外かに分かれる。
     pub fn parse_startpos_test(
         line: &str,
@@ -27,20 +50,21 @@ impl UsiPosition {
     }
 
     /// USI の moves の文字列を、オブジェクトに直訳するぜ☆（＾～＾）局面は動かさない☆（＾～＾）
+    /// moves が無ければ None。読めない指し手があれば、エラーを返す。
     pub fn parse_usi_line_moves(
         line: &str,
         start: &mut usize,
         board_size: BoardSize,
         app: &Application,
-    ) -> Option<UsiTape> {
+    ) -> Result<Option<UsiTape>, ParseError> {
         if Parser::match_keyword(&app.comm, &line, "moves", start)
             || Parser::match_keyword(&app.comm, &line, " moves", start)
         {
         } else {
             // comm.println(&format!("#Moves not matched. line: '{}', start: {}.", line, start));
-            return None;
+            return Ok(None);
         }
 
-        UsiTape::parse_usi_all_moves(line, start, board_size, &app)
+        UsiTape::parse_usi_all_moves(line, start, board_size, app).map(Some)
     }
 }
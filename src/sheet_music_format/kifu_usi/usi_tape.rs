@@ -2,6 +2,8 @@ use instrument::half_player_phase::*;
 use instrument::position::*;
 use sheet_music_format::kifu_usi::fen::*;
 use sheet_music_format::kifu_usi::usi_move::*;
+use sheet_music_format::parse_error::*;
+use sheet_music_format::record_encoding::*;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::*;
@@ -19,12 +21,13 @@ impl UsiTape {
     }
 
     /// USI の moves の文字列を、オブジェクトに直訳するぜ☆（＾～＾）局面は動かさない☆（＾～＾）
+    /// 読めない指し手があれば、エラーを返す。
     pub fn parse_usi_all_moves(
         line: &str,
         start: &mut usize,
         board_size: BoardSize,
         app: &Application,
-    ) -> Option<Self> {
+    ) -> Result<Self, ParseError> {
         let mut urecord = UsiTape { moves: Vec::new() };
 
         Parser::skip_spaces(&app.comm, &line, start);
@@ -35,7 +38,7 @@ impl UsiTape {
 
         // ex.) Parses 7g7f 3c3d.
         loop {
-            let umove = Fen::parse_usi_1move(&line, start, board_size, &app);
+            let umove = Fen::parse_usi_1move(line, start, board_size, app)?;
             // comm.println(&format!("#Umove: `{}`.", umove.to_sign()));
 
             // TODO 内部形式としては RPM で持ちたい。
@@ -50,7 +53,24 @@ impl UsiTape {
         // comm.println(&format!("#Usi record len: {}", urecord.moves.len()));
         //comm.println(&position.to_text(comm, urecord.get_current_phase()));
 
-        Some(urecord)
+        Ok(urecord)
+    }
+
+    /// 空行を除いた全部の行を、行番号と一緒に返す。行番号は 1 から。
+    /// 自己対局の棋譜は `position startpos moves ...` が１行に対局１つで、何行も続く。
+    pub fn read_position_lines(
+        file: &str,
+        app: &Application,
+    ) -> Result<Vec<(usize, String)>, ParseError> {
+        let lines = RecordEncoding::read_lines(file, app)
+            .map_err(|err| ParseError::from_io("USI", file, &err))?;
+
+        Ok(lines
+            .iter()
+            .enumerate()
+            .filter(|(_i, line)| !line.trim().is_empty())
+            .map(|(i, line)| (i + 1, line.trim().to_string()))
+            .collect())
     }
 
     /// 1行目のテキストを返す。
@@ -80,7 +100,6 @@ impl UsiTape {
     pub fn make_usi_move(&mut self, umove: UsiMove, position: &mut Position, app: &Application) {
         if umove.is_drop() {
             // TODO drop
-
         } else {
             let source_id_piece_opt = position.remove_id_piece(
                 umove
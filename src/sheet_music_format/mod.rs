@@ -1,5 +1,11 @@
 pub mod kifu_csa;
+pub mod kifu_jkf;
+pub mod kifu_ki2;
 pub mod kifu_kif;
 pub mod kifu_rpm;
 pub mod kifu_usi;
+pub mod move_annotation;
+pub mod parse_error;
+pub mod record_encoding;
 pub mod tape_label;
+pub mod tape_label_query;
@@ -0,0 +1,112 @@
+use serde::*;
+
+/// 汎用の指し手の注釈。コメントと消費時間と、思考エンジンの読み筋。
+/// テープの id, ope, facing とは別のトラックとして、手目ごとに持つ。
+/// 0手目は、初期局面へのコメント。
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")] // プロパティ名が JSON 側でスネークケースであることを指定
+pub struct MoveAnnotation {
+    // 何手目か。
+    pub ply: i16,
+
+    // コメント。１行ずつ。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<String>,
+
+    // その手の消費時間。秒。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consumed: Option<u32>,
+
+    // その手を指した側の、累計の消費時間。秒。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u32>,
+
+    // その手を選んだ時の読み筋。USI の指し手の並び。先頭は その手自身。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pv: Vec<String>,
+}
+impl MoveAnnotation {
+    pub fn new(ply: i16) -> Self {
+        MoveAnnotation {
+            ply,
+            comments: Vec::new(),
+            consumed: None,
+            total: None,
+            pv: Vec::new(),
+        }
+    }
+
+    // #####
+    // # F #
+    // #####
+
+    /// 手目の一致する注釈を探す。
+    pub fn find(annotations: &[MoveAnnotation], ply: i16) -> Option<&MoveAnnotation> {
+        annotations.iter().find(|annotation| annotation.ply == ply)
+    }
+
+    /// 累計の消費時間が無ければ、その手の消費時間を足し上げて埋める。
+    /// 累計は、先手と後手で別々。
+    pub fn fill_total(annotations: &mut [MoveAnnotation]) {
+        let mut totals = [0u32; 2];
+        for annotation in annotations.iter_mut() {
+            if annotation.ply < 1 {
+                continue;
+            }
+
+            let side = (annotation.ply % 2) as usize;
+            if let Some(total) = annotation.total {
+                totals[side] = total;
+            } else if let Some(consumed) = annotation.consumed {
+                totals[side] += consumed;
+                annotation.total = Some(totals[side]);
+            }
+        }
+    }
+
+    // #####
+    // # I #
+    // #####
+
+    /// コメントも消費時間も読み筋も無ければ真。
+    pub fn is_empty(&self) -> bool {
+        self.comments.is_empty()
+            && self.consumed.is_none()
+            && self.total.is_none()
+            && self.pv.is_empty()
+    }
+
+    // #####
+    // # P #
+    // #####
+
+    pub fn push_comment(&mut self, comment: &str) {
+        self.comments.push(comment.to_string());
+    }
+
+    // #####
+    // # T #
+    // #####
+
+    pub fn to_human_presentable(&self) -> String {
+        let mut text = format!("[Annotation: Ply: {}", self.ply);
+
+        if let Some(consumed) = self.consumed {
+            text = format!("{} Time: {}s", text, consumed);
+        }
+
+        if let Some(total) = self.total {
+            text = format!("{} Total: {}s", text, total);
+        }
+
+        if !self.pv.is_empty() {
+            text = format!("{} PV: {}", text, self.pv.join(" "));
+        }
+
+        for comment in &self.comments {
+            text = format!("{} *{}", text, comment);
+        }
+
+        format!("{}]", text)
+    }
+}
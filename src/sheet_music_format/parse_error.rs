@@ -0,0 +1,118 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// 棋譜を読めなかった理由。
+/// どのファイルの、何行目の、何文字目の、どの文字列が悪かったかを持つ。
+/// パーサーとコンバーターは panic! せず、これを返す。呼び出し側で その棋譜だけ飛ばせる。
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParseError {
+    // "KIF", "KI2", "CSA", "JKF", "USI", "RPM" など。
+    pub format: String,
+    // ファイル名。分からなければ空っぽ。
+    pub file: String,
+    // 行番号。1 から。分からなければ 0。
+    pub line: usize,
+    // 行の中の文字の位置。1 から。分からなければ 0。
+    pub column: usize,
+    // 読めなかった文字列。
+    pub text: String,
+    // 理由。
+    pub message: String,
+}
+impl ParseError {
+    /// JSON のエラーの前後に残す文字数。
+    const JSON_EXCERPT_LEN: usize = 20;
+
+    pub fn new(format: &str, message: &str) -> Self {
+        ParseError {
+            format: format.to_string(),
+            message: message.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // #####
+    // # F #
+    // #####
+
+    /// ファイルを開けなかった、読めなかった時。
+    pub fn from_io(format: &str, file: &str, err: &io::Error) -> Self {
+        ParseError::new(format, &err.to_string()).with_file(file)
+    }
+
+    /// JSON として読めなかった時。行と文字の位置は serde が教えてくれる。
+    /// JSON は１行が長いことがあるので、文字列は その位置の前後だけ残す。
+    pub fn from_json(format: &str, file: &str, contents: &str, err: &serde_json::Error) -> Self {
+        let line = contents.lines().nth(err.line().max(1) - 1).unwrap_or("");
+        let text: String = line
+            .chars()
+            .skip(
+                err.column()
+                    .saturating_sub(ParseError::JSON_EXCERPT_LEN + 1),
+            )
+            .take(ParseError::JSON_EXCERPT_LEN * 2)
+            .collect();
+
+        ParseError::new(format, &err.to_string())
+            .with_file(file)
+            .with_line(err.line(), &text)
+            .with_column(err.column())
+    }
+
+    // #####
+    // # W #
+    // #####
+
+    pub fn with_column(mut self, column: usize) -> Self {
+        self.column = column;
+        self
+    }
+
+    /// ファイル名がまだ無ければ付ける。
+    /// 指し手のパーサーはファイル名を知らないので、ファイルのパーサーが後から付ける。
+    pub fn with_file(mut self, file: &str) -> Self {
+        if self.file.is_empty() {
+            self.file = file.to_string();
+        }
+        self
+    }
+
+    /// 行番号がまだ無ければ、行番号と行の文字列を付ける。
+    pub fn with_line(mut self, line: usize, text: &str) -> Self {
+        if self.line == 0 {
+            self.line = line;
+            if self.text.is_empty() {
+                self.text = text.to_string();
+            }
+        }
+        self
+    }
+
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.text = text.to_string();
+        self
+    }
+}
+impl fmt::Display for ParseError {
+    /// `file.kif:12:5: [KIF] Unexpected piece. `５五王`` のように書く。
+    /// 分からない所は省く。
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.file.is_empty() {
+            write!(f, "{}:", self.file)?;
+            if 0 < self.line {
+                write!(f, "{}:", self.line)?;
+                if 0 < self.column {
+                    write!(f, "{}:", self.column)?;
+                }
+            }
+            write!(f, " ")?;
+        }
+        write!(f, "[{}] {}", self.format, self.message)?;
+        if !self.text.is_empty() {
+            write!(f, " `{}`", self.text)?;
+        }
+        Ok(())
+    }
+}
+impl Error for ParseError {}
@@ -0,0 +1,133 @@
+use encoding_rs::*;
+use regex::Regex;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use studio::application::Application;
+
+/// 棋譜ファイルの文字コード。
+///
+/// 判定の順は、
+/// * BOM
+/// * ヘッダーの `encoding=...`
+///   `#KIF version=2.0 encoding=UTF-8` や `'encoding=Shift_JIS` など。
+/// * UTF-8 として正しく読めるか
+/// * 読めなければ Shift_JIS (CP932)
+///
+/// デスクトップの将棋ソフトが書き出す .kif は、たいてい Shift_JIS。
+pub struct RecordEncoding {}
+impl RecordEncoding {
+    /// ヘッダーの `encoding=...` を探す範囲。バイト数。
+    const HEADER_LEN: usize = 1024;
+
+    // #####
+    // # D #
+    // #####
+
+    /// 文字コードを判定する。
+    ///
+    /// # Returns
+    ///
+    /// (文字コード, BOM のバイト数)
+    pub fn detect(bytes: &[u8], app: &Application) -> (&'static Encoding, usize) {
+        // BOM。
+        if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+            return (encoding, bom_len);
+        }
+
+        // ヘッダー。ASCII の範囲だけ見る。
+        let head = &bytes[..bytes.len().min(RecordEncoding::HEADER_LEN)];
+        let head_text: String = head
+            .iter()
+            .map(|byte| if byte.is_ascii() { *byte as char } else { ' ' })
+            .collect();
+        let re = Regex::new(r"encoding=([A-Za-z0-9_\-]+)")
+            .unwrap_or_else(|f| panic!("{}", app.comm.panic(&f.to_string())));
+        if let Some(matched) = re.captures(&head_text) {
+            if let Some(encoding) =
+                Encoding::for_label(matched.get(1).map_or("", |m| m.as_str()).as_bytes())
+            {
+                return (encoding, 0);
+            }
+        }
+
+        // UTF-8 として読めるか。
+        if std::str::from_utf8(bytes).is_ok() {
+            return (UTF_8, 0);
+        }
+
+        // WHATWG の Shift_JIS は、CP932 (Windows-31J) と同じ。
+        (SHIFT_JIS, 0)
+    }
+
+    /// ファイルを読んで、文字コードを判定する。
+    pub fn detect_file(file: &str, app: &Application) -> io::Result<&'static Encoding> {
+        let mut bytes = Vec::new();
+        File::open(file)?.read_to_end(&mut bytes)?;
+        Ok(RecordEncoding::detect(&bytes, app).0)
+    }
+
+    // #####
+    // # R #
+    // #####
+
+    /// ファイルを読んで、文字コードを判定して、UTF-8 の文字列にする。
+    pub fn read_to_string(file: &str, app: &Application) -> io::Result<String> {
+        let mut bytes = Vec::new();
+        File::open(file)?.read_to_end(&mut bytes)?;
+
+        let (encoding, bom_len) = RecordEncoding::detect(&bytes, app);
+        if app.is_debug() {
+            app.comm
+                .println(&format!("#Encoding: '{}' {}.", file, encoding.name()));
+        }
+
+        // 読めない文字は U+FFFD に置き換わる。
+        let (text, _had_errors) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        Ok(text.into_owned())
+    }
+
+    /// ファイルを読んで、行に分ける。改行は CR LF でも LF でもよい。
+    pub fn read_lines(file: &str, app: &Application) -> io::Result<Vec<String>> {
+        Ok(RecordEncoding::read_to_string(file, app)?
+            .lines()
+            .map(|line| line.to_string())
+            .collect())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_encoding() {
+        let app = Application::new_for_test();
+        let (sjis, _unmappable, _had_errors) = SHIFT_JIS.encode("先手：羽生善治");
+
+        assert_eq!(RecordEncoding::detect(&sjis, &app), (SHIFT_JIS, 0));
+        assert_eq!(
+            RecordEncoding::detect("先手：羽生善治".as_bytes(), &app),
+            (UTF_8, 0)
+        );
+        assert_eq!(
+            RecordEncoding::detect("\u{FEFF}先手".as_bytes(), &app),
+            (UTF_8, 3)
+        );
+        // ヘッダーに書かれた文字コードを信じる。
+        assert_eq!(
+            RecordEncoding::detect(b"#KIF version=2.0 encoding=Shift_JIS\n", &app),
+            (SHIFT_JIS, 0)
+        );
+    }
+
+    #[test]
+    fn reads_shift_jis_lines() {
+        let app = Application::new_for_test();
+        let (sjis, _unmappable, _had_errors) =
+            SHIFT_JIS.encode("先手：羽生善治\r\n後手：森内俊之\r\n");
+        let file = Application::write_test_file("record_encoding/sjis.kif", &sjis);
+
+        let lines = RecordEncoding::read_lines(&file, &app).unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(lines, vec!["先手：羽生善治", "後手：森内俊之"]);
+    }
+}
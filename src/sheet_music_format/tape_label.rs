@@ -18,6 +18,8 @@ use serde::*;
 ///   * 戦型
 /// * ツール付加情報
 ///   * 棋譜の保存形式
+///   * 変化の分かれた手目、分かれる元のテープ名
+///   * 展開したアーカイブ
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")] // プロパティ名が JSON 側でスネークケースであることを指定
 pub struct TapeLabel {
@@ -56,6 +58,22 @@ pub struct TapeLabel {
 
     // 棋譜の保存形式。
     format: String,
+
+    // 変化なら、分かれた手目。本譜なら空っぽ。
+    #[serde(default)]
+    branch_ply: String,
+
+    // 変化なら、分かれる元のテープ名。本譜なら空っぽ。
+    #[serde(default)]
+    branch_from: String,
+
+    // アーカイブから展開した棋譜なら、そのアーカイブのパス。input ディレクトリーからの相対パス。
+    #[serde(default)]
+    archive: String,
+
+    // 対局の途中で止まったテープなら真。ジャーナルから復旧したテープ。
+    #[serde(default)]
+    incomplete: bool,
 }
 impl TapeLabel {
     pub fn new() -> Self {
@@ -72,9 +90,37 @@ impl TapeLabel {
             time_system: String::new(),
             handicap: String::new(),
             battle_type: String::new(),
+            branch_ply: String::new(),
+            branch_from: String::new(),
+            archive: String::new(),
+            incomplete: false,
         }
     }
 
+    // #####
+    // # C #
+    // #####
+
+    /// 本譜のラベルを写して、変化のラベルを作る。
+    ///
+    /// # Arguments
+    ///
+    /// * `number` - 棋譜の中で何番目の手順か。本譜が 0。
+    /// * `branch_ply` - 分かれた手目。
+    /// * `branch_from` - 分かれる元のテープ名。
+    pub fn create_variation_label(
+        &self,
+        number: usize,
+        branch_ply: i16,
+        branch_from: &str,
+    ) -> TapeLabel {
+        let mut label = self.clone();
+        label.name = format!("{} 変化{}手 #{}", self.name, branch_ply, number);
+        label.branch_ply = branch_ply.to_string();
+        label.branch_from = branch_from.to_string();
+        label
+    }
+
     // #####
     // # G #
     // #####
@@ -139,6 +185,92 @@ impl TapeLabel {
         self.battle_type.to_string()
     }
 
+    /// 変化の分かれた手目。本譜なら空っぽ。
+    pub fn get_branch_ply(&self) -> String {
+        self.branch_ply.to_string()
+    }
+
+    /// 変化の分かれる元のテープ名。本譜なら空っぽ。
+    pub fn get_branch_from(&self) -> String {
+        self.branch_from.to_string()
+    }
+
+    /// 展開したアーカイブのパス。アーカイブからでなければ空っぽ。
+    pub fn get_archive(&self) -> String {
+        self.archive.to_string()
+    }
+
+    /// JSON の項目名で引いた値。知らない項目名なら None。
+    pub fn get_value(&self, key: &str) -> Option<String> {
+        match key {
+            "name" => Some(self.get_name()),
+            "game_date" => Some(self.get_game_date()),
+            "start_time" => Some(self.get_start_time()),
+            "end_time" => Some(self.get_end_time()),
+            "place" => Some(self.get_place()),
+            "event" => Some(self.get_event()),
+            "player1" => Some(self.get_player1()),
+            "player2" => Some(self.get_player2()),
+            "time_system" => Some(self.get_time_system()),
+            "handicap" => Some(self.get_handicap()),
+            "battle_type" => Some(self.get_battle_type()),
+            "format" => Some(self.get_format()),
+            "branch_ply" => Some(self.get_branch_ply()),
+            "branch_from" => Some(self.get_branch_from()),
+            "archive" => Some(self.get_archive()),
+            "incomplete" => Some(self.is_incomplete().to_string()),
+            _ => None,
+        }
+    }
+
+    /// 対局の途中で止まったテープか。
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
+    /// 変化か。
+    pub fn is_variation(&self) -> bool {
+        !self.branch_from.is_empty()
+    }
+
+    // #####
+    // # M #
+    // #####
+
+    /// 同じ対局の別のラベルを混ぜる。
+    /// 空っぽの項目は相手の値で埋め、両方に値があれば 長い方を残す。
+    /// テープ名だけは、空っぽの時しか書き換えない。
+    pub fn merge(&mut self, other: &TapeLabel) {
+        if self.name.is_empty() {
+            self.name = other.name.to_string();
+        }
+
+        let fields = [
+            (&mut self.game_date, &other.game_date),
+            (&mut self.start_time, &other.start_time),
+            (&mut self.end_time, &other.end_time),
+            (&mut self.place, &other.place),
+            (&mut self.event, &other.event),
+            (&mut self.player1, &other.player1),
+            (&mut self.player2, &other.player2),
+            (&mut self.time_system, &other.time_system),
+            (&mut self.handicap, &other.handicap),
+            (&mut self.battle_type, &other.battle_type),
+            (&mut self.format, &other.format),
+            (&mut self.branch_ply, &other.branch_ply),
+            (&mut self.branch_from, &other.branch_from),
+            (&mut self.archive, &other.archive),
+        ];
+        for (mine, theirs) in fields {
+            if mine.chars().count() < theirs.chars().count() {
+                *mine = theirs.to_string();
+            }
+        }
+
+        // どちらかが最後まで指してあれば、最後まで指した対局。
+        self.incomplete = self.incomplete && other.incomplete;
+    }
+
     // #####
     // # S #
     // #####
@@ -158,6 +290,20 @@ impl TapeLabel {
         self.game_date = date.to_string();
     }
 
+    /// "2018/05/05 09:44:47" のように 空白で区切った、対局日と開始時間を書く。時間は省略されることがある。
+    pub fn set_game_date_and_start_time(&mut self, text: &str) {
+        match text.find(char::is_whitespace) {
+            Some(i) => {
+                self.set_game_date(&text[..i]);
+                self.set_start_time(text[i..].trim_start());
+            }
+            None => {
+                self.set_game_date(text);
+                self.set_start_time("");
+            }
+        }
+    }
+
     /// 開始時間を書く。
     pub fn set_start_time(&mut self, time: &str) {
         self.start_time = time.to_string();
@@ -202,4 +348,24 @@ impl TapeLabel {
     pub fn set_battle_type(&mut self, battle_type_text: &str) {
         self.battle_type = battle_type_text.to_string();
     }
+
+    // 変化の分かれた手目を書く。
+    pub fn set_branch_ply(&mut self, branch_ply_text: &str) {
+        self.branch_ply = branch_ply_text.to_string();
+    }
+
+    // 変化の分かれる元のテープ名を書く。
+    pub fn set_branch_from(&mut self, branch_from_text: &str) {
+        self.branch_from = branch_from_text.to_string();
+    }
+
+    // 展開したアーカイブのパスを書く。
+    pub fn set_archive(&mut self, archive_text: &str) {
+        self.archive = archive_text.to_string();
+    }
+
+    // 対局の途中で止まったテープか書く。
+    pub fn set_incomplete(&mut self, incomplete: bool) {
+        self.incomplete = incomplete;
+    }
 }
@@ -0,0 +1,386 @@
+use sheet_music_format::kifu_rpm::rpm_tape_reader::*;
+use sheet_music_format::parse_error::*;
+use sheet_music_format::tape_label::TapeLabel;
+use std::cmp::Ordering;
+
+/// 項目と 値の比べ方。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TapeLabelOperator {
+    // `=` 等しい。
+    Equal,
+    // `!=` 等しくない。
+    NotEqual,
+    // `~` 含む。
+    Contains,
+    // `!~` 含まない。
+    NotContains,
+    // `>=` 以上。
+    GreaterEqual,
+    // `<=` 以下。
+    LessEqual,
+    // `>` より大きい。
+    Greater,
+    // `<` 未満。
+    Less,
+}
+impl TapeLabelOperator {
+    /// 記号と比べ方。`>=` を `>` と読まないように、２文字の記号から先に並べる。
+    const SYMBOLS: [(&'static str, TapeLabelOperator); 8] = [
+        ("!=", TapeLabelOperator::NotEqual),
+        ("!~", TapeLabelOperator::NotContains),
+        (">=", TapeLabelOperator::GreaterEqual),
+        ("<=", TapeLabelOperator::LessEqual),
+        ("=", TapeLabelOperator::Equal),
+        ("~", TapeLabelOperator::Contains),
+        (">", TapeLabelOperator::Greater),
+        ("<", TapeLabelOperator::Less),
+    ];
+
+    /// 否定か。
+    pub fn is_negative(self) -> bool {
+        self == TapeLabelOperator::NotEqual || self == TapeLabelOperator::NotContains
+    }
+}
+
+/// 絞り込みの条件１つ。 `player1~"羽生"` なら、項目が player1、比べ方が Contains、値が 羽生。
+#[derive(Clone, Debug)]
+pub struct TapeLabelTerm {
+    pub key: String,
+    pub operator: TapeLabelOperator,
+    pub value: String,
+}
+impl TapeLabelTerm {
+    // #####
+    // # C #
+    // #####
+
+    /// 日付や手目のように数字が並んでいれば、数字ごとに数として比べる。
+    /// 2018/1/5 と 2018-01-01 も比べられる。値の方が短ければ、そこまでしか比べないので、
+    /// `date<=2018` は 2018年の対局も含む。
+    /// 数字が無ければ、文字列として比べる。
+    fn compare(actual: &str, expected: &str) -> Ordering {
+        let numbers = |text: &str| -> Vec<u64> {
+            text.split(|ch: char| !ch.is_ascii_digit())
+                .filter_map(|number| number.parse().ok())
+                .collect()
+        };
+
+        let expected_numbers = numbers(expected);
+        let actual_numbers = numbers(actual);
+        if expected_numbers.is_empty() || actual_numbers.is_empty() {
+            actual.cmp(expected)
+        } else {
+            actual_numbers
+                .iter()
+                .take(expected_numbers.len())
+                .cmp(expected_numbers.iter())
+        }
+    }
+
+    // #####
+    // # M #
+    // #####
+
+    /// ラベルが条件に合うか。
+    /// player のように項目が２つある時は、どちらかが合えば合う。否定なら、両方が合わなければ合う。
+    pub fn matches(&self, label: &TapeLabel) -> bool {
+        let values = TapeLabelQuery::get_values(label, &self.key);
+        if self.operator.is_negative() {
+            values.iter().all(|value| self.matches_value(value))
+        } else {
+            values.iter().any(|value| self.matches_value(value))
+        }
+    }
+
+    fn matches_value(&self, actual: &str) -> bool {
+        use self::TapeLabelOperator::*;
+        match self.operator {
+            Equal => actual == self.value,
+            NotEqual => actual != self.value,
+            Contains => actual.contains(&self.value),
+            NotContains => !actual.contains(&self.value),
+            // 空っぽの項目は、大小を比べられない。
+            GreaterEqual => {
+                !actual.is_empty() && TapeLabelTerm::compare(actual, &self.value) != Ordering::Less
+            }
+            LessEqual => {
+                !actual.is_empty()
+                    && TapeLabelTerm::compare(actual, &self.value) != Ordering::Greater
+            }
+            Greater => {
+                !actual.is_empty()
+                    && TapeLabelTerm::compare(actual, &self.value) == Ordering::Greater
+            }
+            Less => {
+                !actual.is_empty() && TapeLabelTerm::compare(actual, &self.value) == Ordering::Less
+            }
+        }
+    }
+
+    // #####
+    // # P #
+    // #####
+
+    /// `項目 比べ方 値` を読む。値は "" で囲んでもいい。
+    ///
+    /// # Arguments
+    ///
+    /// * `column` - クエリの中の、条件の始まる文字の位置。1 から。
+    fn parse(text: &str, column: usize) -> Result<Self, ParseError> {
+        let error = |message: &str| {
+            ParseError::new("QUERY", message)
+                .with_line(1, text)
+                .with_column(column)
+        };
+
+        let key_len = text
+            .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+            .ok_or_else(|| error("Operator not found."))?;
+        let key = &text[..key_len];
+        if !TapeLabelQuery::KEYS.contains(&key) {
+            return Err(error(&format!(
+                "Unknown key '{}'. Keys: {}.",
+                key,
+                TapeLabelQuery::KEYS.join(", ")
+            )));
+        }
+
+        let rest = &text[key_len..];
+        let (symbol, operator) = TapeLabelOperator::SYMBOLS
+            .iter()
+            .find(|(symbol, _operator)| rest.starts_with(symbol))
+            .ok_or_else(|| error("Operator not found."))?;
+
+        Ok(TapeLabelTerm {
+            key: key.to_string(),
+            operator: *operator,
+            value: rest[symbol.len()..].replace('"', ""),
+        })
+    }
+}
+
+/// ディレクトリーの中のテープ・ボックスから見つけたテープ。
+#[derive(Clone, Debug)]
+pub struct TapeReference {
+    // テープ・ボックスのファイル名。
+    pub file: String,
+    // 箱の中で何番目のテープか。0 から。
+    pub index: usize,
+    // テープ名。
+    pub name: String,
+}
+impl TapeReference {
+    pub fn to_human_presentable(&self) -> String {
+        format!("{} #{} {}", self.file, self.index, self.name)
+    }
+}
+
+/// テープ・ラベルの絞り込み。
+/// `player1~"羽生" date>=2018-01-01 handicap=平手` のように、空白で区切った条件を 全部満たすテープを選ぶ。
+#[derive(Clone, Debug, Default)]
+pub struct TapeLabelQuery {
+    terms: Vec<TapeLabelTerm>,
+}
+impl TapeLabelQuery {
+    /// 絞り込める項目。date は game_date、player は player1 か player2 の 短い書き方。
+    pub const KEYS: [&'static str; 19] = [
+        "name",
+        "date",
+        "game_date",
+        "start_time",
+        "end_time",
+        "place",
+        "event",
+        "player",
+        "player1",
+        "player2",
+        "time_system",
+        "handicap",
+        "battle_type",
+        "format",
+        "branch_ply",
+        "branch_from",
+        "archive",
+        "incomplete",
+        "variation",
+    ];
+
+    // #####
+    // # G #
+    // #####
+
+    /// 項目の値。player だけは２つ。
+    fn get_values(label: &TapeLabel, key: &str) -> Vec<String> {
+        match key {
+            "date" => vec![label.get_game_date()],
+            "player" => vec![label.get_player1(), label.get_player2()],
+            "variation" => vec![label.is_variation().to_string()],
+            _ => label.get_value(key).into_iter().collect(),
+        }
+    }
+
+    // #####
+    // # I #
+    // #####
+
+    /// 条件が無ければ、どのテープも合う。
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    // #####
+    // # M #
+    // #####
+
+    /// ラベルが 全部の条件に合うか。
+    pub fn matches(&self, label: &TapeLabel) -> bool {
+        self.terms.iter().all(|term| term.matches(label))
+    }
+
+    // #####
+    // # P #
+    // #####
+
+    /// 空白で区切った条件を読む。"" で囲んだ中の空白では区切らない。
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut query = TapeLabelQuery::default();
+
+        let mut term = String::new();
+        let mut term_column = 0;
+        let mut quoted = false;
+        for (index, ch) in text.chars().enumerate() {
+            if ch.is_whitespace() && !quoted {
+                if !term.is_empty() {
+                    query.terms.push(TapeLabelTerm::parse(&term, term_column)?);
+                    term.clear();
+                }
+                continue;
+            }
+
+            if term.is_empty() {
+                term_column = index + 1;
+            }
+            if ch == '"' {
+                quoted = !quoted;
+            }
+            term.push(ch);
+        }
+
+        if quoted {
+            return Err(ParseError::new("QUERY", "Quote not closed.")
+                .with_line(1, &term)
+                .with_column(term_column));
+        }
+        if !term.is_empty() {
+            query.terms.push(TapeLabelTerm::parse(&term, term_column)?);
+        }
+
+        Ok(query)
+    }
+
+    // #####
+    // # S #
+    // #####
+
+    /// テープ・ボックス・ファイルを１つ開いて、条件に合うテープを探す。
+    /// テープは１本ずつ読む。
+    pub fn search_box_file(&self, box_file: &str) -> Result<Vec<TapeReference>, ParseError> {
+        let mut references = Vec::new();
+        for (index, tape) in RpmTapeReader::open(box_file)?.enumerate() {
+            let tape = tape?;
+            if self.matches(&tape.label) {
+                references.push(TapeReference {
+                    file: box_file.to_string(),
+                    index,
+                    name: tape.label.get_name(),
+                });
+            }
+        }
+
+        Ok(references)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label() -> TapeLabel {
+        let mut label = TapeLabel::new();
+        label.set_game_date("2018/1/5");
+        label.set_player1("羽生 善治");
+        label.set_player2("藤井 聡太");
+        label.set_handicap("平手");
+        label
+    }
+
+    fn parse(text: &str) -> TapeLabelQuery {
+        TapeLabelQuery::parse(text).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    fn parse_err(text: &str) -> ParseError {
+        match TapeLabelQuery::parse(text) {
+            Ok(_query) => panic!("Unexpected success."),
+            Err(err) => err,
+        }
+    }
+
+    /// 空っぽなら 条件は無く、どのテープも合う。
+    #[test]
+    fn matches_all_tapes_when_empty() {
+        let query = parse("  ");
+        assert!(query.is_empty());
+        assert!(query.matches(&label()));
+    }
+
+    /// 条件は 全部満たさないと合わない。
+    #[test]
+    fn matches_only_when_all_terms_match() {
+        let label = label();
+        assert!(parse("player1~羽生 handicap=平手").matches(&label));
+        assert!(!parse("player1~羽生 handicap=香落ち").matches(&label));
+    }
+
+    /// "" で囲めば 空白も値に入る。
+    #[test]
+    fn reads_quoted_value_with_space() {
+        let label = label();
+        assert!(parse("player1=\"羽生 善治\"").matches(&label));
+        assert!(!parse("player1=\"羽生 善\"").matches(&label));
+    }
+
+    /// player は どちらかが合えば合う。否定なら 両方が合わなければ合う。
+    #[test]
+    fn matches_player_with_either_player() {
+        let label = label();
+        assert!(parse("player~藤井").matches(&label));
+        assert!(!parse("player!~藤井").matches(&label));
+        assert!(parse("player!~加藤").matches(&label));
+    }
+
+    /// 日付は 数字ごとに比べる。値が短ければ そこまでしか比べない。
+    #[test]
+    fn compares_date_by_numbers() {
+        let label = label();
+        assert!(parse("date>=2018-01-01").matches(&label));
+        assert!(parse("date<=2018").matches(&label));
+        assert!(!parse("date>2018-01-05").matches(&label));
+        // 空っぽの項目は 大小を比べられない。
+        assert!(!parse("place>=A").matches(&label));
+    }
+
+    /// 読めない条件は、条件の始まる位置を付けて エラーにする。
+    #[test]
+    fn reports_column_of_bad_term() {
+        let err = parse_err("handicap=平手 color=red");
+        assert_eq!(err.column, 13);
+        assert!(err.to_string().contains("Unknown key 'color'."));
+
+        let err = parse_err("handicap=平手 player1");
+        assert_eq!(err.column, 13);
+        assert!(err.to_string().contains("Operator not found."));
+
+        let err = parse_err("player1=\"羽生");
+        assert!(err.to_string().contains("Quote not closed."));
+    }
+}
@@ -60,6 +60,11 @@ impl ShogiNote {
         self.identify
     }
 
+    /// `[手目]` のフェーズ・チェンジなら手目。手目の無い `|` や、フェーズ・チェンジでなければ None。
+    pub fn get_ply(&self) -> Option<i16> {
+        self.operation.get_phase_change().filter(|ply| -1 < *ply)
+    }
+
     // #####
     // # I #
     // #####
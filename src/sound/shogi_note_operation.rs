@@ -272,6 +272,17 @@ impl ShogiNoteOpe {
                 }
                 (closed_interval, Some(ShogiNoteOpe::change_phase(ply)))
             }
+            '%' => {
+                // 投了。 "%resign" の残りを読み飛ばす。
+                while n0 + 1 < line.len() {
+                    n0 = caret
+                        .seek_a_note(app)
+                        .index
+                        .unwrap_or_else(|| panic!("{}", app.comm.panic("n0 fail.")));
+                    closed_interval.intersect_caret_number(n0 as i16);
+                }
+                (closed_interval, Some(ShogiNoteOpe::resign()))
+            }
             _ => {
                 let last = line.len();
                 panic!("Unexpected line '{}'.", &line[n0..last]);
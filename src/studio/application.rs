@@ -16,7 +16,7 @@ pub struct Application {
 impl Application {
     pub fn new() -> Self {
         let exe_config = KifuwarabeWcsc29ExeConfig::load();
-        let kw29_config = KifuwarabeWcsc29MasterConfig::load(&exe_config);
+        let mut kw29_config = KifuwarabeWcsc29MasterConfig::load(&exe_config);
 
         // logger, logging, log file.
         let mut path = PathBuf::from(&exe_config.logging.directory);
@@ -29,15 +29,79 @@ impl Application {
         let path_name = &path.to_str().unwrap_or_else(|| panic!("Fail. path_name.")); // ログ取れない。無限ループ防止で簡素なpanic。
                                                                                       // print!("#Log path_name: '{}'.", path_name);
 
+        let comm = Communication::from_file(path_name);
+
+        // トレーニング・テープの絞り込みは ここで１回だけ読む。読めなければ 絞り込まない。
+        // 標準出力は USI の邪魔になるので、ログと 標準エラー出力に知らせる。
+        if let Err(err) = kw29_config.parse_training_query() {
+            let message = format!("#training_query: {} Use all training tapes.", err);
+            eprintln!("{}", message);
+            comm.logln(&message);
+        }
+
         // 最初は、デバッグ・フラグをＯｎにして開始☆（＾～＾）標準出力はせず、ログには出す☆（＾～＾）
         Application {
-            comm: Communication::from_file(path_name),
+            comm,
             exe_conf: exe_config,
             kw29_conf: kw29_config,
             kifuwarabe_flag: true,
         }
     }
 
+    /// テスト用。設定ファイルは読まずに、一時ディレクトリーを使う。
+    #[cfg(test)]
+    pub fn new_for_test() -> Self {
+        use conf::kifuwarabe_wcsc29_exe_config::Logging;
+
+        let directory = Application::get_test_directory();
+        let path = |name: &str| directory.join(name).display().to_string();
+
+        let exe_config = KifuwarabeWcsc29ExeConfig {
+            kifuwarabe_wcsc29_master_config_path: String::new(),
+            logging: Logging {
+                directory: path("logs"),
+                file_base_name: "kw29-lib-test".to_string(),
+                file_extension: ".log".to_string(),
+            },
+        };
+        let kw29_config = serde_json::from_value(serde_json::json!({
+            "kifuwarabe_wcsc29_opt": path("opt"),
+            "converter_var_lib": path("var-lib"),
+            "training": path("training"),
+            "learning": path("learning"),
+            "book": path("book"),
+            "tapes_fragments": path("tapes-fragments"),
+        }))
+        .unwrap_or_else(|err| panic!("Unexpected test config: {}", err));
+
+        Application {
+            comm: Communication::from_file(&path("logs/kw29-lib-test.log")),
+            exe_conf: exe_config,
+            kw29_conf: kw29_config,
+            kifuwarabe_flag: false,
+        }
+    }
+
+    /// テスト用のファイルを置くディレクトリー。
+    #[cfg(test)]
+    fn get_test_directory() -> PathBuf {
+        std::env::temp_dir().join("kifuwarabe-wcsc29-lib-test")
+    }
+
+    /// テスト用。一時ディレクトリーにファイルを書いて、そのパスを返す。
+    /// テストは並んで走るので、ファイル名はテストごとに変えること。
+    #[cfg(test)]
+    pub fn write_test_file(name: &str, contents: &[u8]) -> String {
+        use std::fs;
+
+        let path = Application::get_test_directory().join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap_or_else(|err| panic!("{}", err));
+        }
+        fs::write(&path, contents).unwrap_or_else(|err| panic!("{}", err));
+        path.display().to_string()
+    }
+
     pub fn is_debug(&self) -> bool {
         self.kifuwarabe_flag
     }
@@ -70,6 +70,7 @@ impl Awareness {
 /// 常に現在位置を示す☆（＾～＾）
 /// 初期位置は持たない☆（＾～＾）
 /// できるか、できないかではない、これは　そうであるべき　という　思想　だぜ☆（*＾～＾*）
+#[derive(Clone)]
 pub struct Caret {
     facing_left: bool,
     // キャレットの位置。